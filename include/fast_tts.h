@@ -0,0 +1,47 @@
+/*
+ * C bindings for the fast-tts provider layer.
+ *
+ * Build with:
+ *   cargo rustc --lib --release --features cdylib --crate-type cdylib
+ *
+ * Requests are JSON objects with camelCase keys: text (required), output,
+ * provider ("google", "openai", "custom:<name>", ...), language, voice,
+ * gender, rate, pitch, sampleRate, encoding, volumeGainDb, effectsProfileId,
+ * ssml. Credentials come from the same environment variables as the CLI.
+ *
+ * Functions returning int return 0 on success or the CLI exit code for the
+ * error class (2 invalid input, 3 auth, 4 quota, 5 provider HTTP, 6 I/O,
+ * 7 unsupported, 1 other, including a panic inside the library);
+ * fast_tts_last_error() then describes the failure.
+ * Calls block until synthesis finishes.
+ */
+#ifndef FAST_TTS_H
+#define FAST_TTS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Synthesize to the request's "output" path. */
+int fast_tts_synthesize(const char *request_json);
+
+/* Synthesize into memory; free *out_data with fast_tts_bytes_free. */
+int fast_tts_synthesize_bytes(const char *request_json, uint8_t **out_data, size_t *out_len);
+void fast_tts_bytes_free(uint8_t *data, size_t len);
+
+/* Google voice catalog / provider capabilities as JSON; free with fast_tts_string_free. */
+int fast_tts_list_voices_json(char **out_json);
+int fast_tts_providers_json(char **out_json);
+void fast_tts_string_free(char *s);
+
+/* Last error on the calling thread, or NULL. Owned by the library. */
+const char *fast_tts_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FAST_TTS_H */
@@ -0,0 +1,106 @@
+//! `fast-tts announce`: speak stdin line by line as it arrives, so status
+//! messages can be piped in (`long_job | fast-tts announce`).
+
+use anyhow::{Context, Result};
+use fast_tts::SynthesisRequest;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+/// Synthesize each non-empty stdin line with `make_request` and play them in
+/// order, never overlapping, or with `output` append them to that file or
+/// FIFO, which stays open for the whole run. The next line is synthesized
+/// while the current one plays. Returns once stdin closes and the queue is
+/// spoken, or on Ctrl+C.
+pub async fn run(
+    extension: &'static str,
+    output: Option<PathBuf>,
+    make_request: impl Fn(&str, &Path) -> Result<SynthesisRequest>,
+) -> Result<()> {
+    // Bounded: synthesis runs at most one line ahead of playback
+    let (ready, mut playable) = mpsc::channel::<PathBuf>(1);
+    // A plain thread, not spawn_blocking: Ctrl+C must not wait for a FIFO reader
+    let player = std::thread::spawn(move || {
+        let mut sink = match &output {
+            Some(path) => Some(
+                std::fs::OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(path)
+                    .with_context(|| format!("failed to open {}", path.display()))?,
+            ),
+            None => None,
+        };
+        while let Some(path) = playable.blocking_recv() {
+            let played = match &mut sink {
+                Some(sink) => std::fs::File::open(&path)
+                    .and_then(|mut audio| std::io::copy(&mut audio, sink))
+                    .map(|_| ())
+                    .map_err(anyhow::Error::from),
+                None => fast_tts::play_audio(&path),
+            };
+            let _ = std::fs::remove_file(&path);
+            match (played, &output) {
+                // The reader went away; nothing more can be delivered
+                (Err(e), Some(output)) => {
+                    return Err(e.context(format!("failed to write {}", output.display())));
+                }
+                (Err(e), None) => eprintln!("Warning: playback failed: {e:#}"),
+                (Ok(()), _) => {}
+            }
+        }
+        Ok(())
+    });
+
+    // Lines wait here as text while earlier ones are spoken
+    let (read, mut lines) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            if read.send(line).is_err() {
+                return;
+            }
+        }
+    });
+
+    let synthesize = async {
+        let mut count = 0u64;
+        while let Some(line) = lines.recv().await {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            count += 1;
+            let path = std::env::temp_dir().join(format!(
+                "fast-tts-announce-{}-{count}.{extension}",
+                std::process::id()
+            ));
+            let result = async {
+                fast_tts::synthesize(&make_request(line, &path)?).await?;
+                Ok::<_, anyhow::Error>(())
+            }
+            .await;
+            match result {
+                // A closed player means Ctrl+C is already tearing down
+                Ok(()) => {
+                    if ready.send(path).await.is_err() {
+                        break;
+                    }
+                }
+                // One bad line shouldn't silence the rest of the job's output
+                Err(e) => eprintln!("Warning: {line}: {e:#}"),
+            }
+        }
+        drop(ready);
+        Ok::<_, anyhow::Error>(())
+    };
+
+    tokio::select! {
+        result = synthesize => {
+            result?;
+            tokio::task::spawn_blocking(move || player.join())
+                .await?
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("audio player thread panicked")))
+        }
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    }
+}
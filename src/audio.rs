@@ -0,0 +1,674 @@
+//! Decoding rendered audio (WAV, MP3, Ogg Vorbis, FLAC) and comparing two
+//! renders, as used by `fast-tts verify`; joining renders end to end,
+//! laying a music bed under speech and tagging MP3s with cover art.
+
+use crate::{AudioEncoding, FastTtsError};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Length of one fingerprint frame.
+const FRAME_MS: u32 = 20;
+/// How far (in frames) renders may be shifted against each other when matching.
+const MAX_LAG_FRAMES: usize = 10;
+
+/// Decoded PCM: interleaved `f32` samples in -1.0..=1.0.
+#[derive(Debug, Clone)]
+pub struct DecodedAudio {
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub samples: Vec<f32>,
+}
+
+pub fn decode(path: &Path) -> Result<DecodedAudio> {
+    let file =
+        std::fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            MediaSourceStream::new(Box::new(file), Default::default()),
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .with_context(|| format!("unrecognized audio format: {}", path.display()))?;
+    let mut format = probed.format;
+    let track = format
+        .default_track()
+        .with_context(|| format!("no audio track in {}", path.display()))?;
+    let track_id = track.id;
+    let mut audio = DecodedAudio {
+        sample_rate: track.codec_params.sample_rate.unwrap_or_default(),
+        channels: track.codec_params.channels.map_or(1, |c| c.count()),
+        samples: Vec::new(),
+    };
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .with_context(|| format!("unsupported codec in {}", path.display()))?;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break;
+            }
+            Err(e) => return Err(e).context(format!("failed to read {}", path.display())),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                buffer.copy_interleaved_ref(decoded);
+                audio.samples.extend_from_slice(buffer.samples());
+                audio.sample_rate = spec.rate;
+                audio.channels = spec.channels.count();
+            }
+            // A corrupt packet costs a few milliseconds, not the whole file
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(e) => return Err(e).context(format!("failed to decode {}", path.display())),
+        }
+    }
+    Ok(audio)
+}
+
+impl DecodedAudio {
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1)
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frames() as f64 / f64::from(self.sample_rate.max(1)))
+    }
+
+    /// Overall RMS level in dBFS (-inf for digital silence).
+    pub fn loudness_dbfs(&self) -> f64 {
+        let sum: f64 = self.samples.iter().map(|&s| f64::from(s).powi(2)).sum();
+        let mean = sum / self.samples.len().max(1) as f64;
+        10.0 * mean.log10()
+    }
+
+    /// Loudness envelope: log energy of the mono mix per 20 ms frame. It
+    /// survives re-encoding and gain changes but not different wording,
+    /// pacing or voice.
+    pub fn fingerprint(&self) -> Vec<f64> {
+        let channels = self.channels.max(1);
+        let frame_len = (self.sample_rate * FRAME_MS / 1000).max(1) as usize * channels;
+        self.samples
+            .chunks(frame_len)
+            .map(|frame| {
+                let energy: f64 = frame.iter().map(|&s| f64::from(s).powi(2)).sum();
+                (energy / frame.len() as f64 + 1e-10).log10()
+            })
+            .collect()
+    }
+
+    /// The audio as a 16-bit PCM WAV file.
+    pub fn to_wav16(&self) -> Vec<u8> {
+        let channels = self.channels.max(1) as u16;
+        let mut fmt = Vec::with_capacity(16);
+        fmt.extend_from_slice(&1u16.to_le_bytes());
+        fmt.extend_from_slice(&channels.to_le_bytes());
+        fmt.extend_from_slice(&self.sample_rate.to_le_bytes());
+        fmt.extend_from_slice(&(self.sample_rate * u32::from(channels) * 2).to_le_bytes());
+        fmt.extend_from_slice(&(channels * 2).to_le_bytes());
+        fmt.extend_from_slice(&16u16.to_le_bytes());
+        let data: Vec<u8> = self
+            .samples
+            .iter()
+            .flat_map(|&s| ((s * 32767.0).round() as i16).to_le_bytes())
+            .collect();
+        wav_file(&fmt, &data)
+    }
+}
+
+/// Best Pearson correlation of two fingerprints over small time shifts,
+/// clamped to 0..=1. Two silent renders count as identical.
+pub fn similarity(a: &[f64], b: &[f64]) -> f64 {
+    let mut best: Option<f64> = None;
+    for lag in 0..=MAX_LAG_FRAMES {
+        for (x, y) in [(a, b), (b, a)] {
+            let Some(x) = x.get(lag..) else { continue };
+            let len = x.len().min(y.len());
+            if len < 2 {
+                continue;
+            }
+            if let Some(r) = correlation(&x[..len], &y[..len]) {
+                best = Some(best.map_or(r, |b: f64| b.max(r)));
+            }
+        }
+    }
+    let flat = |f: &[f64]| f.iter().all(|&e| e <= -9.0);
+    match best {
+        Some(r) => r.clamp(0.0, 1.0),
+        None if flat(a) && flat(b) => 1.0,
+        None => 0.0,
+    }
+}
+
+fn correlation(x: &[f64], y: &[f64]) -> Option<f64> {
+    let n = x.len() as f64;
+    let (mx, my) = (x.iter().sum::<f64>() / n, y.iter().sum::<f64>() / n);
+    let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+    for (a, b) in x.iter().zip(y) {
+        sxy += (a - mx) * (b - my);
+        sxx += (a - mx).powi(2);
+        syy += (b - my).powi(2);
+    }
+    (sxx > 1e-9 && syy > 1e-9).then(|| sxy / (sxx * syy).sqrt())
+}
+
+/// How far two renders may drift apart and still count as equivalent.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    pub duration_ms: f64,
+    pub loudness_db: f64,
+    pub min_similarity: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            duration_ms: 50.0,
+            loudness_db: 1.0,
+            min_similarity: 0.9,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioSummary {
+    pub duration_ms: f64,
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub loudness_dbfs: f64,
+}
+
+impl From<&DecodedAudio> for AudioSummary {
+    fn from(audio: &DecodedAudio) -> Self {
+        Self {
+            duration_ms: audio.duration().as_secs_f64() * 1000.0,
+            sample_rate: audio.sample_rate,
+            channels: audio.channels,
+            loudness_dbfs: audio.loudness_dbfs(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioComparison {
+    pub a: AudioSummary,
+    pub b: AudioSummary,
+    pub similarity: f64,
+    pub equivalent: bool,
+    /// One line per check that failed
+    pub differences: Vec<String>,
+}
+
+pub fn compare(a: &DecodedAudio, b: &DecodedAudio, tolerance: &Tolerance) -> AudioComparison {
+    let (sa, sb) = (AudioSummary::from(a), AudioSummary::from(b));
+    let similarity = similarity(&a.fingerprint(), &b.fingerprint());
+    let mut differences = Vec::new();
+    if sa.sample_rate != sb.sample_rate {
+        differences.push(format!(
+            "sample rate {} Hz vs {} Hz",
+            sa.sample_rate, sb.sample_rate
+        ));
+    }
+    if sa.channels != sb.channels {
+        differences.push(format!("{} vs {} channel(s)", sa.channels, sb.channels));
+    }
+    let duration_diff = (sa.duration_ms - sb.duration_ms).abs();
+    if duration_diff > tolerance.duration_ms {
+        differences.push(format!("duration differs by {duration_diff:.0} ms"));
+    }
+    // Both silent compares equal; -inf minus -inf would be NaN
+    let loudness_diff = if sa.loudness_dbfs == sb.loudness_dbfs {
+        0.0
+    } else {
+        (sa.loudness_dbfs - sb.loudness_dbfs).abs()
+    };
+    if loudness_diff > tolerance.loudness_db {
+        differences.push(format!("loudness differs by {loudness_diff:.1} dB"));
+    }
+    if similarity < tolerance.min_similarity {
+        differences.push(format!("fingerprint similarity {similarity:.3}"));
+    }
+    AudioComparison {
+        a: sa,
+        b: sb,
+        similarity,
+        equivalent: differences.is_empty(),
+        differences,
+    }
+}
+
+/// Speech level (linear peak) above which the bed is ducked, about -34 dBFS.
+const DUCK_THRESHOLD: f32 = 0.02;
+/// How long the bed plays on after the speech, fading out.
+const BED_TAIL: Duration = Duration::from_secs(1);
+
+/// How a music bed sits under speech in [`mix_background`].
+#[derive(Debug, Clone, Copy)]
+pub struct BedMix {
+    /// Level of the bed against the speech, in dB
+    pub gain_db: f32,
+    /// Further change (zero or negative, in dB) while someone is speaking
+    pub duck_db: f32,
+}
+
+/// Lay `bed` under `speech` at the speech's sample rate and the larger
+/// channel count of the two. The bed loops if it is shorter, is ducked by
+/// `duck_db` whenever the speech is louder than about -34 dBFS (dipping
+/// within ~30 ms and recovering over ~300 ms, like a sidechain compressor),
+/// and plays on for a second after the speech, fading out.
+pub fn mix_background(speech: &DecodedAudio, bed: &DecodedAudio, mix: &BedMix) -> DecodedAudio {
+    let rate = speech.sample_rate.max(1);
+    let channels = speech.channels.max(bed.channels).max(1);
+    let speech_frames = speech.frames();
+    let tail_frames = (BED_TAIL.as_secs_f64() * f64::from(rate)) as usize;
+    let bed_frames = bed.frames();
+    // Per-frame smoothing coefficients for a time constant in seconds
+    let coefficient = |seconds: f32| 1.0 - (-1.0 / (seconds * rate as f32)).exp();
+    let (env_attack, env_release) = (coefficient(0.005), coefficient(0.05));
+    let (duck_attack, duck_release) = (coefficient(0.03), coefficient(0.3));
+    let sample = |audio: &DecodedAudio, frame: usize, channel: usize| {
+        let channels = audio.channels.max(1);
+        audio.samples[frame * channels + channel % channels]
+    };
+
+    let mut samples = Vec::with_capacity((speech_frames + tail_frames) * channels);
+    let (mut envelope, mut duck) = (0.0f32, 0.0f32);
+    for frame in 0..speech_frames + tail_frames {
+        let speaking = frame < speech_frames;
+        let peak = if speaking {
+            (0..speech.channels.max(1))
+                .map(|c| sample(speech, frame, c).abs())
+                .fold(0.0, f32::max)
+        } else {
+            0.0
+        };
+        let follow = if peak > envelope {
+            env_attack
+        } else {
+            env_release
+        };
+        envelope += (peak - envelope) * follow;
+        let target = if envelope > DUCK_THRESHOLD {
+            mix.duck_db
+        } else {
+            0.0
+        };
+        let follow = if target < duck {
+            duck_attack
+        } else {
+            duck_release
+        };
+        duck += (target - duck) * follow;
+        let fade = if speaking {
+            1.0
+        } else {
+            1.0 - (frame - speech_frames) as f32 / tail_frames as f32
+        };
+        let gain = 10f32.powf((mix.gain_db + duck) / 20.0) * fade;
+
+        // Linear interpolation into the (looping) bed at its own sample rate
+        let position = frame as f64 * f64::from(bed.sample_rate) / f64::from(rate);
+        let (index, frac) = (position as usize, position.fract() as f32);
+        for channel in 0..channels {
+            let music = if bed_frames == 0 {
+                0.0
+            } else {
+                let a = sample(bed, index % bed_frames, channel);
+                let b = sample(bed, (index + 1) % bed_frames, channel);
+                a + (b - a) * frac
+            };
+            let voice = if speaking {
+                sample(speech, frame, channel)
+            } else {
+                0.0
+            };
+            samples.push((voice + music * gain).clamp(-1.0, 1.0));
+        }
+    }
+    DecodedAudio {
+        sample_rate: rate,
+        channels,
+        samples,
+    }
+}
+
+/// Join renders of the same encoding (and sample rate) into one file:
+/// WAV data chunks are merged under a single header, MP3 parts lose their
+/// tags and Xing/Info frames (which would report only the first part's
+/// length), and Ogg parts become a chained stream. With `crossfade_ms`,
+/// 16-bit PCM WAV parts overlap by that long with a linear fade instead of
+/// a hard cut, which hides clicks at the joins; other audio is always cut.
+pub fn concat(parts: &[Vec<u8>], encoding: AudioEncoding, crossfade_ms: u32) -> Result<Vec<u8>> {
+    match encoding {
+        AudioEncoding::Mp3 => Ok(parts.iter().flat_map(|p| mp3_frames(p)).copied().collect()),
+        AudioEncoding::OggOpus => Ok(parts.concat()),
+        AudioEncoding::Linear16 | AudioEncoding::Mulaw | AudioEncoding::Alaw => {
+            concat_wav(parts, crossfade_ms)
+        }
+    }
+}
+
+fn concat_wav(parts: &[Vec<u8>], crossfade_ms: u32) -> Result<Vec<u8>> {
+    let headered = parts.iter().filter(|p| p.starts_with(b"RIFF")).count();
+    if headered == 0 {
+        // Headerless PCM from providers that send raw samples
+        return Ok(parts.concat());
+    }
+    if headered != parts.len() {
+        return Err(mismatch("some parts are WAV files and some raw PCM"));
+    }
+    let mut format: Option<&[u8]> = None;
+    let mut data = Vec::new();
+    for part in parts {
+        let (fmt, samples) = wav_chunks(part)?;
+        match format {
+            Some(first) if first != fmt => {
+                return Err(mismatch("parts differ in sample rate or sample format"));
+            }
+            _ => format = Some(fmt),
+        }
+        match pcm16_fade_bytes(fmt, crossfade_ms) {
+            Some((fade, frame)) if !data.is_empty() => crossfade(&mut data, samples, fade, frame),
+            _ => data.extend_from_slice(samples),
+        }
+    }
+    Ok(wav_file(format.unwrap_or_default(), &data))
+}
+
+/// A WAV file from a `fmt ` chunk payload and sample data.
+fn wav_file(fmt: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut wav = Vec::with_capacity(20 + fmt.len() + 8 + data.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&((12 + fmt.len() + 8 + data.len()) as u32).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+    wav.extend_from_slice(fmt);
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    wav.extend_from_slice(data);
+    wav
+}
+
+/// WAV renders joined end to end (as [`concat`] does without a crossfade)
+/// split back into `tracks` files of the full length: track `t` holds the
+/// parts with `owners[i] == t` and silence in place of the others.
+pub(crate) fn aligned_tracks(
+    parts: &[Vec<u8>],
+    owners: &[usize],
+    tracks: usize,
+) -> Result<Vec<Vec<u8>>> {
+    let chunks = parts
+        .iter()
+        .map(|part| wav_chunks(part))
+        .collect::<Result<Vec<_>>>()?;
+    let Some((fmt, _)) = chunks.first() else {
+        return Ok(Vec::new());
+    };
+    if chunks.iter().any(|(other, _)| other != fmt) {
+        return Err(mismatch("parts differ in sample rate or sample format"));
+    }
+    let len: usize = chunks.iter().map(|(_, data)| data.len()).sum();
+    Ok((0..tracks)
+        .map(|track| {
+            let mut data = Vec::with_capacity(len);
+            for ((_, samples), &owner) in chunks.iter().zip(owners) {
+                if owner == track {
+                    data.extend_from_slice(samples);
+                } else {
+                    // Zero is silence for the 16-bit PCM tracks are made from
+                    data.resize(data.len() + samples.len(), 0);
+                }
+            }
+            wav_file(fmt, &data)
+        })
+        .collect())
+}
+
+/// Length in bytes of a `crossfade_ms` fade and of one sample frame, for
+/// 16-bit PCM described by the WAV `fmt ` payload.
+fn pcm16_fade_bytes(fmt: &[u8], crossfade_ms: u32) -> Option<(usize, usize)> {
+    let field = |at: usize| {
+        fmt.get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    // 1 is plain PCM, 0xfffe the extensible header some encoders write
+    let pcm = matches!(field(0)?, 1 | 0xfffe) && field(14)? == 16;
+    let channels = usize::from(field(2)?);
+    let rate = u32::from_le_bytes(fmt.get(4..8)?.try_into().ok()?);
+    if !pcm || crossfade_ms == 0 || channels == 0 {
+        return None;
+    }
+    let frame = channels * 2;
+    let frames = u64::from(rate) * u64::from(crossfade_ms) / 1000;
+    Some((frames as usize * frame, frame))
+}
+
+/// Append `next` to `data`, overlapping up to `fade` bytes of each with a
+/// linear fade out of `data` and fade in of `next`.
+fn crossfade(data: &mut Vec<u8>, next: &[u8], fade: usize, frame: usize) {
+    let len = fade.min(data.len()).min(next.len()) / frame * frame;
+    let frames = len / frame;
+    let tail = data.len() - len;
+    for (idx, (out, incoming)) in data[tail..]
+        .chunks_exact_mut(2)
+        .zip(next[..len].chunks_exact(2))
+        .enumerate()
+    {
+        let t = ((idx * 2 / frame) as f32 + 0.5) / frames as f32;
+        let a = f32::from(i16::from_le_bytes([out[0], out[1]]));
+        let b = f32::from(i16::from_le_bytes([incoming[0], incoming[1]]));
+        let mixed = (a * (1.0 - t) + b * t).round() as i16;
+        out.copy_from_slice(&mixed.to_le_bytes());
+    }
+    data.extend_from_slice(&next[len..]);
+}
+
+fn mismatch(what: &str) -> anyhow::Error {
+    FastTtsError::Unsupported(format!("cannot join audio: {what}")).into()
+}
+
+/// The `fmt ` and `data` chunk payloads of a WAV file.
+fn wav_chunks(wav: &[u8]) -> Result<(&[u8], &[u8])> {
+    if wav.get(8..12) != Some(b"WAVE") {
+        return Err(mismatch("not a WAV file"));
+    }
+    let (mut fmt, mut pos) = (None, 12);
+    while let Some(header) = wav.get(pos..pos + 8) {
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let start = pos + 8;
+        // Streamed WAVs leave the data size at 0 or u32::MAX
+        let end = start.saturating_add(size).min(wav.len());
+        match &header[..4] {
+            b"fmt " => fmt = Some(&wav[start..end]),
+            b"data" => {
+                let end = if size == 0 { wav.len() } else { end };
+                return Ok((
+                    fmt.ok_or_else(|| mismatch("WAV data before format"))?,
+                    &wav[start..end],
+                ));
+            }
+            _ => {}
+        }
+        pos = end + (size & 1);
+    }
+    Err(mismatch("WAV file has no data"))
+}
+
+/// The audio frames of an MP3 file, without ID3 tags or a leading Xing/Info frame.
+fn mp3_frames(mp3: &[u8]) -> &[u8] {
+    let mut frames = mp3;
+    if frames.starts_with(b"ID3") && frames.len() >= 10 {
+        let size = frames[6..10]
+            .iter()
+            .fold(0usize, |size, &b| (size << 7) | usize::from(b & 0x7f));
+        let footer = if frames[5] & 0x10 != 0 { 10 } else { 0 };
+        frames = frames.get(10 + size + footer..).unwrap_or_default();
+    }
+    if frames.len() >= 128 && frames[frames.len() - 128..].starts_with(b"TAG") {
+        frames = &frames[..frames.len() - 128];
+    }
+    if let Some(len) = mp3_frame_len(frames)
+        && frames
+            .get(..len.min(200))
+            .is_some_and(|f| f.windows(4).any(|w| w == b"Xing" || w == b"Info"))
+    {
+        frames = frames.get(len..).unwrap_or_default();
+    }
+    frames
+}
+
+/// The MIME type of a PNG or JPEG image, the formats podcast apps show as
+/// cover art.
+pub fn image_mime(image: &[u8]) -> Option<&'static str> {
+    if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if image.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else {
+        None
+    }
+}
+
+/// `mp3` with `image` (PNG or JPEG) as its front cover in an ID3v2 `APIC`
+/// frame. Frames of an existing plain ID3v2.3/2.4 tag are kept, except an
+/// earlier picture; other tags (2.2, unsynchronised, extended headers) are
+/// replaced.
+pub fn embed_cover(mp3: &[u8], image: &[u8]) -> Result<Vec<u8>> {
+    let mime = image_mime(image).ok_or_else(|| {
+        FastTtsError::InvalidInput("cover art must be a PNG or JPEG image".into())
+    })?;
+    let (version, mut frames, audio) = match id3v2_tag(mp3) {
+        Some((version, frames, audio)) => (version, kept_id3_frames(version, frames), audio),
+        None => (3, Vec::new(), mp3),
+    };
+    let mut apic = Vec::with_capacity(mime.len() + image.len() + 4);
+    // ISO-8859-1 text, MIME type, front cover, empty description
+    apic.push(0);
+    apic.extend_from_slice(mime.as_bytes());
+    apic.push(0);
+    apic.push(3);
+    apic.push(0);
+    apic.extend_from_slice(image);
+    frames.extend_from_slice(b"APIC");
+    frames.extend_from_slice(&id3_size(version, apic.len())?);
+    frames.extend_from_slice(&[0, 0]);
+    frames.extend_from_slice(&apic);
+
+    let mut tagged = Vec::with_capacity(10 + frames.len() + audio.len());
+    tagged.extend_from_slice(&[b'I', b'D', b'3', version, 0, 0]);
+    tagged.extend_from_slice(&syncsafe(frames.len())?);
+    tagged.extend_from_slice(&frames);
+    tagged.extend_from_slice(audio);
+    Ok(tagged)
+}
+
+/// An ID3v2 tag at the start of `mp3`: its major version, the frame bytes
+/// when it is a plain 2.3/2.4 tag (else empty), and the audio after it.
+fn id3v2_tag(mp3: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    if !mp3.starts_with(b"ID3") || mp3.len() < 10 {
+        return None;
+    }
+    let size = mp3[6..10]
+        .iter()
+        .fold(0usize, |size, &b| (size << 7) | usize::from(b & 0x7f));
+    let footer = if mp3[5] & 0x10 != 0 { 10 } else { 0 };
+    let frames = mp3.get(10..10 + size).unwrap_or_default();
+    let audio = mp3.get(10 + size + footer..).unwrap_or_default();
+    match mp3[3] {
+        version @ (3 | 4) if mp3[5] & 0xc0 == 0 => Some((version, frames, audio)),
+        _ => Some((3, &[], audio)),
+    }
+}
+
+/// The frames of a plain tag, without padding or an earlier `APIC`.
+fn kept_id3_frames(version: u8, mut frames: &[u8]) -> Vec<u8> {
+    let mut kept = Vec::with_capacity(frames.len());
+    while frames.len() >= 10 && frames[0] != 0 {
+        let raw = [frames[4], frames[5], frames[6], frames[7]];
+        let size = if version == 4 {
+            raw.iter()
+                .fold(0usize, |size, &b| (size << 7) | usize::from(b & 0x7f))
+        } else {
+            u32::from_be_bytes(raw) as usize
+        };
+        let Some(frame) = frames.get(..10 + size) else {
+            break;
+        };
+        if &frame[..4] != b"APIC" {
+            kept.extend_from_slice(frame);
+        }
+        frames = &frames[10 + size..];
+    }
+    kept
+}
+
+/// A frame size as the tag's version writes it.
+fn id3_size(version: u8, len: usize) -> Result<[u8; 4]> {
+    if version == 4 {
+        syncsafe(len)
+    } else {
+        Ok((len as u32).to_be_bytes())
+    }
+}
+
+/// `len` as four 7-bit bytes, the ID3v2 header (and 2.4 frame) size format.
+fn syncsafe(len: usize) -> Result<[u8; 4]> {
+    if len >= 1 << 28 {
+        return Err(
+            FastTtsError::InvalidInput("cover art is too large for an ID3 tag".into()).into(),
+        );
+    }
+    Ok([
+        (len >> 21) as u8 & 0x7f,
+        (len >> 14) as u8 & 0x7f,
+        (len >> 7) as u8 & 0x7f,
+        len as u8 & 0x7f,
+    ])
+}
+
+/// Byte length of the MPEG Layer III frame starting at `frame[0]`.
+fn mp3_frame_len(frame: &[u8]) -> Option<usize> {
+    const MPEG1_KBPS: [usize; 15] = [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ];
+    const MPEG2_KBPS: [usize; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+    let h = frame.get(..4)?;
+    if h[0] != 0xff || h[1] & 0xe0 != 0xe0 || (h[1] >> 1) & 0b11 != 0b01 {
+        return None;
+    }
+    let version = (h[1] >> 3) & 0b11;
+    let mpeg1 = version == 0b11;
+    let table = if mpeg1 { MPEG1_KBPS } else { MPEG2_KBPS };
+    let kbps = *table.get(usize::from(h[2] >> 4))?;
+    let base_hz = *[44100, 48000, 32000].get(usize::from((h[2] >> 2) & 0b11))?;
+    let hz = match version {
+        0b11 => base_hz,
+        0b10 => base_hz / 2,
+        0b00 => base_hz / 4,
+        _ => return None,
+    };
+    let padding = usize::from((h[2] >> 1) & 1);
+    let per_frame = if mpeg1 { 144 } else { 72 };
+    (kbps > 0).then(|| per_frame * kbps * 1000 / hz + padding)
+}
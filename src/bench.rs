@@ -0,0 +1,126 @@
+//! `fast-tts bench`: time repeated syntheses of the same text per provider,
+//! reporting time to first byte and total time as p50/p95.
+
+use crate::{AudioEncoding, CancellationToken, FastTtsError, Provider, SynthesisRequest};
+use anyhow::Result;
+use serde::Serialize;
+
+/// One timed synthesis.
+pub struct Sample {
+    pub first_byte_ms: u64,
+    pub total_ms: u64,
+    pub bytes: u64,
+}
+
+/// Stream `request` to the end, timing it; nothing is written.
+pub async fn measure(request: &SynthesisRequest) -> Result<Sample> {
+    use futures::StreamExt as _;
+    let started = std::time::Instant::now();
+    let mut stream = crate::synthesize_stream(request).await?;
+    let mut first_byte = None;
+    let mut bytes = 0u64;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        if !chunk.is_empty() {
+            first_byte.get_or_insert_with(|| started.elapsed().as_millis() as u64);
+        }
+        bytes += chunk.len() as u64;
+    }
+    let total_ms = started.elapsed().as_millis() as u64;
+    Ok(Sample {
+        first_byte_ms: first_byte.unwrap_or(total_ms),
+        total_ms,
+        bytes,
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct Percentiles {
+    pub p50: Option<u64>,
+    pub p95: Option<u64>,
+}
+
+impl Percentiles {
+    fn of(samples: &mut [u64]) -> Self {
+        Self {
+            p50: percentile(samples, 50),
+            p95: percentile(samples, 95),
+        }
+    }
+}
+
+/// Nearest-rank percentile of `samples` (sorted in place).
+fn percentile(samples: &mut [u64], pct: usize) -> Option<u64> {
+    samples.sort_unstable();
+    let rank = (samples.len() * pct).div_ceil(100).max(1);
+    samples.get(rank - 1).copied()
+}
+
+/// One provider's benchmark.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchResult {
+    pub provider: String,
+    /// Successful runs
+    pub runs: usize,
+    pub errors: usize,
+    pub first_byte_ms: Percentiles,
+    pub total_ms: Percentiles,
+    pub avg_bytes: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/// Synthesize `text` `runs` times with each provider, one run at a time.
+/// Failed runs are counted, not fatal, unless `cancel` stopped them.
+pub async fn run(
+    providers: &[Provider],
+    text: &str,
+    runs: usize,
+    encoding: AudioEncoding,
+    cancel: &CancellationToken,
+) -> Result<Vec<BenchResult>> {
+    let mut results = Vec::new();
+    for &provider in providers {
+        let request = SynthesisRequest::builder()
+            .provider(provider)
+            .text(text)
+            .encoding(encoding)
+            .cancellation(cancel.clone())
+            .build()?;
+        let (mut first_byte, mut total, mut bytes) = (Vec::new(), Vec::new(), Vec::new());
+        let mut errors = 0;
+        let mut last_error = None;
+        for _ in 0..runs {
+            match measure(&request).await {
+                Ok(sample) => {
+                    first_byte.push(sample.first_byte_ms);
+                    total.push(sample.total_ms);
+                    bytes.push(sample.bytes);
+                }
+                Err(e) if cancel.is_cancelled() => return Err(e),
+                Err(e) => {
+                    errors += 1;
+                    last_error = Some(format!("{e:#}"));
+                }
+            }
+        }
+        results.push(BenchResult {
+            provider: provider.to_string(),
+            runs: total.len(),
+            errors,
+            first_byte_ms: Percentiles::of(&mut first_byte),
+            total_ms: Percentiles::of(&mut total),
+            avg_bytes: (!bytes.is_empty()).then(|| bytes.iter().sum::<u64>() / bytes.len() as u64),
+            last_error,
+        });
+    }
+    Ok(results)
+}
+
+/// The `bench` exit status: failure only when no run succeeded at all.
+pub fn outcome(results: &[BenchResult]) -> Result<()> {
+    if results.iter().all(|r| r.runs == 0) {
+        return Err(FastTtsError::Other("every benchmark run failed".into()).into());
+    }
+    Ok(())
+}
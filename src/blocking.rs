@@ -0,0 +1,51 @@
+//! Blocking wrappers over the async API for programs without their own tokio
+//! runtime. Calls run on a shared background runtime, so they must not be made
+//! from inside an async context (tokio panics on nested `block_on`).
+//!
+//! ```no_run
+//! use fast_tts::{AudioEncoding, SynthesisRequest};
+//! let request = SynthesisRequest::builder()
+//!     .text("Hello")
+//!     .output("hello.mp3")
+//!     .encoding(AudioEncoding::Mp3)
+//!     .build()?;
+//! fast_tts::blocking::synthesize(&request)?;
+//! # anyhow::Ok(())
+//! ```
+
+use crate::{ListVoicesResponse, SynthesisRequest};
+use anyhow::Result;
+use std::sync::OnceLock;
+
+fn runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("failed to start the fast_tts runtime")
+    })
+}
+
+/// Blocking [`crate::synthesize`].
+pub fn synthesize(request: &SynthesisRequest) -> Result<()> {
+    runtime().block_on(crate::synthesize(request))
+}
+
+/// Synthesize `request` into memory; its output path is ignored.
+pub fn synthesize_bytes(request: &SynthesisRequest) -> Result<Vec<u8>> {
+    runtime().block_on(async {
+        use futures::StreamExt as _;
+        let mut stream = crate::synthesize_stream(request).await?;
+        let mut audio = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            audio.extend_from_slice(&chunk?);
+        }
+        Ok(audio)
+    })
+}
+
+/// Blocking [`crate::list_voices`].
+pub fn list_voices() -> Result<ListVoicesResponse> {
+    runtime().block_on(crate::list_voices())
+}
@@ -0,0 +1,61 @@
+//! `fast-tts cache`: the on-disk cache of voice catalogs and access tokens
+//! (FAST_TTS_CACHE_DIR, else the user cache directory).
+
+use crate::FastTtsError;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What the cache holds, or with [`clear`] what it held.
+#[derive(Debug, Serialize)]
+pub struct CacheReport {
+    pub dir: PathBuf,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// The cache directory, whether or not it exists yet.
+pub fn dir() -> Result<PathBuf> {
+    crate::cache_dir()
+        .ok_or_else(|| FastTtsError::Other("cannot determine the cache directory".into()).into())
+}
+
+/// Count the files under the cache directory.
+pub fn info() -> Result<CacheReport> {
+    let dir = dir()?;
+    let (files, bytes) = usage(&dir)?;
+    Ok(CacheReport { dir, files, bytes })
+}
+
+/// Delete everything in the cache; what was removed.
+pub fn clear() -> Result<CacheReport> {
+    let report = info()?;
+    match fs::remove_dir_all(&report.dir) {
+        Ok(()) => Ok(report),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(report),
+        Err(e) => Err(e).with_context(|| format!("failed to clear {}", report.dir.display())),
+    }
+}
+
+fn usage(dir: &Path) -> Result<(usize, u64)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((0, 0)),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", dir.display())),
+    };
+    let (mut files, mut bytes) = (0, 0);
+    for entry in entries {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        if meta.is_dir() {
+            let (f, b) = usage(&entry.path())?;
+            files += f;
+            bytes += b;
+        } else {
+            files += 1;
+            bytes += meta.len();
+        }
+    }
+    Ok((files, bytes))
+}
@@ -0,0 +1,43 @@
+//! Split long text into provider-sized pieces at natural boundaries.
+
+/// Split `text` into pieces of at most `max_chars` characters, preferring to
+/// break after a sentence, then at a line break, then between words. Only a
+/// single word longer than `max_chars` is cut mid-word.
+pub(crate) fn split(text: &str, max_chars: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = text.trim();
+    while rest.chars().count() > max_chars {
+        let limit = rest
+            .char_indices()
+            .nth(max_chars)
+            .map_or(rest.len(), |(i, _)| i);
+        let window = &rest[..limit];
+        let at = last_break(window, |prev, _| matches!(prev, '.' | '!' | '?' | '…'))
+            .or_else(|| last_break(window, |_, next| next == '\n'))
+            .or_else(|| last_break(window, |_, next| next.is_whitespace()))
+            .unwrap_or(limit);
+        chunks.push(rest[..at].trim_end());
+        rest = rest[at..].trim_start();
+    }
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+/// Byte offset of the last whitespace in `window` where `is_break(previous
+/// char, whitespace char)` holds.
+fn last_break(window: &str, is_break: impl Fn(char, char) -> bool) -> Option<usize> {
+    let mut found = None;
+    let mut prev = None;
+    for (i, c) in window.char_indices() {
+        if let Some(p) = prev
+            && c.is_whitespace()
+            && is_break(p, c)
+        {
+            found = Some(i);
+        }
+        prev = Some(c);
+    }
+    found
+}
@@ -0,0 +1,168 @@
+//! `fast-tts clip [--watch]`: speak (or save) text copied to the clipboard.
+
+use anyhow::{Context, Result};
+use fast_tts::{FastTtsError, SynthesisRequest};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub struct ClipOptions {
+    pub watch: bool,
+    /// Shorter copies (after trimming) are ignored
+    pub min_chars: usize,
+    /// Save each copy here instead of speaking it
+    pub out_dir: Option<PathBuf>,
+    pub interval: Duration,
+    /// File extension for the requested encoding
+    pub extension: &'static str,
+}
+
+/// Read the clipboard once, or with `watch` every `interval` until Ctrl+C.
+/// `make_request` turns a copied text and output path into a request.
+pub async fn run(
+    options: ClipOptions,
+    make_request: impl Fn(&str, &Path) -> Result<SynthesisRequest>,
+) -> Result<()> {
+    let mut clipboard = Clipboard::open()?;
+    if !options.watch {
+        let text = clipboard
+            .get_text()
+            .map_err(|e| FastTtsError::InvalidInput(format!("clipboard holds no text: {e}")))?;
+        return speak(&options, &make_request, text.trim(), 1).await;
+    }
+
+    // Poll on a thread: clipboard handles aren't Send everywhere
+    let (texts, mut copied) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        // Only copies made after startup count
+        let mut last = clipboard.get_text().ok();
+        loop {
+            std::thread::sleep(options.interval);
+            let Ok(text) = clipboard.get_text() else {
+                continue;
+            };
+            if last.as_ref() != Some(&text) {
+                last = Some(text.clone());
+                if texts.send(text).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    let (toggles, mut toggled) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for _ in std::io::stdin().lines() {
+            if toggles.send(()).is_err() {
+                return;
+            }
+        }
+    });
+
+    eprintln!("Watching the clipboard; press Enter to pause/resume, Ctrl+C to stop");
+    let mut paused = false;
+    let mut count = 0;
+    loop {
+        tokio::select! {
+            Some(()) = toggled.recv() => {
+                paused = !paused;
+                eprintln!("{}", if paused { "Paused" } else { "Resumed" });
+            }
+            text = copied.recv() => {
+                let Some(text) = text else { return Ok(()) };
+                let text = text.trim();
+                if paused || text.chars().count() < options.min_chars {
+                    continue;
+                }
+                count += 1;
+                // One bad copy (say, too long for the provider) shouldn't end the session
+                if let Err(e) = speak(&options, &make_request, text, count).await {
+                    eprintln!("Warning: {e:#}");
+                }
+            }
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+async fn speak(
+    options: &ClipOptions,
+    make_request: &impl Fn(&str, &Path) -> Result<SynthesisRequest>,
+    text: &str,
+    count: usize,
+) -> Result<()> {
+    if text.is_empty() {
+        return Err(FastTtsError::InvalidInput("clipboard is empty".into()).into());
+    }
+    let output = match &options.out_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)?;
+            dir.join(format!(
+                "clip-{}-{count:03}.{}",
+                unix_time(),
+                options.extension
+            ))
+        }
+        None => std::env::temp_dir().join(format!(
+            "fast-tts-clip-{}.{}",
+            std::process::id(),
+            options.extension
+        )),
+    };
+    fast_tts::synthesize(&make_request(text, &output)?).await?;
+    if options.out_dir.is_some() {
+        println!("Wrote {}", output.display());
+        return Ok(());
+    }
+    let played = tokio::task::spawn_blocking({
+        let output = output.clone();
+        move || fast_tts::play_audio(&output)
+    })
+    .await?;
+    let _ = std::fs::remove_file(&output);
+    played
+}
+
+/// The system clipboard, or the output of FAST_TTS_CLIPBOARD (a command such
+/// as `wl-paste` or `xclip -selection clipboard -o`) when that is set.
+enum Clipboard {
+    System(arboard::Clipboard),
+    Command(Vec<String>),
+}
+
+impl Clipboard {
+    fn open() -> Result<Self> {
+        if let Ok(command) = std::env::var("FAST_TTS_CLIPBOARD")
+            && !command.trim().is_empty()
+        {
+            return Ok(Self::Command(
+                command.split_whitespace().map(String::from).collect(),
+            ));
+        }
+        Ok(Self::System(
+            arboard::Clipboard::new().context("cannot access the clipboard")?,
+        ))
+    }
+
+    fn get_text(&mut self) -> Result<String> {
+        match self {
+            Self::System(clipboard) => Ok(clipboard.get_text()?),
+            Self::Command(command) => {
+                let output = std::process::Command::new(&command[0])
+                    .args(&command[1..])
+                    .stderr(std::process::Stdio::inherit())
+                    .output()
+                    .with_context(|| format!("failed to run {}", command[0]))?;
+                if !output.status.success() {
+                    anyhow::bail!("{} exited with {}", command[0], output.status);
+                }
+                Ok(String::from_utf8(output.stdout)?)
+            }
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,134 @@
+//! `fast-tts compare`: the same text from several providers and voices,
+//! written side by side with an `index.html` to listen to them.
+
+use crate::{AudioEncoding, CancellationToken, FastTtsError, Provider, SynthesisRequest, slug};
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// One provider/voice pair's file.
+#[derive(Debug, Serialize)]
+pub struct CompareResult {
+    pub provider: String,
+    /// The voice asked for, else the provider's default
+    pub voice: String,
+    /// File name in the output directory
+    pub file: String,
+    pub error: Option<String>,
+}
+
+/// Every provider/voice pair: each `PROVIDER=VOICE` entry, plus the default
+/// voice for providers without one.
+pub fn pairs(providers: &[Provider], voices: &[String]) -> Result<Vec<(Provider, Option<String>)>> {
+    let mut explicit = Vec::new();
+    for entry in voices {
+        let (provider, voice) = entry.split_once('=').ok_or_else(|| {
+            FastTtsError::InvalidInput(format!("--voice expects PROVIDER=VOICE, got {entry:?}"))
+        })?;
+        explicit.push((crate::parse_provider(provider)?, voice.to_string()));
+    }
+    let mut pairs = Vec::new();
+    for &provider in providers {
+        let mut chosen: Vec<_> = explicit
+            .iter()
+            .filter(|(p, _)| *p == provider)
+            .map(|(_, v)| Some(v.clone()))
+            .collect();
+        if chosen.is_empty() {
+            chosen.push(None);
+        }
+        pairs.extend(chosen.into_iter().map(|voice| (provider, voice)));
+    }
+    Ok(pairs)
+}
+
+/// Synthesize `text` for every pair into `out_dir`, reporting each result as
+/// it comes, then write the index; its path and the results.
+pub async fn run(
+    text: &str,
+    providers: &[Provider],
+    voices: &[String],
+    out_dir: &Path,
+    encoding: AudioEncoding,
+    cancel: &CancellationToken,
+    mut on_result: impl FnMut(&CompareResult),
+) -> Result<(PathBuf, Vec<CompareResult>)> {
+    let pairs = pairs(providers, voices)?;
+    std::fs::create_dir_all(out_dir)?;
+    let mut results = Vec::new();
+    for (idx, (provider, voice)) in pairs.into_iter().enumerate() {
+        let label = voice
+            .clone()
+            .unwrap_or_else(|| provider.capabilities().default_voice.to_string());
+        let file = format!(
+            "{:02}-{}{}.{}",
+            idx + 1,
+            slug(&provider.to_string()),
+            if label.is_empty() {
+                String::new()
+            } else {
+                format!("-{}", slug(&label))
+            },
+            encoding.file_extension()
+        );
+        let request = SynthesisRequest::builder()
+            .provider(provider)
+            .text(text)
+            .voice(voice)
+            .encoding(encoding)
+            .output(out_dir.join(&file))
+            .cancellation(cancel.clone())
+            .build()?;
+        let error = match crate::synthesize(&request).await {
+            Ok(()) => None,
+            Err(e) if cancel.is_cancelled() => return Err(e),
+            Err(e) => Some(format!("{e:#}")),
+        };
+        let result = CompareResult {
+            provider: provider.to_string(),
+            voice: label,
+            file,
+            error,
+        };
+        on_result(&result);
+        results.push(result);
+    }
+
+    let index = out_dir.join("index.html");
+    std::fs::write(&index, index_html(text, &results))?;
+    Ok((index, results))
+}
+
+/// The `compare` exit status: failure only when every provider failed.
+pub fn outcome(results: &[CompareResult]) -> Result<()> {
+    if results.iter().all(|r| r.error.is_some()) {
+        return Err(FastTtsError::Other("every provider failed".into()).into());
+    }
+    Ok(())
+}
+
+fn index_html(text: &str, results: &[CompareResult]) -> String {
+    use htmlescape::encode_minimal as esc;
+    let rows: String = results
+        .iter()
+        .map(|r| {
+            let player = match &r.error {
+                None => format!(
+                    "<audio controls preload=\"none\" src=\"{}\"></audio>",
+                    esc(&r.file)
+                ),
+                Some(e) => format!("<em>failed: {}</em>", esc(e)),
+            };
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{player}</td></tr>\n",
+                esc(&r.provider),
+                esc(&r.voice)
+            )
+        })
+        .collect();
+    format!(
+        "<!doctype html>\n<html><head><meta charset=\"utf-8\"><title>fast-tts compare</title></head>\n\
+         <body><p>{}</p>\n<table>\n<tr><th>Provider</th><th>Voice</th><th>Audio</th></tr>\n{rows}</table></body></html>\n",
+        esc(text)
+    )
+}
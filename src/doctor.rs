@@ -0,0 +1,110 @@
+//! `fast-tts doctor`: check the local setup without touching the network —
+//! which providers have credentials, which helper programs are installed and
+//! whether the cache is writable.
+
+use crate::{FastTtsError, Provider, config_var, find_program};
+use anyhow::Result;
+use serde::Serialize;
+
+/// One line of the report.
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: String,
+    /// `ok`; `warn` (some features won't work); `fail` (nothing will)
+    pub status: &'static str,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, ok: bool, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: if ok { "ok" } else { "warn" },
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Run every check.
+pub fn run() -> Vec<Check> {
+    let mut checks = Vec::new();
+    let mut configured = 0;
+    for p in Provider::all() {
+        let caps = p.capabilities();
+        if !caps.enabled {
+            continue;
+        }
+        configured += usize::from(caps.configured);
+        checks.push(Check::new(
+            format!("provider {p}"),
+            caps.configured,
+            if caps.configured {
+                "credentials found"
+            } else {
+                "credentials not set"
+            },
+        ));
+    }
+    if configured == 0 {
+        checks.push(Check {
+            name: "credentials".into(),
+            status: "fail",
+            detail: "no provider has credentials; see `fast-tts-cli auth check`".into(),
+        });
+    }
+
+    let ffmpeg = config_var("FAST_TTS_FFMPEG").unwrap_or_else(|| "ffmpeg".into());
+    checks.push(program_check(
+        "ffmpeg",
+        &[ffmpeg.as_str()],
+        "needed to re-encode Opus and for local ONNX voices",
+    ));
+    let players: &[&str] = if cfg!(target_os = "macos") {
+        &["afplay"]
+    } else if cfg!(windows) {
+        &["cmd"]
+    } else {
+        &["ffplay", "mpv", "paplay", "aplay"]
+    };
+    checks.push(program_check(
+        "player",
+        players,
+        "--play and speak need an audio player",
+    ));
+
+    checks.push(
+        match crate::cache::dir().and_then(|dir| writable(&dir).map(|()| dir)) {
+            Ok(dir) => Check::new("cache", true, dir.display().to_string()),
+            Err(e) => Check::new("cache", false, format!("{e:#}")),
+        },
+    );
+    checks
+}
+
+/// The first of `programs` that is installed.
+fn program_check(name: &str, programs: &[&str], missing: &str) -> Check {
+    match programs.iter().find_map(|p| find_program(p)) {
+        Some(path) => Check::new(name, true, path.display().to_string()),
+        None => Check::new(
+            name,
+            false,
+            format!("{} not found; {missing}", programs.join(", ")),
+        ),
+    }
+}
+
+fn writable(dir: &std::path::Path) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let probe = dir.join(".doctor");
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(probe)?;
+    Ok(())
+}
+
+/// The `doctor` exit status: warnings pass, failures don't.
+pub fn outcome(checks: &[Check]) -> Result<()> {
+    if checks.iter().any(|c| c.status == "fail") {
+        return Err(FastTtsError::Auth("no provider has credentials".into()).into());
+    }
+    Ok(())
+}
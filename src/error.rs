@@ -0,0 +1,140 @@
+//! Failure classes with stable process exit codes. Errors still travel as
+//! `anyhow::Error`; typed [`FastTtsError`]s raised at the source (or inferred
+//! from HTTP and I/O errors) decide the class.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum FastTtsError {
+    /// Credentials rejected
+    #[error("{0}")]
+    Auth(String),
+    /// Credentials not configured; same class as `Auth` on the command line
+    #[error("{0}")]
+    MissingCredentials(String),
+    /// Provider rate limit or quota exhausted (HTTP 429)
+    #[error("{0}")]
+    Quota(String),
+    /// Bad arguments, config or request parameters
+    #[error("{0}")]
+    InvalidInput(String),
+    /// The provider answered with an error status, or could not be reached
+    #[error("{message}")]
+    ProviderHttp {
+        status: Option<u16>,
+        message: String,
+    },
+    /// Reading or writing local files
+    #[error("{0}")]
+    Io(String),
+    /// Provider, feature or mode not available in this build or configuration
+    #[error("{0}")]
+    Unsupported(String),
+    /// Network access refused by `--offline`; same class as `Unsupported` on
+    /// the command line
+    #[error("{0}")]
+    Offline(String),
+    /// Stopped through a cancellation token, e.g. on Ctrl+C
+    #[error("{0}")]
+    Cancelled(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+/// `--json` error payload written to stderr.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorReport {
+    pub kind: &'static str,
+    pub exit_code: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    pub message: String,
+}
+
+impl FastTtsError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Auth(_) | Self::MissingCredentials(_) => "auth",
+            Self::Quota(_) => "quota",
+            Self::InvalidInput(_) => "invalid_input",
+            Self::ProviderHttp { .. } => "provider_http",
+            Self::Io(_) => "io",
+            Self::Unsupported(_) | Self::Offline(_) => "unsupported",
+            Self::Cancelled(_) => "cancelled",
+            Self::Other(_) => "other",
+        }
+    }
+
+    /// Documented exit codes; 2 matches clap's own usage errors and 130 the
+    /// shell's code for a process stopped by SIGINT.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Other(_) => 1,
+            Self::InvalidInput(_) => 2,
+            Self::Auth(_) | Self::MissingCredentials(_) => 3,
+            Self::Quota(_) => 4,
+            Self::ProviderHttp { .. } => 5,
+            Self::Io(_) => 6,
+            Self::Unsupported(_) | Self::Offline(_) => 7,
+            Self::Cancelled(_) => 130,
+        }
+    }
+
+    /// Class of `err`: a typed error anywhere in the chain (or added as
+    /// context) wins, then HTTP status and I/O errors. The message keeps the
+    /// full context chain.
+    pub fn classify(err: &anyhow::Error) -> Self {
+        let message = format!("{err:#}");
+        if let Some(typed) = err
+            .downcast_ref::<Self>()
+            .or_else(|| err.chain().find_map(|e| e.downcast_ref::<Self>()))
+        {
+            return typed.with_message(message);
+        }
+        if let Some(http) = err.chain().find_map(|e| e.downcast_ref::<reqwest::Error>()) {
+            return match http.status().map(|s| s.as_u16()) {
+                Some(401 | 403) => Self::Auth(message),
+                Some(429) => Self::Quota(message),
+                status => Self::ProviderHttp { status, message },
+            };
+        }
+        if err
+            .chain()
+            .any(|e| e.downcast_ref::<std::io::Error>().is_some())
+        {
+            return Self::Io(message);
+        }
+        Self::Other(message)
+    }
+
+    fn with_message(&self, message: String) -> Self {
+        match self {
+            Self::Auth(_) => Self::Auth(message),
+            Self::MissingCredentials(_) => Self::MissingCredentials(message),
+            Self::Quota(_) => Self::Quota(message),
+            Self::InvalidInput(_) => Self::InvalidInput(message),
+            Self::ProviderHttp { status, .. } => Self::ProviderHttp {
+                status: *status,
+                message,
+            },
+            Self::Io(_) => Self::Io(message),
+            Self::Unsupported(_) => Self::Unsupported(message),
+            Self::Offline(_) => Self::Offline(message),
+            Self::Cancelled(_) => Self::Cancelled(message),
+            Self::Other(_) => Self::Other(message),
+        }
+    }
+
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            kind: self.kind(),
+            exit_code: self.exit_code(),
+            http_status: match self {
+                Self::ProviderHttp { status, .. } => *status,
+                _ => None,
+            },
+            message: self.to_string(),
+        }
+    }
+}
@@ -0,0 +1,733 @@
+//! RSS 0.9x/2.0, RSS 1.0 (RDF) and Atom feeds, plus just enough HTML
+//! handling to turn an article into speakable text (`fast-tts feed`), and
+//! podcast feeds for the audio it produces (`fast-tts podcast publish`).
+
+use crate::{CancellationToken, FastTtsError};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write as _;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedEntry {
+    /// `<guid>` or `<id>`, falling back to the link, then the title
+    pub guid: String,
+    pub title: String,
+    pub link: Option<String>,
+    /// Publication date as written in the feed (RFC 2822 or RFC 3339)
+    pub published: Option<String>,
+    /// Full article HTML (`content:encoded`, Atom `<content>`)
+    #[serde(skip)]
+    pub content: Option<String>,
+    /// Teaser HTML (`<description>`, Atom `<summary>`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Feed {
+    pub title: String,
+    /// Entries in document order, which for nearly every feed is newest first
+    pub entries: Vec<FeedEntry>,
+}
+
+pub fn parse(xml: &str) -> Result<Feed> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let doc = roxmltree::Document::parse_with_options(xml, options)
+        .map_err(|e| FastTtsError::InvalidInput(format!("not a valid feed: {e}")))?;
+    let root = doc.root_element();
+    if !matches!(root.tag_name().name(), "rss" | "feed" | "RDF") {
+        return Err(FastTtsError::InvalidInput(format!(
+            "not an RSS or Atom feed (root element <{}>)",
+            root.tag_name().name()
+        ))
+        .into());
+    }
+    let channel = root
+        .children()
+        .find(|n| n.has_tag_name("channel"))
+        .unwrap_or(root);
+    let entries = root
+        .descendants()
+        .filter(|n| n.has_tag_name("item") || n.has_tag_name("entry"))
+        .map(entry)
+        .collect();
+    Ok(Feed {
+        title: child_text(channel, "title").unwrap_or_default(),
+        entries,
+    })
+}
+
+fn entry(node: roxmltree::Node<'_, '_>) -> FeedEntry {
+    let link = node
+        .children()
+        .filter(|n| n.has_tag_name("link"))
+        .find_map(|n| match n.attribute("href") {
+            // Atom: the article is rel="alternate" (the default)
+            Some(href) => {
+                matches!(n.attribute("rel"), None | Some("alternate")).then(|| href.to_string())
+            }
+            None => Some(n.text().unwrap_or_default().trim().to_string()),
+        })
+        .filter(|l| !l.is_empty());
+    let title = child_text(node, "title").unwrap_or_default();
+    let guid = child_text(node, "guid")
+        .or_else(|| child_text(node, "id"))
+        .or_else(|| link.clone())
+        .unwrap_or_else(|| title.clone());
+    FeedEntry {
+        guid,
+        title,
+        link,
+        published: ["pubDate", "published", "updated", "date"]
+            .iter()
+            .find_map(|name| child_text(node, name)),
+        content: child_text(node, "encoded").or_else(|| child_text(node, "content")),
+        summary: child_text(node, "description").or_else(|| child_text(node, "summary")),
+    }
+}
+
+/// Trimmed text of the first child named `name` (any namespace); `None` when empty.
+fn child_text(node: roxmltree::Node<'_, '_>, name: &str) -> Option<String> {
+    let child = node.children().find(|n| n.tag_name().name() == name)?;
+    let text: String = child
+        .descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect();
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Elements whose contents are never part of the article text.
+const SKIPPED: &[&str] = &[
+    "script",
+    "style",
+    "noscript",
+    "template",
+    "svg",
+    "nav",
+    "header",
+    "footer",
+    "aside",
+    "form",
+    "figcaption",
+    "button",
+];
+/// Elements that end a paragraph.
+const BLOCKS: &[&str] = &[
+    "p",
+    "div",
+    "br",
+    "li",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+    "pre",
+    "tr",
+    "section",
+    "article",
+    "figure",
+    "hr",
+];
+
+/// The part of a web page most likely to be the article: the first
+/// `<article>`, else `<main>`, else `<body>`, else the whole page.
+pub fn article_html(page: &str) -> &str {
+    let lower = page.to_ascii_lowercase();
+    for tag in ["article", "main", "body"] {
+        if let Some(start) = find_tag(&lower, tag)
+            && let Some(end) = lower[start..].find(&format!("</{tag}")).map(|e| start + e)
+        {
+            return &page[start..end];
+        }
+    }
+    page
+}
+
+/// Offset of the first `<tag` opening an element named exactly `tag`.
+fn find_tag(lower: &str, tag: &str) -> Option<usize> {
+    let needle = format!("<{tag}");
+    let mut at = 0;
+    while let Some(found) = lower[at..].find(&needle).map(|i| at + i) {
+        let after = lower.as_bytes().get(found + needle.len()).copied();
+        if matches!(after, Some(b'>' | b' ' | b'\t' | b'\n' | b'\r' | b'/')) {
+            return Some(found);
+        }
+        at = found + needle.len();
+    }
+    None
+}
+
+/// Plain text of an HTML fragment: markup and non-content elements dropped,
+/// entities decoded, one blank line between paragraphs.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut skip_until: Option<String> = None;
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        if skip_until.is_none() {
+            text.push_str(&rest[..open]);
+        }
+        let after = &rest[open..];
+        let close = if after.starts_with("<!--") {
+            after.find("-->").map(|i| i + 3)
+        } else {
+            after.find('>').map(|i| i + 1)
+        };
+        let Some(close) = close else {
+            rest = "";
+            break;
+        };
+        let tag = &after[1..close - 1];
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match &skip_until {
+            Some(skipped) if closing && *skipped == name => skip_until = None,
+            Some(_) => {}
+            None if !closing && !tag.ends_with('/') && SKIPPED.contains(&name.as_str()) => {
+                skip_until = Some(name);
+            }
+            None if BLOCKS.contains(&name.as_str()) => text.push_str("\n\n"),
+            None => {}
+        }
+        rest = &after[close..];
+    }
+    if skip_until.is_none() {
+        text.push_str(rest);
+    }
+    decode_entities(&text)
+        .split("\n\n")
+        .map(|p| p.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Decode character references, leaving anything unrecognized (such as a
+/// bare `&`) as written.
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let semi = rest[1..]
+            .char_indices()
+            .take(10)
+            .find_map(|(i, c)| (c == ';').then_some(i));
+        let decoded = semi.and_then(|semi| {
+            let name = &rest[1..semi + 1];
+            let c = match name {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => ' ',
+                "ndash" => '–',
+                "mdash" => '—',
+                "hellip" => '…',
+                "lsquo" => '‘',
+                "rsquo" => '’',
+                "ldquo" => '“',
+                "rdquo" => '”',
+                _ => {
+                    let code = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => name.strip_prefix('#').and_then(|d| d.parse().ok()),
+                    };
+                    char::from_u32(code?)?
+                }
+            };
+            Some((c, semi + 2))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Download `url` as text, honoring the process-wide HTTP settings
+/// (`--offline`, `--replay`, extra CAs, ...).
+pub async fn fetch(url: &str) -> Result<String> {
+    let client = crate::build_http_client_for_base(url)?;
+    let resp = crate::send_http(client.get(url))
+        .await?
+        .error_for_status()?;
+    Ok(resp.text().await?)
+}
+
+/// Record of every entry `fast-tts feed` has turned into audio, kept in the
+/// output directory; its GUIDs decide which entries are new.
+pub const MANIFEST: &str = ".fast-tts-feed.jsonl";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Episode {
+    #[serde(flatten)]
+    pub entry: FeedEntry,
+    /// Feed URL the entry came from
+    pub feed: String,
+    /// Audio file name, relative to the manifest's directory
+    pub file: String,
+}
+
+pub fn load_episodes(dir: &Path) -> Result<Vec<Episode>> {
+    let path = dir.join(MANIFEST);
+    let data = match std::fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    Ok(data
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+pub fn append_episode(dir: &Path, episode: &Episode) -> Result<()> {
+    let path = dir.join(MANIFEST);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(episode)?)?;
+    Ok(())
+}
+
+/// `fast-tts feed`: turn the entries of `url` not yet in `out_dir`'s manifest
+/// into audio, oldest first and at most `limit` of them. `synthesize` writes
+/// an entry's text to the file it is given; entries it fails on are not
+/// recorded, so the next run tries them again.
+pub async fn synthesize_new(
+    url: &str,
+    out_dir: &Path,
+    limit: Option<usize>,
+    no_fetch: bool,
+    extension: &str,
+    cancel: &CancellationToken,
+    mut synthesize: impl AsyncFnMut(String, &Path) -> Result<()>,
+) -> Result<()> {
+    let channel = parse(&fetch(url).await?)?;
+    std::fs::create_dir_all(out_dir)?;
+    let seen: std::collections::HashSet<String> = load_episodes(out_dir)?
+        .into_iter()
+        .map(|e| e.entry.guid)
+        .collect();
+    let mut fresh: Vec<_> = channel
+        .entries
+        .into_iter()
+        .filter(|e| !seen.contains(&e.guid))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+    if fresh.is_empty() {
+        eprintln!("No new entries in {url}");
+        return Ok(());
+    }
+    // Oldest first, so the manifest (and a podcast built from it) reads in order
+    fresh.reverse();
+
+    let mut failed = 0;
+    for entry in &fresh {
+        let result = async {
+            let body = entry_text(entry, no_fetch).await?;
+            let title = entry.title.trim_end_matches(['.', '!', '?', ':']);
+            let text = if title.is_empty() {
+                body
+            } else {
+                format!("{title}.\n\n{body}")
+            };
+            let file = unique_file_name(out_dir, &entry.title, extension);
+            synthesize(text, &out_dir.join(&file)).await?;
+            append_episode(
+                out_dir,
+                &Episode {
+                    entry: entry.clone(),
+                    feed: url.to_string(),
+                    file,
+                },
+            )
+        }
+        .await;
+        if let Err(e) = result {
+            if cancel.is_cancelled() {
+                return Err(e);
+            }
+            eprintln!("Warning: {}: {e:#}", entry.title);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(
+            FastTtsError::Other(format!("{failed} of {} new entries failed", fresh.len())).into(),
+        );
+    }
+    Ok(())
+}
+
+/// The entry's full content when the feed carries it, else the linked
+/// article, else the summary.
+async fn entry_text(entry: &FeedEntry, no_fetch: bool) -> Result<String> {
+    if let Some(content) = &entry.content {
+        return Ok(html_to_text(content));
+    }
+    if !no_fetch && let Some(link) = &entry.link {
+        match fetch(link).await {
+            Ok(page) => {
+                let text = html_to_text(article_html(&page));
+                if !text.is_empty() {
+                    return Ok(text);
+                }
+            }
+            Err(e) => eprintln!("Warning: {link}: {e:#}; using the feed summary"),
+        }
+    }
+    let summary = entry
+        .summary
+        .as_deref()
+        .map(html_to_text)
+        .unwrap_or_default();
+    if summary.is_empty() {
+        return Err(FastTtsError::InvalidInput("entry has no text".into()).into());
+    }
+    Ok(summary)
+}
+
+/// `<slug of title>.<ext>` in `dir`, numbered when the name is taken.
+fn unique_file_name(dir: &Path, title: &str, ext: &str) -> String {
+    let mut stem: String = crate::slug(title.trim()).chars().take(60).collect();
+    if stem.trim_matches('_').is_empty() {
+        stem = "entry".into();
+    }
+    let mut name = format!("{stem}.{ext}");
+    let mut n = 2;
+    while dir.join(&name).exists() {
+        name = format!("{stem}-{n}.{ext}");
+        n += 1;
+    }
+    name
+}
+
+/// Channel-level fields of a podcast feed.
+#[derive(Debug, Clone, Default)]
+pub struct Podcast {
+    pub title: String,
+    pub description: String,
+    /// Public URL of the directory holding the episodes
+    pub link: String,
+    pub language: String,
+    pub author: Option<String>,
+    /// Cover image URL (Apple asks for a 1400–3000 px square JPEG or PNG)
+    pub artwork: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PodcastEpisode {
+    pub title: String,
+    pub guid: String,
+    /// Public URL of the audio file
+    pub url: String,
+    pub bytes: u64,
+    pub mime_type: &'static str,
+    /// Unix seconds
+    pub published: u64,
+    pub duration: Option<std::time::Duration>,
+    pub link: Option<String>,
+    pub description: Option<String>,
+}
+
+/// MIME type for an episode file, by extension; `None` for non-audio files.
+pub fn audio_mime_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "mp3" => "audio/mpeg",
+        "m4a" | "mp4" => "audio/mp4",
+        "aac" => "audio/aac",
+        "ogg" | "opus" => "audio/ogg",
+        "flac" => "audio/flac",
+        "wav" => "audio/wav",
+        _ => return None,
+    })
+}
+
+/// `fast-tts podcast publish`: write `output`, a podcast feed of the audio
+/// files in `dir`, with titles and dates from the `feed` manifest where there
+/// is one; how many episodes it lists. `podcast.link` is the public URL of
+/// `dir`, and `artwork` a URL or a file in `dir` (default `cover.jpg` or
+/// `cover.png` when present).
+pub fn publish(mut podcast: Podcast, dir: &Path, output: &Path) -> Result<usize> {
+    let base = reqwest::Url::parse(&format!("{}/", podcast.link.trim_end_matches('/')))
+        .map_err(|e| FastTtsError::InvalidInput(format!("invalid --base-url: {e}")))?;
+    let public_url = |file: &str| -> Result<String> { Ok(base.join(file)?.to_string()) };
+    podcast.artwork = match podcast.artwork.take() {
+        Some(art) if art.starts_with("http://") || art.starts_with("https://") => Some(art),
+        Some(file) if dir.join(&file).is_file() => Some(public_url(&file)?),
+        Some(file) => {
+            return Err(FastTtsError::InvalidInput(format!(
+                "artwork {file} is neither a URL nor a file in {}",
+                dir.display()
+            ))
+            .into());
+        }
+        None => ["cover.jpg", "cover.png"]
+            .into_iter()
+            .find(|f| dir.join(f).is_file())
+            .map(public_url)
+            .transpose()?,
+    };
+
+    let manifest: std::collections::HashMap<String, Episode> = load_episodes(dir)?
+        .into_iter()
+        .map(|e| (e.file.clone(), e))
+        .collect();
+    let mut episodes = Vec::new();
+    for item in
+        std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?
+    {
+        let path = item?.path();
+        let Some(mime_type) = audio_mime_type(&path) else {
+            continue;
+        };
+        let file = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let metadata = std::fs::metadata(&path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let duration = match crate::audio::decode(&path) {
+            Ok(audio) => Some(audio.duration()),
+            Err(e) => {
+                eprintln!("Warning: no duration for {file}: {e:#}");
+                None
+            }
+        };
+        let entry = manifest.get(&file).map(|e| &e.entry);
+        let url = public_url(&file)?;
+        episodes.push(PodcastEpisode {
+            title: entry.map_or_else(
+                || {
+                    path.file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .replace('_', " ")
+                },
+                |e| e.title.clone(),
+            ),
+            guid: entry.map_or_else(|| url.clone(), |e| e.guid.clone()),
+            url,
+            bytes: metadata.len(),
+            mime_type,
+            published: entry
+                .and_then(|e| e.published.as_deref())
+                .and_then(parse_date)
+                .unwrap_or(modified),
+            duration,
+            link: entry.and_then(|e| e.link.clone()),
+            description: entry.and_then(|e| e.summary.as_deref()).map(html_to_text),
+        });
+    }
+    if episodes.is_empty() {
+        return Err(
+            FastTtsError::InvalidInput(format!("no audio files in {}", dir.display())).into(),
+        );
+    }
+    episodes.sort_by(|a, b| b.published.cmp(&a.published).then(a.url.cmp(&b.url)));
+    std::fs::write(output, podcast_rss(&podcast, &episodes))
+        .with_context(|| format!("failed to write {}", output.display()))?;
+    Ok(episodes.len())
+}
+
+/// RSS 2.0 with the iTunes tags podcast apps expect; `episodes` in the
+/// order they should be listed (newest first).
+pub fn podcast_rss(podcast: &Podcast, episodes: &[PodcastEpisode]) -> String {
+    use htmlescape::encode_minimal as esc;
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\" \
+         xmlns:atom=\"http://www.w3.org/2005/Atom\">\n<channel>\n",
+    );
+    xml += &format!("  <title>{}</title>\n", esc(&podcast.title));
+    xml += &format!("  <link>{}</link>\n", esc(&podcast.link));
+    xml += &format!(
+        "  <description>{}</description>\n",
+        esc(&podcast.description)
+    );
+    xml += &format!("  <language>{}</language>\n", esc(&podcast.language));
+    xml += "  <itunes:explicit>false</itunes:explicit>\n";
+    if let Some(author) = &podcast.author {
+        xml += &format!("  <itunes:author>{}</itunes:author>\n", esc(author));
+    }
+    if let Some(artwork) = &podcast.artwork {
+        xml += &format!("  <itunes:image href=\"{}\"/>\n", esc(artwork));
+        xml += &format!(
+            "  <image><url>{}</url><title>{}</title><link>{}</link></image>\n",
+            esc(artwork),
+            esc(&podcast.title),
+            esc(&podcast.link)
+        );
+    }
+    if let Some(latest) = episodes.iter().map(|e| e.published).max() {
+        xml += &format!("  <lastBuildDate>{}</lastBuildDate>\n", rfc2822(latest));
+    }
+    for episode in episodes {
+        xml += "  <item>\n";
+        xml += &format!("    <title>{}</title>\n", esc(&episode.title));
+        xml += &format!(
+            "    <enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>\n",
+            esc(&episode.url),
+            episode.bytes,
+            episode.mime_type
+        );
+        xml += &format!(
+            "    <guid isPermaLink=\"false\">{}</guid>\n",
+            esc(&episode.guid)
+        );
+        xml += &format!("    <pubDate>{}</pubDate>\n", rfc2822(episode.published));
+        if let Some(duration) = episode.duration {
+            let secs = duration.as_secs_f64().round() as u64;
+            xml += &format!(
+                "    <itunes:duration>{:02}:{:02}:{:02}</itunes:duration>\n",
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60
+            );
+        }
+        if let Some(link) = &episode.link {
+            xml += &format!("    <link>{}</link>\n", esc(link));
+        }
+        if let Some(description) = &episode.description {
+            xml += &format!("    <description>{}</description>\n", esc(description));
+        }
+        xml += "  </item>\n";
+    }
+    xml + "</channel>\n</rss>\n"
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// `Wed, 02 Oct 2002 13:00:00 +0000`
+pub fn rfc2822(unix: u64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    let days = unix / 86400;
+    let (year, month, day) = civil_from_days(days as i64);
+    let secs = unix % 86400;
+    format!(
+        "{}, {day:02} {} {year} {:02}:{:02}:{:02} +0000",
+        WEEKDAYS[(days % 7) as usize],
+        MONTHS[month as usize - 1],
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Unix seconds of an RFC 3339 (`2024-05-01T09:30:00Z`) or RFC 2822
+/// (`Wed, 01 May 2024 09:30:00 +0200`) date.
+pub fn parse_date(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let (date, time, zone) = if text.as_bytes().get(4) == Some(&b'-') {
+        let (date, rest) = text.split_once(['T', 't', ' '])?;
+        let zone_at = rest.find(['Z', 'z', '+', '-']).unwrap_or(rest.len());
+        let mut ymd = date.splitn(3, '-').map(|n| n.parse::<i64>().ok());
+        let (y, m, d) = (ymd.next()??, ymd.next()??, ymd.next()??);
+        ((y, m, d), &rest[..zone_at], &rest[zone_at..])
+    } else {
+        // Weekday is optional; day month year time zone
+        let text = text.split_once(',').map_or(text, |(_, rest)| rest);
+        let mut parts = text.split_whitespace();
+        let d = parts.next()?.parse().ok()?;
+        let month = parts.next()?;
+        let m = MONTHS
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(month))? as i64
+            + 1;
+        let y = parts.next()?.parse().ok()?;
+        ((y, m, d), parts.next()?, parts.next().unwrap_or("+0000"))
+    };
+    let mut hms = time.split(':').map(|n| {
+        // Drop fractional seconds
+        n.split('.').next().and_then(|n| n.parse::<i64>().ok())
+    });
+    let (h, min) = (hms.next()??, hms.next()??);
+    let sec = hms.next().flatten().unwrap_or(0);
+    let offset = match zone.as_bytes().first() {
+        Some(sign @ (b'+' | b'-')) => {
+            let digits: String = zone[1..].chars().filter(char::is_ascii_digit).collect();
+            let (oh, om) = (
+                digits.get(..2)?.parse::<i64>().ok()?,
+                digits
+                    .get(2..4)
+                    .and_then(|m| m.parse::<i64>().ok())
+                    .unwrap_or(0),
+            );
+            let offset = oh * 3600 + om * 60;
+            if *sign == b'-' { -offset } else { offset }
+        }
+        // Z, UT, GMT and the obsolete US zone names are treated as UTC
+        _ => 0,
+    };
+    let (y, m, d) = date;
+    let unix = days_from_civil(y, m, d) * 86400 + h * 3600 + min * 60 + sec - offset;
+    u64::try_from(unix).ok()
+}
+
+// Howard Hinnant's proleptic Gregorian calendar conversions
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (
+        if m <= 2 {
+            yoe + era * 400 + 1
+        } else {
+            yoe + era * 400
+        },
+        m,
+        d,
+    )
+}
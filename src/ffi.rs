@@ -0,0 +1,229 @@
+//! C ABI over the library for desktop apps (see `include/fast_tts.h`). Calls
+//! go through [`crate::blocking`]; requests travel as JSON so new options don't
+//! change the ABI. Failing calls return the CLI's exit code for the error
+//! class and leave the message in [`fast_tts_last_error`].
+
+use crate::{FastTtsError, Gender, SynthesisRequest, parse_encoding_from_str, parse_provider};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct FfiRequest {
+    text: String,
+    output: Option<PathBuf>,
+    provider: Option<String>,
+    language: Option<String>,
+    voice: Option<String>,
+    gender: Option<String>,
+    rate: Option<f32>,
+    pitch: Option<f32>,
+    sample_rate: Option<i32>,
+    encoding: Option<String>,
+    volume_gain_db: Option<f32>,
+    #[serde(default)]
+    effects_profile_id: Vec<String>,
+    #[serde(default)]
+    ssml: bool,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, recording any error for `fast_tts_last_error` and mapping it to an exit code.
+/// A panic stops here as an internal error instead of unwinding into (and
+/// aborting) the host app.
+fn call(f: impl FnOnce() -> Result<()>) -> i32 {
+    LAST_ERROR.with(|e| e.borrow_mut().take());
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        let detail = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("panic");
+        Err(FastTtsError::Other(format!("internal error: {detail}")).into())
+    });
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            let class = FastTtsError::classify(&err);
+            let message = CString::new(class.to_string().replace('\0', " ")).unwrap_or_default();
+            LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+            i32::from(class.exit_code())
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be null or a valid NUL-terminated string.
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(FastTtsError::InvalidInput(format!("{name} must not be null")).into());
+    }
+    // SAFETY: non-null and NUL-terminated per the caller's contract
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|_| FastTtsError::InvalidInput(format!("{name} is not valid UTF-8")).into())
+}
+
+fn parse_request(json: &str) -> Result<SynthesisRequest> {
+    let req: FfiRequest = serde_json::from_str(json)
+        .map_err(|e| FastTtsError::InvalidInput(format!("invalid request JSON: {e}")))?;
+    let gender = req
+        .gender
+        .as_deref()
+        .map(|g| Gender::from_str(g, true))
+        .transpose()
+        .map_err(|e| FastTtsError::InvalidInput(format!("invalid gender: {e}")))?;
+    let mut builder = SynthesisRequest::builder()
+        .text(req.text)
+        .voice(req.voice)
+        .gender(gender)
+        .rate(req.rate.unwrap_or(1.0))
+        .pitch(req.pitch.unwrap_or(0.0))
+        .sample_rate(req.sample_rate)
+        .encoding(parse_encoding_from_str(
+            req.encoding.as_deref().unwrap_or("MP3"),
+        )?)
+        .volume_gain_db(req.volume_gain_db.unwrap_or(0.0))
+        .effects_profile_id(req.effects_profile_id)
+        .ssml(req.ssml);
+    if let Some(provider) = &req.provider {
+        builder = builder.provider(parse_provider(provider)?);
+    }
+    if let Some(language) = req.language {
+        builder = builder.language(language);
+    }
+    if let Some(output) = req.output {
+        builder = builder.output(output);
+    }
+    builder.build()
+}
+
+/// Synthesize the JSON request (which must include `output`) to a file.
+///
+/// # Safety
+/// `request_json` must be a valid NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fast_tts_synthesize(request_json: *const c_char) -> i32 {
+    call(|| {
+        // SAFETY: forwarded from the caller's contract
+        let request = parse_request(unsafe { str_arg(request_json, "request_json") }?)?;
+        crate::blocking::synthesize(&request)
+    })
+}
+
+/// Synthesize the JSON request into memory. On success `*out_data` holds
+/// `*out_len` bytes that must be released with `fast_tts_bytes_free`.
+///
+/// # Safety
+/// `request_json` must be a valid NUL-terminated string; `out_data` and
+/// `out_len` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fast_tts_synthesize_bytes(
+    request_json: *const c_char,
+    out_data: *mut *mut u8,
+    out_len: *mut usize,
+) -> i32 {
+    call(|| {
+        if out_data.is_null() || out_len.is_null() {
+            return Err(
+                FastTtsError::InvalidInput("output pointers must not be null".into()).into(),
+            );
+        }
+        // SAFETY: forwarded from the caller's contract
+        let request = parse_request(unsafe { str_arg(request_json, "request_json") }?)?;
+        let audio = crate::blocking::synthesize_bytes(&request)?;
+        let audio = Box::into_raw(audio.into_boxed_slice());
+        // SAFETY: both pointers were checked for null and are writable per the contract
+        unsafe {
+            *out_len = audio.len();
+            *out_data = audio.cast::<u8>();
+        }
+        Ok(())
+    })
+}
+
+/// Release a buffer returned by `fast_tts_synthesize_bytes`.
+///
+/// # Safety
+/// `data`/`len` must come from one successful `fast_tts_synthesize_bytes` call
+/// and not have been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fast_tts_bytes_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        // SAFETY: reconstructs the boxed slice leaked by fast_tts_synthesize_bytes
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(data, len)) });
+    }
+}
+
+/// Google's voice catalog as JSON in `*out_json`, released with `fast_tts_string_free`.
+///
+/// # Safety
+/// `out_json` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fast_tts_list_voices_json(out_json: *mut *mut c_char) -> i32 {
+    call(|| {
+        let voices = crate::blocking::list_voices()?;
+        // SAFETY: forwarded from the caller's contract
+        unsafe { write_string(out_json, &serde_json::to_string(&voices)?) }
+    })
+}
+
+/// Capabilities of every provider (including custom templates) as JSON in
+/// `*out_json`, released with `fast_tts_string_free`.
+///
+/// # Safety
+/// `out_json` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fast_tts_providers_json(out_json: *mut *mut c_char) -> i32 {
+    call(|| {
+        let providers: Vec<_> = crate::Provider::all()
+            .into_iter()
+            .map(crate::Provider::capabilities)
+            .collect();
+        // SAFETY: forwarded from the caller's contract
+        unsafe { write_string(out_json, &serde_json::to_string(&providers)?) }
+    })
+}
+
+/// # Safety
+/// `out` must be null or valid for writes.
+unsafe fn write_string(out: *mut *mut c_char, value: &str) -> Result<()> {
+    if out.is_null() {
+        return Err(FastTtsError::InvalidInput("output pointer must not be null".into()).into());
+    }
+    let value = CString::new(value).context("string contains NUL")?;
+    // SAFETY: checked for null; writable per the caller's contract
+    unsafe { *out = value.into_raw() };
+    Ok(())
+}
+
+/// Release a string returned through an `out_json` parameter.
+///
+/// # Safety
+/// `s` must come from this library and not have been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fast_tts_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: reconstructs the CString leaked by write_string
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// Message for the last failed call on this thread, or null. Valid until the
+/// next call on the same thread; do not free.
+#[unsafe(no_mangle)]
+pub extern "C" fn fast_tts_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| {
+        e.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
@@ -0,0 +1,129 @@
+//! `--target-duration`: re-synthesize at adjusted speaking rates until the
+//! audio lands within a tolerance of a target length.
+
+use crate::{AudioEncoding, FastTtsError, SynthesisRequest, audio};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Renders tried before giving up on the tolerance.
+const MAX_ATTEMPTS: u32 = 6;
+
+/// The render left at the request's output by [`synthesize_to_duration`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DurationFit {
+    pub rate: f32,
+    #[serde(rename = "durationMs", serialize_with = "as_millis")]
+    pub duration: Duration,
+    pub attempts: u32,
+}
+
+fn as_millis<S: serde::Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_u64(duration.as_millis() as u64)
+}
+
+/// Synthesize `request` so its audio lasts `target` give or take `tolerance`.
+/// Speech length is taken to scale inversely with the speaking rate: each
+/// render is measured and the rate corrected by the ratio of measured to
+/// target length, within the provider's rate range, starting from the
+/// request's own rate. The closest render ends up at the request's output;
+/// when none is within the tolerance (the rate limit was hit or the renders
+/// didn't converge) that render is kept and an
+/// [`FastTtsError::InvalidInput`] says how far off it is.
+pub async fn synthesize_to_duration(
+    request: &SynthesisRequest,
+    target: Duration,
+    tolerance: Duration,
+) -> Result<DurationFit> {
+    let caps = request.provider.capabilities();
+    let Some((min_rate, max_rate)) = caps.rate_range else {
+        return Err(FastTtsError::Unsupported(format!(
+            "--target-duration needs a speaking-rate control, which provider {} doesn't have",
+            request.provider
+        ))
+        .into());
+    };
+    if request.encoding == AudioEncoding::OggOpus {
+        return Err(FastTtsError::Unsupported(
+            "--target-duration can't measure OGG_OPUS audio; use LINEAR16 or MP3".into(),
+        )
+        .into());
+    }
+    if target.is_zero() {
+        return Err(FastTtsError::InvalidInput("--target-duration must be above 0".into()).into());
+    }
+
+    let ext = request.encoding.file_extension();
+    let attempt_path = request.output.with_extension(format!("fit.{ext}"));
+    let mut attempt = request.clone();
+    attempt.output = attempt_path.clone();
+    let mut best: Option<DurationFit> = None;
+    let mut attempts = 0;
+    let result = async {
+        while attempts < MAX_ATTEMPTS {
+            attempts += 1;
+            crate::synthesize(&attempt).await?;
+            let duration = audio::decode(&attempt_path)?.duration();
+            tracing::info!(
+                attempt = attempts,
+                rate = attempt.rate,
+                duration_ms = duration.as_millis() as u64,
+                "measured render"
+            );
+            let off = duration.abs_diff(target);
+            if best
+                .as_ref()
+                .is_none_or(|b| off < b.duration.abs_diff(target))
+            {
+                std::fs::rename(&attempt_path, &request.output)
+                    .with_context(|| format!("failed to write {}", request.output.display()))?;
+                if let (Some(from), Some(to)) = (
+                    attempt.segment_offsets_output(),
+                    request.segment_offsets_output(),
+                ) {
+                    std::fs::rename(&from, &to)
+                        .with_context(|| format!("failed to write {}", to.display()))?;
+                }
+                best = Some(DurationFit {
+                    rate: attempt.rate,
+                    duration,
+                    attempts,
+                });
+            }
+            if off <= tolerance {
+                break;
+            }
+            let next = (attempt.rate * (duration.as_secs_f32() / target.as_secs_f32()))
+                .clamp(min_rate, max_rate);
+            // Pinned at a limit of the rate range: another render won't differ
+            if (next - attempt.rate).abs() < 0.001 {
+                break;
+            }
+            attempt.rate = next;
+        }
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+    let _ = std::fs::remove_file(&attempt_path);
+    if let Some(table) = attempt.segment_offsets_output() {
+        let _ = std::fs::remove_file(table);
+    }
+    result?;
+
+    let mut fit = best.context("no render was produced")?;
+    fit.attempts = attempts;
+    if fit.duration.abs_diff(target) > tolerance {
+        return Err(FastTtsError::InvalidInput(format!(
+            "could not fit the speech to {:.1}s (±{:.1}s): the closest render is {:.1}s at rate {:.2} (provider {} allows {min_rate}–{max_rate}); it was kept in {}",
+            target.as_secs_f32(),
+            tolerance.as_secs_f32(),
+            fit.duration.as_secs_f32(),
+            fit.rate,
+            request.provider,
+            request.output.display()
+        ))
+        .into());
+    }
+    Ok(fit)
+}
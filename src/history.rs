@@ -0,0 +1,300 @@
+//! Local job history (`fast-tts history`): one JSON line per synthesis or
+//! bulk run, with the arguments needed to run it again.
+
+use anyhow::{Context, Result};
+use fast_tts::{FastTtsError, Provider, SynthesisRequest};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    pub id: u64,
+    /// Unix seconds when the job finished
+    pub time: u64,
+    pub provider: String,
+    /// The audio file, or the config file for bulk runs
+    pub output: PathBuf,
+    /// [`SynthesisRequest::params_hash`]; absent for bulk runs
+    pub params_hash: Option<String>,
+    /// `--seed`, when one was given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u32>,
+    pub chars: usize,
+    pub duration_ms: u64,
+    pub cost_usd: Option<f64>,
+    pub error: Option<String>,
+    /// Command-line arguments (without the program name) that reproduce the job
+    pub args: Vec<String>,
+    /// Working directory the arguments' relative paths refer to
+    pub cwd: PathBuf,
+}
+
+/// A finished job, before it gets an id.
+pub struct Job<'a> {
+    pub provider: Provider,
+    pub output: &'a Path,
+    pub params_hash: Option<String>,
+    pub seed: Option<u32>,
+    pub chars: usize,
+    pub elapsed: Duration,
+    pub result: &'a Result<()>,
+}
+
+impl<'a> Job<'a> {
+    pub fn for_request(
+        request: &'a SynthesisRequest,
+        elapsed: Duration,
+        result: &'a Result<()>,
+    ) -> Self {
+        Self {
+            provider: request.provider(),
+            output: request.output(),
+            params_hash: Some(request.params_hash()),
+            seed: request.seed(),
+            chars: request.text().chars().count(),
+            elapsed,
+            result,
+        }
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("FAST_TTS_HISTORY").filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    dirs::data_dir().map(|d| d.join("fast-tts-cli").join("history.jsonl"))
+}
+
+pub fn load() -> Result<Vec<Entry>> {
+    let Some(path) = history_path() else {
+        return Ok(Vec::new());
+    };
+    let data = match fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to read history: {}", path.display()));
+        }
+    };
+    // Skip lines from a crashed write instead of refusing the whole file
+    Ok(data
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+pub fn find(id: u64) -> Result<Entry> {
+    load()?
+        .into_iter()
+        .find(|e| e.id == id)
+        .ok_or_else(|| FastTtsError::InvalidInput(format!("no history entry with id {id}")).into())
+}
+
+/// Append `job`, run with `args`, to the history file.
+pub fn record(job: Job<'_>, args: &[String]) -> Result<()> {
+    let path = history_path().context("cannot determine data directory for history")?;
+    let id = load()?.last().map_or(1, |e| e.id + 1);
+    let entry = Entry {
+        id,
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        provider: job.provider.to_string(),
+        output: job.output.to_path_buf(),
+        params_hash: job.params_hash,
+        seed: job.seed,
+        chars: job.chars,
+        duration_ms: job.elapsed.as_millis() as u64,
+        cost_usd: job
+            .provider
+            .capabilities()
+            .usd_per_million_chars
+            .map(|price| job.chars as f64 * price / 1_000_000.0),
+        error: job.result.as_ref().err().map(|e| format!("{e:#}")),
+        args: redact(args),
+        cwd: std::env::current_dir()?,
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed to open history: {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+    Ok(())
+}
+
+/// Drop secrets passed on the command line; a rerun takes them from the
+/// environment or profile instead.
+fn redact(args: &[String]) -> Vec<String> {
+    let mut kept = Vec::with_capacity(args.len());
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--api-key" | "--mcp-auth-token" => {
+                iter.next();
+            }
+            a if a.starts_with("--api-key=") || a.starts_with("--mcp-auth-token=") => {}
+            _ => kept.push(arg.clone()),
+        }
+    }
+    kept
+}
+
+fn age(time: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let secs = now.saturating_sub(time);
+    match secs {
+        0..60 => format!("{secs}s ago"),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+fn cost(entry: &Entry) -> String {
+    entry
+        .cost_usd
+        .map_or("-".into(), |usd| format!("${usd:.4}"))
+}
+
+pub fn print_list(limit: usize, json: bool) -> Result<()> {
+    let entries = load()?;
+    let recent = &entries[entries.len().saturating_sub(limit)..];
+    if json {
+        println!("{}", serde_json::to_string_pretty(recent)?);
+        return Ok(());
+    }
+    for entry in recent.iter().rev() {
+        println!(
+            "{:>4}  {:>8}  {:<10}  {:>7} chars  {:>6.1}s  {:>8}  {}  {}",
+            entry.id,
+            age(entry.time),
+            entry.provider,
+            entry.chars,
+            entry.duration_ms as f64 / 1000.0,
+            cost(entry),
+            if entry.error.is_some() {
+                "failed"
+            } else {
+                "ok"
+            },
+            entry.output.display()
+        );
+    }
+    Ok(())
+}
+
+pub fn print_entry(id: u64, json: bool) -> Result<()> {
+    let entry = find(id)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entry)?);
+        return Ok(());
+    }
+    println!("id:       {}", entry.id);
+    println!("when:     {}", age(entry.time));
+    println!("provider: {}", entry.provider);
+    println!("output:   {}", entry.output.display());
+    if let Some(hash) = &entry.params_hash {
+        println!("params:   {hash}");
+    }
+    if let Some(seed) = entry.seed {
+        println!("seed:     {seed}");
+    }
+    println!("chars:    {}", entry.chars);
+    println!("duration: {:.1}s", entry.duration_ms as f64 / 1000.0);
+    println!("cost:     {}", cost(&entry));
+    if let Some(error) = &entry.error {
+        println!("error:    {error}");
+    }
+    println!("cwd:      {}", entry.cwd.display());
+    println!("command:  fast-tts-cli {}", shell_join(&entry.args));
+    Ok(())
+}
+
+/// Totals over the history, overall and per provider.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    pub jobs: usize,
+    pub failed: usize,
+    pub chars: usize,
+    /// Estimated from list prices; providers without one count as free
+    pub cost_usd: f64,
+    #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub providers: std::collections::BTreeMap<String, Usage>,
+}
+
+impl Usage {
+    fn add(&mut self, entry: &Entry) {
+        self.jobs += 1;
+        self.failed += usize::from(entry.error.is_some());
+        self.chars += entry.chars;
+        self.cost_usd += entry.cost_usd.unwrap_or_default();
+    }
+}
+
+/// Sum the jobs of the last `days` days, or of all time.
+pub fn usage(days: Option<u64>) -> Result<Usage> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let since = days.map_or(0, |d| now.saturating_sub(d * 86400));
+    let mut total = Usage::default();
+    for entry in load()?.iter().filter(|e| e.time >= since) {
+        total.add(entry);
+        total
+            .providers
+            .entry(entry.provider.clone())
+            .or_default()
+            .add(entry);
+    }
+    Ok(total)
+}
+
+pub fn print_usage(days: Option<u64>, json: bool) -> Result<()> {
+    let usage = usage(days)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&usage)?);
+        return Ok(());
+    }
+    let row = |name: &str, u: &Usage| {
+        println!(
+            "{name:<11} {:>5} jobs  {:>5} failed  {:>9} chars  ${:.4}",
+            u.jobs, u.failed, u.chars, u.cost_usd
+        );
+    };
+    for (provider, u) in &usage.providers {
+        row(provider, u);
+    }
+    row("total", &usage);
+    Ok(())
+}
+
+fn shell_join(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            if !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./:=,@+".contains(c))
+            {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
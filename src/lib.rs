@@ -0,0 +1,3951 @@
+//! fast-tts: text-to-speech across Google Cloud, OpenAI, Azure, ElevenLabs,
+//! Deepgram, LMNT, Gemini and Polly behind one request type.
+//!
+//! ```no_run
+//! # async fn demo() -> anyhow::Result<()> {
+//! use fast_tts::{AudioEncoding, Provider, SynthesisRequest};
+//! let request = SynthesisRequest::builder()
+//!     .provider(Provider::Openai)
+//!     .text("Hello")
+//!     .output("hello.mp3")
+//!     .encoding(AudioEncoding::Mp3)
+//!     .build()?;
+//! fast_tts::synthesize(&request).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Process-wide HTTP settings (extra CAs, gateway headers, record/replay)
+//! come from [`init_http`]; without it, defaults apply.
+
+use anyhow::{Context, Result};
+use base64::Engine as _;
+use clap::ValueEnum;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+use tracing::Instrument as _;
+// use std::collections::HashSet; // not currently used
+// use std::time::Duration; // reserved for future retries/timeouts
+
+pub mod audio;
+pub mod bench;
+pub mod blocking;
+pub mod cache;
+#[cfg(feature = "provider-elevenlabs")]
+mod chunk;
+pub mod compare;
+pub mod doctor;
+mod error;
+pub mod feed;
+#[cfg(feature = "cdylib")]
+pub mod ffi;
+mod fit;
+#[cfg(any(feature = "mcp", feature = "serve"))]
+mod listen;
+pub mod logging;
+mod markup;
+#[cfg(feature = "mcp")]
+mod mcp;
+mod opus;
+pub mod otel;
+pub mod providers;
+mod request;
+pub mod sections;
+#[cfg(feature = "serve")]
+pub mod serve;
+mod ssml;
+pub mod status;
+mod suggest;
+
+pub use error::{ErrorReport, FastTtsError};
+pub use fit::{DurationFit, synthesize_to_duration};
+#[cfg(feature = "mcp")]
+pub use mcp::{McpServerOptions, run_mcp_server};
+pub use opus::{OpusApplication, OpusSettings, OpusVbr, parse_bitrate};
+pub use providers::TtsProvider;
+pub use request::{SynthesisRequest, SynthesisRequestBuilder};
+pub use tokio_util::sync::CancellationToken;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum Gender {
+    Neutral,
+    Male,
+    Female,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum Provider {
+    Google,
+    Openai,
+    Elevenlabs,
+    Deepgram,
+    Lmnt,
+    Polly,
+    Azure,
+    Hume,
+    Listnr,
+    Murf,
+    Gemini,
+    /// Local Piper voice models (`--features piper`)
+    Piper,
+    /// Local Kokoro-82M model (`--features kokoro`)
+    Kokoro,
+    /// The operating system's speech engine (`say`, SAPI, espeak-ng)
+    System,
+    /// HTTP provider described by a template file, selected as `custom:<name>`
+    #[value(skip)]
+    Custom(&'static str),
+    /// WebAssembly component provider (`--features wasm`), selected as `wasm:<path>`
+    #[value(skip)]
+    Wasm(&'static str),
+}
+
+impl Provider {
+    /// What this provider supports in the current build.
+    pub fn capabilities(self) -> ProviderCapabilities {
+        providers::get(self).capabilities()
+    }
+
+    /// Built-in providers followed by every custom template that loads.
+    pub fn all() -> Vec<Provider> {
+        let mut all = Provider::value_variants().to_vec();
+        all.extend(providers::custom::all());
+        #[cfg(feature = "wasm")]
+        all.extend(providers::wasm::all());
+        all
+    }
+}
+
+impl std::fmt::Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Provider::Custom(name) => write!(f, "custom:{name}"),
+            Provider::Wasm(spec) => write!(f, "wasm:{spec}"),
+            other => write!(f, "{}", format!("{other:?}").to_lowercase()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+#[value(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AudioEncoding {
+    Linear16,
+    Mp3,
+    OggOpus,
+    Mulaw,
+    Alaw,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum McpMode {
+    Stdio,
+    Sse,
+    Http,
+}
+
+impl AudioEncoding {
+    pub fn api_str(&self) -> &'static str {
+        match self {
+            AudioEncoding::Linear16 => "LINEAR16",
+            AudioEncoding::Mp3 => "MP3",
+            AudioEncoding::OggOpus => "OGG_OPUS",
+            AudioEncoding::Mulaw => "MULAW",
+            AudioEncoding::Alaw => "ALAW",
+        }
+    }
+
+    #[cfg_attr(not(any(feature = "mcp", feature = "serve")), allow(dead_code))]
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            AudioEncoding::Linear16 | AudioEncoding::Mulaw | AudioEncoding::Alaw => "audio/wav",
+            AudioEncoding::Mp3 => "audio/mpeg",
+            AudioEncoding::OggOpus => "audio/ogg",
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            AudioEncoding::Linear16 | AudioEncoding::Mulaw | AudioEncoding::Alaw => "wav",
+            AudioEncoding::Mp3 => "mp3",
+            AudioEncoding::OggOpus => "ogg",
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum SynthesisInput<'a> {
+    #[serde(rename_all = "camelCase")]
+    Text { text: &'a str },
+    #[serde(rename_all = "camelCase")]
+    Ssml { ssml: &'a str },
+    #[serde(rename_all = "camelCase")]
+    MultiSpeaker {
+        multi_speaker_markup: MultiSpeakerMarkup<'a>,
+    },
+}
+
+#[derive(Serialize)]
+struct MultiSpeakerMarkup<'a> {
+    turns: &'a [markup::DialogueTurn],
+}
+
+/// Google's voice for `multiSpeakerMarkup`; speakers are named R, S, T and so on.
+const GOOGLE_MULTI_SPEAKER_VOICE: &str = "en-US-Studio-MultiSpeaker";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VoiceSelectionParams<'a> {
+    language_code: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ssml_gender: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    custom_voice: Option<CustomVoiceParams>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    voice_clone: Option<VoiceCloneParams>,
+}
+
+/// A trained custom voice: `projects/{project}/locations/{location}/models/{model}`.
+#[derive(Serialize)]
+struct CustomVoiceParams {
+    model: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VoiceCloneParams {
+    voice_cloning_key: String,
+}
+
+/// Google custom voice and voice clone settings: `model` (`--custom-voice-model`)
+/// or `GOOGLE_CUSTOM_VOICE_MODEL`, and `GOOGLE_VOICE_CLONING_KEY`.
+fn google_custom_voice(
+    model: Option<&str>,
+) -> (Option<CustomVoiceParams>, Option<VoiceCloneParams>) {
+    let setting = |var| config_var(var).filter(|v| !v.is_empty());
+    let model = model
+        .map(str::to_string)
+        .or_else(|| setting("GOOGLE_CUSTOM_VOICE_MODEL"));
+    (
+        model.map(|model| CustomVoiceParams { model }),
+        setting("GOOGLE_VOICE_CLONING_KEY")
+            .map(|voice_cloning_key| VoiceCloneParams { voice_cloning_key }),
+    )
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AudioConfig<'a> {
+    audio_encoding: &'a str,
+    speaking_rate: f32,
+    pitch: f32,
+    volume_gain_db: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sample_rate_hertz: Option<i32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    effects_profile_id: Vec<&'a str>,
+    enable_legacy_wav_header: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SynthesizeRequest<'a> {
+    input: SynthesisInput<'a>,
+    voice: VoiceSelectionParams<'a>,
+    audio_config: AudioConfig<'a>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ListVoicesResponse {
+    pub voices: Vec<Voice>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Voice {
+    pub name: String,
+    pub language_codes: Vec<String>,
+    pub ssml_gender: String,
+    pub natural_sample_rate_hertz: Option<i32>,
+    /// Voice family where the provider has several, e.g. Azure `Neural` or `HD`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voice_type: Option<String>,
+    /// Speaking styles the voice accepts (Azure `mstts:express-as`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub styles: Vec<String>,
+    /// Role-play roles the voice accepts (Azure)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    /// Engines that can speak the voice (Polly `standard`, `neural`, ...)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub engines: Vec<String>,
+}
+
+/// Synthesize `request` with its provider, writing the audio to its output path.
+/// If the request's cancellation token fires first, a partially written new
+/// file is removed and [`FastTtsError::Cancelled`] is returned.
+pub async fn synthesize(request: &SynthesisRequest) -> Result<()> {
+    let provider = request.provider;
+    let span = tracing::info_span!(
+        "synthesize",
+        provider = ?provider,
+        encoding = request.encoding.api_str(),
+        text.chars = request.text.chars().count(),
+        otel.status_code = tracing::field::Empty,
+    );
+    let existed = request.output.exists();
+    let started = std::time::Instant::now();
+    let result = request
+        .cancel
+        .run_until_cancelled(dispatch_synthesis(request))
+        .instrument(span.clone())
+        .await
+        .unwrap_or_else(|| {
+            if !existed {
+                let _ = fs::remove_file(&request.output);
+            }
+            Err(cancelled())
+        });
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    span.in_scope(|| match &result {
+        Ok(()) => tracing::info!(
+            elapsed_ms,
+            output = %request.output.display(),
+            "synthesis finished"
+        ),
+        Err(e) => {
+            span.record("otel.status_code", "ERROR");
+            tracing::info!(elapsed_ms, error = %format!("{e:#}"), "synthesis failed");
+        }
+    });
+    result
+}
+
+fn cancelled() -> anyhow::Error {
+    FastTtsError::Cancelled("cancelled".into()).into()
+}
+
+async fn dispatch_synthesis(request: &SynthesisRequest) -> Result<()> {
+    if request.output.as_os_str().is_empty() {
+        return Err(FastTtsError::InvalidInput("output path is required".into()).into());
+    }
+    ensure_provider_enabled(request.provider)?;
+    if request.tracks {
+        return markup::synthesize_tracks(request).await;
+    }
+    if request.voice_markup && (request.segment_offsets || markup::has_voice_markup(&request.text))
+    {
+        markup::synthesize_segments(request).await?;
+    } else {
+        providers::get(request.provider)
+            .synthesize(request)
+            .await
+            .map_err(|e| suggest::explain_voice_rejection(e, request))?;
+    }
+    if opus::needs_transcode(request) {
+        opus::transcode(&request.output, &request.opus).await?;
+    }
+    Ok(())
+}
+
+/// Synthesize `request` as a stream of audio chunks; its output path is ignored.
+/// OpenAI, Azure, ElevenLabs and Deepgram stream as the provider responds, the
+/// others synthesize in full and are then chunked.
+pub async fn synthesize_stream(request: &SynthesisRequest) -> Result<AudioStream> {
+    let span = tracing::info_span!(
+        "synthesize",
+        provider = ?request.provider,
+        encoding = request.encoding.api_str(),
+        text.chars = request.text.chars().count(),
+        stream = true,
+        otel.status_code = tracing::field::Empty,
+    );
+    let open = async {
+        ensure_provider_enabled(request.provider)?;
+        if request.tracks
+            || request.segment_offsets
+            || (request.voice_markup && markup::has_voice_markup(&request.text))
+        {
+            return Err(FastTtsError::Unsupported(
+                "voice markup, tracks and segment offsets need a file output and cannot be streamed"
+                    .into(),
+            )
+            .into());
+        }
+        if opus::needs_transcode(request) {
+            return Err(FastTtsError::Unsupported(format!(
+                "provider {} can't apply these Opus settings while streaming; they need a file output",
+                request.provider
+            ))
+            .into());
+        }
+        providers::get(request.provider)
+            .synthesize_stream(request)
+            .await
+            .map_err(|e| suggest::explain_voice_rejection(e, request))
+    };
+    let result = request
+        .cancel
+        .run_until_cancelled(open)
+        .instrument(span.clone())
+        .await
+        .unwrap_or_else(|| Err(cancelled()))
+        .map(|stream| cancellable(stream, request.cancel.clone()));
+    if result.is_err() {
+        span.record("otel.status_code", "ERROR");
+    }
+    result
+}
+
+/// End `stream` with a [`FastTtsError::Cancelled`] item once `token` fires.
+fn cancellable(stream: AudioStream, token: CancellationToken) -> AudioStream {
+    use futures::StreamExt as _;
+    Box::pin(futures::stream::unfold(
+        Some((stream, token)),
+        |state| async move {
+            let (mut stream, token) = state?;
+            match token.run_until_cancelled(stream.next()).await {
+                Some(Some(item)) => Some((item, Some((stream, token)))),
+                Some(None) => None,
+                None => Some((Err(cancelled()), None)),
+            }
+        },
+    ))
+}
+
+fn ensure_provider_enabled(provider: Provider) -> Result<()> {
+    if !provider_enabled(provider) {
+        let flag = provider_feature_flag(provider);
+        // Only the cloud providers are part of all-cloud
+        let or_all = if flag.starts_with("provider-") {
+            " or all-cloud"
+        } else {
+            ""
+        };
+        return Err(FastTtsError::Unsupported(format!(
+            "provider {provider:?} not enabled in this build. Rebuild with --features {flag}{or_all}"
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Named provider/credential set from `profiles.yaml` in the config dir.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub provider: Option<String>,
+    /// Credential variables (e.g. OPENAI_API_KEY) that override the environment
+    #[serde(default)]
+    pub env: std::collections::HashMap<String, String>,
+}
+
+impl Profile {
+    /// The profile's provider, or `fallback` when it doesn't name one.
+    pub fn provider_or(&self, fallback: Provider) -> Result<Provider> {
+        match &self.provider {
+            Some(name) => parse_provider(name),
+            None => Ok(fallback),
+        }
+    }
+}
+
+tokio::task_local! {
+    static PROFILE_ENV: std::collections::HashMap<String, String>;
+}
+
+fn profiles_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("FAST_TTS_PROFILES").filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    dirs::config_dir().map(|d| d.join("fast-tts-cli").join("profiles.yaml"))
+}
+
+pub fn load_profile(name: &str) -> Result<Profile> {
+    let path = profiles_path().context("cannot determine config directory for profiles")?;
+    let data = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read profiles: {}", path.display()))?;
+    let mut profiles: std::collections::HashMap<String, Profile> = serde_yaml::from_str(&data)
+        .map_err(|e| {
+            FastTtsError::InvalidInput(format!("invalid profiles file {}: {e}", path.display()))
+        })?;
+    profiles.remove(name).ok_or_else(|| {
+        FastTtsError::InvalidInput(format!("profile {name:?} not found in {}", path.display()))
+            .into()
+    })
+}
+
+/// Run `fut` with the profile's credentials taking precedence over the environment.
+pub async fn with_profile<F: std::future::Future>(profile: &Profile, fut: F) -> F::Output {
+    PROFILE_ENV.scope(profile.env.clone(), fut).await
+}
+
+/// Look up a provider setting, preferring the active profile over the environment.
+fn config_var(var: &str) -> Option<String> {
+    PROFILE_ENV
+        .try_with(|env| env.get(var).cloned())
+        .ok()
+        .flatten()
+        .or_else(|| std::env::var(var).ok())
+}
+
+/// Where `program` runs from: itself when it is a path, else the first
+/// match on PATH.
+fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains(std::path::is_separator) {
+        return Path::new(program).is_file().then(|| program.into());
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?).find_map(|dir| {
+        [dir.join(program), dir.join(format!("{program}.exe"))]
+            .into_iter()
+            .find(|p| p.is_file())
+    })
+}
+
+/// Parse a length such as `60s`, `1.5s`, `500ms`, `2m`, `1m30s`, `1:30` or a
+/// bare number of seconds.
+pub fn parse_duration(raw: &str) -> Result<std::time::Duration> {
+    let invalid = || {
+        FastTtsError::InvalidInput(format!(
+            "invalid duration {raw:?}; use e.g. 60s, 1m30s, 1:30 or 500ms"
+        ))
+    };
+    let raw = raw.trim();
+    let seconds = if let Some((minutes, seconds)) = raw.split_once(':') {
+        minutes.parse::<u32>().map_err(|_| invalid())? as f64 * 60.0
+            + seconds.parse::<f64>().map_err(|_| invalid())?
+    } else if let Some(ms) = raw.strip_suffix("ms") {
+        ms.trim().parse::<f64>().map_err(|_| invalid())? / 1000.0
+    } else if let Some((minutes, rest)) = raw.split_once('m') {
+        let seconds = match rest.strip_suffix('s') {
+            Some(seconds) => seconds.parse::<f64>().map_err(|_| invalid())?,
+            None if rest.is_empty() => 0.0,
+            None => return Err(invalid().into()),
+        };
+        minutes.trim().parse::<u32>().map_err(|_| invalid())? as f64 * 60.0 + seconds
+    } else {
+        let seconds = raw.strip_suffix('s').unwrap_or(raw).trim();
+        seconds.parse::<f64>().map_err(|_| invalid())?
+    };
+    std::time::Duration::try_from_secs_f64(seconds).map_err(|_| invalid().into())
+}
+
+/// Parse a level such as `-18dB`, `-18 db` or `-18`.
+pub fn parse_db(raw: &str) -> Result<f32> {
+    let trimmed = raw.trim();
+    let number = trimmed
+        .strip_suffix("dB")
+        .or_else(|| trimmed.strip_suffix("db"))
+        .or_else(|| trimmed.strip_suffix("DB"))
+        .unwrap_or(trimmed);
+    number
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|db| db.is_finite())
+        .ok_or_else(|| {
+            FastTtsError::InvalidInput(format!("invalid level {raw:?}; use e.g. -18dB")).into()
+        })
+}
+
+/// Parse a provider name; `custom:<name>` loads that template definition and
+/// `wasm:<path>` that component.
+pub fn parse_provider(name: &str) -> Result<Provider> {
+    if let Some(custom) = name.strip_prefix("custom:") {
+        return providers::custom::load(custom).map(|t| t.id());
+    }
+    if let Some(_spec) = name.strip_prefix("wasm:") {
+        #[cfg(feature = "wasm")]
+        return providers::wasm::load(_spec).map(|p| p.id());
+        #[cfg(not(feature = "wasm"))]
+        return Err(FastTtsError::Unsupported(
+            "WebAssembly plugin providers need a build with --features wasm".into(),
+        )
+        .into());
+    }
+    Provider::from_str(name, true)
+        .map_err(|_| FastTtsError::InvalidInput(format!("unknown provider: {name}")).into())
+}
+
+/// `parse_provider` for names sent by serve and MCP clients: `wasm:` may only
+/// pick a plugin installed in the plugins directory, not a path.
+#[cfg(any(feature = "serve", feature = "mcp"))]
+pub(crate) fn parse_remote_provider(name: &str) -> Result<Provider> {
+    #[cfg(feature = "wasm")]
+    if let Some(spec) = name.strip_prefix("wasm:") {
+        return providers::wasm::load_installed(spec).map(|p| p.id());
+    }
+    parse_provider(name)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BulkDefaults {
+    language: Option<String>,
+    voice: Option<String>,
+    gender: Option<String>,
+    rate: Option<f32>,
+    pitch: Option<f32>,
+    sample_rate: Option<i32>,
+    encoding: Option<String>,
+    volume_gain_db: Option<f32>,
+    effects_profile_id: Option<Vec<String>>,
+    ssml: Option<bool>,
+    output_dir: Option<String>,
+    auto_extension: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BulkItem {
+    #[serde(default)]
+    text: String,
+    /// Speaker turns, spoken as one multi-speaker dialogue instead of `text`
+    turns: Option<Vec<markup::DialogueTurn>>,
+    /// Read `text` as a `SPEAKER: text` dialogue script
+    dialogue: Option<bool>,
+    output: Option<String>,
+    language: Option<String>,
+    voice: Option<String>,
+    gender: Option<String>,
+    rate: Option<f32>,
+    pitch: Option<f32>,
+    sample_rate: Option<i32>,
+    encoding: Option<String>,
+    volume_gain_db: Option<f32>,
+    effects_profile_id: Option<Vec<String>>,
+    ssml: Option<bool>,
+    auto_extension: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BulkConfig {
+    defaults: Option<BulkDefaults>,
+    items: Vec<BulkItem>,
+}
+
+/// Knobs for [`run_bulk_from_config`].
+#[derive(Debug, Clone)]
+pub struct BulkOptions {
+    pub timeout_ms: u64,
+    pub retries: usize,
+    /// Play each file once written
+    pub play: bool,
+    /// Stops the item in flight and skips the rest
+    pub cancel: CancellationToken,
+    /// Carry on after a failed item and report the failures at the end
+    pub keep_going: bool,
+    /// While the value is `true` the run waits before starting the next item
+    pub paused: Option<tokio::sync::watch::Receiver<bool>>,
+    /// Fix item output extensions to match their encoding instead of failing;
+    /// `autoExtension` in the config overrides it per item
+    pub auto_extension: bool,
+    /// Runs of more characters than this only start when `confirm` agrees;
+    /// `None` never asks
+    pub confirm_above_chars: Option<usize>,
+    /// Asked with the provider and character count of a run over
+    /// `confirm_above_chars`; without it such runs are refused
+    pub confirm: Option<fn(Provider, usize) -> bool>,
+    /// Resolve item outputs inside this directory, refusing ones that would
+    /// leave it (absolute or `..` paths); for configs from untrusted callers
+    pub output_root: Option<PathBuf>,
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 30_000,
+            retries: 2,
+            play: false,
+            cancel: CancellationToken::new(),
+            keep_going: false,
+            paused: None,
+            auto_extension: false,
+            confirm_above_chars: None,
+            confirm: None,
+            output_root: None,
+        }
+    }
+}
+
+/// Progress of a bulk run; indexes are 1-based.
+#[derive(Debug)]
+pub enum BulkEvent<'a> {
+    /// Every item validated; sent once before the first one starts
+    Planned {
+        provider: Provider,
+        items: usize,
+        chars: usize,
+    },
+    /// `cancel` skips just this item
+    Started {
+        index: usize,
+        output: &'a Path,
+        chars: usize,
+        cancel: &'a CancellationToken,
+    },
+    Written {
+        index: usize,
+        output: &'a Path,
+        bytes: u64,
+        elapsed: std::time::Duration,
+    },
+    Skipped {
+        index: usize,
+        output: &'a Path,
+    },
+    Failed {
+        index: usize,
+        output: &'a Path,
+        error: &'a anyhow::Error,
+    },
+}
+
+/// Synthesize every item of a bulk config in order, reporting progress to
+/// `on_event`. All items are validated before the first request is sent.
+pub async fn run_bulk_from_config(
+    path: &PathBuf,
+    options: &BulkOptions,
+    on_event: impl FnMut(BulkEvent<'_>),
+) -> Result<()> {
+    let data = fs::read_to_string(path)
+        .with_context(|| format!("failed to read config: {}", path.display()))?;
+    let is_yaml = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| matches!(e.to_lowercase().as_str(), "yml" | "yaml"))
+        .unwrap_or(false);
+
+    let cfg: BulkConfig = if is_yaml {
+        serde_yaml::from_str(&data).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(&data).map_err(|e| e.to_string())
+    }
+    .map_err(|e| FastTtsError::InvalidInput(format!("invalid config {}: {e}", path.display())))?;
+    run_bulk(cfg, options, on_event).await
+}
+
+/// [`run_bulk_from_config`] for a config that is already parsed.
+async fn run_bulk(
+    cfg: BulkConfig,
+    options: &BulkOptions,
+    mut on_event: impl FnMut(BulkEvent<'_>),
+) -> Result<()> {
+    if !provider_enabled(Provider::Google) {
+        anyhow::bail!(
+            "Bulk synthesis requires Google provider. Rebuild with --features provider-google or all-cloud"
+        );
+    }
+    let defaults = cfg.defaults.unwrap_or(BulkDefaults {
+        language: None,
+        voice: None,
+        gender: None,
+        rate: Some(1.0),
+        pitch: Some(0.0),
+        sample_rate: None,
+        encoding: Some("LINEAR16".to_string()),
+        volume_gain_db: Some(0.0),
+        effects_profile_id: Some(vec![]),
+        ssml: Some(false),
+        output_dir: None,
+        auto_extension: None,
+    });
+
+    let mut requests = Vec::with_capacity(cfg.items.len());
+    for (idx, item) in cfg.items.iter().enumerate() {
+        let language = item
+            .language
+            .as_ref()
+            .or(defaults.language.as_ref())
+            .cloned()
+            .unwrap_or_default();
+        let voice = item.voice.as_ref().or(defaults.voice.as_ref()).cloned();
+        let gender = item.gender.as_ref().or(defaults.gender.as_ref()).map(|g| {
+            match g.to_uppercase().as_str() {
+                "MALE" => Gender::Male,
+                "FEMALE" => Gender::Female,
+                _ => Gender::Neutral,
+            }
+        });
+        let rate = item.rate.or(defaults.rate).unwrap_or(1.0);
+        let pitch = item.pitch.or(defaults.pitch).unwrap_or(0.0);
+        let sample_rate = item.sample_rate.or(defaults.sample_rate);
+        let encoding = parse_encoding_from_str(
+            item.encoding
+                .as_ref()
+                .or(defaults.encoding.as_ref())
+                .map_or("LINEAR16", String::as_str),
+        )
+        .with_context(|| format!("bulk item {}", idx + 1))?;
+        let volume_gain_db = item
+            .volume_gain_db
+            .or(defaults.volume_gain_db)
+            .unwrap_or(0.0);
+        let effects_profile_id: Vec<String> = item
+            .effects_profile_id
+            .clone()
+            .or(defaults.effects_profile_id.clone())
+            .unwrap_or_default();
+        let is_ssml = item.ssml.or(defaults.ssml).unwrap_or(false);
+        let text = match &item.turns {
+            Some(turns) => turns
+                .iter()
+                .map(|t| format!("{}: {}", t.speaker, t.text.replace('\n', " ")))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            None => item.text.clone(),
+        };
+        let dialogue = item.turns.is_some() || item.dialogue.unwrap_or(false);
+
+        // Unnamed items are numbered, with the extension of their own encoding
+        let output = match &item.output {
+            Some(o) => PathBuf::from(o),
+            None => {
+                let name = format!("item_{}.{}", idx + 1, encoding.file_extension());
+                match &defaults.output_dir {
+                    Some(dir) => PathBuf::from(dir).join(name),
+                    None => PathBuf::from(name),
+                }
+            }
+        };
+
+        let auto_extension = item
+            .auto_extension
+            .or(defaults.auto_extension)
+            .unwrap_or(options.auto_extension);
+        let output = if auto_extension {
+            output_with_extension(&output, encoding)
+        } else {
+            output
+        };
+        let output = match &options.output_root {
+            Some(root) => {
+                if !output
+                    .components()
+                    .all(|c| matches!(c, std::path::Component::Normal(_)))
+                {
+                    return Err(FastTtsError::InvalidInput(format!(
+                        "bulk item {}: output {} must be a relative path without ..",
+                        idx + 1,
+                        output.display()
+                    ))
+                    .into());
+                }
+                root.join(output)
+            }
+            None => output,
+        };
+
+        // For now, bulk uses the Google provider; extend with per-item providers if needed
+        let request = SynthesisRequest::builder()
+            .provider(Provider::Google)
+            .text(text)
+            .output(output.clone())
+            .language(language)
+            .voice(voice)
+            .gender(gender)
+            .rate(rate)
+            .pitch(pitch)
+            .sample_rate(sample_rate)
+            .encoding(encoding)
+            .volume_gain_db(volume_gain_db)
+            .effects_profile_id(effects_profile_id)
+            .ssml(is_ssml)
+            .dialogue(dialogue)
+            .timeout_ms(options.timeout_ms)
+            .retries(options.retries)
+            .build()
+            .with_context(|| format!("bulk item {} ({})", idx + 1, output.display()))?;
+        requests.push(request);
+    }
+
+    let chars = requests.iter().map(|r| r.text.chars().count()).sum();
+    if let Some(limit) = options.confirm_above_chars
+        && chars > limit
+        && !options
+            .confirm
+            .is_some_and(|confirm| confirm(Provider::Google, chars))
+    {
+        return Err(FastTtsError::Cancelled(format!(
+            "bulk run of {chars} characters is over the {limit}-character confirmation threshold and was not confirmed; pass --yes to run it"
+        ))
+        .into());
+    }
+    on_event(BulkEvent::Planned {
+        provider: Provider::Google,
+        items: requests.len(),
+        chars,
+    });
+    let mut failed = 0;
+    for (idx, mut request) in requests.into_iter().enumerate() {
+        let index = idx + 1;
+        if let Some(paused) = &options.paused {
+            let mut paused = paused.clone();
+            let resumed = paused.wait_for(|p| !*p);
+            if options.cancel.run_until_cancelled(resumed).await.is_none() {
+                return Err(cancelled());
+            }
+        }
+        if options.cancel.is_cancelled() {
+            return Err(cancelled());
+        }
+        request.cancel = options.cancel.child_token();
+        on_event(BulkEvent::Started {
+            index,
+            output: &request.output,
+            chars: request.text.chars().count(),
+            cancel: &request.cancel,
+        });
+        let started = std::time::Instant::now();
+        let result = synthesize(&request)
+            .instrument(tracing::info_span!(
+                "bulk.item",
+                index,
+                output = %request.output.display()
+            ))
+            .await;
+        match result {
+            Ok(()) => {
+                let bytes = fs::metadata(&request.output)
+                    .map(|m| m.len())
+                    .unwrap_or_default();
+                on_event(BulkEvent::Written {
+                    index,
+                    output: &request.output,
+                    bytes,
+                    elapsed: started.elapsed(),
+                });
+                if options.play
+                    && let Err(e) = play_audio(&request.output)
+                {
+                    eprintln!(
+                        "Warning: playback failed for {}: {e}",
+                        request.output.display()
+                    );
+                }
+            }
+            Err(_) if request.cancel.is_cancelled() && !options.cancel.is_cancelled() => {
+                on_event(BulkEvent::Skipped {
+                    index,
+                    output: &request.output,
+                });
+            }
+            Err(e) => {
+                let e = e.context(bulk_item_label(index, &request));
+                on_event(BulkEvent::Failed {
+                    index,
+                    output: &request.output,
+                    error: &e,
+                });
+                if !options.keep_going || options.cancel.is_cancelled() {
+                    return Err(e);
+                }
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(FastTtsError::Other(format!("{failed} bulk item(s) failed")).into());
+    }
+    Ok(())
+}
+
+/// Which bulk item failed and what it asked for, so the error can be traced
+/// back to its config entry.
+fn bulk_item_label(index: usize, request: &SynthesisRequest) -> String {
+    let mut label = format!(
+        "bulk item {index} ({}): provider {}, language {}, encoding {}",
+        request.output.display(),
+        request.provider,
+        request.language,
+        request.encoding.api_str()
+    );
+    if let Some(voice) = &request.voice {
+        label.push_str(&format!(", voice {voice}"));
+    }
+    label
+}
+
+pub fn play_audio(path: &Path) -> Result<()> {
+    // Best-effort cross-platform playback using system tools
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("non-UTF8 path not supported for playback"))?;
+
+    #[cfg(target_os = "macos")]
+    {
+        // Prefer afplay (CLI player); fall back to opening default app
+        if Command::new("afplay").arg(path_str).status().is_ok() {
+            return Ok(());
+        }
+        Command::new("open")
+            .arg(path_str)
+            .status()
+            .map(|_| ())
+            .context("failed to open audio with 'open'")?
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // Try ffplay/paplay/aplay, then fall back to xdg-open
+        let try_cmds: &[(&str, &[&str])] = &[
+            ("ffplay", &["-autoexit", "-nodisp"]),
+            ("paplay", &[]),
+            ("aplay", &[]),
+        ];
+        for (bin, args) in try_cmds {
+            if Command::new(bin).args(*args).arg(path_str).status().is_ok() {
+                return Ok(());
+            }
+        }
+        Command::new("xdg-open")
+            .arg(path_str)
+            .status()
+            .map(|_| ())
+            .context("failed to open audio with 'xdg-open'")?
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // Use cmd to start default associated app
+        Command::new("cmd")
+            .args(["/C", "start", "", path_str])
+            .status()
+            .map(|_| ())
+            .context("failed to open audio with 'start'")?
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+    {
+        anyhow::bail!("playback not supported on this OS");
+    }
+
+    Ok(())
+}
+
+/// Drain `stream` into `output` (stdout when `None`) and, with `play`, into a
+/// player reading stdin so audio starts before synthesis finishes.
+pub async fn write_stream(
+    mut stream: AudioStream,
+    output: Option<&Path>,
+    play: bool,
+) -> Result<u64> {
+    use futures::StreamExt as _;
+    use std::io::Write as _;
+    let mut sink: Box<dyn std::io::Write + Send> = match output {
+        Some(path) => Box::new(create_output_file(path)?),
+        None => Box::new(std::io::stdout()),
+    };
+    let mut player = if play { spawn_stdin_player() } else { None };
+    let mut written = 0u64;
+    let result: Result<()> = async {
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            sink.write_all(&chunk)?;
+            // A player that exits early shouldn't fail the download
+            if let Some(child) = &mut player
+                && child
+                    .stdin
+                    .as_mut()
+                    .is_some_and(|stdin| stdin.write_all(&chunk).is_err())
+            {
+                player = None;
+            }
+            written += chunk.len() as u64;
+        }
+        sink.flush()?;
+        Ok(())
+    }
+    .await;
+    if let Some(mut child) = player {
+        drop(child.stdin.take());
+        let _ = child.wait();
+    }
+    if let Err(e) = result {
+        if let Some(path) = output {
+            remove_partial_output(path);
+        }
+        return Err(e);
+    }
+    Ok(written)
+}
+
+/// Start the first available player that accepts audio on stdin.
+fn spawn_stdin_player() -> Option<std::process::Child> {
+    let players: &[(&str, &[&str])] = &[
+        (
+            "ffplay",
+            &["-autoexit", "-nodisp", "-loglevel", "quiet", "-i", "-"],
+        ),
+        ("mpv", &["--no-video", "--really-quiet", "-"]),
+    ];
+    let child = players.iter().find_map(|(bin, args)| {
+        Command::new(bin)
+            .args(*args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .ok()
+    });
+    if child.is_none() {
+        eprintln!("Warning: streaming playback needs ffplay or mpv on PATH");
+    }
+    child
+}
+
+// Provider parsing removed (Google only)
+fn base_url() -> String {
+    std::env::var("FAST_TTS_BASE_URL")
+        .unwrap_or_else(|_| "https://texttospeech.googleapis.com".to_string())
+}
+
+/// Process-wide HTTP client settings shared by every provider.
+#[derive(Debug, Default)]
+struct HttpOptions {
+    root_certs: Vec<reqwest::Certificate>,
+    insecure: bool,
+    identity: Option<reqwest::Identity>,
+    extra_headers: HeaderMap,
+    transcript: Option<std::sync::Mutex<fs::File>>,
+    cassette: Option<Cassette>,
+    resolve: Vec<(String, Vec<std::net::SocketAddr>)>,
+    offline: bool,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum CassetteMode {
+    Record,
+    Replay,
+}
+
+/// One recorded HTTP exchange. Requests are matched on method and the
+/// redacted URL, in recording order.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Interaction {
+    method: String,
+    url: String,
+    status: u16,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+    /// Base64-encoded response body
+    body: String,
+    #[serde(default, skip)]
+    used: bool,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug)]
+struct Cassette {
+    mode: CassetteMode,
+    path: PathBuf,
+    interactions: std::sync::Mutex<Vec<Interaction>>,
+}
+
+impl Cassette {
+    fn open(path: &Path, mode: CassetteMode) -> Result<Self> {
+        let interactions = match mode {
+            CassetteMode::Record => Vec::new(),
+            CassetteMode::Replay => {
+                let data = fs::read_to_string(path)
+                    .with_context(|| format!("failed to read cassette: {}", path.display()))?;
+                let file: CassetteFile = serde_json::from_str(&data)
+                    .with_context(|| format!("invalid cassette: {}", path.display()))?;
+                file.interactions
+            }
+        };
+        Ok(Self {
+            mode,
+            path: path.to_path_buf(),
+            interactions: std::sync::Mutex::new(interactions),
+        })
+    }
+
+    fn replay(&self, method: &str, url: &str) -> Result<reqwest::Response> {
+        let mut interactions = self
+            .interactions
+            .lock()
+            .map_err(|_| anyhow::anyhow!("cassette lock poisoned"))?;
+        let hit = interactions
+            .iter_mut()
+            .find(|i| !i.used && i.method == method && i.url == url)
+            .with_context(|| {
+                format!(
+                    "no recorded response for {method} {url} in cassette {}",
+                    self.path.display()
+                )
+            })?;
+        hit.used = true;
+        let body = base64::engine::general_purpose::STANDARD
+            .decode(&hit.body)
+            .context("invalid base64 body in cassette")?;
+        let mut builder = http::Response::builder().status(hit.status);
+        for (name, value) in &hit.headers {
+            builder = builder.header(name, value);
+        }
+        Ok(reqwest::Response::from(builder.body(body)?))
+    }
+
+    async fn record(
+        &self,
+        method: &str,
+        url: &str,
+        resp: reqwest::Response,
+    ) -> Result<reqwest::Response> {
+        let status = resp.status();
+        let headers: Vec<(String, String)> = resp
+            .headers()
+            .iter()
+            .filter(|(name, _)| !SECRET_HEADERS.contains(&name.as_str()))
+            .filter_map(|(name, value)| {
+                value
+                    .to_str()
+                    .ok()
+                    .map(|v| (name.as_str().to_string(), v.to_string()))
+            })
+            .collect();
+        let body = resp.bytes().await?;
+        {
+            let mut interactions = self
+                .interactions
+                .lock()
+                .map_err(|_| anyhow::anyhow!("cassette lock poisoned"))?;
+            interactions.push(Interaction {
+                method: method.to_string(),
+                url: url.to_string(),
+                status: status.as_u16(),
+                headers: headers.clone(),
+                body: base64::engine::general_purpose::STANDARD.encode(&body),
+                used: false,
+            });
+            // Persist after every exchange so a failing run still leaves a usable cassette
+            let file = CassetteFile {
+                interactions: interactions.clone(),
+            };
+            fs::write(&self.path, serde_json::to_string_pretty(&file)?)
+                .with_context(|| format!("failed to write cassette: {}", self.path.display()))?;
+        }
+        let mut builder = http::Response::builder().status(status);
+        for (name, value) in &headers {
+            builder = builder.header(name, value);
+        }
+        Ok(reqwest::Response::from(builder.body(body.to_vec())?))
+    }
+}
+
+static HTTP_OPTIONS: OnceLock<HttpOptions> = OnceLock::new();
+
+/// Process-wide HTTP settings, mirroring the CLI's network flags.
+#[derive(Debug, Default, Clone)]
+pub struct HttpConfig {
+    /// Extra root CA bundles (PEM); `FAST_TTS_CA_BUNDLE` is added automatically
+    pub ca_certs: Vec<PathBuf>,
+    pub insecure: bool,
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    /// Extra headers as `"Name: value"`
+    pub headers: Vec<String>,
+    /// Append sanitized request/response metadata here (JSON lines)
+    pub debug_http: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    /// curl-style `HOST:PORT:ADDR[,ADDR...]` pins
+    pub resolve: Vec<String>,
+    /// Forbid network calls; `FAST_TTS_OFFLINE=1` also enables this
+    pub offline: bool,
+}
+
+/// Apply `config` to every provider client. Only the first call takes effect,
+/// and it must come before the first request.
+pub fn init_http(config: &HttpConfig) -> Result<()> {
+    let mut ca_paths = config.ca_certs.clone();
+    if let Ok(bundle) = std::env::var("FAST_TTS_CA_BUNDLE")
+        && !bundle.trim().is_empty()
+    {
+        ca_paths.push(PathBuf::from(bundle));
+    }
+    let mut root_certs = Vec::new();
+    for path in &ca_paths {
+        let pem = fs::read(path)
+            .with_context(|| format!("failed to read CA certificate: {}", path.display()))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("invalid PEM in CA certificate: {}", path.display()))?;
+        if certs.is_empty() {
+            anyhow::bail!("no certificates found in {}", path.display());
+        }
+        root_certs.extend(certs);
+    }
+    let identity = match &config.client_cert {
+        Some(cert_path) => Some(load_client_identity(
+            cert_path,
+            config.client_key.as_deref(),
+        )?),
+        None => None,
+    };
+    let mut extra_headers = HeaderMap::new();
+    for raw in &config.headers {
+        let (name, value) = parse_header_arg(raw)?;
+        extra_headers.append(name, value);
+    }
+    let transcript = match &config.debug_http {
+        Some(path) => {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("failed to open HTTP transcript: {}", path.display()))?;
+            Some(std::sync::Mutex::new(file))
+        }
+        None => None,
+    };
+    let cassette = match (&config.record, &config.replay) {
+        (Some(path), _) => Some(Cassette::open(path, CassetteMode::Record)?),
+        (None, Some(path)) => Some(Cassette::open(path, CassetteMode::Replay)?),
+        (None, None) => None,
+    };
+    let resolve = config
+        .resolve
+        .iter()
+        .map(|raw| parse_resolve_arg(raw))
+        .collect::<Result<Vec<_>>>()?;
+    if config.insecure {
+        eprintln!("Warning: TLS certificate verification is disabled (--insecure)");
+    }
+    let _ = HTTP_OPTIONS.set(HttpOptions {
+        root_certs,
+        insecure: config.insecure,
+        identity,
+        extra_headers,
+        transcript,
+        cassette,
+        resolve,
+        offline: config.offline || env_flag("FAST_TTS_OFFLINE"),
+    });
+    Ok(())
+}
+
+/// A credential as a header value, marked sensitive so `--debug-http` redacts it.
+pub(crate) fn secret_header(value: &str) -> Result<HeaderValue> {
+    let mut value =
+        HeaderValue::from_str(value).context("credential is not a valid HTTP header value")?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+fn parse_header_arg(
+    raw: &str,
+) -> Result<(reqwest::header::HeaderName, reqwest::header::HeaderValue)> {
+    let (name, value) = raw
+        .split_once(':')
+        .with_context(|| format!("invalid --header {raw:?}: expected \"Name: value\""))?;
+    let name = reqwest::header::HeaderName::from_bytes(name.trim().as_bytes())
+        .with_context(|| format!("invalid header name in {raw:?}"))?;
+    let value = reqwest::header::HeaderValue::from_str(value.trim())
+        .with_context(|| format!("invalid header value in {raw:?}"))?;
+    Ok((name, value))
+}
+
+fn parse_resolve_arg(raw: &str) -> Result<(String, Vec<std::net::SocketAddr>)> {
+    let mut parts = raw.splitn(3, ':');
+    let (Some(host), Some(port), Some(addrs)) = (parts.next(), parts.next(), parts.next()) else {
+        anyhow::bail!("invalid --resolve {raw:?}: expected HOST:PORT:ADDR");
+    };
+    if host.is_empty() {
+        anyhow::bail!("invalid --resolve {raw:?}: missing host");
+    }
+    let port: u16 = port
+        .parse()
+        .with_context(|| format!("invalid port in --resolve {raw:?}"))?;
+    let addrs = addrs
+        .split(',')
+        .map(|a| {
+            let a = a.trim().trim_start_matches('[').trim_end_matches(']');
+            a.parse::<std::net::IpAddr>()
+                .map(|ip| std::net::SocketAddr::new(ip, port))
+                .with_context(|| format!("invalid address {a:?} in --resolve {raw:?}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((host.to_lowercase(), addrs))
+}
+
+fn load_client_identity(cert_path: &Path, key_path: Option<&Path>) -> Result<reqwest::Identity> {
+    // rustls expects certificate chain and private key in one PEM buffer
+    let mut pem = fs::read(cert_path)
+        .with_context(|| format!("failed to read client certificate: {}", cert_path.display()))?;
+    if let Some(key_path) = key_path {
+        let key = fs::read(key_path)
+            .with_context(|| format!("failed to read client key: {}", key_path.display()))?;
+        if !pem.ends_with(b"\n") {
+            pem.push(b'\n');
+        }
+        pem.extend_from_slice(&key);
+    }
+    reqwest::Identity::from_pem(&pem).with_context(|| {
+        format!(
+            "invalid client certificate/key PEM: {}",
+            cert_path.display()
+        )
+    })
+}
+
+/// True when an environment variable is set to 1/true/yes.
+fn env_flag(var: &str) -> bool {
+    std::env::var(var)
+        .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Whether network access is disabled (`--offline` / `FAST_TTS_OFFLINE`).
+pub fn offline() -> bool {
+    http_options().offline
+}
+
+fn http_options() -> &'static HttpOptions {
+    HTTP_OPTIONS.get_or_init(HttpOptions::default)
+}
+
+fn replay_mode() -> bool {
+    http_options()
+        .cassette
+        .as_ref()
+        .is_some_and(|c| c.mode == CassetteMode::Replay)
+}
+
+/// Fail fast when `--offline` forbids the network call described by `what`.
+fn ensure_online(what: &str) -> Result<()> {
+    if http_options().offline && !replay_mode() {
+        return Err(FastTtsError::Offline(format!(
+            "network access disabled by --offline: refusing {what} (use --replay or a local provider)"
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Read a provider secret from the environment. During --replay a
+/// placeholder is returned so cassettes play back without credentials.
+fn provider_secret(var: &str, provider: &str) -> Result<String> {
+    match config_var(var) {
+        Some(v) => Ok(v),
+        None if replay_mode() => Ok("replay".to_string()),
+        None => Err(FastTtsError::MissingCredentials(format!(
+            "{var} is required for provider {provider}"
+        ))
+        .into()),
+    }
+}
+
+fn build_http_client_for_base(base: &str) -> Result<reqwest::Client> {
+    let opts = http_options();
+    let mut builder = reqwest::Client::builder();
+    if base.contains("127.0.0.1") || base.contains("localhost") {
+        builder = builder.no_proxy();
+    }
+    for cert in &opts.root_certs {
+        builder = builder.add_root_certificate(cert.clone());
+    }
+    if opts.insecure {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    if let Some(identity) = &opts.identity {
+        builder = builder.identity(identity.clone());
+    }
+    if !opts.extra_headers.is_empty() {
+        builder = builder.default_headers(opts.extra_headers.clone());
+    }
+    // The resolver only sees host names, so like curl an override applies to
+    // the host and port it names: other ports on that host resolve normally
+    if let Ok(url) = reqwest::Url::parse(base)
+        && let Some(host) = url.host_str()
+    {
+        let port = url.port_or_known_default();
+        for (_, addrs) in opts.resolve.iter().filter(|(name, addrs)| {
+            name.eq_ignore_ascii_case(host) && addrs.first().map(|a| a.port()) == port
+        }) {
+            builder = builder.resolve_to_addrs(host, addrs);
+        }
+    }
+    Ok(builder.build()?)
+}
+
+/// Header names whose values must never appear in transcripts; values marked
+/// [sensitive](HeaderValue::is_sensitive) are redacted whatever their name.
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "xi-api-key",
+    "x-goog-api-key",
+    "ocp-apim-subscription-key",
+    "api-key",
+    "x-api-key",
+    "cookie",
+    "set-cookie",
+];
+
+/// Query parameters that carry credentials (e.g. Gemini's `?key=`).
+const SECRET_QUERY_PARAMS: &[&str] = &[
+    "key",
+    "api_key",
+    "apikey",
+    "token",
+    "access_token",
+    "x-amz-signature",
+    "x-amz-security-token",
+];
+
+fn redact_url(url: &reqwest::Url) -> String {
+    let mut redacted = url.clone();
+    if url.query().is_some() {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| {
+                let v = if SECRET_QUERY_PARAMS.contains(&k.to_lowercase().as_str()) {
+                    "REDACTED".to_string()
+                } else {
+                    v.into_owned()
+                };
+                (k.into_owned(), v)
+            })
+            .collect();
+        redacted.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    redacted.to_string()
+}
+
+fn redact_headers(headers: &HeaderMap) -> serde_json::Map<String, serde_json::Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let shown = if value.is_sensitive() || SECRET_HEADERS.contains(&name.as_str()) {
+                "REDACTED".to_string()
+            } else {
+                value.to_str().unwrap_or("<binary>").to_string()
+            };
+            (name.as_str().to_string(), serde_json::Value::String(shown))
+        })
+        .collect()
+}
+
+/// Send a request built on a provider client, recording it in the
+/// `--debug-http` transcript when enabled.
+async fn send_http(req: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let (client, req) = req.build_split();
+    let req = req?;
+    let method = req.method().to_string();
+    let url = redact_url(req.url());
+    let span = tracing::info_span!(
+        "http.request",
+        otel.kind = "client",
+        otel.name = %format!("{method} {}", req.url().host_str().unwrap_or_default()),
+        http.request.method = %method,
+        url.full = %url,
+        http.response.status_code = tracing::field::Empty,
+        otel.status_code = tracing::field::Empty,
+    );
+    let started = std::time::Instant::now();
+    let result = dispatch_http(client, req, &method, &url)
+        .instrument(span.clone())
+        .await;
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    let entered = span.enter();
+    match &result {
+        Ok(resp) => {
+            span.record("http.response.status_code", resp.status().as_u16());
+            if !resp.status().is_success() {
+                span.record("otel.status_code", "ERROR");
+            }
+            tracing::debug!(status = resp.status().as_u16(), elapsed_ms, "http request");
+        }
+        Err(e) => {
+            span.record("otel.status_code", "ERROR");
+            tracing::debug!(elapsed_ms, error = %e, "http request failed");
+        }
+    }
+    drop(entered);
+    result
+}
+
+async fn dispatch_http(
+    client: reqwest::Client,
+    req: reqwest::Request,
+    method: &str,
+    url: &str,
+) -> Result<reqwest::Response> {
+    let opts = http_options();
+    ensure_online(&format!("{method} {url}"))?;
+    let Some(cassette) = &opts.cassette else {
+        return execute_logged(&client, req).await;
+    };
+    match cassette.mode {
+        CassetteMode::Replay => cassette.replay(method, url),
+        CassetteMode::Record => {
+            let resp = execute_logged(&client, req).await?;
+            cassette.record(method, url, resp).await
+        }
+    }
+}
+
+async fn execute_logged(
+    client: &reqwest::Client,
+    req: reqwest::Request,
+) -> Result<reqwest::Response> {
+    let Some(transcript) = &http_options().transcript else {
+        return Ok(client.execute(req).await?);
+    };
+
+    let method = req.method().to_string();
+    let url = redact_url(req.url());
+    let request_headers = redact_headers(req.headers());
+    let request_bytes = req
+        .body()
+        .and_then(|b| b.as_bytes())
+        .map(|b| b.len() as u64);
+    let started = std::time::Instant::now();
+    let result = client.execute(req).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let mut entry = serde_json::json!({
+        "timestamp": std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        "method": method,
+        "url": url,
+        "requestHeaders": request_headers,
+        "requestBytes": request_bytes,
+        "latencyMs": latency_ms,
+    });
+    match &result {
+        Ok(resp) => {
+            entry["status"] = resp.status().as_u16().into();
+            entry["responseHeaders"] = redact_headers(resp.headers()).into();
+            entry["responseBytes"] = resp.content_length().into();
+        }
+        Err(e) => entry["error"] = e.to_string().into(),
+    }
+    if let Ok(mut file) = transcript.lock() {
+        use std::io::Write as _;
+        let _ = writeln!(file, "{entry}");
+    }
+    Ok(result?)
+}
+
+/// Size of the write buffer used when streaming audio to disk.
+const STREAM_BUFFER_BYTES: usize = 64 * 1024;
+
+fn create_output_file(output: &Path) -> Result<std::io::BufWriter<fs::File>> {
+    if let Some(parent) = output.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create output directory: {}", parent.display()))?;
+    }
+    // Opening a FIFO blocks until its reader is there
+    let file = if is_fifo(output) {
+        fs::OpenOptions::new().write(true).open(output)
+    } else {
+        fs::File::create(output)
+    }
+    .with_context(|| format!("failed to write {}", output.display()))?;
+    Ok(std::io::BufWriter::with_capacity(STREAM_BUFFER_BYTES, file))
+}
+
+/// Delete a half-written output file. FIFOs and devices are left alone:
+/// the reader on the other end owns them.
+fn remove_partial_output(output: &Path) {
+    if fs::symlink_metadata(output).is_ok_and(|m| m.is_file()) {
+        let _ = fs::remove_file(output);
+    }
+}
+
+/// Whether `path` is an existing named pipe, which is written as a stream:
+/// no extension check, no truncation, never removed.
+pub fn is_fifo(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt as _;
+        fs::metadata(path).is_ok_and(|m| m.file_type().is_fifo())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// Audio delivered incrementally by [`synthesize_stream`].
+pub type AudioStream = std::pin::Pin<Box<dyn futures::Stream<Item = Result<bytes::Bytes>> + Send>>;
+
+/// Yield a raw audio response body chunk by chunk as it arrives.
+fn response_stream(resp: reqwest::Response) -> AudioStream {
+    Box::pin(futures::stream::try_unfold(resp, |mut resp| async move {
+        Ok(resp.chunk().await?.map(|chunk| (chunk, resp)))
+    }))
+}
+
+/// Copy a raw audio response body to `output` chunk by chunk.
+async fn stream_response_to_file(mut resp: reqwest::Response, output: &Path) -> Result<u64> {
+    use std::io::Write as _;
+    let mut writer = create_output_file(output)?;
+    let mut written = 0u64;
+    let result: Result<()> = async {
+        while let Some(chunk) = resp.chunk().await? {
+            writer.write_all(&chunk)?;
+            written += chunk.len() as u64;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        remove_partial_output(output);
+        return Err(e.context(format!("failed to write {}", output.display())));
+    }
+    Ok(written)
+}
+
+/// Incrementally locates a JSON string field holding base64 data and decodes
+/// it as chunks arrive, so neither the encoded nor decoded audio is held in
+/// memory at once.
+struct Base64FieldDecoder<'a> {
+    fields: &'a [&'a str],
+    state: Base64FieldState,
+}
+
+enum Base64FieldState {
+    /// Looking for `"field" : "`; holds the unmatched tail of the input.
+    Seeking(Vec<u8>),
+    /// Inside the string value; holds base64 characters not yet decodable.
+    InValue(Vec<u8>),
+    Done,
+}
+
+impl<'a> Base64FieldDecoder<'a> {
+    fn new(fields: &'a [&'a str]) -> Self {
+        Self {
+            fields,
+            state: Base64FieldState::Seeking(Vec::new()),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        matches!(self.state, Base64FieldState::Done)
+    }
+
+    /// Feed the next chunk of the response body, writing decoded bytes to `out`.
+    fn feed(&mut self, chunk: &[u8], out: &mut impl std::io::Write) -> Result<u64> {
+        let value_start;
+        let mut input = chunk;
+        if let Base64FieldState::Seeking(buf) = &mut self.state {
+            buf.extend_from_slice(chunk);
+            match find_json_string_value(buf, self.fields) {
+                Some(start) => {
+                    value_start = buf.split_off(start);
+                    input = &value_start;
+                    self.state = Base64FieldState::InValue(Vec::new());
+                }
+                None => {
+                    // Keep enough tail to match a key split across chunks
+                    let keep = self.fields.iter().map(|f| f.len()).max().unwrap_or(0) + 16;
+                    if buf.len() > keep {
+                        buf.drain(..buf.len() - keep);
+                    }
+                    return Ok(0);
+                }
+            }
+        }
+        let Base64FieldState::InValue(pending) = &mut self.state else {
+            return Ok(0);
+        };
+        let (value, closed) = match input.iter().position(|&b| b == b'"') {
+            Some(end) => (&input[..end], true),
+            None => (input, false),
+        };
+        // JSON may escape '/' as "\/"; base64 never contains backslashes
+        pending.extend(value.iter().copied().filter(|&b| b != b'\\'));
+        let usable = if closed {
+            pending.len()
+        } else {
+            pending.len() / 4 * 4
+        };
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(&pending[..usable])
+            .context("invalid base64 audio content in response")?;
+        out.write_all(&decoded)?;
+        pending.drain(..usable);
+        if closed {
+            self.state = Base64FieldState::Done;
+        }
+        Ok(decoded.len() as u64)
+    }
+}
+
+/// Return the offset just past the opening quote of the first matching field's value.
+fn find_json_string_value(buf: &[u8], fields: &[&str]) -> Option<usize> {
+    for field in fields {
+        let key = format!("\"{field}\"");
+        let key = key.as_bytes();
+        let mut from = 0;
+        while let Some(pos) = buf[from..].windows(key.len()).position(|w| w == key) {
+            let mut i = from + pos + key.len();
+            while i < buf.len() && buf[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            if i < buf.len() && buf[i] == b':' {
+                i += 1;
+                while i < buf.len() && buf[i].is_ascii_whitespace() {
+                    i += 1;
+                }
+                if i < buf.len() && buf[i] == b'"' {
+                    return Some(i + 1);
+                }
+            }
+            from += pos + 1;
+        }
+    }
+    None
+}
+
+/// Stream-decode a base64 JSON string field (e.g. Google's `audioContent`) into `output`.
+async fn stream_base64_field_to_file(
+    mut resp: reqwest::Response,
+    output: &Path,
+    fields: &[&str],
+) -> Result<u64> {
+    use std::io::Write as _;
+    let mut writer = create_output_file(output)?;
+    let mut decoder = Base64FieldDecoder::new(fields);
+    let mut written = 0u64;
+    let result: Result<()> = async {
+        while let Some(chunk) = resp.chunk().await? {
+            written += decoder.feed(&chunk, &mut writer)?;
+        }
+        if !decoder.is_done() {
+            anyhow::bail!("response did not include {}", fields[0]);
+        }
+        writer.flush()?;
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        remove_partial_output(output);
+        return Err(e);
+    }
+    Ok(written)
+}
+
+/// Cached voice catalog plus the validator used to revalidate it.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VoiceCatalogCache {
+    url: String,
+    etag: Option<String>,
+    body: String,
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    if let Ok(dir) = std::env::var("FAST_TTS_CACHE_DIR")
+        && !dir.trim().is_empty()
+    {
+        return Some(PathBuf::from(dir));
+    }
+    dirs::cache_dir().map(|d| d.join("fast-tts-cli"))
+}
+
+fn voice_cache_path(provider: &str) -> Option<PathBuf> {
+    cache_dir().map(|d| d.join("voices").join(format!("{provider}.json")))
+}
+
+/// `provider`'s cached catalog, without touching the network.
+fn cached_voices(provider: Provider) -> Option<Vec<Voice>> {
+    let path = voice_cache_path(&provider.to_string())?;
+    let cache: VoiceCatalogCache = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    let catalog: ListVoicesResponse = serde_json::from_str(&cache.body).ok()?;
+    Some(catalog.voices)
+}
+
+/// Voice names from `provider`'s cached catalog, without touching the network.
+fn cached_voice_names(provider: Provider) -> Option<Vec<String>> {
+    Some(
+        cached_voices(provider)?
+            .into_iter()
+            .map(|v| v.name)
+            .collect(),
+    )
+}
+
+/// Save `data` as `provider`'s cached catalog for `url`. Cache failures only
+/// cost a refetch next time.
+fn store_voice_cache(provider: &str, url: String, etag: Option<String>, data: &ListVoicesResponse) {
+    let Some(path) = voice_cache_path(provider) else {
+        return;
+    };
+    let Ok(body) = serde_json::to_string(data) else {
+        return;
+    };
+    let entry = VoiceCatalogCache { url, etag, body };
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(entry) = serde_json::to_string(&entry) {
+        let _ = fs::write(&path, entry);
+    }
+}
+
+fn read_voice_cache(path: &Path, url: &str) -> Option<VoiceCatalogCache> {
+    let data = fs::read_to_string(path).ok()?;
+    let cache: VoiceCatalogCache = serde_json::from_str(&data).ok()?;
+    (cache.url == url).then_some(cache)
+}
+
+/// The Google Cloud voice catalog; see [`TtsProvider::list_voices`] for others.
+pub async fn list_voices() -> Result<ListVoicesResponse> {
+    providers::get(Provider::Google).list_voices().await
+}
+
+/// Google's catalog, served from the local cache when unchanged.
+async fn fetch_google_voices() -> Result<ListVoicesResponse> {
+    let base = base_url();
+    fetch_voice_catalog(
+        "google",
+        format!("{base}/v1/voices"),
+        google_auth_headers(),
+        |body| Ok(serde_json::from_str(body)?),
+    )
+    .await
+}
+
+/// Fetch `provider`'s voice catalog from `url`, revalidating a cached copy
+/// with `If-None-Match` so unchanged catalogs come back as a cheap 304.
+/// `parse` turns the response body into the common shape, which is what gets
+/// cached; `headers` (usually auth) is only awaited when a request is sent.
+async fn fetch_voice_catalog(
+    provider: &str,
+    url: String,
+    headers: impl std::future::Future<Output = Result<HeaderMap>>,
+    parse: fn(&str) -> Result<ListVoicesResponse>,
+) -> Result<ListVoicesResponse> {
+    let cache_path = voice_cache_path(provider);
+    let cached = cache_path
+        .as_deref()
+        .and_then(|p| read_voice_cache(p, &url));
+    tracing::debug!(
+        provider,
+        hit = cached.is_some(),
+        "voice catalog cache lookup"
+    );
+
+    if http_options().offline
+        && !replay_mode()
+        && let Some(cached) = &cached
+    {
+        return Ok(serde_json::from_str(&cached.body)?);
+    }
+
+    let client = build_http_client_for_base(&url)?;
+    let mut headers = headers.await?;
+    if let Some(etag) = cached.as_ref().and_then(|c| c.etag.as_deref()) {
+        headers.insert(reqwest::header::IF_NONE_MATCH, etag.parse()?);
+    }
+
+    let resp = send_http(client.get(&url).headers(headers)).await?;
+    if resp.status() == reqwest::StatusCode::NOT_MODIFIED
+        && let Some(cached) = cached
+    {
+        return Ok(serde_json::from_str(&cached.body)?);
+    }
+    let resp = resp.error_for_status()?;
+    let etag = resp
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let data =
+        parse(&resp.text().await?).with_context(|| format!("invalid {provider} voice catalog"))?;
+    store_voice_cache(provider, url, etag, &data);
+    Ok(data)
+}
+
+/// Cache key for Polly's catalog, which comes from the AWS SDK rather than a URL.
+#[cfg(feature = "polly")]
+const POLLY_VOICES_KEY: &str = "polly:DescribeVoices";
+
+/// Polly's `DescribeVoices`, with the engines each voice supports.
+#[cfg(feature = "polly")]
+async fn fetch_polly_voices() -> Result<ListVoicesResponse> {
+    if http_options().offline
+        && !replay_mode()
+        && let Some(cached) = voice_cache_path("polly")
+            .as_deref()
+            .and_then(|p| read_voice_cache(p, POLLY_VOICES_KEY))
+    {
+        return Ok(serde_json::from_str(&cached.body)?);
+    }
+    ensure_online("Amazon Polly describe_voices")?;
+    let config = aws_config::load_from_env().await;
+    let client = aws_sdk_polly::Client::new(&config);
+    let mut voices = Vec::new();
+    let mut next_token = None;
+    loop {
+        let page = client
+            .describe_voices()
+            .include_additional_language_codes(true)
+            .set_next_token(next_token)
+            .send()
+            .await
+            .context("Polly DescribeVoices failed")?;
+        voices.extend(page.voices().iter().map(|v| {
+            Voice {
+                name: v.id().map(|id| id.as_str().to_string()).unwrap_or_default(),
+                language_codes: v
+                    .language_code()
+                    .into_iter()
+                    .chain(v.additional_language_codes())
+                    .map(|code| code.as_str().to_string())
+                    .collect(),
+                ssml_gender: v
+                    .gender()
+                    .map_or("NEUTRAL".into(), |g| g.as_str().to_uppercase()),
+                engines: v
+                    .supported_engines()
+                    .iter()
+                    .map(|e| e.as_str().to_string())
+                    .collect(),
+                ..Voice::default()
+            }
+        }));
+        next_token = page.next_token().map(str::to_string);
+        if next_token.is_none() {
+            break;
+        }
+    }
+    let data = ListVoicesResponse { voices };
+    store_voice_cache("polly", POLLY_VOICES_KEY.into(), None, &data);
+    Ok(data)
+}
+
+#[cfg(feature = "polly")]
+const POLLY_ENGINES: &[&str] = &["standard", "neural", "long-form", "generative"];
+
+/// `POLLY_ENGINE` (default neural), checked against the engines `voice`
+/// supports when the Polly catalog is cached (`--list-voices`).
+#[cfg(feature = "polly")]
+fn polly_engine(voice: &str) -> Result<String> {
+    let engine = config_var("POLLY_ENGINE")
+        .unwrap_or_else(|| "neural".into())
+        .to_lowercase();
+    if !POLLY_ENGINES.contains(&engine.as_str()) {
+        return Err(FastTtsError::InvalidInput(format!(
+            "unknown POLLY_ENGINE {engine} (known: {})",
+            POLLY_ENGINES.join(", ")
+        ))
+        .into());
+    }
+    if let Some(voices) = cached_voices(Provider::Polly)
+        && let Some(known) = voices.iter().find(|v| v.name == voice)
+        && !known.engines.is_empty()
+        && !known.engines.contains(&engine)
+    {
+        return Err(FastTtsError::InvalidInput(format!(
+            "Polly voice {voice} does not support the {engine} engine (supports: {})",
+            known.engines.join(", ")
+        ))
+        .into());
+    }
+    Ok(engine)
+}
+
+/// Deepgram's Aura models from `/v1/models`; each model is one voice.
+#[cfg(feature = "provider-deepgram")]
+async fn fetch_deepgram_voices() -> Result<ListVoicesResponse> {
+    #[derive(Deserialize)]
+    struct Models {
+        #[serde(default)]
+        tts: Vec<Model>,
+    }
+    #[derive(Deserialize)]
+    struct Model {
+        canonical_name: String,
+        #[serde(default)]
+        languages: Vec<String>,
+        #[serde(default)]
+        metadata: ModelMetadata,
+    }
+    #[derive(Deserialize, Default)]
+    struct ModelMetadata {
+        #[serde(default)]
+        tags: Vec<String>,
+    }
+
+    let auth = async {
+        let api_key = provider_secret("DEEPGRAM_API_KEY", "deepgram")?;
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, secret_header(&format!("Token {api_key}"))?);
+        Ok(headers)
+    };
+    fetch_voice_catalog(
+        "deepgram",
+        "https://api.deepgram.com/v1/models".into(),
+        auth,
+        |body| {
+            let models: Models = serde_json::from_str(body)?;
+            let voices = models
+                .tts
+                .into_iter()
+                .map(|m| Voice {
+                    name: m.canonical_name,
+                    language_codes: m.languages,
+                    ssml_gender: if m.metadata.tags.iter().any(|t| t == "feminine") {
+                        "FEMALE"
+                    } else if m.metadata.tags.iter().any(|t| t == "masculine") {
+                        "MALE"
+                    } else {
+                        "NEUTRAL"
+                    }
+                    .into(),
+                    natural_sample_rate_hertz: Some(24000),
+                    ..Voice::default()
+                })
+                .collect();
+            Ok(ListVoicesResponse { voices })
+        },
+    )
+    .await
+}
+
+/// Azure's regional `/voices/list`, with each voice's locales, type and
+/// styles/roles.
+#[cfg(feature = "provider-azure")]
+async fn fetch_azure_voices() -> Result<ListVoicesResponse> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct AzureVoice {
+        short_name: String,
+        gender: String,
+        locale: String,
+        #[serde(default)]
+        secondary_locale_list: Vec<String>,
+        #[serde(default)]
+        sample_rate_hertz: Option<String>,
+        #[serde(default)]
+        voice_type: Option<String>,
+        #[serde(default)]
+        style_list: Vec<String>,
+        #[serde(default)]
+        role_play_list: Vec<String>,
+    }
+
+    let region = config_var("AZURE_SPEECH_REGION").ok_or_else(|| {
+        FastTtsError::MissingCredentials(
+            "AZURE_SPEECH_REGION is required for provider azure".into(),
+        )
+    })?;
+    let auth = async {
+        let key = provider_secret("AZURE_SPEECH_KEY", "azure")?;
+        let mut headers = HeaderMap::new();
+        headers.insert("Ocp-Apim-Subscription-Key", secret_header(&key)?);
+        Ok(headers)
+    };
+    fetch_voice_catalog(
+        "azure",
+        format!("https://{region}.tts.speech.microsoft.com/cognitiveservices/voices/list"),
+        auth,
+        |body| {
+            let voices: Vec<AzureVoice> = serde_json::from_str(body)?;
+            let voices = voices
+                .into_iter()
+                .map(|v| Voice {
+                    // HD (DragonHD) voices report themselves as Neural
+                    voice_type: if v.short_name.contains("DragonHD") {
+                        Some("HD".into())
+                    } else {
+                        v.voice_type
+                    },
+                    language_codes: std::iter::once(v.locale)
+                        .chain(v.secondary_locale_list)
+                        .collect(),
+                    ssml_gender: v.gender.to_uppercase(),
+                    natural_sample_rate_hertz: v.sample_rate_hertz.and_then(|r| r.parse().ok()),
+                    styles: v.style_list,
+                    roles: v.role_play_list,
+                    name: v.short_name,
+                    ..Voice::default()
+                })
+                .collect();
+            Ok(ListVoicesResponse { voices })
+        },
+    )
+    .await
+}
+
+/// Read an SSML document for `--ssml-file`: UTF-8 or UTF-16 with a byte-order
+/// mark, without its XML declaration. A stray `&` or an HTML-only entity such
+/// as `&nbsp;` is [`FastTtsError::InvalidInput`].
+pub fn read_ssml_file(path: &Path) -> Result<String> {
+    ssml::read_file(path)
+}
+
+/// `output` with the extension `encoding` needs: a different audio extension
+/// is replaced, anything else (`intro`, `take.2`) gets it appended. FIFOs are
+/// left alone.
+pub fn output_with_extension(output: &Path, encoding: AudioEncoding) -> PathBuf {
+    const AUDIO: &[&str] = &["wav", "mp3", "ogg", "opus", "oga", "pcm", "ulaw", "alaw"];
+    let want_ext = encoding.file_extension();
+    if is_fifo(output) {
+        return output.to_path_buf();
+    }
+    match output
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|s| s.to_lowercase())
+    {
+        Some(ext) if ext == want_ext => output.to_path_buf(),
+        Some(ext) if AUDIO.contains(&ext.as_str()) => output.with_extension(want_ext),
+        _ => {
+            let mut name = output.as_os_str().to_owned();
+            name.push(".");
+            name.push(want_ext);
+            PathBuf::from(name)
+        }
+    }
+}
+
+pub fn validate_output_extension(output: &Path, encoding: AudioEncoding) -> Result<()> {
+    if is_fifo(output) {
+        return Ok(());
+    }
+    let want_ext = encoding.file_extension();
+    match output
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|s| s.to_lowercase())
+    {
+        Some(ref ext) if ext == want_ext => Ok(()),
+        Some(ext) => Err(FastTtsError::InvalidInput(format!(
+            "output extension .{} does not match encoding {} (expected .{})",
+            ext,
+            encoding.api_str(),
+            want_ext
+        ))
+        .into()),
+        None => Err(FastTtsError::InvalidInput(format!(
+            "output must have .{} extension for encoding {}",
+            want_ext,
+            encoding.api_str()
+        ))
+        .into()),
+    }
+}
+
+#[cfg(feature = "provider-openai")]
+/// Send a speech request; like the other `*_audio_response` helpers, the
+/// successful response body is the raw audio.
+async fn openai_audio_response(
+    text: &str,
+    voice: Option<&str>,
+    encoding: AudioEncoding,
+) -> Result<reqwest::Response> {
+    let api_key = provider_secret("OPENAI_API_KEY", "openai")?;
+    let model = config_var("OPENAI_TTS_MODEL").unwrap_or_else(|| "gpt-4o-mini-tts".to_string());
+    let voice_name = voice.unwrap_or("alloy");
+    let out_format = match encoding {
+        AudioEncoding::Mp3 => "mp3",
+        AudioEncoding::OggOpus => "opus",
+        _ => "wav",
+    };
+    let url = "https://api.openai.com/v1/audio/speech";
+    let client = build_http_client_for_base(url)?;
+    Ok(send_http(
+        client
+            .post(url)
+            .bearer_auth(api_key)
+            .json(&serde_json::json!({
+                "model": model,
+                "voice": voice_name,
+                "input": text,
+                "format": out_format
+            })),
+    )
+    .await?
+    .error_for_status()?)
+}
+
+#[cfg(feature = "provider-azure")]
+async fn azure_audio_response(
+    text: &str,
+    is_ssml: bool,
+    language: &str,
+    voice: Option<&str>,
+    encoding: AudioEncoding,
+    sample_rate: Option<i32>,
+) -> Result<reqwest::Response> {
+    let key = provider_secret("AZURE_SPEECH_KEY", "azure")?;
+    // A Speech container or custom domain replaces the regional host
+    let endpoint = config_var("AZURE_SPEECH_ENDPOINT");
+    let region = config_var("AZURE_SPEECH_REGION");
+    let voice_name = voice.unwrap_or(match language {
+        // sensible defaults by locale
+        l if l.starts_with("en-US") => "en-US-JennyNeural",
+        l if l.starts_with("en-GB") => "en-GB-LibbyNeural",
+        _ => "en-US-JennyNeural",
+    });
+    let format = match (encoding, sample_rate) {
+        (AudioEncoding::Mp3, Some(_)) => "audio-48khz-192kbitrate-mono-mp3".to_string(),
+        (AudioEncoding::Mp3, None) => "audio-24khz-160kbitrate-mono-mp3".to_string(),
+        (AudioEncoding::OggOpus, _) => "ogg-48khz-16bit-mono-opus".to_string(),
+        (AudioEncoding::Linear16, Some(sr)) if sr >= 48000 => {
+            "riff-48khz-16bit-mono-pcm".to_string()
+        }
+        (AudioEncoding::Linear16, _) => "riff-24khz-16bit-mono-pcm".to_string(),
+        (AudioEncoding::Mulaw, _) => "mulaw-8khz-8bit-mono".to_string(),
+        (AudioEncoding::Alaw, _) => "alaw-8khz-8bit-mono".to_string(),
+    };
+    let ssml = ssml::azure_document(text, is_ssml, language, voice_name);
+    let url = match (&endpoint, &region) {
+        (Some(endpoint), _) => format!("{}/cognitiveservices/v1", endpoint.trim_end_matches('/')),
+        (None, Some(region)) => {
+            format!("https://{region}.tts.speech.microsoft.com/cognitiveservices/v1")
+        }
+        (None, None) => {
+            return Err(FastTtsError::MissingCredentials(
+                "AZURE_SPEECH_REGION is required for provider azure".into(),
+            )
+            .into());
+        }
+    };
+    let client = build_http_client_for_base(&url)?;
+    // Prefer short-lived bearer tokens; AZURE_SPEECH_AUTH=key sends the key directly
+    let auth = if config_var("AZURE_SPEECH_AUTH").is_some_and(|v| v.eq_ignore_ascii_case("key")) {
+        ("Ocp-Apim-Subscription-Key", secret_header(&key)?)
+    } else {
+        let region = region.context(
+            "AZURE_SPEECH_REGION is required for the Azure token exchange (or set AZURE_SPEECH_AUTH=key)",
+        )?;
+        let token = azure_access_token(&region, &key).await?;
+        (
+            AUTHORIZATION.as_str(),
+            secret_header(&format!("Bearer {token}"))?,
+        )
+    };
+    Ok(send_http(
+        client
+            .post(&url)
+            .header(auth.0, auth.1)
+            .header("X-Microsoft-OutputFormat", format)
+            .header(CONTENT_TYPE, "application/ssml+xml")
+            .header("User-Agent", "fast-tts-cli")
+            .body(ssml),
+    )
+    .await?
+    .error_for_status()?)
+}
+
+/// Text around an ElevenLabs request when long input is sent in pieces.
+#[cfg(feature = "provider-elevenlabs")]
+#[derive(Default, Clone, Copy)]
+struct ElevenlabsContext<'a> {
+    previous: Option<&'a str>,
+    next: Option<&'a str>,
+}
+
+#[cfg(feature = "provider-elevenlabs")]
+async fn elevenlabs_audio_response(
+    text: &str,
+    voice: Option<&str>,
+    encoding: AudioEncoding,
+    model_id: Option<&str>,
+    seed: Option<u32>,
+    context: ElevenlabsContext<'_>,
+) -> Result<reqwest::Response> {
+    let api_key = provider_secret("ELEVENLABS_API_KEY", "elevenlabs")?;
+    let voice_id = voice.unwrap_or("Rachel");
+    let model = model_id.unwrap_or("eleven_multilingual_v2");
+    let format = match encoding {
+        AudioEncoding::Mp3 => "mp3",
+        AudioEncoding::OggOpus => "ogg",
+        _ => "wav",
+    };
+    let url = format!("https://api.elevenlabs.io/v1/text-to-speech/{voice_id}");
+    let client = build_http_client_for_base(&url)?;
+    let mut body = serde_json::json!({
+        "text": text,
+        "model_id": model,
+        "voice_settings": {"stability": 0.5, "similarity_boost": 0.5},
+        "output_format": format
+    });
+    if let Some(seed) = seed {
+        body["seed"] = seed.into();
+    }
+    // Request stitching: the neighbouring text keeps intonation continuous
+    if let Some(previous) = context.previous {
+        body["previous_text"] = previous.into();
+    }
+    if let Some(next) = context.next {
+        body["next_text"] = next.into();
+    }
+    Ok(send_http(
+        client
+            .post(&url)
+            .header("xi-api-key", secret_header(&api_key)?)
+            .header(CONTENT_TYPE, "application/json")
+            .json(&body),
+    )
+    .await?
+    .error_for_status()?)
+}
+
+#[cfg(feature = "provider-deepgram")]
+async fn deepgram_audio_response(
+    text: &str,
+    voice: Option<&str>,
+    encoding: AudioEncoding,
+    model_id: Option<&str>,
+    bit_rate: Option<u32>,
+) -> Result<reqwest::Response> {
+    let api_key = provider_secret("DEEPGRAM_API_KEY", "deepgram")?;
+    let model = model_id.unwrap_or("aura-asteria-en");
+    let voice_name = voice.unwrap_or("aura-asteria-en");
+    let format = match encoding {
+        AudioEncoding::Mp3 => "mp3",
+        AudioEncoding::OggOpus => "opus",
+        _ => "wav",
+    };
+    let url = "https://api.deepgram.com/v1/speak";
+    let client = build_http_client_for_base(url)?;
+    let mut query = vec![
+        ("model", model.to_string()),
+        ("voice", voice_name.to_string()),
+        ("format", format.to_string()),
+    ];
+    if let Some(bit_rate) = bit_rate {
+        query.push(("bit_rate", bit_rate.to_string()));
+    }
+    Ok(send_http(
+        client
+            .post(url)
+            .header(AUTHORIZATION, secret_header(&format!("Token {api_key}"))?)
+            .query(&query)
+            .body(text.to_string()),
+    )
+    .await?
+    .error_for_status()?)
+}
+
+#[cfg(feature = "provider-lmnt")]
+async fn lmnt_audio(
+    text: &str,
+    voice: Option<&str>,
+    encoding: AudioEncoding,
+    sample_rate: Option<i32>,
+    speed: f32,
+    seed: Option<u32>,
+) -> Result<Vec<u8>> {
+    let api_key = provider_secret("LMNT_API_KEY", "lmnt")?;
+    let format = match encoding {
+        AudioEncoding::Mp3 => "mp3",
+        _ => "wav",
+    };
+    let url = "https://api.lmnt.com/v1/ai/speech";
+    let client = build_http_client_for_base(url)?;
+    let mut body = serde_json::json!({
+        "text": text,
+        "voice": voice.unwrap_or("lily"),
+        "format": format,
+        "speed": speed,
+    });
+    if let Some(rate) = sample_rate {
+        body["sample_rate"] = rate.into();
+    }
+    if let Some(seed) = seed {
+        body["seed"] = seed.into();
+    }
+    let resp = send_http(
+        client
+            .post(url)
+            .header("X-API-Key", secret_header(&api_key)?)
+            .header(CONTENT_TYPE, "application/json")
+            .json(&body),
+    )
+    .await?
+    .error_for_status()?;
+
+    #[derive(Deserialize)]
+    struct LmntSpeech {
+        audio: String,
+    }
+    let speech: LmntSpeech = resp
+        .json()
+        .await
+        .context("LMNT response did not include audio data")?;
+    base64::engine::general_purpose::STANDARD
+        .decode(speech.audio)
+        .context("failed decoding audio data from LMNT response")
+}
+
+/// LMNT's voices from `/v1/ai/voice/list`, the account's own clones included;
+/// a voice's id is its `--voice` value.
+#[cfg(feature = "provider-lmnt")]
+async fn fetch_lmnt_voices() -> Result<ListVoicesResponse> {
+    #[derive(Deserialize)]
+    struct LmntVoice {
+        id: String,
+        #[serde(default)]
+        gender: Option<String>,
+    }
+
+    let auth = async {
+        let api_key = provider_secret("LMNT_API_KEY", "lmnt")?;
+        let mut headers = HeaderMap::new();
+        headers.insert("X-API-Key", secret_header(&api_key)?);
+        Ok(headers)
+    };
+    fetch_voice_catalog(
+        "lmnt",
+        "https://api.lmnt.com/v1/ai/voice/list".into(),
+        auth,
+        |body| {
+            let listed: Vec<LmntVoice> = serde_json::from_str(body)?;
+            let voices = listed
+                .into_iter()
+                .map(|v| Voice {
+                    name: v.id,
+                    ssml_gender:
+                        match v.gender.as_deref().map(str::to_ascii_uppercase).as_deref() {
+                            Some("F" | "FEMALE") => "FEMALE",
+                            Some("M" | "MALE") => "MALE",
+                            _ => "NEUTRAL",
+                        }
+                        .into(),
+                    natural_sample_rate_hertz: Some(24000),
+                    ..Voice::default()
+                })
+                .collect();
+            Ok(ListVoicesResponse { voices })
+        },
+    )
+    .await
+}
+
+#[cfg(feature = "provider-gemini")]
+async fn synthesize_gemini(
+    text: &str,
+    output: &Path,
+    voice: Option<&str>,
+    encoding: AudioEncoding,
+) -> Result<()> {
+    let api_key = provider_secret("GEMINI_API_KEY", "gemini")?;
+    // Allow overriding the model; default to a fast, generally-available model
+    let model =
+        config_var("GEMINI_TTS_MODEL").unwrap_or_else(|| "gemini-1.5-flash-latest".to_string());
+
+    let format = match encoding {
+        AudioEncoding::Mp3 => "mp3",
+        AudioEncoding::OggOpus => "ogg",
+        AudioEncoding::Linear16 => "wav",
+        AudioEncoding::Mulaw | AudioEncoding::Alaw => {
+            anyhow::bail!(
+                "Gemini speech does not support {} encoding; use MP3/OGG_OPUS/LINEAR16",
+                encoding.api_str()
+            )
+        }
+    };
+
+    #[derive(serde::Serialize)]
+    struct AudioPart<'a> {
+        voice: Option<&'a str>,
+        format: &'a str,
+    }
+
+    let url = format!(
+        "https://generativelanguage.googleapis.com/v1beta/models/{model}:generateContent?key={api_key}"
+    );
+
+    // Build request payload using Gemini generateContent structure
+    let request_body = serde_json::json!({
+        "contents": [
+            {
+                "role": "user",
+                "parts": [
+                    { "text": text },
+                    { "audio": AudioPart { voice, format } }
+                ]
+            }
+        ]
+    });
+
+    let client = build_http_client_for_base(&url)?;
+    let resp = send_http(
+        client
+            .post(&url)
+            .header(CONTENT_TYPE, "application/json")
+            .json(&request_body),
+    )
+    .await?
+    .error_for_status()?;
+
+    #[derive(Deserialize)]
+    struct GeminiAudio {
+        data: String,
+        #[allow(dead_code)]
+        #[serde(rename = "mimeType")]
+        mime_type: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct GeminiPartResp {
+        #[serde(default)]
+        audio: Option<GeminiAudio>,
+        #[allow(dead_code)]
+        #[serde(default)]
+        text: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct GeminiContentResp {
+        parts: Vec<GeminiPartResp>,
+    }
+    #[derive(Deserialize)]
+    struct GeminiCandidate {
+        content: GeminiContentResp,
+    }
+    #[derive(Deserialize)]
+    struct GeminiResponse {
+        candidates: Vec<GeminiCandidate>,
+    }
+
+    let gr: GeminiResponse = resp.json().await?;
+
+    // Find first audio part with data
+    let audio_b64 = gr
+        .candidates
+        .into_iter()
+        .flat_map(|c| c.content.parts)
+        .find_map(|p| p.audio.map(|a| a.data))
+        .context("Gemini response did not include audio data")?;
+
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(audio_b64)
+        .context("failed decoding audio data from Gemini response")?;
+
+    if let Some(parent) = output.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output, bytes)?;
+    Ok(())
+}
+
+#[cfg(feature = "polly")]
+async fn synthesize_polly(
+    text: &str,
+    output: &Path,
+    voice: Option<&str>,
+    encoding: AudioEncoding,
+) -> Result<()> {
+    use aws_sdk_polly::types::{Engine, OutputFormat, VoiceId};
+    let voice_id = voice.unwrap_or("Joanna");
+    let engine = polly_engine(voice_id)?;
+    ensure_online("Amazon Polly synthesis")?;
+    let config = aws_config::load_from_env().await;
+    let client = aws_sdk_polly::Client::new(&config);
+    let output_format = match encoding {
+        AudioEncoding::Mp3 => OutputFormat::Mp3,
+        AudioEncoding::OggOpus => OutputFormat::OggVorbis,
+        _ => OutputFormat::Pcm,
+    };
+    let resp = client
+        .synthesize_speech()
+        .set_text(Some(text.to_string()))
+        .set_voice_id(Some(VoiceId::from(voice_id)))
+        .set_output_format(Some(output_format))
+        .set_engine(Some(Engine::from(engine.as_str())))
+        .send()
+        .await?;
+    let data = resp
+        .audio_stream
+        .collect()
+        .await
+        .context("failed to read Polly audio")?
+        .into_bytes();
+    if let Some(parent) = output.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    fs::write(output, data)?;
+    Ok(())
+}
+#[allow(clippy::too_many_arguments)]
+async fn synthesize_to_wav(
+    text: &str,
+    output: &Path,
+    language: &str,
+    voice: Option<&str>,
+    gender: Option<Gender>,
+    rate: f32,
+    pitch: f32,
+    sample_rate: Option<i32>,
+    encoding: AudioEncoding,
+    volume_gain_db: f32,
+    effects_profile_id: &[String],
+    is_ssml: bool,
+    dialogue: &[markup::DialogueTurn],
+    custom_voice_model: Option<&str>,
+    _timeout_ms: u64,
+    _retries: usize,
+) -> Result<()> {
+    if let Some(parent) = output.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create output directory: {}", parent.display()))?;
+    }
+
+    let base = base_url();
+    let client = build_http_client_for_base(&base)?;
+    // Multi-speaker markup is only served by the beta API
+    let url = if dialogue.is_empty() {
+        format!("{base}/v1/text:synthesize")
+    } else {
+        format!("{base}/v1beta1/text:synthesize")
+    };
+
+    let gender_str = gender.map(|g| match g {
+        Gender::Neutral => "NEUTRAL",
+        Gender::Male => "MALE",
+        Gender::Female => "FEMALE",
+    });
+    let (custom_voice, voice_clone) = google_custom_voice(custom_voice_model);
+
+    let req_body = SynthesizeRequest {
+        input: if !dialogue.is_empty() {
+            SynthesisInput::MultiSpeaker {
+                multi_speaker_markup: MultiSpeakerMarkup { turns: dialogue },
+            }
+        } else if is_ssml {
+            SynthesisInput::Ssml { ssml: text }
+        } else {
+            SynthesisInput::Text { text }
+        },
+        voice: VoiceSelectionParams {
+            language_code: language,
+            name: voice.or((!dialogue.is_empty()).then_some(GOOGLE_MULTI_SPEAKER_VOICE)),
+            ssml_gender: gender_str,
+            custom_voice,
+            voice_clone,
+        },
+        audio_config: AudioConfig {
+            audio_encoding: encoding.api_str(),
+            speaking_rate: rate,
+            pitch,
+            volume_gain_db,
+            sample_rate_hertz: sample_rate,
+            effects_profile_id: effects_profile_id.iter().map(String::as_str).collect(),
+            enable_legacy_wav_header: false,
+        },
+    };
+
+    let mut headers = google_auth_headers().await?;
+    headers.insert(CONTENT_TYPE, "application/json".parse()?);
+
+    let resp = send_http(client.post(url).headers(headers).json(&req_body)).await?;
+    let resp = google_error_for_status(resp).await?;
+
+    stream_base64_field_to_file(resp, output, &["audioContent", "audio_content"]).await?;
+    Ok(())
+}
+
+/// `error_for_status`, plus Google's own error message (which names the
+/// offending field, e.g. `audio_config.speaking_rate`) as context.
+async fn google_error_for_status(resp: reqwest::Response) -> Result<reqwest::Response> {
+    let Err(err) = resp.error_for_status_ref() else {
+        return Ok(resp);
+    };
+    let body: serde_json::Value = resp.json().await.unwrap_or_default();
+    Err(match body["error"]["message"].as_str() {
+        Some(message) => anyhow::Error::from(err).context(format!("Google: {message}")),
+        None => err.into(),
+    })
+}
+
+/// Word-level timing in seconds from the start of the synthesized audio.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(not(feature = "mcp"), allow(dead_code))]
+struct WordMark {
+    word: String,
+    start: f64,
+    end: f64,
+}
+
+/// Approximate duration of the final word, which has no following mark.
+const LAST_WORD_SECS: f64 = 0.6;
+
+/// Synthesize plain text with Google and return per-word timings, using an
+/// SSML `<mark>` before every word and the v1beta1 time-pointing API.
+#[allow(clippy::too_many_arguments)]
+#[cfg_attr(not(feature = "mcp"), allow(dead_code))]
+async fn google_speech_marks(
+    text: &str,
+    output: &Path,
+    language: &str,
+    voice: Option<&str>,
+    gender: Option<Gender>,
+    rate: f32,
+    pitch: f32,
+    encoding: AudioEncoding,
+) -> Result<Vec<WordMark>> {
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct TimepointedRequest<'a> {
+        #[serde(flatten)]
+        request: SynthesizeRequest<'a>,
+        enable_time_pointing: [&'a str; 1],
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Timepoint {
+        mark_name: String,
+        #[serde(default)]
+        time_seconds: f64,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct TimepointedResponse {
+        audio_content: String,
+        #[serde(default)]
+        timepoints: Vec<Timepoint>,
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.is_empty() {
+        anyhow::bail!("text is empty");
+    }
+    let mut ssml = String::from("<speak>");
+    for (i, word) in words.iter().enumerate() {
+        ssml.push_str(&format!(
+            "<mark name=\"{i}\"/>{} ",
+            htmlescape::encode_minimal(word)
+        ));
+    }
+    ssml.push_str("</speak>");
+
+    let (custom_voice, voice_clone) = google_custom_voice(None);
+    let body = TimepointedRequest {
+        request: SynthesizeRequest {
+            input: SynthesisInput::Ssml { ssml: &ssml },
+            voice: VoiceSelectionParams {
+                language_code: language,
+                name: voice,
+                ssml_gender: gender.map(|g| match g {
+                    Gender::Neutral => "NEUTRAL",
+                    Gender::Male => "MALE",
+                    Gender::Female => "FEMALE",
+                }),
+                custom_voice,
+                voice_clone,
+            },
+            audio_config: AudioConfig {
+                audio_encoding: encoding.api_str(),
+                speaking_rate: rate,
+                pitch,
+                volume_gain_db: 0.0,
+                sample_rate_hertz: None,
+                effects_profile_id: Vec::new(),
+                enable_legacy_wav_header: false,
+            },
+        },
+        enable_time_pointing: ["SSML_MARK"],
+    };
+
+    let base = base_url();
+    let client = build_http_client_for_base(&base)?;
+    let mut headers = google_auth_headers().await?;
+    headers.insert(CONTENT_TYPE, "application/json".parse()?);
+    let resp: TimepointedResponse = send_http(
+        client
+            .post(format!("{base}/v1beta1/text:synthesize"))
+            .headers(headers)
+            .json(&body),
+    )
+    .await?
+    .error_for_status()?
+    .json()
+    .await
+    .context("invalid time-pointing response")?;
+
+    let audio = base64::engine::general_purpose::STANDARD
+        .decode(resp.audio_content)
+        .context("invalid base64 audio")?;
+    let mut file = create_output_file(output)?;
+    std::io::Write::write_all(&mut file, &audio)?;
+    std::io::Write::flush(&mut file)?;
+
+    let mut starts = vec![None; words.len()];
+    for tp in resp.timepoints {
+        if let Ok(i) = tp.mark_name.parse::<usize>()
+            && i < starts.len()
+        {
+            starts[i] = Some(tp.time_seconds);
+        }
+    }
+    let mut marks: Vec<WordMark> = words
+        .iter()
+        .zip(&starts)
+        .filter_map(|(word, start)| {
+            start.map(|start| WordMark {
+                word: word.to_string(),
+                start,
+                end: start + LAST_WORD_SECS,
+            })
+        })
+        .collect();
+    for i in 1..marks.len() {
+        marks[i - 1].end = marks[i].start;
+    }
+    Ok(marks)
+}
+
+/// Group word marks into caption cues, breaking after sentence punctuation
+/// or every `max_words` words.
+#[cfg_attr(not(feature = "mcp"), allow(dead_code))]
+fn subtitle_cues(marks: &[WordMark], max_words: usize) -> Vec<(f64, f64, String)> {
+    let mut cues = Vec::new();
+    let mut current: Vec<&WordMark> = Vec::new();
+    for mark in marks {
+        current.push(mark);
+        let sentence_end = mark.word.ends_with(['.', '!', '?', ';', ':']);
+        if sentence_end || current.len() >= max_words {
+            cues.push(cue_from(&current));
+            current.clear();
+        }
+    }
+    if !current.is_empty() {
+        cues.push(cue_from(&current));
+    }
+    cues
+}
+
+fn cue_from(words: &[&WordMark]) -> (f64, f64, String) {
+    let text = words
+        .iter()
+        .map(|w| w.word.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    (words[0].start, words[words.len() - 1].end, text)
+}
+
+fn subtitle_timestamp(secs: f64, millis_sep: char) -> String {
+    let total_ms = (secs.max(0.0) * 1000.0).round() as u64;
+    let (h, m, s, ms) = (
+        total_ms / 3_600_000,
+        total_ms / 60_000 % 60,
+        total_ms / 1000 % 60,
+        total_ms % 1000,
+    );
+    format!("{h:02}:{m:02}:{s:02}{millis_sep}{ms:03}")
+}
+
+#[cfg_attr(not(feature = "mcp"), allow(dead_code))]
+fn format_srt(cues: &[(f64, f64, String)]) -> String {
+    let mut out = String::new();
+    for (i, (start, end, text)) in cues.iter().enumerate() {
+        out.push_str(&format!(
+            "{}\n{} --> {}\n{text}\n\n",
+            i + 1,
+            subtitle_timestamp(*start, ','),
+            subtitle_timestamp(*end, ',')
+        ));
+    }
+    out
+}
+
+#[cfg_attr(not(feature = "mcp"), allow(dead_code))]
+fn format_vtt(cues: &[(f64, f64, String)]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for (start, end, text) in cues {
+        out.push_str(&format!(
+            "{} --> {}\n{text}\n\n",
+            subtitle_timestamp(*start, '.'),
+            subtitle_timestamp(*end, '.')
+        ));
+    }
+    out
+}
+
+/// Set by `--api-key`; takes precedence over `GOOGLE_API_KEY`.
+static GOOGLE_API_KEY_ARG: OnceLock<String> = OnceLock::new();
+
+/// Use `key` for Google Cloud (sent as `X-Goog-Api-Key`) instead of `GOOGLE_API_KEY`.
+pub fn set_google_api_key(key: impl Into<String>) {
+    let _ = GOOGLE_API_KEY_ARG.set(key.into());
+}
+
+fn google_api_key() -> Option<String> {
+    GOOGLE_API_KEY_ARG
+        .get()
+        .cloned()
+        .or_else(|| config_var("GOOGLE_API_KEY"))
+        .filter(|k| !k.trim().is_empty())
+}
+
+/// Authentication headers for Google Cloud TTS: an API key when configured,
+/// otherwise an OAuth bearer token.
+async fn google_auth_headers() -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    if let Some(key) = google_api_key() {
+        headers.insert("x-goog-api-key", secret_header(&key)?);
+    } else {
+        let token = fetch_access_token().await?;
+        headers.insert(AUTHORIZATION, secret_header(&format!("Bearer {token}"))?);
+    }
+    Ok(headers)
+}
+
+async fn fetch_access_token() -> Result<String> {
+    if let Ok(token) = std::env::var("FAST_TTS_TOKEN")
+        && !token.trim().is_empty()
+    {
+        tracing::info!(source = "FAST_TTS_TOKEN", "using Google access token");
+        return Ok(token);
+    }
+    if replay_mode() {
+        return Ok("replay".to_string());
+    }
+    // Supports two common methods:
+    // 1) GOOGLE_APPLICATION_CREDENTIALS pointing at a service account key or
+    //    external_account (workload identity federation) JSON
+    // 2) gcloud application-default credentials at well-known path
+    if let Some(path) = config_var("GOOGLE_APPLICATION_CREDENTIALS") {
+        let cache_key = format!("service_account:{path}");
+        if let Some(token) = cached_google_token(&cache_key) {
+            return Ok(token);
+        }
+        tracing::info!(source = %path, "fetching Google access token");
+        let token = fetch_token_from_credentials_file(PathBuf::from(path)).await?;
+        return Ok(store_google_token(&cache_key, token));
+    }
+
+    if let Some(path) = default_adc_path()
+        && path.exists()
+    {
+        let cache_key = format!("adc:{}", path.display());
+        if let Some(token) = cached_google_token(&cache_key) {
+            return Ok(token);
+        }
+        tracing::info!(source = %path.display(), "fetching Google access token");
+        if let Ok(token) = fetch_token_from_credentials_file(path).await {
+            return Ok(store_google_token(&cache_key, token));
+        }
+    }
+
+    // Last resort: gcloud keeps its own token cache, so nothing is stored here
+    if GCLOUD_AUTH_FALLBACK.load(std::sync::atomic::Ordering::Relaxed) {
+        tracing::info!(source = "gcloud", "fetching Google access token");
+        return fetch_token_from_gcloud().await;
+    }
+
+    let adc_hint = default_adc_path()
+        .map(|p| format!(" (checked {})", p.display()))
+        .unwrap_or_default();
+    anyhow::bail!(
+        "No Google credentials found{adc_hint}. Set GOOGLE_APPLICATION_CREDENTIALS or run 'gcloud auth application-default login'"
+    );
+}
+
+/// Set by `--gcloud-auth` / `FAST_TTS_GCLOUD_AUTH`.
+static GCLOUD_AUTH_FALLBACK: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+/// Allow shelling out to `gcloud auth print-access-token` when no other
+/// Google credentials work (`FAST_TTS_GCLOUD_AUTH=1` also enables it).
+pub fn set_gcloud_auth_fallback(enabled: bool) {
+    GCLOUD_AUTH_FALLBACK.store(
+        enabled || env_flag("FAST_TTS_GCLOUD_AUTH"),
+        std::sync::atomic::Ordering::Relaxed,
+    );
+}
+
+async fn fetch_token_from_gcloud() -> Result<String> {
+    ensure_online("gcloud auth print-access-token")?;
+    let gcloud = if cfg!(windows) {
+        "gcloud.cmd"
+    } else {
+        "gcloud"
+    };
+    // gcloud can take seconds to refresh its token; keep it off the runtime threads
+    let out = tokio::task::spawn_blocking(move || {
+        Command::new(gcloud)
+            .args(["auth", "print-access-token"])
+            .output()
+    })
+    .await?
+    .context("failed to run 'gcloud auth print-access-token' (is the gcloud CLI installed?)")?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "gcloud auth print-access-token failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    let token = String::from_utf8(out.stdout)
+        .context("gcloud printed a non-UTF8 token")?
+        .trim()
+        .to_string();
+    if token.is_empty() {
+        anyhow::bail!("gcloud auth print-access-token returned an empty token");
+    }
+    Ok(token)
+}
+
+/// Dispatch on the credential JSON `type` field, as the Google auth libraries do.
+async fn fetch_token_from_credentials_file(path: PathBuf) -> Result<GoogleTokenResponse> {
+    let data = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read credentials file: {}", path.display()))?;
+    #[derive(Deserialize)]
+    struct CredentialType {
+        #[serde(default)]
+        r#type: Option<String>,
+    }
+    let kind: CredentialType = serde_json::from_str(&data)
+        .with_context(|| format!("invalid credentials JSON: {}", path.display()))?;
+    match kind.r#type.as_deref() {
+        Some("authorized_user") => fetch_token_from_adc(path).await,
+        Some("external_account") => fetch_token_from_external_account(path).await,
+        // Older service-account keys may omit the type field
+        Some("service_account") | None => fetch_token_from_service_account(path).await,
+        Some(other) => anyhow::bail!(
+            "unsupported Google credential type {other:?} in {}",
+            path.display()
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct ExternalAccount {
+    audience: String,
+    subject_token_type: String,
+    token_url: Option<String>,
+    service_account_impersonation_url: Option<String>,
+    credential_source: ExternalCredentialSource,
+}
+
+#[derive(Deserialize)]
+struct ExternalCredentialSource {
+    file: Option<String>,
+    url: Option<String>,
+    #[serde(default)]
+    headers: std::collections::HashMap<String, String>,
+    environment_id: Option<String>,
+    format: Option<ExternalCredentialFormat>,
+}
+
+#[derive(Deserialize)]
+struct ExternalCredentialFormat {
+    r#type: String,
+    subject_token_field_name: Option<String>,
+}
+
+/// Read the third-party subject token (OIDC/SAML) named by `credential_source`.
+async fn read_external_subject_token(source: &ExternalCredentialSource) -> Result<String> {
+    let raw = if let Some(file) = &source.file {
+        fs::read_to_string(file)
+            .with_context(|| format!("failed to read subject token file: {file}"))?
+    } else if let Some(url) = &source.url {
+        let client = build_http_client_for_base(url)?;
+        let mut req = client.get(url);
+        for (name, value) in &source.headers {
+            req = req.header(name, value);
+        }
+        send_http(req).await?.error_for_status()?.text().await?
+    } else if let Some(env_id) = &source.environment_id {
+        anyhow::bail!(
+            "workload identity credential source {env_id:?} is not supported; use a file or url source"
+        );
+    } else {
+        anyhow::bail!("external_account credential_source must set file or url");
+    };
+    match &source.format {
+        Some(format) if format.r#type == "json" => {
+            let field = format.subject_token_field_name.as_deref().context(
+                "credential_source.format.subject_token_field_name is required for json",
+            )?;
+            let value: serde_json::Value =
+                serde_json::from_str(&raw).context("subject token source is not valid JSON")?;
+            value
+                .get(field)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .with_context(|| format!("subject token field {field:?} not found"))
+        }
+        _ => Ok(raw.trim().to_string()),
+    }
+}
+
+/// Workload identity federation: exchange an external OIDC/SAML token at Google
+/// STS, then optionally impersonate a service account.
+async fn fetch_token_from_external_account(path: PathBuf) -> Result<GoogleTokenResponse> {
+    let data = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read credentials file: {}", path.display()))?;
+    let account: ExternalAccount = serde_json::from_str(&data)
+        .with_context(|| format!("invalid external_account credentials: {}", path.display()))?;
+    let subject_token = read_external_subject_token(&account.credential_source).await?;
+
+    let scope = "https://www.googleapis.com/auth/cloud-platform";
+    let token_url = account
+        .token_url
+        .as_deref()
+        .unwrap_or("https://sts.googleapis.com/v1/token");
+    let client = build_http_client_for_base(token_url)?;
+    let sts: GoogleTokenResponse = send_http(client.post(token_url).form(&serde_json::json!({
+        "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+        "audience": account.audience,
+        "scope": scope,
+        "requested_token_type": "urn:ietf:params:oauth:token-type:access_token",
+        "subject_token": subject_token,
+        "subject_token_type": account.subject_token_type,
+    })))
+    .await?
+    .error_for_status()
+    .context("workload identity token exchange failed")?
+    .json()
+    .await?;
+
+    let Some(impersonation_url) = &account.service_account_impersonation_url else {
+        return Ok(sts);
+    };
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ImpersonatedToken {
+        access_token: String,
+    }
+    let lifetime_secs = 3600;
+    let client = build_http_client_for_base(impersonation_url)?;
+    let impersonated: ImpersonatedToken = send_http(
+        client
+            .post(impersonation_url)
+            .bearer_auth(&sts.access_token)
+            .json(&serde_json::json!({
+                "scope": [scope],
+                "lifetime": format!("{lifetime_secs}s"),
+            })),
+    )
+    .await?
+    .error_for_status()
+    .context("service account impersonation failed")?
+    .json()
+    .await?;
+    Ok(GoogleTokenResponse {
+        access_token: impersonated.access_token,
+        expires_in: Some(lifetime_secs),
+    })
+}
+
+/// OAuth token endpoint response shared by every Google credential flow.
+#[derive(Deserialize)]
+struct GoogleTokenResponse {
+    access_token: String,
+    /// Lifetime in seconds; Google issues one-hour tokens when omitted
+    expires_in: Option<u64>,
+}
+
+/// Access token cached in memory and under the cache dir, keyed by credential source.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedToken {
+    source: String,
+    access_token: String,
+    expires_at: u64,
+}
+
+/// Refresh tokens this many seconds before they actually expire.
+const TOKEN_REFRESH_MARGIN_SECS: u64 = 300;
+
+/// In-memory tokens by provider name.
+static TOKENS: std::sync::Mutex<Vec<(String, CachedToken)>> = std::sync::Mutex::new(Vec::new());
+
+fn unix_now_nanos() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+}
+
+/// Unguessable-enough identifier for sessions and stored audio: a randomly
+/// keyed SipHash of a counter and the clock, plus the pid.
+#[cfg_attr(not(any(feature = "mcp", feature = "serve")), allow(dead_code))]
+fn random_id() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed));
+    hasher.write_u128(unix_now_nanos());
+    format!("{:016x}{:08x}", hasher.finish(), std::process::id())
+}
+
+/// Compare secrets without short-circuiting on the first differing byte.
+#[cfg_attr(not(any(feature = "mcp", feature = "serve")), allow(dead_code))]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn token_cache_path(provider: &str) -> Option<PathBuf> {
+    cache_dir().map(|d| d.join("tokens").join(format!("{provider}.json")))
+}
+
+fn cached_token(provider: &str, source: &str, margin_secs: u64) -> Option<String> {
+    let token = lookup_cached_token(provider, source, margin_secs);
+    tracing::debug!(provider, hit = token.is_some(), "token cache lookup");
+    token
+}
+
+fn lookup_cached_token(provider: &str, source: &str, margin_secs: u64) -> Option<String> {
+    let fresh = |t: &CachedToken| t.source == source && t.expires_at > unix_now() + margin_secs;
+    if let Ok(tokens) = TOKENS.lock()
+        && let Some((_, token)) = tokens.iter().find(|(p, t)| p == provider && fresh(t))
+    {
+        return Some(token.access_token.clone());
+    }
+    let data = fs::read_to_string(token_cache_path(provider)?).ok()?;
+    let token: CachedToken = serde_json::from_str(&data).ok()?;
+    if !fresh(&token) {
+        return None;
+    }
+    let access_token = token.access_token.clone();
+    remember_token(provider, token);
+    Some(access_token)
+}
+
+fn store_token(provider: &str, source: &str, access_token: String, expires_in: u64) -> String {
+    let cached = CachedToken {
+        source: source.to_string(),
+        access_token,
+        expires_at: unix_now() + expires_in,
+    };
+    if let Some(path) = token_cache_path(provider) {
+        // Best effort: a failed write only means the next run exchanges again
+        let _ = write_private_file(&path, &serde_json::to_vec(&cached).unwrap_or_default());
+    }
+    let access_token = cached.access_token.clone();
+    remember_token(provider, cached);
+    access_token
+}
+
+fn remember_token(provider: &str, token: CachedToken) {
+    if let Ok(mut tokens) = TOKENS.lock() {
+        tokens.retain(|(p, _)| p != provider);
+        tokens.push((provider.to_string(), token));
+    }
+}
+
+fn cached_google_token(source: &str) -> Option<String> {
+    cached_token("google", source, TOKEN_REFRESH_MARGIN_SECS)
+}
+
+fn store_google_token(source: &str, token: GoogleTokenResponse) -> String {
+    store_token(
+        "google",
+        source,
+        token.access_token,
+        token.expires_in.unwrap_or(3600),
+    )
+}
+
+#[cfg(feature = "provider-azure")]
+/// Short, non-reversible fingerprint used to key caches by secret without storing it.
+fn secret_fingerprint(secret: &str) -> String {
+    use sha2::{Digest as _, Sha256};
+    // SHA-256 rather than std's hasher, whose output may change between Rust
+    // releases and would orphan the tokens cached on disk
+    Sha256::digest(secret.as_bytes())[..8]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(feature = "provider-azure")]
+/// Azure issues 10-minute bearer tokens from the regional STS endpoint.
+const AZURE_TOKEN_LIFETIME_SECS: u64 = 600;
+
+#[cfg(feature = "provider-azure")]
+/// Exchange the Azure subscription key for a short-lived bearer token, reusing
+/// a cached one while it has at least a minute left.
+async fn azure_access_token(region: &str, key: &str) -> Result<String> {
+    let source = format!("{region}:{}", secret_fingerprint(key));
+    if let Some(token) = cached_token("azure", &source, 60) {
+        return Ok(token);
+    }
+    tracing::info!(region, "fetching Azure access token");
+    let url = format!("https://{region}.api.cognitive.microsoft.com/sts/v1.0/issueToken");
+    let client = build_http_client_for_base(&url)?;
+    let token = send_http(
+        client
+            .post(&url)
+            .header("Ocp-Apim-Subscription-Key", secret_header(key)?)
+            .header(reqwest::header::CONTENT_LENGTH, "0"),
+    )
+    .await?
+    .error_for_status()
+    .context("Azure issueToken exchange failed")?
+    .text()
+    .await?;
+    Ok(store_token(
+        "azure",
+        &source,
+        token.trim().to_string(),
+        AZURE_TOKEN_LIFETIME_SECS,
+    ))
+}
+
+/// Write a file readable only by the current user (0600 on Unix).
+fn write_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    use std::io::Write as _;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt as _;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents)?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    token_uri: Option<String>,
+}
+
+async fn fetch_token_from_service_account(path: PathBuf) -> Result<GoogleTokenResponse> {
+    use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+
+    let key_data = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read service account key: {}", path.display()))?;
+    let key: ServiceAccountKey = serde_json::from_str(&key_data)?;
+
+    let scope = "https://www.googleapis.com/auth/cloud-platform";
+    let token_uri = key
+        .token_uri
+        .unwrap_or_else(|| "https://oauth2.googleapis.com/token".to_string());
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as usize;
+
+    #[derive(Serialize)]
+    struct Claims<'a> {
+        iss: &'a str,
+        scope: &'a str,
+        aud: &'a str,
+        exp: usize,
+        iat: usize,
+    }
+
+    let claims = Claims {
+        iss: &key.client_email,
+        scope,
+        aud: &token_uri,
+        exp: now + 3600,
+        iat: now,
+    };
+
+    let mut header = Header::new(Algorithm::RS256);
+    header.typ = Some("JWT".to_string());
+
+    let encoding_key = EncodingKey::from_rsa_pem(key.private_key.as_bytes())
+        .context("invalid RSA private key in service account")?;
+    let jwt = encode(&header, &claims, &encoding_key)?;
+
+    let client = build_http_client_for_base(&token_uri)?;
+    let resp = send_http(client.post(&token_uri).form(&serde_json::json!({
+        "grant_type": "urn:ietf:params:oauth:grant-type:jwt-bearer",
+        "assertion": jwt,
+    })))
+    .await?
+    .error_for_status()?;
+    Ok(resp.json().await?)
+}
+
+pub fn parse_encoding_from_str(s: &str) -> Result<AudioEncoding> {
+    match s.trim().to_uppercase().as_str() {
+        "LINEAR16" => Ok(AudioEncoding::Linear16),
+        "MP3" => Ok(AudioEncoding::Mp3),
+        "OGG_OPUS" => Ok(AudioEncoding::OggOpus),
+        "MULAW" => Ok(AudioEncoding::Mulaw),
+        "ALAW" => Ok(AudioEncoding::Alaw),
+        other => Err(FastTtsError::InvalidInput(format!("unsupported encoding: {other}")).into()),
+    }
+}
+
+async fn fetch_token_from_adc(path: PathBuf) -> Result<GoogleTokenResponse> {
+    // Application Default Credentials created by gcloud have refresh_token, client_id, client_secret
+    let data = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read ADC file: {}", path.display()))?;
+    #[derive(Deserialize)]
+    struct AdcFile {
+        client_id: String,
+        client_secret: String,
+        refresh_token: String,
+        #[allow(dead_code)]
+        r#type: String,
+    }
+    let adc: AdcFile = serde_json::from_str(&data)?;
+
+    let token_url = "https://oauth2.googleapis.com/token";
+    let client = build_http_client_for_base(token_url)?;
+    let resp = send_http(client.post(token_url).form(&serde_json::json!({
+        "grant_type": "refresh_token",
+        "client_id": adc.client_id,
+        "client_secret": adc.client_secret,
+        "refresh_token": adc.refresh_token,
+    })))
+    .await?
+    .error_for_status()?;
+    Ok(resp.json().await?)
+}
+
+fn default_adc_path() -> Option<PathBuf> {
+    gcloud_config_dir().map(|d| d.join("application_default_credentials.json"))
+}
+
+/// gcloud's config directory: `CLOUDSDK_CONFIG` when set, `%APPDATA%\gcloud`
+/// on Windows, and `~/.config/gcloud` elsewhere (including macOS).
+fn gcloud_config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("CLOUDSDK_CONFIG").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    if cfg!(windows) {
+        return std::env::var_os("APPDATA")
+            .map(PathBuf::from)
+            .or_else(dirs::config_dir)
+            .map(|d| d.join("gcloud"));
+    }
+    dirs::home_dir().map(|home| home.join(".config").join("gcloud"))
+}
+
+/// Outcome of `auth check` for one provider.
+#[derive(Debug)]
+pub enum AuthStatus {
+    Ok,
+    Missing(String),
+    Invalid(String),
+    Error(String),
+    Unsupported(String),
+}
+
+impl AuthStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AuthStatus::Ok => "ok",
+            AuthStatus::Missing(_) => "missing",
+            AuthStatus::Invalid(_) => "invalid",
+            AuthStatus::Error(_) => "error",
+            AuthStatus::Unsupported(_) => "skipped",
+        }
+    }
+
+    pub fn detail(&self) -> &str {
+        match self {
+            AuthStatus::Ok => "",
+            AuthStatus::Missing(d)
+            | AuthStatus::Invalid(d)
+            | AuthStatus::Error(d)
+            | AuthStatus::Unsupported(d) => d,
+        }
+    }
+
+    /// Classify the result of a probe request.
+    fn from_probe(result: Result<reqwest::Response>) -> Self {
+        match result {
+            Ok(resp) if resp.status().is_success() => AuthStatus::Ok,
+            Ok(resp)
+                if matches!(
+                    resp.status(),
+                    reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+                ) =>
+            {
+                AuthStatus::Invalid(format!("rejected with HTTP {}", resp.status().as_u16()))
+            }
+            Ok(resp) => AuthStatus::Error(format!("unexpected HTTP {}", resp.status().as_u16())),
+            Err(e) => AuthStatus::Error(format!("{e:#}")),
+        }
+    }
+}
+
+fn google_credentials_configured() -> bool {
+    google_api_key().is_some()
+        || std::env::var("FAST_TTS_TOKEN").is_ok_and(|t| !t.trim().is_empty())
+        || config_var("GOOGLE_APPLICATION_CREDENTIALS").is_some()
+        || default_adc_path().is_some_and(|p| p.exists())
+        || GCLOUD_AUTH_FALLBACK.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+fn missing_env(vars: &[&str]) -> Option<AuthStatus> {
+    let missing: Vec<&str> = vars
+        .iter()
+        .copied()
+        .filter(|v| config_var(v).map(|x| x.trim().is_empty()).unwrap_or(true))
+        .collect();
+    (!missing.is_empty()).then(|| AuthStatus::Missing(format!("{} not set", missing.join(", "))))
+}
+
+/// Probe one provider with the cheapest authenticated request it offers.
+pub async fn check_provider_auth(provider: Provider) -> AuthStatus {
+    let probe = async {
+        match provider {
+            Provider::Google => {
+                if !google_credentials_configured() {
+                    return Ok(AuthStatus::Missing(
+                        "no API key, service account, or ADC credentials found".into(),
+                    ));
+                }
+                let headers = match google_auth_headers().await {
+                    Ok(h) => h,
+                    Err(e) => return Ok(AuthStatus::Invalid(format!("{e:#}"))),
+                };
+                let base = base_url();
+                let client = build_http_client_for_base(&base)?;
+                let url = format!("{base}/v1/voices?languageCode=en-US");
+                Ok(AuthStatus::from_probe(
+                    send_http(client.get(url).headers(headers)).await,
+                ))
+            }
+            Provider::Openai => {
+                if let Some(missing) = missing_env(&["OPENAI_API_KEY"]) {
+                    return Ok(missing);
+                }
+                let key = provider_secret("OPENAI_API_KEY", "openai")?;
+                let url = "https://api.openai.com/v1/models";
+                let client = build_http_client_for_base(url)?;
+                Ok(AuthStatus::from_probe(
+                    send_http(client.get(url).bearer_auth(key)).await,
+                ))
+            }
+            Provider::Elevenlabs => {
+                if let Some(missing) = missing_env(&["ELEVENLABS_API_KEY"]) {
+                    return Ok(missing);
+                }
+                let key = provider_secret("ELEVENLABS_API_KEY", "elevenlabs")?;
+                let url = "https://api.elevenlabs.io/v1/user";
+                let client = build_http_client_for_base(url)?;
+                Ok(AuthStatus::from_probe(
+                    send_http(client.get(url).header("xi-api-key", secret_header(&key)?)).await,
+                ))
+            }
+            Provider::Deepgram => {
+                if let Some(missing) = missing_env(&["DEEPGRAM_API_KEY"]) {
+                    return Ok(missing);
+                }
+                let key = provider_secret("DEEPGRAM_API_KEY", "deepgram")?;
+                let url = "https://api.deepgram.com/v1/projects";
+                let client = build_http_client_for_base(url)?;
+                Ok(AuthStatus::from_probe(
+                    send_http(
+                        client
+                            .get(url)
+                            .header(AUTHORIZATION, secret_header(&format!("Token {key}"))?),
+                    )
+                    .await,
+                ))
+            }
+            Provider::Lmnt => {
+                if let Some(missing) = missing_env(&["LMNT_API_KEY"]) {
+                    return Ok(missing);
+                }
+                let key = provider_secret("LMNT_API_KEY", "lmnt")?;
+                let url = "https://api.lmnt.com/v1/account";
+                let client = build_http_client_for_base(url)?;
+                Ok(AuthStatus::from_probe(
+                    send_http(client.get(url).header("X-API-Key", secret_header(&key)?)).await,
+                ))
+            }
+            Provider::Azure => {
+                if let Some(missing) = missing_env(&["AZURE_SPEECH_KEY", "AZURE_SPEECH_REGION"]) {
+                    return Ok(missing);
+                }
+                let key = provider_secret("AZURE_SPEECH_KEY", "azure")?;
+                let region = config_var("AZURE_SPEECH_REGION").unwrap_or_default();
+                let url = format!(
+                    "https://{region}.tts.speech.microsoft.com/cognitiveservices/voices/list"
+                );
+                let client = build_http_client_for_base(&url)?;
+                Ok(AuthStatus::from_probe(
+                    send_http(
+                        client
+                            .get(&url)
+                            .header("Ocp-Apim-Subscription-Key", secret_header(&key)?),
+                    )
+                    .await,
+                ))
+            }
+            Provider::Gemini => {
+                if let Some(missing) = missing_env(&["GEMINI_API_KEY"]) {
+                    return Ok(missing);
+                }
+                let key = provider_secret("GEMINI_API_KEY", "gemini")?;
+                let url = "https://generativelanguage.googleapis.com/v1beta/models";
+                let client = build_http_client_for_base(url)?;
+                Ok(AuthStatus::from_probe(
+                    send_http(client.get(url).query(&[("key", key)])).await,
+                ))
+            }
+            Provider::Polly => {
+                #[cfg(feature = "polly")]
+                {
+                    ensure_online("Amazon Polly describe_voices")?;
+                    let config = aws_config::load_from_env().await;
+                    let client = aws_sdk_polly::Client::new(&config);
+                    Ok(match client.describe_voices().send().await {
+                        Ok(_) => AuthStatus::Ok,
+                        Err(e) => AuthStatus::Invalid(e.to_string()),
+                    })
+                }
+                #[cfg(not(feature = "polly"))]
+                {
+                    Ok(AuthStatus::Unsupported(
+                        "built without --features polly".into(),
+                    ))
+                }
+            }
+            Provider::Piper => Ok(providers::piper::auth_status()),
+            Provider::Kokoro => Ok(providers::kokoro::auth_status()),
+            Provider::System => Ok(providers::system::auth_status()),
+            Provider::Hume | Provider::Listnr | Provider::Murf => {
+                Ok(AuthStatus::Unsupported("provider not implemented".into()))
+            }
+            // Arbitrary services have no known cheap probe
+            Provider::Custom(_) | Provider::Wasm(_) => {
+                Ok(if provider_credentials_present(provider) {
+                    AuthStatus::Ok
+                } else {
+                    AuthStatus::Missing(format!("credentials for {provider} not set"))
+                })
+            }
+        }
+    };
+    probe
+        .await
+        .unwrap_or_else(|e: anyhow::Error| AuthStatus::Error(format!("{e:#}")))
+}
+
+/// Whether `p` was compiled into this build.
+pub fn provider_enabled(p: Provider) -> bool {
+    match p {
+        Provider::Google => cfg!(feature = "provider-google"),
+        Provider::Openai => cfg!(feature = "provider-openai"),
+        Provider::Elevenlabs => cfg!(feature = "provider-elevenlabs"),
+        Provider::Deepgram => cfg!(feature = "provider-deepgram"),
+        Provider::Lmnt => cfg!(feature = "provider-lmnt"),
+        Provider::Polly => cfg!(feature = "polly"),
+        Provider::Azure => cfg!(feature = "provider-azure"),
+        Provider::Gemini => cfg!(feature = "provider-gemini"),
+        Provider::Piper => cfg!(feature = "piper"),
+        Provider::Kokoro => cfg!(feature = "kokoro"),
+        Provider::System => true,
+        Provider::Hume | Provider::Listnr | Provider::Murf => false,
+        Provider::Custom(_) => true,
+        Provider::Wasm(_) => cfg!(feature = "wasm"),
+    }
+}
+
+/// `provider` alone, or every provider compiled into this build.
+pub fn selected_providers(provider: Option<Provider>) -> Vec<Provider> {
+    match provider {
+        Some(p) => vec![p],
+        None => Provider::all()
+            .into_iter()
+            .filter(|p| provider_enabled(*p))
+            .collect(),
+    }
+}
+
+/// File-name-safe form of a provider, voice or title.
+pub(crate) fn slug(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// What a provider accepts, so callers can pick valid parameters up front;
+/// [`SynthesisRequestBuilder::build`] checks requests against it.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderCapabilities {
+    pub provider: String,
+    pub enabled: bool,
+    pub configured: bool,
+    pub encodings: Vec<&'static str>,
+    pub sample_rates: Vec<u32>,
+    pub default_voice: &'static str,
+    pub voice_selection: &'static str,
+    /// SSML flavour accepted with `ssml`, or `None` when only plain text is
+    pub ssml_dialect: Option<&'static str>,
+    /// Longest accepted text, in UTF-8 bytes, when the service documents one
+    pub max_input_bytes: Option<usize>,
+    /// Audio arrives while it is generated rather than after synthesis finishes
+    pub streaming: bool,
+    /// Word timestamps are available (captions)
+    pub timestamps: bool,
+    /// Accepts a sampling seed (`--seed`) for repeatable output
+    pub seed: bool,
+    /// Speaks dialogue scripts (`--dialogue`) with several voices in one request
+    pub multi_speaker: bool,
+    /// Accepted `--rate`, `--pitch` (semitones) and `--volume` (dB) as
+    /// `[min, max]`; `None` when the provider has no such control
+    pub rate_range: Option<(f32, f32)>,
+    pub pitch_range: Option<(f32, f32)>,
+    pub volume_range: Option<(f32, f32)>,
+    /// Known `--effects-profile` ids; empty when the provider has none
+    pub effects_profiles: Vec<&'static str>,
+    /// Short `--effects-profile` names accepted in place of an id
+    pub effects_profile_aliases: std::collections::BTreeMap<&'static str, &'static str>,
+    /// Sets the OGG_OPUS bitrate (`--bitrate`) itself; otherwise, and for
+    /// `--vbr`/`--application`, the audio is re-encoded with ffmpeg
+    pub opus_bitrate: bool,
+    /// Header the provider de-duplicates retried requests by; it carries
+    /// [`SynthesisRequest::idempotency_key`]
+    pub idempotency_header: Option<&'static str>,
+    /// Approximate list price for cost estimates, when known
+    pub usd_per_million_chars: Option<f64>,
+}
+
+/// Whether credentials for `p` are present (not whether they are valid; see `auth check`).
+fn provider_credentials_present(p: Provider) -> bool {
+    match p {
+        Provider::Google => google_credentials_configured(),
+        Provider::Openai => missing_env(&["OPENAI_API_KEY"]).is_none(),
+        Provider::Elevenlabs => missing_env(&["ELEVENLABS_API_KEY"]).is_none(),
+        Provider::Deepgram => missing_env(&["DEEPGRAM_API_KEY"]).is_none(),
+        Provider::Lmnt => missing_env(&["LMNT_API_KEY"]).is_none(),
+        Provider::Azure => missing_env(&["AZURE_SPEECH_KEY", "AZURE_SPEECH_REGION"]).is_none(),
+        Provider::Gemini => missing_env(&["GEMINI_API_KEY"]).is_none(),
+        Provider::Polly => {
+            missing_env(&["AWS_ACCESS_KEY_ID"]).is_none() || missing_env(&["AWS_PROFILE"]).is_none()
+        }
+        Provider::Piper => providers::piper::configured(),
+        Provider::Kokoro => providers::kokoro::configured(),
+        Provider::System => providers::system::available(),
+        Provider::Hume | Provider::Listnr | Provider::Murf => false,
+        Provider::Custom(name) => providers::custom::load(name)
+            .is_ok_and(|t| t.auth_env().is_none_or(|var| missing_env(&[var]).is_none())),
+        // A plugin reads its own settings through `config-var`
+        #[cfg(feature = "wasm")]
+        Provider::Wasm(spec) => providers::wasm::load(spec).is_ok(),
+        #[cfg(not(feature = "wasm"))]
+        Provider::Wasm(_) => false,
+    }
+}
+
+/// Machine-readable failure for MCP tool calls, so agents can decide between
+/// retrying, falling back to another provider, or giving up.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(not(any(feature = "mcp", feature = "serve")), allow(dead_code))]
+struct ToolFailure {
+    code: &'static str,
+    provider: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    http_status: Option<u16>,
+    retryable: bool,
+    message: String,
+}
+
+#[cfg_attr(not(any(feature = "mcp", feature = "serve")), allow(dead_code))]
+impl ToolFailure {
+    fn classify(provider: Provider, err: &anyhow::Error) -> Self {
+        let http = err.chain().find_map(|e| e.downcast_ref::<reqwest::Error>());
+        let typed = FastTtsError::classify(err);
+        let http_status = http
+            .and_then(|e| e.status())
+            .map(|s| s.as_u16())
+            .or(match typed {
+                FastTtsError::ProviderHttp { status, .. } => status,
+                _ => None,
+            });
+        let (code, retryable) = match (http_status, http) {
+            (Some(401 | 403), _) => ("auth_failed", false),
+            (Some(429), _) => ("rate_limited", true),
+            (Some(408), _) => ("timeout", true),
+            (Some(s), _) if s >= 500 => ("provider_unavailable", true),
+            (Some(_), _) => ("invalid_request", false),
+            (None, Some(e)) if e.is_timeout() => ("timeout", true),
+            (None, Some(e)) if e.is_connect() || e.is_request() => ("network", true),
+            _ => match typed {
+                FastTtsError::Cancelled(_) => ("cancelled", false),
+                FastTtsError::Offline(_) => ("offline", false),
+                FastTtsError::MissingCredentials(_) => ("auth_missing", false),
+                FastTtsError::Auth(_) => ("auth_failed", false),
+                FastTtsError::Quota(_) => ("rate_limited", true),
+                FastTtsError::Unsupported(_) => ("unsupported", false),
+                FastTtsError::InvalidInput(_) => ("invalid_request", false),
+                FastTtsError::ProviderHttp { .. } => ("provider_unavailable", true),
+                FastTtsError::Io(_) | FastTtsError::Other(_) => ("internal", false),
+            },
+        };
+        let message = format!("{err:#}");
+        ToolFailure {
+            code,
+            provider: provider.to_string(),
+            http_status,
+            retryable,
+            message,
+        }
+    }
+}
+
+/// Cargo feature that compiles in `p`.
+pub fn provider_feature_flag(p: Provider) -> &'static str {
+    match p {
+        Provider::Google => "provider-google",
+        Provider::Openai => "provider-openai",
+        Provider::Elevenlabs => "provider-elevenlabs",
+        Provider::Deepgram => "provider-deepgram",
+        Provider::Lmnt => "provider-lmnt",
+        Provider::Polly => "polly",
+        Provider::Azure => "provider-azure",
+        Provider::Gemini => "provider-gemini",
+        Provider::Piper => "piper",
+        Provider::Kokoro => "kokoro",
+        Provider::System => "",
+        Provider::Hume => "provider-hume",
+        Provider::Listnr => "provider-listnr",
+        Provider::Murf => "provider-murf",
+        Provider::Custom(_) => "",
+        Provider::Wasm(_) => "wasm",
+    }
+}
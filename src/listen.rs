@@ -0,0 +1,168 @@
+//! Listening sockets shared by `serve` and the MCP HTTP/SSE server: TCP
+//! (`HOST:PORT`), Unix domain sockets (`unix:/path`) and systemd socket
+//! activation (`LISTEN_FDS`), which takes precedence over the address.
+
+use anyhow::{Context, Result};
+use axum::Router;
+use std::net::SocketAddr;
+
+pub enum Listener {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl Listener {
+    pub async fn bind(addr: &str) -> Result<Self> {
+        #[cfg(unix)]
+        if let Some(listener) = activated()? {
+            return Ok(listener);
+        }
+        if let Some(path) = addr.strip_prefix("unix:") {
+            #[cfg(unix)]
+            {
+                return bind_unix(std::path::Path::new(path));
+            }
+            #[cfg(not(unix))]
+            anyhow::bail!("unix sockets are not supported on this platform: {path}");
+        }
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("failed to bind {addr}"))?;
+        Ok(Self::Tcp(listener))
+    }
+
+    /// Human-readable address for the startup banner.
+    pub fn describe(&self, scheme: &str) -> String {
+        match self {
+            Self::Tcp(l) => match l.local_addr() {
+                Ok(addr) => format!("{scheme}://{addr}"),
+                Err(_) => "TCP socket".to_string(),
+            },
+            #[cfg(unix)]
+            Self::Unix(l) => match l
+                .local_addr()
+                .ok()
+                .and_then(|a| a.as_pathname().map(|p| p.to_owned()))
+            {
+                Some(path) => format!("unix:{}", path.display()),
+                None => "unix socket".to_string(),
+            },
+        }
+    }
+
+    /// Whether connections arrive over TCP (and so carry a peer IP).
+    #[cfg(feature = "serve")]
+    pub fn is_tcp(&self) -> bool {
+        matches!(self, Self::Tcp(_))
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &std::path::Path) -> Result<Listener> {
+    use std::os::unix::fs::FileTypeExt as _;
+    // A socket file left behind by a previous run would make bind fail
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        std::fs::remove_file(path)
+            .with_context(|| format!("failed to remove stale socket {}", path.display()))?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("failed to bind unix socket {}", path.display()))?;
+    Ok(Listener::Unix(listener))
+}
+
+/// The first socket passed by systemd (`LISTEN_PID`/`LISTEN_FDS`), if any.
+#[cfg(unix)]
+fn activated() -> Result<Option<Listener>> {
+    use std::os::fd::{FromRawFd as _, IntoRawFd as _};
+    const SD_LISTEN_FDS_START: i32 = 3;
+    let for_us = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .is_some_and(|pid| pid == std::process::id());
+    let count = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse::<i32>().ok())
+        .unwrap_or(0);
+    if !for_us || count < 1 {
+        return Ok(None);
+    }
+    if count > 1 {
+        eprintln!("Warning: {count} sockets passed by systemd; using only the first");
+    }
+    // SAFETY: systemd hands us ownership of the descriptors starting at
+    // SD_LISTEN_FDS_START, and nothing else in the process uses fd 3.
+    let tcp = unsafe { std::net::TcpListener::from_raw_fd(SD_LISTEN_FDS_START) };
+    // getsockname fails for non-inet families, which tells TCP and Unix sockets apart
+    if tcp.local_addr().is_ok() {
+        tcp.set_nonblocking(true)?;
+        return Ok(Some(Listener::Tcp(tokio::net::TcpListener::from_std(tcp)?)));
+    }
+    // SAFETY: same descriptor, re-wrapped after releasing it from the TCP wrapper.
+    let unix = unsafe { std::os::unix::net::UnixListener::from_raw_fd(tcp.into_raw_fd()) };
+    unix.local_addr()
+        .context("socket passed by systemd is neither TCP nor a Unix stream socket")?;
+    unix.set_nonblocking(true)?;
+    Ok(Some(Listener::Unix(tokio::net::UnixListener::from_std(
+        unix,
+    )?)))
+}
+
+/// Serve `app` until `shutdown` completes. TCP connections expose the peer
+/// address as `ConnectInfo<SocketAddr>`; Unix socket connections do not.
+pub async fn serve(
+    listener: Listener,
+    app: Router,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    match listener {
+        Listener::Tcp(listener) => {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown)
+            .await?
+        }
+        #[cfg(unix)]
+        Listener::Unix(listener) => serve_unix(listener, app, shutdown).await?,
+    }
+    Ok(())
+}
+
+/// `axum::serve` only accepts TCP listeners, so Unix socket connections are
+/// driven through hyper directly.
+#[cfg(unix)]
+async fn serve_unix(
+    listener: tokio::net::UnixListener,
+    app: Router,
+    shutdown: impl std::future::Future<Output = ()>,
+) -> Result<()> {
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    let mut shutdown = std::pin::pin!(shutdown);
+    let mut connections = tokio::task::JoinSet::new();
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("Warning: accept failed: {e}");
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        let service = hyper_util::service::TowerToHyperService::new(app.clone());
+        connections.spawn(async move {
+            let _ = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+    // Same bounded drain window as the MCP server's in-flight requests
+    let _ = tokio::time::timeout(std::time::Duration::from_secs(30), async {
+        while connections.join_next().await.is_some() {}
+    })
+    .await;
+    Ok(())
+}
@@ -0,0 +1,59 @@
+//! Diagnostic logging to stderr (`--verbose`, `--quiet`, `--log-format`),
+//! installed together with the optional OTLP exporter from [`crate::otel`].
+
+use crate::otel::OtelGuard;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use tracing_subscriber::Layer as _;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::layer::SubscriberExt as _;
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum LogFormat {
+    /// One human-readable line per event
+    #[default]
+    Text,
+    /// One JSON object per event
+    Json,
+}
+
+/// How much to log: `quiet` silences everything, otherwise warnings plus one
+/// level per `verbose` step (info, debug, trace).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LogConfig {
+    pub verbose: u8,
+    pub quiet: bool,
+    pub format: LogFormat,
+}
+
+impl LogConfig {
+    fn level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::OFF;
+        }
+        match self.verbose {
+            0 => LevelFilter::WARN,
+            1 => LevelFilter::INFO,
+            2 => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// Install the global subscriber. Only this crate's events are logged;
+/// keep the returned guard alive so queued spans are exported on exit.
+pub fn init(config: LogConfig, offline: bool) -> Result<Option<OtelGuard>> {
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_target(false);
+    let fmt = match config.format {
+        LogFormat::Text => fmt.boxed(),
+        LogFormat::Json => fmt.json().boxed(),
+    };
+    let fmt = fmt.with_filter(Targets::new().with_target(env!("CARGO_CRATE_NAME"), config.level()));
+    let (otel, guard) = crate::otel::layer(offline)?.unzip();
+    let subscriber = tracing_subscriber::registry().with(fmt).with(otel);
+    tracing::subscriber::set_global_default(subscriber)
+        .context("a global tracing subscriber is already installed")?;
+    Ok(guard)
+}
@@ -1,354 +1,24 @@
-use anyhow::{Context, Result};
-use base64::Engine as _;
-use clap::{ArgAction, Parser, ValueEnum};
-use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap};
-use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::{Path, PathBuf};
-use std::process::Command;
-// use std::collections::HashSet; // not currently used
-// use std::time::Duration; // reserved for future retries/timeouts
-
-#[cfg(feature = "mcp")]
-mod mcp_integration {
-    use super::*;
-    use anyhow::Result;
-
-    // Import rust-sdk types guarded by feature
-    use ::mcp_server::{ByteTransport, Router, Server};
-    use axum::{
-        self, Json, Router as AxumRouter,
-        response::sse::{Event, Sse},
-        routing::{get, post},
-    };
-    use mcp_spec::content::Content;
-    use mcp_spec::handler::{PromptError, ResourceError, ToolError};
-    use mcp_spec::prompt::{Prompt, PromptArgument};
-    use mcp_spec::protocol::{JsonRpcRequest, JsonRpcResponse, ServerCapabilities};
-    use std::convert::Infallible;
-    use std::time::Duration;
-    use tokio as mcp_tokio;
-    use tokio_stream::StreamExt as _;
-    use tower_service::Service;
-
-    pub async fn run_mcp_server(mode: McpMode, addr: Option<String>) -> Result<()> {
-        // Build Router implementation backed by our CLI functions
-        let router = RouterService(FastTtsRouter);
-        let mut server = Server::new(router);
-
-        match mode {
-            McpMode::Stdio => {
-                let transport = ByteTransport::new(mcp_tokio::io::stdin(), mcp_tokio::io::stdout());
-                server.run(transport).await?;
-            }
-            McpMode::Http | McpMode::Sse => {
-                async fn rpc(
-                    Json(req): Json<JsonRpcRequest>,
-                ) -> Result<Json<JsonRpcResponse>, axum::http::StatusCode> {
-                    let mut service = mcp_server::router::RouterService(FastTtsRouter);
-                    service
-                        .call(req)
-                        .await
-                        .map(Json)
-                        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)
-                }
-
-                async fn sse() -> Sse<impl futures::Stream<Item = Result<Event, Infallible>>> {
-                    let interval = mcp_tokio::time::interval(Duration::from_secs(10));
-                    let stream = tokio_stream::wrappers::IntervalStream::new(interval)
-                        .map(|_| Ok(Event::default().comment("keepalive")));
-                    Sse::new(stream)
-                }
-
-                let app: AxumRouter = AxumRouter::new()
-                    .route("/rpc", post(rpc))
-                    .route("/events", get(sse));
-
-                let bind_addr = addr.unwrap_or_else(|| "127.0.0.1:2024".to_string());
-                let listener = mcp_tokio::net::TcpListener::bind(&bind_addr).await?;
-                axum::serve(listener, app).await?;
-            }
-        }
-        Ok(())
-    }
-
-    use mcp_server::router::{CapabilitiesBuilder, RouterService};
-
-    #[derive(Clone)]
-    struct FastTtsRouter;
-
-    impl Router for FastTtsRouter {
-        fn name(&self) -> String {
-            "fast-tts-cli".to_string()
-        }
-        fn instructions(&self) -> String {
-            "Google Cloud TTS server providing synthesize and listVoices tools.".to_string()
-        }
-        fn capabilities(&self) -> ServerCapabilities {
-            CapabilitiesBuilder::new().with_tools(false).build()
-        }
-        fn list_tools(&self) -> Vec<mcp_spec::tool::Tool> {
-            vec![
-                mcp_spec::tool::Tool::new(
-                    "synthesize".to_string(),
-                    "Synthesize speech and write to a file".to_string(),
-                    serde_json::json!({
-                        "type": "object",
-                        "properties": {
-                            "text": {"type": "string"},
-                            "output": {"type": "string"},
-                            "language": {"type": "string"},
-                            "voice": {"type": "string"},
-                            "gender": {"type": "string"},
-                            "rate": {"type": "number"},
-                            "pitch": {"type": "number"},
-                            "sampleRate": {"type": "integer"},
-                            "encoding": {"type": "string"},
-                            "volumeGainDb": {"type": "number"},
-                            "effectsProfileId": {"type": "array", "items": {"type": "string"}},
-                            "ssml": {"type": "boolean"},
-                            "play": {"type": "boolean"}
-                        },
-                        "required": ["text", "output"]
-                    }),
-                ),
-                mcp_spec::tool::Tool::new(
-                    "listVoices".to_string(),
-                    "List available voices from provider".to_string(),
-                    serde_json::json!({ "type": "object", "properties": {"json": {"type": "boolean"}}, "required": [] }),
-                ),
-            ]
-        }
-
-        fn call_tool(
-            &self,
-            tool_name: &str,
-            arguments: serde_json::Value,
-        ) -> std::pin::Pin<
-            Box<dyn std::future::Future<Output = Result<Vec<Content>, ToolError>> + Send + 'static>,
-        > {
-            let name = tool_name.to_string();
-            Box::pin(async move {
-                match name.as_str() {
-                    "synthesize" => {
-                        let text = arguments
-                            .get("text")
-                            .and_then(|v| v.as_str())
-                            .ok_or_else(|| ToolError::InvalidParameters("text required".into()))?
-                            .to_string();
-                        let output = arguments
-                            .get("output")
-                            .and_then(|v| v.as_str())
-                            .ok_or_else(|| ToolError::InvalidParameters("output required".into()))?
-                            .to_string();
-                        let language = arguments
-                            .get("language")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("en-US")
-                            .to_string();
-                        let voice = arguments
-                            .get("voice")
-                            .and_then(|v| v.as_str())
-                            .map(|s| s.to_string());
-                        let gender_opt =
-                            arguments.get("gender").and_then(|v| v.as_str()).map(|g| {
-                                match g.to_uppercase().as_str() {
-                                    "MALE" => Gender::Male,
-                                    "FEMALE" => Gender::Female,
-                                    _ => Gender::Neutral,
-                                }
-                            });
-                        let rate = arguments
-                            .get("rate")
-                            .and_then(|v| v.as_f64())
-                            .unwrap_or(1.0) as f32;
-                        let pitch = arguments
-                            .get("pitch")
-                            .and_then(|v| v.as_f64())
-                            .unwrap_or(0.0) as f32;
-                        let sample_rate = arguments
-                            .get("sampleRate")
-                            .and_then(|v| v.as_i64())
-                            .map(|n| n as i32);
-                        let encoding = arguments
-                            .get("encoding")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("LINEAR16");
-                        let volume_gain_db = arguments
-                            .get("volumeGainDb")
-                            .and_then(|v| v.as_f64())
-                            .unwrap_or(0.0) as f32;
-                        let effects_profile_id: Vec<String> = arguments
-                            .get("effectsProfileId")
-                            .and_then(|v| v.as_array())
-                            .map(|arr| {
-                                arr.iter()
-                                    .filter_map(|x| x.as_str().map(|s| s.to_string()))
-                                    .collect()
-                            })
-                            .unwrap_or_default();
-                        let is_ssml = arguments
-                            .get("ssml")
-                            .and_then(|v| v.as_bool())
-                            .unwrap_or(false);
-                        let play = arguments
-                            .get("play")
-                            .and_then(|v| v.as_bool())
-                            .unwrap_or(false);
-
-                        let output_path = PathBuf::from(&output);
-                        let enc = super::parse_encoding_from_str(encoding)
-                            .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
-
-                        super::synthesize_to_wav(
-                            &text,
-                            &output_path,
-                            &language,
-                            voice.as_deref(),
-                            gender_opt,
-                            rate,
-                            pitch,
-                            sample_rate,
-                            enc,
-                            volume_gain_db,
-                            &effects_profile_id
-                                .iter()
-                                .map(|s| s.as_str())
-                                .collect::<Vec<_>>(),
-                            is_ssml,
-                            30_000,
-                            2,
-                        )
-                        .await
-                        .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
-
-                        if play {
-                            if let Err(e) = super::play_audio(&output_path) {
-                                eprintln!("Warning: playback failed: {e}");
-                            }
-                        }
-
-                        Ok(vec![Content::text(
-                            serde_json::json!({"ok": true, "output": output}).to_string(),
-                        )])
-                    }
-                    "listVoices" => {
-                        let token = super::fetch_access_token()
-                            .await
-                            .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
-                        let base = super::base_url();
-                        let client = super::build_http_client_for_base(&base)
-                            .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
-                        let url = format!("{base}/v1/voices");
-                        let mut headers = HeaderMap::new();
-                        let auth_val: reqwest::header::HeaderValue = format!("Bearer {token}")
-                            .parse()
-                            .map_err(|e: reqwest::header::InvalidHeaderValue| {
-                                ToolError::ExecutionError(e.to_string())
-                            })?;
-                        headers.insert(AUTHORIZATION, auth_val);
-                        let resp = client
-                            .get(url)
-                            .headers(headers)
-                            .send()
-                            .await
-                            .map_err(|e| ToolError::ExecutionError(e.to_string()))?
-                            .error_for_status()
-                            .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
-                        let data: super::ListVoicesResponse = resp
-                            .json()
-                            .await
-                            .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
-                        Ok(vec![Content::text(
-                            serde_json::to_string(&data).unwrap_or_else(|_| "{}".into()),
-                        )])
-                    }
-                    _ => Err(ToolError::NotFound(format!("Tool {} not found", name))),
-                }
-            })
-        }
-
-        fn list_resources(&self) -> Vec<mcp_spec::resource::Resource> {
-            vec![]
-        }
-        fn read_resource(
-            &self,
-            _uri: &str,
-        ) -> std::pin::Pin<
-            Box<dyn std::future::Future<Output = Result<String, ResourceError>> + Send + 'static>,
-        > {
-            Box::pin(async { Err(ResourceError::NotFound("not supported".into())) })
-        }
-        fn list_prompts(&self) -> Vec<Prompt> {
-            vec![]
-        }
-        fn get_prompt(
-            &self,
-            _prompt_name: &str,
-        ) -> std::pin::Pin<
-            Box<dyn std::future::Future<Output = Result<String, PromptError>> + Send + 'static>,
-        > {
-            Box::pin(async { Ok(String::new()) })
-        }
-    }
-}
-#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
-enum Gender {
-    Neutral,
-    Male,
-    Female,
-}
-
-#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
-enum Provider {
-    Google,
-    Openai,
-    Elevenlabs,
-    Deepgram,
-    Polly,
-    Azure,
-    Hume,
-    Listnr,
-    Murf,
-    Gemini,
-}
-
-#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
-#[value(rename_all = "SCREAMING_SNAKE_CASE")]
-enum AudioEncoding {
-    Linear16,
-    Mp3,
-    OggOpus,
-    Mulaw,
-    Alaw,
-}
+//! `fast-tts` command line: argument parsing over the `fast_tts` library.
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
-enum McpMode {
-    Stdio,
-    Sse,
-    Http,
-}
-
-impl AudioEncoding {
-    fn api_str(&self) -> &'static str {
-        match self {
-            AudioEncoding::Linear16 => "LINEAR16",
-            AudioEncoding::Mp3 => "MP3",
-            AudioEncoding::OggOpus => "OGG_OPUS",
-            AudioEncoding::Mulaw => "MULAW",
-            AudioEncoding::Alaw => "ALAW",
-        }
-    }
-
-    fn file_extension(&self) -> &'static str {
-        match self {
-            AudioEncoding::Linear16 | AudioEncoding::Mulaw | AudioEncoding::Alaw => "wav",
-            AudioEncoding::Mp3 => "mp3",
-            AudioEncoding::OggOpus => "ogg",
-        }
-    }
-}
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser, Subcommand};
+use fast_tts::audio::DecodedAudio;
+use fast_tts::logging::{LogConfig, LogFormat};
+use fast_tts::sections::SplitAt;
+use fast_tts::{
+    AudioEncoding, BulkEvent, BulkOptions, CancellationToken, FastTtsError, Gender, HttpConfig,
+    McpMode, OpusApplication, OpusSettings, OpusVbr, Provider, SynthesisRequest, load_profile,
+    play_audio, provider_enabled, provider_feature_flag, run_bulk_from_config,
+};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+mod announce;
+#[cfg(feature = "clipboard")]
+mod clip;
+mod history;
+#[cfg(feature = "tui")]
+mod tui;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -357,15 +27,26 @@ impl AudioEncoding {
     about = "Generate audio from Google Cloud Text-to-Speech"
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// Text to synthesize (use quotes)
     text: Option<String>,
 
     /// Output file path (matches encoding)
     output: Option<PathBuf>,
 
-    /// BCP-47 language code (e.g. en-US)
-    #[arg(short = 'l', long = "language", default_value = "en-US")]
-    language: String,
+    /// Append or correct the output extension to match --encoding instead of failing (also bulk items)
+    #[arg(long = "auto-extension", action = ArgAction::SetTrue)]
+    auto_extension: bool,
+
+    /// Write the output into this directory; the output may then be a bare name and gets its extension from --encoding
+    #[arg(long = "output-dir", value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// BCP-47 language code (e.g. en-US); defaults to the --voice's language, else en-US
+    #[arg(short = 'l', long = "language")]
+    language: Option<String>,
 
     /// Specific voice name (e.g. en-US-Neural2-F)
     #[arg(short = 'v', long = "voice")]
@@ -396,11 +77,24 @@ struct Cli {
     )]
     encoding: AudioEncoding,
 
+    /// OGG_OPUS bitrate, e.g. 24k (6k–510k)
+    #[arg(long = "bitrate", value_name = "BITRATE", value_parser = fast_tts::parse_bitrate)]
+    bitrate: Option<u32>,
+
+    /// OGG_OPUS variable bitrate mode
+    #[arg(long = "vbr", value_enum)]
+    vbr: Option<OpusVbr>,
+
+    /// OGG_OPUS tuning: voip for speech intelligibility, audio for fidelity
+    #[arg(long = "application", value_enum)]
+    application: Option<OpusApplication>,
+
     /// Volume gain in dB (-96.0–16.0)
     #[arg(long = "volume", default_value_t = 0.0)]
     volume_gain_db: f32,
 
-    /// Audio effects profile id(s) (comma-separated or repeat flag)
+    /// Audio effects profile id(s) (comma-separated or repeat flag); Google
+    /// also accepts phone, headphones, car and smart-speaker
     #[arg(long = "effects-profile", num_args = 0.., value_delimiter = ',')]
     effects_profile_id: Vec<String>,
 
@@ -408,28 +102,165 @@ struct Cli {
     #[arg(long = "ssml", action = ArgAction::SetTrue)]
     ssml: bool,
 
+    /// Read SSML from this file (UTF-8, or UTF-16 with a BOM) instead of the
+    /// text argument; implies --ssml
+    #[arg(long = "ssml-file", value_name = "FILE", conflicts_with = "dialogue")]
+    ssml_file: Option<PathBuf>,
+
+    /// Sampling seed for repeatable output (ElevenLabs); recorded in history and --json reports
+    #[arg(long = "seed")]
+    seed: Option<u32>,
+
+    /// Read the text as a dialogue script, one `SPEAKER: line` per turn, and
+    /// speak it with Google's multi-speaker voice (speakers R, S, T, ...)
+    #[arg(long = "dialogue", action = ArgAction::SetTrue)]
+    dialogue: bool,
+
+    /// Google custom voice model (projects/P/locations/L/models/M); default GOOGLE_CUSTOM_VOICE_MODEL
+    #[arg(long = "custom-voice-model")]
+    custom_voice_model: Option<String>,
+
+    /// Piper model: a .onnx file (with its .onnx.json next to it) or the name
+    /// of one in PIPER_MODELS_DIR; default PIPER_MODEL, or the only one installed.
+    /// Kokoro: which .onnx export in KOKORO_DIR to run (default KOKORO_MODEL)
+    #[arg(long = "model", value_name = "PATH|NAME")]
+    model: Option<String>,
+
+    /// Switch voices inside the text with [voice:NAME] ... [/voice] tags; the
+    /// segments are synthesized separately and joined into one file
+    #[arg(long = "voice-markup", action = ArgAction::SetTrue)]
+    voice_markup: bool,
+
+    /// Crossfade in ms (0–500) where the parts of one render are joined (voice
+    /// markup, long ElevenLabs text); 16-bit PCM only, e.g. 30 to hide clicks
+    #[arg(long = "crossfade-ms", default_value_t = 0)]
+    crossfade_ms: u32,
+
+    /// Split the text at lines that are exactly MARKER (e.g. ---) and write
+    /// each section to its own file, named by --name-template
+    #[arg(long = "split-on-marker", value_name = "MARKER", allow_hyphen_values = true, conflicts_with_all = ["split_on_break", "stream", "config_path", "dialogue"])]
+    split_on_marker: Option<String>,
+
+    /// Split SSML at <break> elements at least this long (e.g. 2s) and write
+    /// each section to its own file, named by --name-template
+    #[arg(long = "split-on-break", value_name = "DURATION", value_parser = fast_tts::parse_duration, conflicts_with_all = ["stream", "config_path", "dialogue"])]
+    split_on_break: Option<std::time::Duration>,
+
+    /// File names for split sections, next to the output: {stem} and {ext}
+    /// of the output, {n} (1, 2, ... zero-padded) and {slug} (first words)
+    #[arg(
+        long = "name-template",
+        value_name = "TEMPLATE",
+        default_value = "{stem}-{n}.{ext}"
+    )]
+    name_template: String,
+
+    /// With --dialogue or --voice-markup, also write each speaker to its own
+    /// OUTPUT_STEM.SPEAKER.wav, aligned with the mix (LINEAR16 only)
+    #[arg(long = "tracks", action = ArgAction::SetTrue)]
+    tracks: bool,
+
+    /// With --voice-markup or --tracks, also write OUTPUT_STEM.segments.json:
+    /// where each segment or turn starts and ends in the joined output
+    #[arg(long = "segment-offsets", action = ArgAction::SetTrue, conflicts_with = "stream")]
+    segment_offsets: bool,
+
+    /// Fit the speech to this length (e.g. 60s, 1m30s, 1:30) by adjusting the
+    /// speaking rate and re-synthesizing; --rate is the first guess
+    #[arg(long = "target-duration", value_name = "DURATION", value_parser = fast_tts::parse_duration, conflicts_with_all = ["stream", "config_path"])]
+    target_duration: Option<std::time::Duration>,
+
+    /// How far from --target-duration the audio may end up
+    #[arg(long = "duration-tolerance", value_name = "DURATION", value_parser = fast_tts::parse_duration, default_value = "250ms")]
+    duration_tolerance: std::time::Duration,
+
+    /// Music bed (WAV, MP3, Ogg Vorbis or FLAC) to mix under the speech,
+    /// looped as needed; needs LINEAR16 output
+    #[arg(long = "bg-music", value_name = "FILE", conflicts_with_all = ["stream", "config_path"])]
+    bg_music: Option<PathBuf>,
+
+    /// PNG or JPEG cover art to embed in MP3 output (ID3 APIC), which most
+    /// podcast hosts require
+    #[arg(long = "cover", value_name = "IMAGE", conflicts_with_all = ["stream", "config_path"])]
+    cover: Option<PathBuf>,
+
+    /// Level of --bg-music against the speech
+    #[arg(long = "bg-gain", value_name = "DB", value_parser = fast_tts::parse_db, default_value = "-18dB", allow_hyphen_values = true)]
+    bg_gain_db: f32,
+
+    /// How far --bg-music dips while someone is speaking
+    #[arg(long = "duck", value_name = "DB", value_parser = fast_tts::parse_db, default_value = "-8dB", allow_hyphen_values = true)]
+    duck_db: f32,
+
     /// Play the output audio after synthesis
     #[arg(long = "play", action = ArgAction::SetTrue)]
     play: bool,
 
+    /// Write (and with --play, start playing) audio as the provider sends it; output `-` is stdout
+    #[arg(long = "stream", action = ArgAction::SetTrue)]
+    stream: bool,
+
     /// Use config file (YAML or JSON) for bulk synthesis
     #[arg(long = "config", value_name = "FILE")]
     config_path: Option<PathBuf>,
 
-    /// TTS provider (future: more providers). Only 'google' works now.
-    #[arg(long = "provider", value_enum, default_value = "google")]
+    /// TTS provider (google, openai, azure, elevenlabs, deepgram, lmnt, gemini, polly, piper, kokoro, system), custom:<name> or wasm:<path>
+    #[arg(long = "provider", value_parser = fast_tts::parse_provider, default_value = "google")]
     provider: Provider,
 
     // Provider selection is compile-time via cargo features
-
     /// List available voices and exit
     #[arg(long = "list-voices", action = ArgAction::SetTrue)]
     list_voices: bool,
 
-    /// Emit JSON for --list-voices
-    #[arg(long = "json", action = ArgAction::SetTrue)]
+    /// Machine-readable output: results as JSON on stdout (one object per written
+    /// file, the voice list, `auth check`), errors as JSON on stderr
+    #[arg(long = "json", action = ArgAction::SetTrue, global = true)]
     json_output: bool,
 
+    /// Log progress to stderr: once for info (auth, timings), twice for debug
+    /// (each HTTP call, cache hits), three times for trace
+    #[arg(long = "verbose", action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Suppress all log output, including warnings
+    #[arg(short = 'q', long = "quiet", action = ArgAction::SetTrue, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print only the written file path(s) on stdout, one per line, for
+    /// `OUT=$(fast-tts ...)`
+    #[arg(long = "print-path-only", action = ArgAction::SetTrue, conflicts_with_all = ["json_output", "tui"])]
+    print_path_only: bool,
+
+    /// Format of log lines on stderr
+    #[arg(long = "log-format", value_enum, default_value = "text", global = true)]
+    log_format: LogFormat,
+
+    /// Show a live dashboard for --config runs (p pause, s skip item, q abort)
+    #[arg(long = "tui", action = ArgAction::SetTrue, requires = "config_path")]
+    tui: bool,
+
+    /// Show a desktop notification when the synthesis or --config run finishes or fails
+    #[arg(long = "notify", action = ArgAction::SetTrue)]
+    notify: bool,
+
+    /// Synthesize in this process even when a `serve --daemon` is running
+    #[arg(long = "no-daemon", action = ArgAction::SetTrue)]
+    no_daemon: bool,
+
+    /// Don't record this job in the local history (`fast-tts history`)
+    #[arg(long = "no-history", action = ArgAction::SetTrue)]
+    no_history: bool,
+
+    /// Ask before a --config run of more than this many characters (also
+    /// FAST_TTS_CONFIRM_ABOVE_CHARS; default 100000, 0 always asks)
+    #[arg(long = "confirm-above-chars", value_name = "CHARS")]
+    confirm_above_chars: Option<usize>,
+
+    /// Start a large --config run without asking
+    #[arg(short = 'y', long = "yes", action = ArgAction::SetTrue)]
+    yes: bool,
+
     /// Request timeout in milliseconds
     #[arg(long = "timeout", default_value_t = 30_000)]
     timeout_ms: u64,
@@ -445,77 +276,463 @@ struct Cli {
     /// Address or URL for MCP SSE/HTTP (e.g. 127.0.0.1:2024 or http://127.0.0.1:2024)
     #[arg(long = "mcp-addr")]
     mcp_addr: Option<String>,
-}
 
-#[derive(Serialize)]
-#[serde(untagged)]
-enum SynthesisInput<'a> {
-    #[serde(rename_all = "camelCase")]
-    Text { text: &'a str },
-    #[serde(rename_all = "camelCase")]
-    Ssml { ssml: &'a str },
-}
+    /// Bearer token required by MCP HTTP/SSE routes (also FAST_TTS_MCP_TOKEN)
+    #[arg(long = "mcp-auth-token", value_name = "TOKEN")]
+    mcp_auth_token: Option<String>,
+
+    /// Maximum synthesize calls the MCP server runs at once; extra calls wait
+    #[arg(long = "mcp-max-concurrent", default_value_t = 4)]
+    mcp_max_concurrent: usize,
+
+    /// PEM certificate chain for serving MCP HTTP/SSE over TLS
+    #[arg(long = "mcp-tls-cert", value_name = "PEM", requires = "mcp_tls_key")]
+    mcp_tls_cert: Option<PathBuf>,
+
+    /// PEM private key matching --mcp-tls-cert
+    #[arg(long = "mcp-tls-key", value_name = "PEM", requires = "mcp_tls_cert")]
+    mcp_tls_key: Option<PathBuf>,
+
+    /// Confine MCP tool output paths to this directory (relative paths land inside it)
+    #[arg(long = "mcp-output-dir", value_name = "DIR")]
+    mcp_output_dir: Option<PathBuf>,
+
+    /// Extra root CA certificate(s) in PEM format (also FAST_TTS_CA_BUNDLE)
+    #[arg(long = "ca-cert", value_name = "PEM")]
+    ca_cert: Vec<PathBuf>,
+
+    /// Disable TLS certificate verification (only for self-signed test endpoints)
+    #[arg(long = "insecure", action = ArgAction::SetTrue)]
+    insecure: bool,
+
+    /// Client certificate (PEM) for mutual TLS; may also contain the key
+    #[arg(long = "client-cert", value_name = "PEM")]
+    client_cert: Option<PathBuf>,
+
+    /// Private key (PEM) for --client-cert when stored separately
+    #[arg(long = "client-key", value_name = "PEM", requires = "client_cert")]
+    client_key: Option<PathBuf>,
+
+    /// Extra request header sent to every provider (repeatable, "Name: value")
+    #[arg(long = "header", value_name = "HEADER")]
+    headers: Vec<String>,
+
+    /// Append sanitized HTTP request/response metadata to FILE (JSON lines)
+    #[arg(long = "debug-http", value_name = "FILE")]
+    debug_http: Option<PathBuf>,
+
+    /// Record provider responses into a cassette file for later --replay
+    #[arg(long = "record", value_name = "FILE", conflicts_with = "replay")]
+    record: Option<PathBuf>,
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct VoiceSelectionParams<'a> {
-    language_code: &'a str,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    name: Option<&'a str>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    ssml_gender: Option<&'a str>,
+    /// Serve provider responses from a cassette instead of the network
+    #[arg(long = "replay", value_name = "FILE")]
+    replay: Option<PathBuf>,
+
+    /// Pin a host to specific address(es), like curl: HOST:PORT:ADDR[,ADDR...]; only requests to that port use them
+    #[arg(long = "resolve", value_name = "HOST:PORT:ADDR")]
+    resolve: Vec<String>,
+
+    /// Forbid all network calls; only --replay cassettes and local providers work (also FAST_TTS_OFFLINE=1)
+    #[arg(long = "offline", action = ArgAction::SetTrue)]
+    offline: bool,
+
+    /// Fall back to `gcloud auth print-access-token` when no other Google credentials work
+    #[arg(long = "gcloud-auth", action = ArgAction::SetTrue)]
+    gcloud_auth: bool,
+
+    /// Google Cloud API key sent as X-Goog-Api-Key (also GOOGLE_API_KEY)
+    #[arg(long = "api-key", value_name = "KEY")]
+    api_key: Option<String>,
+
+    /// Named profile from profiles.yaml supplying provider and credentials
+    #[arg(long = "profile", value_name = "NAME")]
+    profile: Option<String>,
 }
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct AudioConfig<'a> {
-    audio_encoding: &'a str,
-    speaking_rate: f32,
-    pitch: f32,
-    volume_gain_db: f32,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    sample_rate_hertz: Option<i32>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    effects_profile_id: Vec<&'a str>,
-    enable_legacy_wav_header: bool,
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Inspect provider credentials
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommand,
+    },
+    /// Run a REST API server (POST /synthesize, GET /voices, GET /providers)
+    Serve {
+        /// Address to listen on
+        #[arg(long = "addr", default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// Seconds that audio stored for `"response": "url"` stays downloadable
+        #[arg(long = "audio-ttl", default_value_t = 3600)]
+        audio_ttl: u64,
+        /// YAML file of API keys (with optional rate limits and character quotas); requests must present one
+        #[arg(long = "api-keys")]
+        api_keys: Option<PathBuf>,
+        /// Requests per minute allowed from each client IP
+        #[arg(long = "rate-limit")]
+        rate_limit: Option<u32>,
+        /// Longest text accepted per synthesize request, in characters
+        #[arg(long = "max-text-chars", default_value_t = 5000)]
+        max_text_chars: usize,
+        /// Bucket for `"response": "signedUrl"` as s3://BUCKET/PREFIX (credentials
+        /// and region from AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY, AWS_REGION)
+        #[arg(long = "storage", value_name = "s3://BUCKET/PREFIX")]
+        storage: Option<String>,
+        /// S3-compatible endpoint (MinIO, R2, ...) instead of AWS; objects are addressed path-style
+        #[arg(long = "storage-endpoint", value_name = "URL", requires = "storage")]
+        storage_endpoint: Option<String>,
+        /// Seconds that signed URLs stay valid (at most 7 days)
+        #[arg(long = "signed-url-ttl", default_value_t = 3600)]
+        signed_url_ttl: u64,
+        /// Syntheses run at once; further requests wait in the queue
+        #[arg(long = "max-concurrent", default_value_t = 8)]
+        max_concurrent: usize,
+        /// Requests that may wait for a synthesis slot; when the queue is full
+        /// the server answers 429 with Retry-After
+        #[arg(long = "queue-depth", default_value_t = 32)]
+        queue_depth: usize,
+        /// Listen on the daemon socket (FAST_TTS_SOCKET or $XDG_RUNTIME_DIR/fast-tts-cli.sock)
+        /// instead of --addr; plain synthesis commands then go through this server
+        #[arg(long = "daemon", action = ArgAction::SetTrue)]
+        daemon: bool,
+    },
+    /// List, inspect and re-run past synthesis jobs (stored in FAST_TTS_HISTORY
+    /// or the user data directory)
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+    /// Synthesize one text with several providers/voices into a folder with an index.html
+    Compare {
+        /// Text to synthesize
+        text: String,
+        /// Providers to compare (comma-separated)
+        #[arg(long = "providers", value_parser = fast_tts::parse_provider, value_delimiter = ',', required = true)]
+        providers: Vec<Provider>,
+        /// Voice for one provider as PROVIDER=VOICE (repeatable; default: the provider's default voice)
+        #[arg(long = "voice", value_name = "PROVIDER=VOICE")]
+        voices: Vec<String>,
+        /// Directory for the audio files and index.html
+        #[arg(long = "out-dir", value_name = "DIR")]
+        out_dir: PathBuf,
+        /// Audio encoding requested from every provider
+        #[arg(
+            long = "encoding",
+            value_enum,
+            default_value = "MP3",
+            ignore_case = true
+        )]
+        encoding: AudioEncoding,
+    },
+    /// Compare two renders (duration, format, loudness, fingerprint); exits 1 unless equivalent
+    Verify {
+        a: PathBuf,
+        b: PathBuf,
+        /// Largest duration difference still considered equivalent
+        #[arg(long = "max-duration-diff-ms", default_value_t = 50.0)]
+        max_duration_diff_ms: f64,
+        /// Largest loudness difference still considered equivalent
+        #[arg(long = "max-loudness-diff-db", default_value_t = 1.0)]
+        max_loudness_diff_db: f64,
+        /// Lowest fingerprint similarity (0–1) still considered equivalent
+        #[arg(long = "min-similarity", default_value_t = 0.9)]
+        min_similarity: f64,
+    },
+    /// Speak each line from stdin as it arrives, one at a time (uses the global voice options)
+    Announce {
+        /// Append each line's audio to this file or named pipe instead of playing it
+        #[arg(long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Speak the clipboard text, or with --watch every new copy (uses the global voice options)
+    Clip {
+        /// Keep watching and speak each newly copied text; Enter pauses/resumes
+        #[arg(long = "watch", action = ArgAction::SetTrue)]
+        watch: bool,
+        /// Ignore copies shorter than this many characters
+        #[arg(long = "min-chars", default_value_t = 20)]
+        min_chars: usize,
+        /// Save each copy as a file in DIR instead of playing it
+        #[arg(long = "out-dir", value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+        /// How often to check the clipboard, in milliseconds
+        #[arg(long = "interval", default_value_t = 500)]
+        interval_ms: u64,
+    },
+    /// Synthesize new entries of an RSS/Atom feed into DIR (uses the global voice options)
+    Feed {
+        /// Feed URL
+        url: String,
+        /// Directory for the audio files and the record of entries already synthesized
+        #[arg(long = "out-dir", value_name = "DIR")]
+        out_dir: PathBuf,
+        /// Synthesize at most this many new entries, newest first
+        #[arg(long = "limit")]
+        limit: Option<usize>,
+        /// Only use the text inside the feed; don't download linked articles
+        #[arg(long = "no-fetch", action = ArgAction::SetTrue)]
+        no_fetch: bool,
+    },
+    /// Publish generated audio as a podcast
+    Podcast {
+        #[command(subcommand)]
+        command: PodcastCommand,
+    },
+    /// List the --encoding and --sample-rate values each provider accepts
+    Encodings {
+        /// Only this provider (default: every provider in this build)
+        #[arg(long = "provider", value_parser = fast_tts::parse_provider)]
+        provider: Option<Provider>,
+    },
+    /// Probe each configured provider with a one-character synthesis and
+    /// report whether it is up and how long it took
+    Status {
+        /// Only probe this provider (default: every configured provider)
+        #[arg(value_parser = fast_tts::parse_provider)]
+        provider: Option<Provider>,
+    },
+    /// Measure time-to-first-byte and total latency per provider
+    Bench {
+        /// Providers to compare (comma-separated)
+        #[arg(long = "providers", value_parser = fast_tts::parse_provider, value_delimiter = ',', required = true)]
+        providers: Vec<Provider>,
+        /// File with the text to synthesize
+        #[arg(long = "text", value_name = "FILE")]
+        text: PathBuf,
+        /// Runs per provider
+        #[arg(short = 'n', default_value_t = 10)]
+        runs: usize,
+        /// Audio encoding requested from every provider
+        #[arg(
+            long = "encoding",
+            value_enum,
+            default_value = "MP3",
+            ignore_case = true
+        )]
+        encoding: AudioEncoding,
+    },
+    /// Check credentials, helper programs and the cache directory without
+    /// touching the network
+    Doctor,
+    /// Inspect or clear the cache of voice catalogs and access tokens
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Sum jobs, characters and estimated cost per provider from the history
+    Usage {
+        /// Only the last N days (default: all of the history)
+        #[arg(long = "days", value_name = "N")]
+        days: Option<u64>,
+    },
 }
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct SynthesizeRequest<'a> {
-    input: SynthesisInput<'a>,
-    voice: VoiceSelectionParams<'a>,
-    audio_config: AudioConfig<'a>,
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// Show where the cache is and how much it holds
+    Info,
+    /// Delete every cached file
+    Clear,
 }
 
-#[derive(Deserialize)]
-struct SynthesizeResponse {
-    audio_content: String,
+#[derive(Subcommand, Debug)]
+enum HistoryCommand {
+    /// Show recent jobs, newest first
+    List {
+        /// Number of jobs to show
+        #[arg(short = 'n', default_value_t = 20)]
+        limit: usize,
+    },
+    /// Show one job, including the command that reproduces it
+    Show { id: u64 },
+    /// Run a past job again with its recorded arguments
+    Rerun { id: u64 },
 }
 
-#[derive(Deserialize, Serialize)]
-struct ListVoicesResponse {
-    voices: Vec<Voice>,
+#[derive(Subcommand, Debug)]
+enum PodcastCommand {
+    /// Write a podcast RSS feed listing the audio files in DIR
+    Publish {
+        /// Directory with the episodes (e.g. a `feed --out-dir`)
+        #[arg(long = "dir", value_name = "DIR")]
+        dir: PathBuf,
+        /// Public URL the directory is served from
+        #[arg(long = "base-url", value_name = "URL")]
+        base_url: String,
+        /// Podcast title (default: the directory name)
+        #[arg(long = "title")]
+        title: Option<String>,
+        #[arg(long = "description")]
+        description: Option<String>,
+        #[arg(long = "author")]
+        author: Option<String>,
+        /// Cover image: a URL, or a file in DIR (default: cover.jpg/cover.png when present)
+        #[arg(long = "artwork")]
+        artwork: Option<String>,
+        /// Where to write the feed (default: DIR/feed.xml)
+        #[arg(long = "output", value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
 }
 
-#[derive(Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-struct Voice {
-    name: String,
-    language_codes: Vec<String>,
-    ssml_gender: String,
-    natural_sample_rate_hertz: Option<i32>,
+#[derive(Subcommand, Debug)]
+enum AuthCommand {
+    /// Make a minimal authenticated call per provider and report credential status
+    Check {
+        /// Only check this provider (default: all enabled providers)
+        #[arg(value_parser = fast_tts::parse_provider)]
+        provider: Option<Provider>,
+    },
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let args = Cli::parse();
+    let json_output = args.json_output;
+    let result = match with_rerun(args) {
+        Ok((args, argv)) => {
+            let notify = args.notify.then(|| job_label(&args)).flatten();
+            let started = std::time::Instant::now();
+            let result = run(args, argv).await;
+            #[cfg(feature = "notify")]
+            if let Some(label) = notify {
+                notify_finished(&label, started.elapsed(), &result);
+            }
+            #[cfg(not(feature = "notify"))]
+            let _ = (notify, started);
+            result
+        }
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            let failure = FastTtsError::classify(&err);
+            if json_output {
+                let report = serde_json::json!({ "error": failure.report() });
+                eprintln!("{report}");
+            } else {
+                eprintln!("Error: {err:?}");
+            }
+            ExitCode::from(failure.exit_code())
+        }
+    }
+}
+
+/// The command line to run: `history rerun ID` swaps in the recorded
+/// arguments, so the replay is configured exactly like the original job.
+fn with_rerun(args: Cli) -> Result<(Cli, Vec<String>)> {
+    if let Some(Commands::History {
+        command: HistoryCommand::Rerun { id },
+    }) = args.command
+    {
+        let entry = history::find(id)?;
+        std::env::set_current_dir(&entry.cwd)
+            .with_context(|| format!("cannot enter the job's directory {}", entry.cwd.display()))?;
+        let program = std::iter::once("fast-tts-cli".to_string());
+        let cli = Cli::try_parse_from(program.chain(entry.args.iter().cloned())).map_err(|e| {
+            FastTtsError::InvalidInput(format!("history entry {id} no longer parses: {e}"))
+        })?;
+        return Ok((cli, entry.args));
+    }
+    Ok((args, std::env::args().skip(1).collect()))
+}
+
+/// What `--notify` reports on: the output file or bulk config, or `None`
+/// for commands that aren't synthesis jobs.
+fn job_label(args: &Cli) -> Option<String> {
+    if args.command.is_some() || args.list_voices || args.mcp_mode.is_some() {
+        return None;
+    }
+    let path = args.config_path.as_ref().or(args.output.as_ref())?;
+    let name = path.file_name().unwrap_or(path.as_os_str());
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(feature = "notify")]
+fn notify_finished(label: &str, elapsed: std::time::Duration, result: &Result<()>) {
+    let (summary, body) = match result {
+        Ok(()) => (
+            "fast-tts finished",
+            format!("{label} done in {:.0}s", elapsed.as_secs_f64()),
+        ),
+        Err(err) => ("fast-tts failed", format!("{label}: {err}")),
+    };
+    // A command (e.g. notify-send, or a script) in place of the native notification
+    if let Some(notifier) = std::env::var_os("FAST_TTS_NOTIFIER").filter(|n| !n.is_empty()) {
+        match std::process::Command::new(&notifier)
+            .args([summary, &body])
+            .status()
+        {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!(
+                "Warning: notifier {} exited with {status}",
+                notifier.to_string_lossy()
+            ),
+            Err(e) => eprintln!(
+                "Warning: notifier {} failed: {e}",
+                notifier.to_string_lossy()
+            ),
+        }
+        return;
+    }
+    if let Err(e) = notify_rust::Notification::new()
+        .appname("fast-tts")
+        .summary(summary)
+        .body(&body)
+        .show()
+    {
+        eprintln!("Warning: desktop notification failed: {e}");
+    }
+}
+
+async fn run(args: Cli, argv: Vec<String>) -> Result<()> {
+    #[cfg(not(feature = "notify"))]
+    if args.notify {
+        anyhow::bail!("This binary was built without the 'notify' feature");
+    }
+    fast_tts::init_http(&HttpConfig {
+        ca_certs: args.ca_cert.clone(),
+        insecure: args.insecure,
+        client_cert: args.client_cert.clone(),
+        client_key: args.client_key.clone(),
+        headers: args.headers.clone(),
+        debug_http: args.debug_http.clone(),
+        record: args.record.clone(),
+        replay: args.replay.clone(),
+        resolve: args.resolve.clone(),
+        offline: args.offline,
+    })?;
+    let _otel = fast_tts::logging::init(
+        LogConfig {
+            verbose: args.verbose,
+            quiet: args.quiet,
+            format: args.log_format,
+        },
+        fast_tts::offline(),
+    )?;
+    fast_tts::set_gcloud_auth_fallback(args.gcloud_auth);
+    if let Some(key) = &args.api_key {
+        fast_tts::set_google_api_key(key.clone());
+    }
 
     // If running in MCP server mode, start the server and exit.
     if let Some(_mode) = args.mcp_mode {
         #[cfg(feature = "mcp")]
         {
-            return mcp_integration::run_mcp_server(_mode, args.mcp_addr).await;
+            let auth_token = args
+                .mcp_auth_token
+                .clone()
+                .or_else(|| std::env::var("FAST_TTS_MCP_TOKEN").ok())
+                .filter(|t| !t.is_empty());
+            let options = fast_tts::McpServerOptions {
+                addr: args.mcp_addr.clone(),
+                auth_token,
+                max_concurrent: args.mcp_max_concurrent,
+                tls: args.mcp_tls_cert.clone().zip(args.mcp_tls_key.clone()),
+                output_dir: args.mcp_output_dir.clone(),
+            };
+            return fast_tts::run_mcp_server(_mode, options).await;
         }
         #[cfg(not(feature = "mcp"))]
         {
@@ -525,386 +742,864 @@ async fn main() -> Result<()> {
         }
     }
 
-    if let Some(cfg_path) = &args.config_path {
-        run_bulk_from_config(cfg_path, args.timeout_ms, args.retries, args.play).await?;
-        return Ok(());
+    if let Some(Commands::Auth {
+        command: AuthCommand::Check { provider },
+    }) = args.command
+    {
+        return run_auth_check(provider, args.json_output).await;
     }
 
-    if args.list_voices {
-        if !provider_enabled(Provider::Google) {
-            anyhow::bail!(
-                "Google provider not enabled in this build. Rebuild with --features provider-google or all-providers"
+    match &args.command {
+        Some(Commands::History {
+            command: HistoryCommand::List { limit },
+        }) => return history::print_list(*limit, args.json_output),
+        Some(Commands::History {
+            command: HistoryCommand::Show { id },
+        }) => return history::print_entry(*id, args.json_output),
+        Some(Commands::Usage { days }) => return history::print_usage(*days, args.json_output),
+        Some(Commands::Doctor) => return run_doctor(args.json_output),
+        Some(Commands::Cache { command }) => return run_cache(command, args.json_output),
+        _ => {}
+    }
+
+    if let Some(Commands::Compare {
+        text,
+        providers,
+        voices,
+        out_dir,
+        encoding,
+    }) = &args.command
+    {
+        return run_compare(
+            text,
+            providers,
+            voices,
+            out_dir,
+            *encoding,
+            args.json_output,
+        )
+        .await;
+    }
+
+    if let Some(Commands::Verify {
+        a,
+        b,
+        max_duration_diff_ms,
+        max_loudness_diff_db,
+        min_similarity,
+    }) = &args.command
+    {
+        let tolerance = fast_tts::audio::Tolerance {
+            duration_ms: *max_duration_diff_ms,
+            loudness_db: *max_loudness_diff_db,
+            min_similarity: *min_similarity,
+        };
+        return run_verify(a, b, &tolerance, args.json_output);
+    }
+
+    if let Some(Commands::Podcast {
+        command:
+            PodcastCommand::Publish {
+                dir,
+                base_url,
+                title,
+                description,
+                author,
+                artwork,
+                output,
+            },
+    }) = &args.command
+    {
+        let podcast = fast_tts::feed::Podcast {
+            title: title.clone().unwrap_or_else(|| {
+                std::path::absolute(dir)
+                    .ok()
+                    .and_then(|d| d.file_name().map(|n| n.to_string_lossy().into_owned()))
+                    .unwrap_or_else(|| "Podcast".into())
+            }),
+            description: description.clone().unwrap_or_default(),
+            link: base_url.clone(),
+            language: args.language.clone().unwrap_or_else(|| "en-US".into()),
+            author: author.clone(),
+            artwork: artwork.clone(),
+        };
+        let output = output.clone().unwrap_or_else(|| dir.join("feed.xml"));
+        let episodes = fast_tts::feed::publish(podcast, dir, &output)?;
+        if args.json_output {
+            println!(
+                "{}",
+                serde_json::json!({ "output": output, "episodes": episodes })
             );
+        } else {
+            println!("Wrote {} ({episodes} episodes)", output.display());
         }
-        list_voices(args.json_output).await?;
         return Ok(());
     }
 
-    let text = args
-        .text
-        .as_deref()
-        .context("text and output are required unless --list-voices is used")?;
-    let output = args
-        .output
-        .as_deref()
-        .context("text and output are required unless --list-voices is used")?;
-
-    validate_output_extension(output, args.encoding)?;
-
-    if !provider_enabled(args.provider) {
-        anyhow::bail!(
-            "provider {:?} not enabled in this build. Rebuild with --features {} or all-providers",
-            args.provider,
-            provider_feature_flag(args.provider)
-        );
+    if let Some(Commands::Feed {
+        url,
+        out_dir,
+        limit,
+        no_fetch,
+    }) = &args.command
+    {
+        let (profile, provider) = profile_and_provider(&args)?;
+        let finish = finish_options(&args)?;
+        let cancel = cancel_on_ctrl_c();
+        return fast_tts::feed::synthesize_new(
+            url,
+            out_dir,
+            *limit,
+            *no_fetch,
+            args.encoding.file_extension(),
+            &cancel,
+            async |text, output| {
+                let request = request_builder(&args, provider)
+                    .text(text)
+                    .output(output)
+                    .cancellation(cancel.clone())
+                    .build()?;
+                synthesize_one(&args, &request, profile.as_ref(), output, false, &finish).await
+            },
+        )
+        .await;
     }
 
-    match args.provider {
-        Provider::Google => {
-            synthesize_to_wav(
-                text,
-                output,
-                &args.language,
-                args.voice.as_deref(),
-                args.gender,
-                args.rate,
-                args.pitch,
-                args.sample_rate,
-                args.encoding,
-                args.volume_gain_db,
-                &args
-                    .effects_profile_id
-                    .iter()
-                    .map(|s| s.as_str())
-                    .collect::<Vec<_>>(),
-                args.ssml,
-                args.timeout_ms,
-                args.retries,
-            )
-            .await?;
-        }
-        Provider::Gemini => {
-            synthesize_gemini(text, output, args.voice.as_deref(), args.encoding).await?;
-        }
-        Provider::Azure => {
-            synthesize_azure(
-                text,
-                output,
-                &args.language,
-                args.voice.as_deref(),
-                args.encoding,
-                args.sample_rate,
-            )
-            .await?;
+    if let Some(Commands::Announce { output }) = &args.command {
+        let (profile, provider) = profile_and_provider(&args)?;
+        let extension = args.encoding.file_extension();
+        let announce = announce::run(extension, output.clone(), |text, output| {
+            request_builder(&args, provider)
+                .text(text)
+                .output(output)
+                .build()
+        });
+        return match &profile {
+            Some(profile) => fast_tts::with_profile(profile, announce).await,
+            None => announce.await,
+        };
+    }
+
+    if let Some(Commands::Clip {
+        watch,
+        min_chars,
+        out_dir,
+        interval_ms,
+    }) = &args.command
+    {
+        #[cfg(feature = "clipboard")]
+        {
+            let options = clip::ClipOptions {
+                watch: *watch,
+                min_chars: *min_chars,
+                out_dir: out_dir.clone(),
+                interval: std::time::Duration::from_millis(*interval_ms),
+                extension: args.encoding.file_extension(),
+            };
+            let (profile, provider) = profile_and_provider(&args)?;
+            let clip = clip::run(options, |text, output| {
+                request_builder(&args, provider)
+                    .text(text)
+                    .output(output)
+                    .build()
+            });
+            return match &profile {
+                Some(profile) => fast_tts::with_profile(profile, clip).await,
+                None => clip.await,
+            };
         }
-        Provider::Openai => {
-            synthesize_openai(text, output, args.voice.as_deref(), args.encoding).await?;
+        #[cfg(not(feature = "clipboard"))]
+        {
+            let _ = (watch, min_chars, out_dir, interval_ms);
+            anyhow::bail!("This binary was built without the 'clipboard' feature");
         }
-        Provider::Elevenlabs => {
-            synthesize_elevenlabs(
-                text,
-                output,
-                args.voice.as_deref(),
-                args.encoding,
-                std::env::var("ELEVENLABS_MODEL_ID").ok().as_deref(),
-            )
-            .await?;
+    }
+
+    if let Some(Commands::Encodings { provider }) = &args.command {
+        return print_encodings(*provider, args.json_output);
+    }
+
+    if let Some(Commands::Status { provider }) = &args.command {
+        return run_status(*provider, args.timeout_ms, args.json_output).await;
+    }
+
+    if let Some(Commands::Bench {
+        providers,
+        text,
+        runs,
+        encoding,
+    }) = &args.command
+    {
+        return run_bench(providers, text, *runs, *encoding, args.json_output).await;
+    }
+
+    if let Some(Commands::Serve {
+        addr,
+        audio_ttl,
+        api_keys,
+        rate_limit,
+        max_text_chars,
+        storage,
+        storage_endpoint,
+        signed_url_ttl,
+        max_concurrent,
+        queue_depth,
+        daemon,
+    }) = &args.command
+    {
+        #[cfg(feature = "serve")]
+        {
+            let addr = if *daemon {
+                daemon_addr()?
+            } else {
+                addr.clone()
+            };
+            let storage = storage
+                .as_deref()
+                .map(|location| {
+                    fast_tts::serve::storage::ObjectStore::from_env(
+                        location,
+                        storage_endpoint.as_deref(),
+                        std::time::Duration::from_secs(*signed_url_ttl),
+                    )
+                })
+                .transpose()?;
+            return fast_tts::serve::run(fast_tts::serve::ServeOptions {
+                addr,
+                audio_ttl: std::time::Duration::from_secs(*audio_ttl),
+                api_keys: api_keys.clone(),
+                rate_limit: *rate_limit,
+                max_text_chars: *max_text_chars,
+                storage,
+                max_concurrent: *max_concurrent,
+                queue_depth: *queue_depth,
+            })
+            .await;
         }
-        Provider::Deepgram => {
-            synthesize_deepgram(
-                text,
-                output,
-                args.voice.as_deref(),
-                args.encoding,
-                std::env::var("DEEPGRAM_TTS_MODEL").ok().as_deref(),
-            )
-            .await?;
+        #[cfg(not(feature = "serve"))]
+        {
+            let _ = (
+                addr,
+                audio_ttl,
+                api_keys,
+                rate_limit,
+                max_text_chars,
+                storage,
+                storage_endpoint,
+                signed_url_ttl,
+                max_concurrent,
+                queue_depth,
+                daemon,
+            );
+            anyhow::bail!("This binary was built without the 'serve' feature");
         }
-        Provider::Polly => {
-            #[cfg(feature = "polly")]
+    }
+
+    if let Some(cfg_path) = &args.config_path {
+        let options = BulkOptions {
+            timeout_ms: args.timeout_ms,
+            retries: args.retries,
+            play: args.play,
+            cancel: cancel_on_ctrl_c(),
+            auto_extension: args.auto_extension,
+            confirm_above_chars: confirm_above_chars(&args)?,
+            confirm: Some(confirm_bulk_run),
+            ..BulkOptions::default()
+        };
+        let started = std::time::Instant::now();
+        let mut planned = (args.provider, 0);
+        let mut observe = |event: &BulkEvent<'_>| {
+            if let BulkEvent::Planned {
+                provider, chars, ..
+            } = event
             {
-                synthesize_polly(text, output, args.voice.as_deref(), args.encoding).await?;
+                planned = (*provider, *chars);
             }
-            #[cfg(not(feature = "polly"))]
+        };
+        let result = if args.tui {
+            #[cfg(feature = "tui")]
             {
-                anyhow::bail!("Amazon Polly support requires --features polly");
+                tui::run_bulk(cfg_path, options, observe).await
             }
+            #[cfg(not(feature = "tui"))]
+            {
+                let _ = &mut observe;
+                Err(anyhow::anyhow!(
+                    "This binary was built without the 'tui' feature"
+                ))
+            }
+        } else {
+            run_bulk_from_config(cfg_path, &options, |event| {
+                observe(&event);
+                match event {
+                    BulkEvent::Written { index, output, .. } => {
+                        if args.print_path_only {
+                            println!("{}", output.display());
+                        } else if args.json_output {
+                            println!(
+                                "{}",
+                                serde_json::json!({ "index": index, "output": output })
+                            );
+                        } else {
+                            println!("Wrote {}", output.display());
+                        }
+                    }
+                    BulkEvent::Failed {
+                        index,
+                        output,
+                        error,
+                    } if args.json_output => {
+                        let report = FastTtsError::classify(error).report();
+                        println!(
+                            "{}",
+                            serde_json::json!({ "index": index, "output": output, "error": report })
+                        );
+                    }
+                    _ => {}
+                }
+            })
+            .await
+        };
+        if !args.no_history {
+            let (provider, chars) = planned;
+            record_history(
+                history::Job {
+                    provider,
+                    output: cfg_path,
+                    params_hash: None,
+                    seed: None,
+                    chars,
+                    elapsed: started.elapsed(),
+                    result: &result,
+                },
+                &argv,
+            );
         }
-        Provider::Hume | Provider::Listnr | Provider::Murf => {
+        return result;
+    }
+
+    if args.list_voices {
+        let (profile, provider) = profile_and_provider(&args)?;
+        if !provider_enabled(provider) {
             anyhow::bail!(
-                "provider {:?} not yet implemented. Please open an issue with API details.",
-                args.provider
+                "provider {provider} not enabled in this build. Rebuild with --features {} or all-cloud",
+                provider_feature_flag(provider)
             );
         }
+        let voices = fast_tts::providers::get(provider).list_voices();
+        let data = match &profile {
+            Some(profile) => fast_tts::with_profile(profile, voices).await?,
+            None => voices.await?,
+        };
+        return print_voices(&data, args.json_output);
     }
 
-    println!("Wrote {}", output.display());
-    if args.play {
-        if let Err(e) = play_audio(output) {
-            eprintln!("Warning: playback failed: {e}");
+    // With --ssml-file the only positional argument is the output
+    let (text, output) = match &args.ssml_file {
+        Some(path) => {
+            if args.text.is_some() && args.output.is_some() {
+                return Err(FastTtsError::InvalidInput(
+                    "--ssml-file replaces the text argument; pass only the output".into(),
+                )
+                .into());
+            }
+            let output = args
+                .output
+                .clone()
+                .or(args.text.as_deref().map(PathBuf::from));
+            (Some(fast_tts::read_ssml_file(path)?), output)
         }
+        None => (args.text.clone(), args.output.clone()),
+    };
+    let text = text.ok_or_else(|| {
+        FastTtsError::InvalidInput(
+            "text and output are required unless --list-voices is used".into(),
+        )
+    })?;
+    let output = output.as_deref().ok_or_else(|| {
+        FastTtsError::InvalidInput(
+            "text and output are required unless --list-voices is used".into(),
+        )
+    })?;
+
+    let (profile, provider) = profile_and_provider(&args)?;
+    let to_stdout = args.stream && output == std::path::Path::new("-");
+    let output = if to_stdout {
+        output.to_path_buf()
+    } else {
+        output_path(&args, output)
+    };
+    let output = output.as_path();
+    let ssml = args.ssml || args.ssml_file.is_some();
+    let split_at = match (&args.split_on_marker, args.split_on_break) {
+        (Some(marker), _) => Some(SplitAt::Marker(marker)),
+        (None, Some(min)) => Some(SplitAt::Break(min)),
+        (None, None) => None,
+    };
+    let sections = match split_at {
+        Some(at) => {
+            let sections = fast_tts::sections::split(&text, ssml, at)?;
+            let outputs = fast_tts::sections::output_names(output, &args.name_template, &sections)?;
+            sections.into_iter().zip(outputs).collect()
+        }
+        None => vec![(text, output.to_path_buf())],
+    };
+    // Every section is checked before the first one is paid for
+    let cancel = cancel_on_ctrl_c();
+    let requests = sections
+        .into_iter()
+        .map(|(text, path)| {
+            request_builder(&args, provider)
+                .text(text)
+                .ssml(ssml)
+                .output(if to_stdout { PathBuf::new() } else { path })
+                .cancellation(cancel.clone())
+                .build()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let finish = finish_options(&args)?;
+    for request in &requests {
+        let output = if to_stdout { output } else { request.output() };
+        let started = std::time::Instant::now();
+        let result =
+            synthesize_one(&args, request, profile.as_ref(), output, to_stdout, &finish).await;
+        if !args.no_history {
+            record_history(
+                history::Job::for_request(request, started.elapsed(), &result),
+                &argv,
+            );
+        }
+        result?;
     }
     Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct BulkDefaults {
-    language: Option<String>,
-    voice: Option<String>,
-    gender: Option<String>,
-    rate: Option<f32>,
-    pitch: Option<f32>,
-    sample_rate: Option<i32>,
-    encoding: Option<String>,
-    volume_gain_db: Option<f32>,
-    effects_profile_id: Option<Vec<String>>,
-    ssml: Option<bool>,
-    output_dir: Option<String>,
+/// `output` under `--output-dir`, with the extension fixed up when asked to
+/// (always in `--output-dir` mode, where a bare name is expected).
+fn output_path(args: &Cli, output: &std::path::Path) -> PathBuf {
+    match &args.output_dir {
+        Some(dir) => fast_tts::output_with_extension(&dir.join(output), args.encoding),
+        None if args.auto_extension => fast_tts::output_with_extension(output, args.encoding),
+        None => output.to_path_buf(),
+    }
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct BulkItem {
-    text: String,
-    output: Option<String>,
-    language: Option<String>,
-    voice: Option<String>,
-    gender: Option<String>,
-    rate: Option<f32>,
-    pitch: Option<f32>,
-    sample_rate: Option<i32>,
-    encoding: Option<String>,
-    volume_gain_db: Option<f32>,
-    effects_profile_id: Option<Vec<String>>,
-    ssml: Option<bool>,
+/// The `--profile` to apply, if any, and the provider it selects.
+fn profile_and_provider(args: &Cli) -> Result<(Option<fast_tts::Profile>, Provider)> {
+    let profile = args.profile.as_deref().map(load_profile).transpose()?;
+    let provider = match &profile {
+        Some(profile) => profile.provider_or(args.provider)?,
+        None => args.provider,
+    };
+    Ok((profile, provider))
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct BulkConfig {
-    defaults: Option<BulkDefaults>,
-    items: Vec<BulkItem>,
+/// A builder with the voice and audio options from the command line; the
+/// caller adds text and output.
+fn request_builder(args: &Cli, provider: Provider) -> fast_tts::SynthesisRequestBuilder {
+    SynthesisRequest::builder()
+        .provider(provider)
+        .language(args.language.as_deref().unwrap_or_default())
+        .voice(args.voice.as_deref())
+        .gender(args.gender)
+        .rate(args.rate)
+        .pitch(args.pitch)
+        .sample_rate(args.sample_rate)
+        .encoding(args.encoding)
+        .opus(OpusSettings {
+            bitrate: args.bitrate,
+            vbr: args.vbr,
+            application: args.application,
+        })
+        .volume_gain_db(args.volume_gain_db)
+        .effects_profile_id(args.effects_profile_id.clone())
+        .ssml(args.ssml)
+        .voice_markup(args.voice_markup)
+        .dialogue(args.dialogue)
+        .seed(args.seed)
+        .crossfade_ms(args.crossfade_ms)
+        .tracks(args.tracks)
+        .segment_offsets(args.segment_offsets)
+        .custom_voice_model(args.custom_voice_model.as_deref())
+        .model(args.model.as_deref())
+        .timeout_ms(args.timeout_ms)
+        .retries(args.retries)
 }
 
-async fn run_bulk_from_config(
-    path: &PathBuf,
-    timeout_ms: u64,
-    retries: usize,
-    play: bool,
+async fn synthesize_one(
+    args: &Cli,
+    request: &SynthesisRequest,
+    profile: Option<&fast_tts::Profile>,
+    output: &std::path::Path,
+    to_stdout: bool,
+    finish: &Finish,
 ) -> Result<()> {
-    if !provider_enabled(Provider::Google) {
-        anyhow::bail!(
-            "Bulk synthesis requires Google provider. Rebuild with --features provider-google or all-providers"
-        );
+    if let Some(target) = args.target_duration {
+        let fit = fast_tts::synthesize_to_duration(request, target, args.duration_tolerance);
+        let fit = match profile {
+            Some(profile) => fast_tts::with_profile(profile, fit).await?,
+            None => fit.await?,
+        };
+        finish_output(finish, output)?;
+        let bytes = std::fs::metadata(output)
+            .map(|m| m.len())
+            .unwrap_or_default();
+        report_written(args, request, output, bytes, Some(&fit));
+        if args.play
+            && let Err(e) = play_audio(output)
+        {
+            eprintln!("Warning: playback failed: {e}");
+        }
+        return Ok(());
     }
-    let data = fs::read_to_string(path)
-        .with_context(|| format!("failed to read config: {}", path.display()))?;
-    let is_yaml = path
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|e| matches!(e.to_lowercase().as_str(), "yml" | "yaml"))
-        .unwrap_or(false);
-
-    let cfg: BulkConfig = if is_yaml {
-        serde_yaml::from_str(&data)?
-    } else {
-        serde_json::from_str(&data)?
-    };
-
-    let defaults = cfg.defaults.unwrap_or(BulkDefaults {
-        language: Some("en-US".to_string()),
-        voice: None,
-        gender: None,
-        rate: Some(1.0),
-        pitch: Some(0.0),
-        sample_rate: None,
-        encoding: Some("LINEAR16".to_string()),
-        volume_gain_db: Some(0.0),
-        effects_profile_id: Some(vec![]),
-        ssml: Some(false),
-        output_dir: None,
-    });
-
-    for (idx, item) in cfg.items.iter().enumerate() {
-        let language = item
-            .language
-            .as_ref()
-            .or(defaults.language.as_ref())
-            .cloned()
-            .unwrap_or_else(|| "en-US".into());
-        let voice = item.voice.as_ref().or(defaults.voice.as_ref()).cloned();
-        let gender = item.gender.as_ref().or(defaults.gender.as_ref()).map(|g| {
-            match g.to_uppercase().as_str() {
-                "MALE" => Gender::Male,
-                "FEMALE" => Gender::Female,
-                _ => Gender::Neutral,
+    #[cfg(all(unix, feature = "serve"))]
+    if use_daemon(args)
+        && let Some(stream) =
+            fast_tts::serve::daemon::synthesize_stream(request, args.profile.as_deref()).await?
+    {
+        let live_play = args.play && args.stream;
+        let mut bytes =
+            fast_tts::write_stream(stream, (!to_stdout).then_some(output), live_play).await?;
+        if !to_stdout {
+            if finish.bed.is_some() || finish.cover.is_some() {
+                finish_output(finish, output)?;
+                bytes = std::fs::metadata(output).map_or(bytes, |m| m.len());
             }
-        });
-        let rate = item.rate.or(defaults.rate).unwrap_or(1.0);
-        let pitch = item.pitch.or(defaults.pitch).unwrap_or(0.0);
-        let sample_rate = item.sample_rate.or(defaults.sample_rate);
-        let encoding = item
-            .encoding
-            .as_ref()
-            .or(defaults.encoding.as_ref())
-            .cloned()
-            .unwrap_or_else(|| "LINEAR16".into());
-        let volume_gain_db = item
-            .volume_gain_db
-            .or(defaults.volume_gain_db)
-            .unwrap_or(0.0);
-        let effects_profile_id: Vec<String> = item
-            .effects_profile_id
-            .clone()
-            .or(defaults.effects_profile_id.clone())
-            .unwrap_or_default();
-        let is_ssml = item.ssml.or(defaults.ssml).unwrap_or(false);
-
-        // Determine output path
-        let output = if let Some(o) = &item.output {
-            PathBuf::from(o)
-        } else if let Some(dir) = &defaults.output_dir {
-            let ext = match encoding.to_uppercase().as_str() {
-                "LINEAR16" | "MULAW" | "ALAW" => "wav",
-                "MP3" => "mp3",
-                "OGG_OPUS" => "ogg",
-                _ => "bin",
-            };
-            PathBuf::from(dir).join(format!("item_{}.{}", idx + 1, ext))
-        } else {
-            let ext = match encoding.to_uppercase().as_str() {
-                "LINEAR16" | "MULAW" | "ALAW" => "wav",
-                "MP3" => "mp3",
-                "OGG_OPUS" => "ogg",
-                _ => "bin",
-            };
-            PathBuf::from(format!("item_{}.{}", idx + 1, ext))
-        };
-
-        validate_output_extension(&output, parse_encoding_from_str(&encoding)?)?;
-
-        // For now, bulk uses Google flow; extend with per-provider if needed
-        synthesize_to_wav(
-            &item.text,
-            &output,
-            &language,
-            voice.as_deref(),
-            gender,
-            rate,
-            pitch,
-            sample_rate,
-            parse_encoding_from_str(&encoding)?,
-            volume_gain_db,
-            &effects_profile_id
-                .iter()
-                .map(|s| s.as_str())
-                .collect::<Vec<_>>(),
-            is_ssml,
-            timeout_ms,
-            retries,
-        )
-        .await?;
-
-        println!("Wrote {}", output.display());
-        if play {
-            if let Err(e) = play_audio(&output) {
-                eprintln!("Warning: playback failed for {}: {e}", output.display());
+            report_written(args, request, output, bytes, None);
+        }
+        if args.play
+            && !live_play
+            && let Err(e) = play_audio(output)
+        {
+            eprintln!("Warning: playback failed: {e}");
+        }
+        return Ok(());
+    }
+    if args.stream {
+        let stream = match profile {
+            Some(profile) => {
+                fast_tts::with_profile(profile, fast_tts::synthesize_stream(request)).await?
             }
+            None => fast_tts::synthesize_stream(request).await?,
+        };
+        let bytes =
+            fast_tts::write_stream(stream, (!to_stdout).then_some(output), args.play).await?;
+        if !to_stdout {
+            report_written(args, request, output, bytes, None);
         }
+        return Ok(());
+    }
+    match profile {
+        Some(profile) => fast_tts::with_profile(profile, fast_tts::synthesize(request)).await?,
+        None => fast_tts::synthesize(request).await?,
+    }
+    finish_output(finish, output)?;
+
+    let bytes = std::fs::metadata(output)
+        .map(|m| m.len())
+        .unwrap_or_default();
+    report_written(args, request, output, bytes, None);
+    if args.play
+        && let Err(e) = play_audio(output)
+    {
+        eprintln!("Warning: playback failed: {e}");
     }
-
     Ok(())
 }
 
-fn play_audio(path: &Path) -> Result<()> {
-    // Best-effort cross-platform playback using system tools
-    let path_str = path
-        .to_str()
-        .ok_or_else(|| anyhow::anyhow!("non-UTF8 path not supported for playback"))?;
+/// What happens to a written file after synthesis: the --bg-music mix and
+/// the --cover tag.
+#[derive(Default)]
+struct Finish {
+    bed: Option<(DecodedAudio, fast_tts::audio::BedMix)>,
+    cover: Option<Vec<u8>>,
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        // Prefer afplay (CLI player); fall back to opening default app
-        if Command::new("afplay").arg(path_str).status().is_ok() {
-            return Ok(());
+/// The --bg-music bed and --cover image, checked before any synthesis is
+/// paid for.
+fn finish_options(args: &Cli) -> Result<Finish> {
+    let mut finish = Finish::default();
+    if let Some(path) = &args.bg_music {
+        if args.encoding != AudioEncoding::Linear16 {
+            return Err(FastTtsError::InvalidInput(format!(
+                "--bg-music writes 16-bit WAV; use --encoding LINEAR16, not {}",
+                args.encoding.api_str()
+            ))
+            .into());
         }
-        Command::new("open")
-            .arg(path_str)
-            .status()
-            .map(|_| ())
-            .context("failed to open audio with 'open'")?
+        if args.duck_db > 0.0 {
+            return Err(FastTtsError::InvalidInput(format!(
+                "--duck lowers the music while someone speaks; use a negative level such as -{}dB",
+                args.duck_db
+            ))
+            .into());
+        }
+        let bed = fast_tts::audio::decode(path)?;
+        if bed.frames() == 0 {
+            return Err(
+                FastTtsError::InvalidInput(format!("{} has no audio", path.display())).into(),
+            );
+        }
+        let mix = fast_tts::audio::BedMix {
+            gain_db: args.bg_gain_db,
+            duck_db: args.duck_db,
+        };
+        finish.bed = Some((bed, mix));
     }
-
-    #[cfg(target_os = "linux")]
-    {
-        // Try ffplay/paplay/aplay, then fall back to xdg-open
-        let try_cmds: &[(&str, &[&str])] = &[
-            ("ffplay", &["-autoexit", "-nodisp"]),
-            ("paplay", &[]),
-            ("aplay", &[]),
-        ];
-        for (bin, args) in try_cmds {
-            if Command::new(bin).args(*args).arg(path_str).status().is_ok() {
-                return Ok(());
-            }
+    if let Some(path) = &args.cover {
+        if args.encoding != AudioEncoding::Mp3 {
+            return Err(FastTtsError::InvalidInput(format!(
+                "--cover is stored in an MP3's ID3 tag; use --encoding MP3, not {}",
+                args.encoding.api_str()
+            ))
+            .into());
         }
-        Command::new("xdg-open")
-            .arg(path_str)
-            .status()
-            .map(|_| ())
-            .context("failed to open audio with 'xdg-open'")?
+        let image =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        if fast_tts::audio::image_mime(&image).is_none() {
+            return Err(FastTtsError::InvalidInput(format!(
+                "{} is not a PNG or JPEG image",
+                path.display()
+            ))
+            .into());
+        }
+        finish.cover = Some(image);
     }
+    Ok(finish)
+}
 
-    #[cfg(target_os = "windows")]
-    {
-        // Use cmd to start default associated app
-        Command::new("cmd")
-            .args(["/C", "start", "", path_str])
-            .status()
-            .map(|_| ())
-            .context("failed to open audio with 'start'")?
+/// Mix the bed under the speech at `output` and tag it with the cover.
+fn finish_output(finish: &Finish, output: &std::path::Path) -> Result<()> {
+    let write = |audio: Vec<u8>| {
+        std::fs::write(output, audio)
+            .with_context(|| format!("failed to write {}", output.display()))
+    };
+    if let Some((bed, mix)) = &finish.bed {
+        let speech = fast_tts::audio::decode(output)?;
+        write(fast_tts::audio::mix_background(&speech, bed, mix).to_wav16())?;
+    }
+    if let Some(image) = &finish.cover {
+        let mp3 = std::fs::read(output)
+            .with_context(|| format!("failed to read {}", output.display()))?;
+        write(fast_tts::audio::embed_cover(&mp3, image)?)?;
     }
+    Ok(())
+}
+
+/// The daemon has its own HTTP setup and credentials, so any per-run
+/// override of those means synthesizing locally.
+#[cfg(all(unix, feature = "serve"))]
+fn use_daemon(args: &Cli) -> bool {
+    !args.no_daemon
+        && !args.voice_markup
+        && !args.tracks
+        && !args.segment_offsets
+        && args.crossfade_ms == 0
+        && args.bitrate.is_none()
+        && args.vbr.is_none()
+        && args.application.is_none()
+        && !fast_tts::offline()
+        && args.ca_cert.is_empty()
+        && !args.insecure
+        && args.client_cert.is_none()
+        && args.headers.is_empty()
+        && args.debug_http.is_none()
+        && args.record.is_none()
+        && args.replay.is_none()
+        && args.resolve.is_empty()
+        && args.api_key.is_none()
+        && !args.gcloud_auth
+}
 
-    #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+#[cfg(feature = "serve")]
+fn daemon_addr() -> Result<String> {
+    #[cfg(unix)]
     {
-        anyhow::bail!("playback not supported on this OS");
+        let path = fast_tts::serve::daemon::socket_path().ok_or_else(|| {
+            FastTtsError::InvalidInput(
+                "no runtime directory for the daemon socket; set FAST_TTS_SOCKET".into(),
+            )
+        })?;
+        Ok(format!("unix:{}", path.display()))
     }
+    #[cfg(not(unix))]
+    Err(FastTtsError::Unsupported("--daemon needs Unix domain sockets".into()).into())
+}
 
-    Ok(())
+/// History is best effort: failing to record never fails the job.
+fn record_history(job: history::Job<'_>, argv: &[String]) {
+    if let Err(e) = history::record(job, argv) {
+        eprintln!("Warning: could not record history: {e:#}");
+    }
 }
 
-// Provider parsing removed (Google only)
-fn base_url() -> String {
-    std::env::var("FAST_TTS_BASE_URL")
-        .unwrap_or_else(|_| "https://texttospeech.googleapis.com".to_string())
+fn run_verify(
+    a: &std::path::Path,
+    b: &std::path::Path,
+    tolerance: &fast_tts::audio::Tolerance,
+    json: bool,
+) -> Result<()> {
+    let comparison = fast_tts::audio::compare(
+        &fast_tts::audio::decode(a)?,
+        &fast_tts::audio::decode(b)?,
+        tolerance,
+    );
+    if json {
+        println!("{}", serde_json::to_string_pretty(&comparison)?);
+    } else {
+        let (x, y) = (&comparison.a, &comparison.b);
+        let row = |label: &str, x: String, y: String| println!("{label:<12}{x:<24}{y}");
+        row("", a.display().to_string(), b.display().to_string());
+        row(
+            "duration",
+            format!("{:.3}s", x.duration_ms / 1000.0),
+            format!("{:.3}s", y.duration_ms / 1000.0),
+        );
+        row(
+            "sample rate",
+            format!("{} Hz", x.sample_rate),
+            format!("{} Hz", y.sample_rate),
+        );
+        row("channels", x.channels.to_string(), y.channels.to_string());
+        row(
+            "loudness",
+            format!("{:.1} dBFS", x.loudness_dbfs),
+            format!("{:.1} dBFS", y.loudness_dbfs),
+        );
+        println!("{:<12}{:.3}", "similarity", comparison.similarity);
+    }
+    if !comparison.equivalent {
+        anyhow::bail!("renders differ: {}", comparison.differences.join("; "));
+    }
+    if !json {
+        println!("equivalent");
+    }
+    Ok(())
 }
 
-fn build_http_client_for_base(base: &str) -> Result<reqwest::Client> {
-    let mut builder = reqwest::Client::builder();
-    if base.contains("127.0.0.1") || base.contains("localhost") {
-        builder = builder.no_proxy();
+/// The result line on stdout: `Wrote <path>`, the bare path with
+/// `--print-path-only`, or with `--json` one object describing the file.
+/// With `fit`, the rate and length `--target-duration` settled on are reported
+/// too, as is every per-speaker track.
+fn report_written(
+    args: &Cli,
+    request: &SynthesisRequest,
+    output: &std::path::Path,
+    bytes: u64,
+    fit: Option<&fast_tts::DurationFit>,
+) {
+    let tracks = request.track_outputs();
+    let offsets = request.segment_offsets_output();
+    if args.print_path_only {
+        println!("{}", output.display());
+        for (_, track) in &tracks {
+            println!("{}", track.display());
+        }
+        if let Some(offsets) = &offsets {
+            println!("{}", offsets.display());
+        }
+    } else if args.json_output {
+        let mut report = serde_json::json!({
+            "output": output,
+            "provider": request.provider().to_string(),
+            "encoding": request.encoding().api_str(),
+            "bytes": bytes,
+        });
+        if let Some(seed) = request.seed() {
+            report["seed"] = seed.into();
+        }
+        if let Some(fit) = fit {
+            report["fit"] = serde_json::json!(fit);
+        }
+        if !tracks.is_empty() {
+            report["tracks"] = tracks
+                .iter()
+                .map(|(speaker, track)| (speaker.clone(), serde_json::json!(track)))
+                .collect::<serde_json::Map<_, _>>()
+                .into();
+        }
+        if let Some(offsets) = &offsets {
+            report["segmentOffsets"] = serde_json::json!(offsets);
+        }
+        println!("{report}");
+    } else {
+        match fit {
+            Some(fit) => println!(
+                "Wrote {} ({:.1}s at rate {:.2}, {} render{})",
+                output.display(),
+                fit.duration.as_secs_f32(),
+                fit.rate,
+                fit.attempts,
+                if fit.attempts == 1 { "" } else { "s" }
+            ),
+            None => println!("Wrote {}", output.display()),
+        }
+        for (speaker, track) in &tracks {
+            println!("Wrote {} ({speaker})", track.display());
+        }
+        if let Some(offsets) = &offsets {
+            println!("Wrote {} (segment offsets)", offsets.display());
+        }
     }
-    Ok(builder.build()?)
 }
 
-async fn list_voices(json_output: bool) -> Result<()> {
-    let token = fetch_access_token().await?;
-    let base = base_url();
-    let client = build_http_client_for_base(&base)?;
-    let url = format!("{base}/v1/voices");
-    let mut headers = HeaderMap::new();
-    headers.insert(AUTHORIZATION, format!("Bearer {token}").parse()?);
+/// Character count above which a bulk run needs confirming; `None` with --yes.
+fn confirm_above_chars(args: &Cli) -> Result<Option<usize>> {
+    if args.yes {
+        return Ok(None);
+    }
+    let limit = match args.confirm_above_chars {
+        Some(limit) => limit,
+        None => match std::env::var("FAST_TTS_CONFIRM_ABOVE_CHARS") {
+            Ok(value) => value.trim().parse().map_err(|_| {
+                FastTtsError::InvalidInput(format!(
+                    "FAST_TTS_CONFIRM_ABOVE_CHARS must be a character count, got {value:?}"
+                ))
+            })?,
+            Err(_) => 100_000,
+        },
+    };
+    Ok(Some(limit))
+}
 
-    let resp = client
-        .get(url)
-        .headers(headers)
-        .send()
-        .await?
-        .error_for_status()?;
+/// Show the estimate for a large bulk run and ask whether to start it; with
+/// no terminal to ask on, the answer is no.
+fn confirm_bulk_run(provider: Provider, chars: usize) -> bool {
+    use std::io::{IsTerminal as _, Write as _};
+    let cost = fast_tts::providers::get(provider)
+        .capabilities()
+        .usd_per_million_chars
+        .map_or(String::new(), |usd| {
+            format!(" (about ${:.2})", usd * chars as f64 / 1e6)
+        });
+    eprintln!("This run will synthesize {chars} characters with {provider}{cost}.");
+    if !std::io::stdin().is_terminal() {
+        return false;
+    }
+    eprint!("Continue? [y/N] ");
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok()
+        && matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
 
-    let data: ListVoicesResponse = resp.json().await?;
+/// Ctrl+C cancels the returned token so partial output gets removed; a second
+/// Ctrl+C exits immediately.
+fn cancel_on_ctrl_c() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let token = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            token.cancel();
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
+    cancel
+}
 
+fn print_voices(data: &fast_tts::ListVoicesResponse, json_output: bool) -> Result<()> {
     if json_output {
         println!("{}", serde_json::to_string_pretty(&data)?);
     } else {
@@ -918,8 +1613,21 @@ async fn list_voices(json_output: bool) -> Result<()> {
                 .natural_sample_rate_hertz
                 .map(|r| r.to_string())
                 .unwrap_or_else(|| "-".into());
+            let mut extra = String::new();
+            if let Some(voice_type) = &v.voice_type {
+                extra.push_str(&format!("  {voice_type}"));
+            }
+            if !v.styles.is_empty() {
+                extra.push_str(&format!("  styles: {}", v.styles.join(",")));
+            }
+            if !v.roles.is_empty() {
+                extra.push_str(&format!("  roles: {}", v.roles.join(",")));
+            }
+            if !v.engines.is_empty() {
+                extra.push_str(&format!("  engines: {}", v.engines.join(",")));
+            }
             println!(
-                "{:<28} {:<7} {:>6} Hz  [{}]",
+                "{:<28} {:<7} {:>6} Hz  [{}]{extra}",
                 v.name, v.ssml_gender, rate, langs
             );
         }
@@ -927,594 +1635,228 @@ async fn list_voices(json_output: bool) -> Result<()> {
     Ok(())
 }
 
-fn validate_output_extension(output: &Path, encoding: AudioEncoding) -> Result<()> {
-    let want_ext = encoding.file_extension();
-    match output
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|s| s.to_lowercase())
-    {
-        Some(ref ext) if ext == want_ext => Ok(()),
-        Some(ext) => anyhow::bail!(
-            "output extension .{} does not match encoding {} (expected .{})",
-            ext,
-            encoding.api_str(),
-            want_ext
-        ),
-        None => anyhow::bail!(
-            "output must have .{} extension for encoding {}",
-            want_ext,
-            encoding.api_str()
-        ),
-    }
-}
-
-async fn synthesize_openai(
-    text: &str,
-    output: &Path,
-    voice: Option<&str>,
-    encoding: AudioEncoding,
-) -> Result<()> {
-    let api_key = std::env::var("OPENAI_API_KEY")
-        .context("OPENAI_API_KEY is required for provider openai")?;
-    let model = std::env::var("OPENAI_TTS_MODEL").unwrap_or_else(|_| "gpt-4o-mini-tts".to_string());
-    let voice_name = voice.unwrap_or("alloy");
-    let out_format = match encoding {
-        AudioEncoding::Mp3 => "mp3",
-        AudioEncoding::OggOpus => "opus",
-        _ => "wav",
-    };
-    let client = reqwest::Client::new();
-    let url = "https://api.openai.com/v1/audio/speech";
-    let resp = client
-        .post(url)
-        .bearer_auth(api_key)
-        .json(&serde_json::json!({
-            "model": model,
-            "voice": voice_name,
-            "input": text,
-            "format": out_format
-        }))
-        .send()
-        .await?
-        .error_for_status()?;
-    let bytes = resp.bytes().await?;
-    if let Some(parent) = output.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent)?;
+/// Print the encodings (with file extension) and sample rates from each
+/// provider's capabilities.
+fn print_encodings(provider: Option<Provider>, json_output: bool) -> Result<()> {
+    let mut report = Vec::new();
+    for p in fast_tts::selected_providers(provider) {
+        let caps = p.capabilities();
+        if !caps.enabled {
+            return Err(FastTtsError::Unsupported(format!(
+                "provider {p} is not enabled in this build (--features {})",
+                provider_feature_flag(p)
+            ))
+            .into());
         }
-    }
-    fs::write(output, &bytes)?;
-    Ok(())
-}
-
-async fn synthesize_azure(
-    text: &str,
-    output: &Path,
-    language: &str,
-    voice: Option<&str>,
-    encoding: AudioEncoding,
-    sample_rate: Option<i32>,
-) -> Result<()> {
-    let key = std::env::var("AZURE_SPEECH_KEY")
-        .context("AZURE_SPEECH_KEY is required for provider azure")?;
-    let region = std::env::var("AZURE_SPEECH_REGION")
-        .context("AZURE_SPEECH_REGION is required for provider azure")?;
-    let voice_name = voice.unwrap_or(match language {
-        // sensible defaults by locale
-        l if l.starts_with("en-US") => "en-US-JennyNeural",
-        l if l.starts_with("en-GB") => "en-GB-LibbyNeural",
-        _ => "en-US-JennyNeural",
-    });
-    let format = match (encoding, sample_rate) {
-        (AudioEncoding::Mp3, Some(_)) => "audio-48khz-192kbitrate-mono-mp3".to_string(),
-        (AudioEncoding::Mp3, None) => "audio-24khz-160kbitrate-mono-mp3".to_string(),
-        (AudioEncoding::OggOpus, _) => "ogg-48khz-16bit-mono-opus".to_string(),
-        (AudioEncoding::Linear16, Some(sr)) if sr >= 48000 => {
-            "riff-48khz-16bit-mono-pcm".to_string()
+        let encodings: Vec<AudioEncoding> = caps
+            .encodings
+            .iter()
+            .filter_map(|e| fast_tts::parse_encoding_from_str(e).ok())
+            .collect();
+        if json_output {
+            report.push(serde_json::json!({
+                "provider": caps.provider,
+                "encodings": encodings
+                    .iter()
+                    .map(|e| serde_json::json!({
+                        "encoding": e.api_str(),
+                        "extension": e.file_extension(),
+                        "sampleRates": caps.sample_rates,
+                    }))
+                    .collect::<Vec<_>>(),
+            }));
+            continue;
         }
-        (AudioEncoding::Linear16, _) => "riff-24khz-16bit-mono-pcm".to_string(),
-        (AudioEncoding::Mulaw, _) => "mulaw-8khz-8bit-mono".to_string(),
-        (AudioEncoding::Alaw, _) => "alaw-8khz-8bit-mono".to_string(),
-    };
-    let ssml = format!(
-        "<speak version=\"1.0\" xml:lang=\"{lang}\"><voice xml:lang=\"{lang}\" name=\"{voice}\">{text}</voice></speak>",
-        lang = language,
-        voice = voice_name,
-        text = htmlescape::encode_minimal(text)
-    );
-    let url = format!("https://{region}.tts.speech.microsoft.com/cognitiveservices/v1");
-    let client = reqwest::Client::new();
-    let resp = client
-        .post(&url)
-        .header("Ocp-Apim-Subscription-Key", key)
-        .header("X-Microsoft-OutputFormat", format)
-        .header(CONTENT_TYPE, "application/ssml+xml")
-        .header("User-Agent", "fast-tts-cli")
-        .body(ssml)
-        .send()
-        .await?
-        .error_for_status()?;
-    let bytes = resp.bytes().await?;
-    if let Some(parent) = output.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent)?;
+        println!("{}", caps.provider);
+        if encodings.is_empty() {
+            println!("  (no encodings: {})", caps.voice_selection);
+            continue;
         }
+        let rates = if caps.sample_rates.is_empty() {
+            "provider default".to_string()
+        } else {
+            caps.sample_rates
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        for encoding in encodings {
+            println!(
+                "  {:<9} .{:<4} --sample-rate {rates}",
+                encoding.api_str(),
+                encoding.file_extension()
+            );
+        }
+    }
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&report)?);
     }
-    fs::write(output, &bytes)?;
     Ok(())
 }
 
-async fn synthesize_elevenlabs(
-    text: &str,
-    output: &Path,
-    voice: Option<&str>,
-    encoding: AudioEncoding,
-    model_id: Option<&str>,
-) -> Result<()> {
-    let api_key = std::env::var("ELEVENLABS_API_KEY")
-        .context("ELEVENLABS_API_KEY is required for provider elevenlabs")?;
-    let voice_id = voice.unwrap_or("Rachel");
-    let model = model_id.unwrap_or("eleven_multilingual_v2");
-    let format = match encoding {
-        AudioEncoding::Mp3 => "mp3",
-        AudioEncoding::OggOpus => "ogg",
-        _ => "wav",
-    };
-    let url = format!("https://api.elevenlabs.io/v1/text-to-speech/{voice_id}");
-    let client = reqwest::Client::new();
-    let resp = client
-        .post(&url)
-        .header("xi-api-key", api_key)
-        .header(CONTENT_TYPE, "application/json")
-        .json(&serde_json::json!({
-            "text": text,
-            "model_id": model,
-            "voice_settings": {"stability": 0.5, "similarity_boost": 0.5},
-            "output_format": format
-        }))
-        .send()
-        .await?
-        .error_for_status()?;
-    let bytes = resp.bytes().await?;
-    if let Some(parent) = output.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent)?;
+fn run_doctor(json_output: bool) -> Result<()> {
+    let checks = fast_tts::doctor::run();
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+    } else {
+        for check in &checks {
+            println!("{:<20} {:<4} {}", check.name, check.status, check.detail);
         }
     }
-    fs::write(output, &bytes)?;
-    Ok(())
+    fast_tts::doctor::outcome(&checks)
 }
 
-async fn synthesize_deepgram(
-    text: &str,
-    output: &Path,
-    voice: Option<&str>,
-    encoding: AudioEncoding,
-    model_id: Option<&str>,
-) -> Result<()> {
-    let api_key = std::env::var("DEEPGRAM_API_KEY")
-        .context("DEEPGRAM_API_KEY is required for provider deepgram")?;
-    let model = model_id.unwrap_or("aura-asteria-en");
-    let voice_name = voice.unwrap_or("aura-asteria-en");
-    let format = match encoding {
-        AudioEncoding::Mp3 => "mp3",
-        AudioEncoding::OggOpus => "opus",
-        _ => "wav",
+fn run_cache(command: &CacheCommand, json_output: bool) -> Result<()> {
+    let report = match command {
+        CacheCommand::Info => fast_tts::cache::info()?,
+        CacheCommand::Clear => fast_tts::cache::clear()?,
     };
-    let url = "https://api.deepgram.com/v1/speak";
-    let client = reqwest::Client::new();
-    let resp = client
-        .post(url)
-        .header("Authorization", format!("Token {api_key}"))
-        .query(&[("model", model), ("voice", voice_name), ("format", format)])
-        .body(text.to_string())
-        .send()
-        .await?
-        .error_for_status()?;
-    let bytes = resp.bytes().await?;
-    if let Some(parent) = output.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent)?;
-        }
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
     }
-    fs::write(output, &bytes)?;
+    let verb = match command {
+        CacheCommand::Info => "holds",
+        CacheCommand::Clear => "cleared",
+    };
+    println!(
+        "{} {verb} {} files ({} bytes)",
+        report.dir.display(),
+        report.files,
+        report.bytes
+    );
     Ok(())
 }
 
-async fn synthesize_gemini(
-    text: &str,
-    output: &Path,
-    voice: Option<&str>,
-    encoding: AudioEncoding,
-) -> Result<()> {
-    let api_key = std::env::var("GEMINI_API_KEY")
-        .context("GEMINI_API_KEY is required for provider gemini")?;
-    // Allow overriding the model; default to a fast, generally-available model
-    let model =
-        std::env::var("GEMINI_TTS_MODEL").unwrap_or_else(|_| "gemini-1.5-flash-latest".to_string());
-
-    let format = match encoding {
-        AudioEncoding::Mp3 => "mp3",
-        AudioEncoding::OggOpus => "ogg",
-        AudioEncoding::Linear16 => "wav",
-        AudioEncoding::Mulaw | AudioEncoding::Alaw => {
-            anyhow::bail!(
-                "Gemini speech does not support {} encoding; use MP3/OGG_OPUS/LINEAR16",
-                encoding.api_str()
-            )
+async fn run_auth_check(provider: Option<Provider>, json_output: bool) -> Result<()> {
+    let results = fast_tts::status::check_auth(provider).await;
+    if json_output {
+        let report: Vec<serde_json::Value> = results
+            .iter()
+            .map(|(p, status)| {
+                serde_json::json!({
+                    "provider": p.to_string(),
+                    "status": status.label(),
+                    "detail": status.detail(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for (p, status) in &results {
+            println!(
+                "{:<11} {:<8} {}",
+                p.to_string(),
+                status.label(),
+                status.detail()
+            );
         }
-    };
-
-    #[derive(serde::Serialize)]
-    struct AudioPart<'a> {
-        voice: Option<&'a str>,
-        format: &'a str,
-    }
-
-    let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/{model}:generateContent?key={api_key}"
-    );
-
-    // Build request payload using Gemini generateContent structure
-    let request_body = serde_json::json!({
-        "contents": [
-            {
-                "role": "user",
-                "parts": [
-                    { "text": text },
-                    { "audio": AudioPart { voice, format } }
-                ]
-            }
-        ]
-    });
-
-    let client = reqwest::Client::new();
-    let resp = client
-        .post(&url)
-        .header(CONTENT_TYPE, "application/json")
-        .json(&request_body)
-        .send()
-        .await?
-        .error_for_status()?;
-
-    #[derive(Deserialize)]
-    struct GeminiAudio {
-        data: String,
-        #[allow(dead_code)]
-        #[serde(rename = "mimeType")]
-        mime_type: Option<String>,
-    }
-    #[derive(Deserialize)]
-    struct GeminiPartResp {
-        #[serde(default)]
-        audio: Option<GeminiAudio>,
-        #[allow(dead_code)]
-        #[serde(default)]
-        text: Option<String>,
-    }
-    #[derive(Deserialize)]
-    struct GeminiContentResp {
-        parts: Vec<GeminiPartResp>,
-    }
-    #[derive(Deserialize)]
-    struct GeminiCandidate {
-        content: GeminiContentResp,
     }
-    #[derive(Deserialize)]
-    struct GeminiResponse {
-        candidates: Vec<GeminiCandidate>,
-    }
-
-    let gr: GeminiResponse = resp.json().await?;
-
-    // Find first audio part with data
-    let audio_b64 = gr
-        .candidates
-        .into_iter()
-        .flat_map(|c| c.content.parts)
-        .find_map(|p| p.audio.map(|a| a.data))
-        .context("Gemini response did not include audio data")?;
-
-    let bytes = base64::engine::general_purpose::STANDARD
-        .decode(audio_b64)
-        .context("failed decoding audio data from Gemini response")?;
+    fast_tts::status::auth_outcome(&results, provider.is_some())
+}
 
-    if let Some(parent) = output.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent)?;
+async fn run_status(provider: Option<Provider>, timeout_ms: u64, json_output: bool) -> Result<()> {
+    let results = fast_tts::status::check(provider, timeout_ms, &cancel_on_ctrl_c()).await?;
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    } else {
+        for row in &results {
+            let latency = row.latency_ms.map_or("-".into(), |ms| format!("{ms}ms"));
+            println!(
+                "{:<11} {:<12} {:>8}  {}",
+                row.provider,
+                row.status,
+                latency,
+                row.detail.lines().next().unwrap_or_default()
+            );
         }
     }
-    fs::write(output, bytes)?;
-    Ok(())
+    fast_tts::status::outcome(&results)
 }
 
-#[cfg(feature = "polly")]
-async fn synthesize_polly(
-    text: &str,
-    output: &Path,
-    voice: Option<&str>,
+async fn run_bench(
+    providers: &[Provider],
+    text_path: &std::path::Path,
+    runs: usize,
     encoding: AudioEncoding,
+    json_output: bool,
 ) -> Result<()> {
-    use aws_sdk_polly::types::{Engine, OutputFormat, VoiceId};
-    let config = aws_config::load_from_env().await;
-    let client = aws_sdk_polly::Client::new(&config);
-    let voice_id = voice.unwrap_or("Joanna");
-    let output_format = match encoding {
-        AudioEncoding::Mp3 => OutputFormat::Mp3,
-        AudioEncoding::OggOpus => OutputFormat::OggVorbis,
-        _ => OutputFormat::Pcm,
-    };
-    let resp = client
-        .synthesize_speech()
-        .set_text(Some(text.to_string()))
-        .set_voice_id(Some(VoiceId::from(voice_id)))
-        .set_output_format(Some(output_format))
-        .set_engine(Some(Engine::Neural))
-        .send()
-        .await?;
-    let data = resp
-        .audio_stream
-        .unwrap()
-        .into_bytes()
-        .collect::<Result<Vec<_>, _>>()?;
-    if let Some(parent) = output.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent)?;
+    let text = std::fs::read_to_string(text_path).map_err(|e| {
+        FastTtsError::InvalidInput(format!("cannot read {}: {e}", text_path.display()))
+    })?;
+    let results =
+        fast_tts::bench::run(providers, &text, runs, encoding, &cancel_on_ctrl_c()).await?;
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    } else {
+        let ms = |v: Option<u64>| v.map_or("-".into(), |v| format!("{v}ms"));
+        println!(
+            "{:<11} {:>4} {:>6} {:>9} {:>9} {:>9} {:>9} {:>10}",
+            "provider",
+            "runs",
+            "errors",
+            "ttfb p50",
+            "ttfb p95",
+            "total p50",
+            "total p95",
+            "avg bytes"
+        );
+        for row in &results {
+            println!(
+                "{:<11} {:>4} {:>6} {:>9} {:>9} {:>9} {:>9} {:>10}",
+                row.provider,
+                row.runs,
+                row.errors,
+                ms(row.first_byte_ms.p50),
+                ms(row.first_byte_ms.p95),
+                ms(row.total_ms.p50),
+                ms(row.total_ms.p95),
+                row.avg_bytes.map_or("-".into(), |b| b.to_string()),
+            );
+        }
+        for row in &results {
+            if let Some(error) = &row.last_error {
+                eprintln!("{}: {error}", row.provider);
+            }
         }
     }
-    fs::write(output, data)?;
-    Ok(())
+    fast_tts::bench::outcome(&results)
 }
-#[allow(clippy::too_many_arguments)]
-async fn synthesize_to_wav(
+
+async fn run_compare(
     text: &str,
-    output: &Path,
-    language: &str,
-    voice: Option<&str>,
-    gender: Option<Gender>,
-    rate: f32,
-    pitch: f32,
-    sample_rate: Option<i32>,
+    providers: &[Provider],
+    voices: &[String],
+    out_dir: &std::path::Path,
     encoding: AudioEncoding,
-    volume_gain_db: f32,
-    effects_profile_id: &[&str],
-    is_ssml: bool,
-    _timeout_ms: u64,
-    _retries: usize,
+    json_output: bool,
 ) -> Result<()> {
-    if let Some(parent) = output.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent).with_context(|| {
-                format!("failed to create output directory: {}", parent.display())
-            })?;
-        }
-    }
-
-    let token = fetch_access_token().await?;
-    let base = base_url();
-    let client = build_http_client_for_base(&base)?;
-    let url = format!("{base}/v1/text:synthesize");
-
-    let gender_str = gender.map(|g| match g {
-        Gender::Neutral => "NEUTRAL",
-        Gender::Male => "MALE",
-        Gender::Female => "FEMALE",
-    });
-
-    let req_body = SynthesizeRequest {
-        input: if is_ssml {
-            SynthesisInput::Ssml { ssml: text }
-        } else {
-            SynthesisInput::Text { text }
-        },
-        voice: VoiceSelectionParams {
-            language_code: language,
-            name: voice,
-            ssml_gender: gender_str,
-        },
-        audio_config: AudioConfig {
-            audio_encoding: encoding.api_str(),
-            speaking_rate: rate,
-            pitch,
-            volume_gain_db,
-            sample_rate_hertz: sample_rate,
-            effects_profile_id: effects_profile_id.to_vec(),
-            enable_legacy_wav_header: false,
-        },
-    };
-
-    let mut headers = HeaderMap::new();
-    headers.insert(AUTHORIZATION, format!("Bearer {token}").parse()?);
-    headers.insert(CONTENT_TYPE, "application/json".parse()?);
-
-    let resp = client
-        .post(url)
-        .headers(headers)
-        .json(&req_body)
-        .send()
-        .await?
-        .error_for_status()?;
-
-    let data: SynthesizeResponse = resp.json().await?;
-    let audio = base64::engine::general_purpose::STANDARD.decode(data.audio_content)?;
-    fs::write(output, audio).with_context(|| format!("failed to write {}", output.display()))?;
-    Ok(())
-}
-
-async fn fetch_access_token() -> Result<String> {
-    if let Ok(token) = std::env::var("FAST_TTS_TOKEN") {
-        if !token.trim().is_empty() {
-            return Ok(token);
-        }
-    }
-    // Supports two common methods:
-    // 1) GOOGLE_APPLICATION_CREDENTIALS pointing at a service account JSON key
-    // 2) gcloud application-default credentials at well-known path
-    if let Ok(path) = std::env::var("GOOGLE_APPLICATION_CREDENTIALS") {
-        return fetch_token_from_service_account(PathBuf::from(path)).await;
-    }
-
-    if let Some(path) = default_adc_path() {
-        if path.exists() {
-            if let Ok(token) = fetch_token_from_adc(path).await {
-                return Ok(token);
+    let (index, results) = fast_tts::compare::run(
+        text,
+        providers,
+        voices,
+        out_dir,
+        encoding,
+        &cancel_on_ctrl_c(),
+        |result| {
+            if json_output {
+                return;
             }
-        }
-    }
-
-    anyhow::bail!(
-        "No Google credentials found. Set GOOGLE_APPLICATION_CREDENTIALS or run 'gcloud auth application-default login'"
-    );
-}
-
-#[derive(Deserialize)]
-struct ServiceAccountKey {
-    client_email: String,
-    private_key: String,
-    token_uri: Option<String>,
-}
-
-async fn fetch_token_from_service_account(path: PathBuf) -> Result<String> {
-    use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
-
-    let key_data = fs::read_to_string(&path)
-        .with_context(|| format!("failed to read service account key: {}", path.display()))?;
-    let key: ServiceAccountKey = serde_json::from_str(&key_data)?;
-
-    let scope = "https://www.googleapis.com/auth/cloud-platform";
-    let token_uri = key
-        .token_uri
-        .unwrap_or_else(|| "https://oauth2.googleapis.com/token".to_string());
-
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as usize;
-
-    #[derive(Serialize)]
-    struct Claims<'a> {
-        iss: &'a str,
-        scope: &'a str,
-        aud: &'a str,
-        exp: usize,
-        iat: usize,
-    }
-
-    let claims = Claims {
-        iss: &key.client_email,
-        scope,
-        aud: &token_uri,
-        exp: now + 3600,
-        iat: now,
-    };
-
-    let mut header = Header::new(Algorithm::RS256);
-    header.typ = Some("JWT".to_string());
-
-    let encoding_key = EncodingKey::from_rsa_pem(key.private_key.as_bytes())
-        .context("invalid RSA private key in service account")?;
-    let jwt = encode(&header, &claims, &encoding_key)?;
-
-    let client = reqwest::Client::new();
-    let resp = client
-        .post(&token_uri)
-        .form(&serde_json::json!({
-            "grant_type": "urn:ietf:params:oauth:grant-type:jwt-bearer",
-            "assertion": jwt,
-        }))
-        .send()
-        .await?
-        .error_for_status()?;
-    #[derive(Deserialize)]
-    struct TokenResp {
-        access_token: String,
-    }
-    let tr: TokenResp = resp.json().await?;
-    Ok(tr.access_token)
-}
-
-fn parse_encoding_from_str(s: &str) -> Result<AudioEncoding> {
-    match s.trim().to_uppercase().as_str() {
-        "LINEAR16" => Ok(AudioEncoding::Linear16),
-        "MP3" => Ok(AudioEncoding::Mp3),
-        "OGG_OPUS" => Ok(AudioEncoding::OggOpus),
-        "MULAW" => Ok(AudioEncoding::Mulaw),
-        "ALAW" => Ok(AudioEncoding::Alaw),
-        other => anyhow::bail!("unsupported encoding: {other}"),
-    }
-}
-
-async fn fetch_token_from_adc(path: PathBuf) -> Result<String> {
-    // Application Default Credentials created by gcloud have refresh_token, client_id, client_secret
-    let data = fs::read_to_string(&path)
-        .with_context(|| format!("failed to read ADC file: {}", path.display()))?;
-    #[derive(Deserialize)]
-    struct AdcFile {
-        client_id: String,
-        client_secret: String,
-        refresh_token: String,
-        #[allow(dead_code)]
-        r#type: String,
-    }
-    let adc: AdcFile = serde_json::from_str(&data)?;
-
-    let client = reqwest::Client::new();
-    let resp = client
-        .post("https://oauth2.googleapis.com/token")
-        .form(&serde_json::json!({
-            "grant_type": "refresh_token",
-            "client_id": adc.client_id,
-            "client_secret": adc.client_secret,
-            "refresh_token": adc.refresh_token,
-        }))
-        .send()
-        .await?
-        .error_for_status()?;
-    #[derive(Deserialize)]
-    struct TokenResp {
-        access_token: String,
-    }
-    let tr: TokenResp = resp.json().await?;
-    Ok(tr.access_token)
-}
-
-fn default_adc_path() -> Option<PathBuf> {
-    // macOS/Linux default location
-    if let Some(home) = dirs::home_dir() {
-        let p = home
-            .join(".config")
-            .join("gcloud")
-            .join("application_default_credentials.json");
-        return Some(p);
-    }
-    None
-}
-
-fn provider_enabled(p: Provider) -> bool {
-    match p {
-        Provider::Google => cfg!(feature = "provider-google"),
-        Provider::Openai => cfg!(feature = "provider-openai"),
-        Provider::Elevenlabs => cfg!(feature = "provider-elevenlabs"),
-        Provider::Deepgram => cfg!(feature = "provider-deepgram"),
-        Provider::Polly => cfg!(feature = "polly"),
-        Provider::Azure => cfg!(feature = "provider-azure"),
-        Provider::Gemini => cfg!(feature = "provider-gemini"),
-        Provider::Hume | Provider::Listnr | Provider::Murf => false,
-    }
-}
-
-fn provider_feature_flag(p: Provider) -> &'static str {
-    match p {
-        Provider::Google => "provider-google",
-        Provider::Openai => "provider-openai",
-        Provider::Elevenlabs => "provider-elevenlabs",
-        Provider::Deepgram => "provider-deepgram",
-        Provider::Polly => "polly",
-        Provider::Azure => "provider-azure",
-        Provider::Gemini => "provider-gemini",
-        Provider::Hume => "provider-hume",
-        Provider::Listnr => "provider-listnr",
-        Provider::Murf => "provider-murf",
+            match &result.error {
+                None => println!("Wrote {}", out_dir.join(&result.file).display()),
+                Some(e) => eprintln!("{} {}: {e}", result.provider, result.voice),
+            }
+        },
+    )
+    .await?;
+    if json_output {
+        println!(
+            "{}",
+            serde_json::json!({ "index": index, "results": results })
+        );
+    } else {
+        println!("Wrote {}", index.display());
     }
+    fast_tts::compare::outcome(&results)
 }
@@ -0,0 +1,298 @@
+//! Inline voice switching: `[voice:NAME]` switches the voice for the text
+//! that follows, `[/voice]` returns to the request's own voice. Each run of
+//! text is synthesized separately and the audio stitched into one file.
+//! Dialogue scripts and their per-speaker tracks live here too, as do the
+//! offset tables locating each part in the joined file.
+
+use crate::{SynthesisRequest, audio, providers};
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const OPEN: &str = "[voice:";
+const CLOSE: &str = "[/voice]";
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Segment {
+    pub voice: Option<String>,
+    pub text: String,
+}
+
+pub(crate) fn has_voice_markup(text: &str) -> bool {
+    text.contains(OPEN)
+}
+
+/// Split `text` at voice tags. Adjacent runs with the same voice are merged
+/// and runs with nothing to say are dropped.
+pub(crate) fn split(text: &str, default: Option<&str>) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut voice = default.map(str::to_string);
+    let mut push = |voice: &Option<String>, text: &str| {
+        if text.trim().is_empty() {
+            return;
+        }
+        match segments.last_mut() {
+            Some(last) if last.voice == *voice => last.text.push_str(text),
+            _ => segments.push(Segment {
+                voice: voice.clone(),
+                text: text.to_string(),
+            }),
+        }
+    };
+    let mut rest = text;
+    while let Some(at) = rest.find('[') {
+        let tag = &rest[at..];
+        if let Some(after) = tag.strip_prefix(OPEN)
+            && let Some(end) = after.find(']')
+        {
+            push(&voice, &rest[..at]);
+            let name = after[..end].trim();
+            voice = (!name.is_empty()).then(|| name.to_string());
+            rest = &after[end + 1..];
+        } else if let Some(after) = tag.strip_prefix(CLOSE) {
+            push(&voice, &rest[..at]);
+            voice = default.map(str::to_string);
+            rest = after;
+        } else {
+            push(&voice, &rest[..=at]);
+            rest = &rest[at + 1..];
+        }
+    }
+    push(&voice, rest);
+    for segment in &mut segments {
+        segment.text = segment.text.trim().to_string();
+    }
+    segments
+}
+
+/// Removes the per-segment files however synthesis ends.
+struct Parts(Vec<PathBuf>);
+
+impl Drop for Parts {
+    fn drop(&mut self) {
+        for part in &self.0 {
+            let _ = std::fs::remove_file(part);
+        }
+    }
+}
+
+/// Synthesize each voice segment of `request` and write the joined audio
+/// to its output.
+pub(crate) async fn synthesize_segments(request: &SynthesisRequest) -> Result<()> {
+    let segments = split(&request.text, request.voice.as_deref());
+    let provider = providers::get(request.provider);
+    let ext = request.encoding.file_extension();
+    let mut parts = Parts(Vec::new());
+    let mut audio = Vec::new();
+    let mut labels = Vec::new();
+    for (idx, segment) in segments.into_iter().enumerate() {
+        let part = request
+            .output
+            .with_extension(format!("part{}.{ext}", idx + 1));
+        parts.0.push(part.clone());
+        let mut part_request = request.clone();
+        part_request.text = segment.text;
+        part_request.voice = segment.voice;
+        part_request.output = part.clone();
+        provider
+            .synthesize(&part_request)
+            .await
+            .with_context(|| match &part_request.voice {
+                Some(voice) => format!("segment {} (voice {voice})", idx + 1),
+                None => format!("segment {}", idx + 1),
+            })?;
+        audio.push(std::fs::read(&part)?);
+        let label = part_request.voice.unwrap_or_else(|| "default".into());
+        labels.push((label, part_request.text));
+    }
+    let joined = audio::concat(&audio, request.encoding, request.crossfade_ms)?;
+    std::fs::write(&request.output, joined)
+        .with_context(|| format!("failed to write {}", request.output.display()))?;
+    write_offsets(request, &parts.0, labels)
+}
+
+/// Where one part of a joined render sits in it.
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PartOffset {
+    index: usize,
+    /// Speaker (dialogue) or voice (voice markup)
+    label: String,
+    text: String,
+    start_ms: u64,
+    end_ms: u64,
+}
+
+/// Write the [`segment_offsets`](crate::SynthesisRequestBuilder::segment_offsets)
+/// table for `request`, measuring each part file; a crossfade overlaps
+/// neighbouring parts.
+fn write_offsets(
+    request: &SynthesisRequest,
+    parts: &[PathBuf],
+    labels: Vec<(String, String)>,
+) -> Result<()> {
+    let Some(path) = request.segment_offsets_output() else {
+        return Ok(());
+    };
+    // Only 16-bit PCM is crossfaded when joined
+    let fade = match request.encoding {
+        crate::AudioEncoding::Linear16 => Duration::from_millis(request.crossfade_ms.into()),
+        _ => Duration::ZERO,
+    };
+    let mut offsets = Vec::with_capacity(parts.len());
+    let mut end = Duration::ZERO;
+    for (idx, (part, (label, text))) in parts.iter().zip(labels).enumerate() {
+        let length = audio::decode(part)?.duration();
+        let start = end - fade.min(end).min(length);
+        end = start + length;
+        offsets.push(PartOffset {
+            index: idx + 1,
+            label,
+            text,
+            start_ms: start.as_millis() as u64,
+            end_ms: end.as_millis() as u64,
+        });
+    }
+    let table = serde_json::json!({
+        "durationMs": end.as_millis() as u64,
+        "segments": offsets,
+    });
+    std::fs::write(&path, serde_json::to_string_pretty(&table)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// The requests for each turn (dialogue) or voice segment (voice markup) of
+/// a tracks render, each with the speaker or voice it belongs to.
+fn track_parts(request: &SynthesisRequest) -> Vec<(String, SynthesisRequest)> {
+    if !request.dialogue.is_empty() {
+        return request
+            .dialogue
+            .iter()
+            .map(|turn| {
+                let mut part = request.clone();
+                part.text = turn.text.clone();
+                part.dialogue = vec![turn.clone()];
+                (turn.speaker.clone(), part)
+            })
+            .collect();
+    }
+    split(&request.text, request.voice.as_deref())
+        .into_iter()
+        .map(|segment| {
+            let mut part = request.clone();
+            part.text = segment.text;
+            part.voice = segment.voice;
+            let label = part.voice.clone().unwrap_or_else(|| "default".into());
+            (label, part)
+        })
+        .collect()
+}
+
+/// The speakers (or voices) of a tracks render in order of appearance, with
+/// the file each one's track is written to.
+pub(crate) fn track_outputs(request: &SynthesisRequest) -> Vec<(String, PathBuf)> {
+    let mut tracks: Vec<(String, PathBuf)> = Vec::new();
+    for (label, _) in track_parts(request) {
+        if tracks.iter().all(|(seen, _)| *seen != label) {
+            let safe: String = label
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || matches!(c, '-' | '_') {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            let path = request
+                .output
+                .with_extension(format!("{safe}.{}", request.encoding.file_extension()));
+            tracks.push((label, path));
+        }
+    }
+    tracks
+}
+
+/// Synthesize each turn or voice segment of `request` on its own, write the
+/// joined audio to its output and one track per speaker next to it.
+pub(crate) async fn synthesize_tracks(request: &SynthesisRequest) -> Result<()> {
+    let provider = providers::get(request.provider);
+    let ext = request.encoding.file_extension();
+    let tracks = track_outputs(request);
+    let mut parts = Parts(Vec::new());
+    let mut audio = Vec::new();
+    let mut owners = Vec::new();
+    let mut labels = Vec::new();
+    for (idx, (label, mut part_request)) in track_parts(request).into_iter().enumerate() {
+        let part = request
+            .output
+            .with_extension(format!("part{}.{ext}", idx + 1));
+        parts.0.push(part.clone());
+        part_request.output = part.clone();
+        provider
+            .synthesize(&part_request)
+            .await
+            .with_context(|| format!("part {} ({label})", idx + 1))?;
+        audio.push(std::fs::read(&part)?);
+        owners.push(tracks.iter().position(|(l, _)| *l == label).unwrap_or(0));
+        labels.push((label, part_request.text));
+    }
+    let joined = audio::concat(&audio, request.encoding, 0)?;
+    std::fs::write(&request.output, joined)
+        .with_context(|| format!("failed to write {}", request.output.display()))?;
+    for (track, (_, path)) in audio::aligned_tracks(&audio, &owners, tracks.len())?
+        .into_iter()
+        .zip(&tracks)
+    {
+        std::fs::write(path, track)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    write_offsets(request, &parts.0, labels)
+}
+
+/// One turn of a dialogue script, sent as Google `multiSpeakerMarkup`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct DialogueTurn {
+    pub speaker: String,
+    pub text: String,
+}
+
+/// Parse a dialogue script: each `SPEAKER: text` line starts a turn, and
+/// lines without a speaker continue the previous one.
+pub(crate) fn parse_dialogue(script: &str) -> Result<Vec<DialogueTurn>, String> {
+    let mut turns: Vec<DialogueTurn> = Vec::new();
+    for (idx, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let speaker = line
+            .split_once(':')
+            .map(|(speaker, text)| (speaker.trim(), text.trim()))
+            .filter(|(speaker, _)| {
+                !speaker.is_empty() && speaker.len() <= 32 && !speaker.contains(char::is_whitespace)
+            });
+        match (speaker, turns.last_mut()) {
+            (Some((speaker, text)), _) => turns.push(DialogueTurn {
+                speaker: speaker.to_string(),
+                text: text.to_string(),
+            }),
+            (None, Some(last)) => {
+                if !last.text.is_empty() {
+                    last.text.push(' ');
+                }
+                last.text.push_str(line);
+            }
+            (None, None) => {
+                return Err(format!(
+                    "dialogue line {} has no speaker; start turns with `SPEAKER: text`",
+                    idx + 1
+                ));
+            }
+        }
+    }
+    if let Some(turn) = turns.iter().find(|t| t.text.is_empty()) {
+        return Err(format!("speaker {} has a turn with no text", turn.speaker));
+    }
+    Ok(turns)
+}
@@ -0,0 +1,1234 @@
+//! `--mcp`: the synthesize, speak and subtitle tools, voice catalogs and
+//! prompts over MCP, on stdio or HTTP (`/rpc`, and SSE at `/sse`).
+
+mod protocol;
+
+use super::*;
+use anyhow::Result;
+use axum::{
+    self, Json, Router as AxumRouter,
+    extract::{Query, State},
+    http::StatusCode,
+    middleware,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::{get, post},
+};
+use futures::channel::mpsc::UnboundedSender;
+use protocol::{
+    Content, JsonRpcRequest, JsonRpcResponse, Prompt, PromptArgument, PromptError,
+    REQUEST_CANCELLED, Resource, ResourceContents, ResourceError, Router, Tool, ToolError,
+};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio as mcp_tokio;
+use tokio_rustls::rustls;
+use tokio_stream::StreamExt as _;
+
+/// Caps concurrent synthesize calls across all transports and sessions.
+static SYNTH_LIMIT: OnceLock<mcp_tokio::sync::Semaphore> = OnceLock::new();
+
+fn synth_limit() -> &'static mcp_tokio::sync::Semaphore {
+    SYNTH_LIMIT.get_or_init(|| mcp_tokio::sync::Semaphore::new(DEFAULT_MAX_CONCURRENT))
+}
+
+const DEFAULT_MAX_CONCURRENT: usize = 4;
+
+mcp_tokio::task_local! {
+    /// Cancelled by `notifications/cancelled` (or a stalled shutdown) for the tool call it scopes.
+    static TOOL_CANCEL: CancellationToken;
+}
+
+fn tool_cancellation() -> CancellationToken {
+    TOOL_CANCEL.try_with(|t| t.clone()).unwrap_or_default()
+}
+
+/// Wait for a synthesis slot unless the call is cancelled first.
+async fn acquire_slot(
+    cancel: &CancellationToken,
+) -> Result<mcp_tokio::sync::SemaphorePermit<'static>, ToolError> {
+    cancel
+        .run_until_cancelled(synth_limit().acquire())
+        .await
+        .ok_or_else(|| ToolError::ExecutionError("cancelled".into()))?
+        .map_err(|e| ToolError::ExecutionError(format!("synthesis limiter closed: {e}")))
+}
+
+/// Serializes `speak` playback on the host.
+static SPEAKER: mcp_tokio::sync::Mutex<()> = mcp_tokio::sync::Mutex::const_new(());
+
+/// Deletes a synthesize output on drop (error or cancellation) unless disarmed.
+/// Only armed for files this call creates, so a failed overwrite keeps the old file.
+struct PartialOutput(Option<PathBuf>);
+
+impl PartialOutput {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for PartialOutput {
+    fn drop(&mut self) {
+        if let Some(path) = self.0.take() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+pub struct McpServerOptions {
+    pub addr: Option<String>,
+    pub auth_token: Option<String>,
+    pub max_concurrent: usize,
+    /// (certificate chain, private key) PEM paths
+    pub tls: Option<(PathBuf, PathBuf)>,
+    /// Root that tool `output` paths must stay under
+    pub output_dir: Option<PathBuf>,
+}
+
+/// Canonical sandbox root for tool output paths, if `--mcp-output-dir` is set.
+static OUTPUT_ROOT: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Resolve a tool-supplied output path, confining it to `--mcp-output-dir`.
+/// Relative paths are taken relative to that directory; `..` is never allowed.
+fn resolve_output(requested: &str) -> Result<PathBuf, ToolError> {
+    let path = Path::new(requested);
+    let Some(root) = OUTPUT_ROOT.get().and_then(|r| r.as_ref()) else {
+        return Ok(path.to_path_buf());
+    };
+    if path
+        .components()
+        .any(|c| matches!(c, std::path::Component::ParentDir))
+    {
+        return Err(ToolError::InvalidParameters(format!(
+            "output may not contain '..': {requested}"
+        )));
+    }
+    let joined = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        root.join(path)
+    };
+    let outside = || {
+        ToolError::InvalidParameters(format!(
+            "output must be inside {}: {requested}",
+            root.display()
+        ))
+    };
+    let rest = joined.strip_prefix(root).map_err(|_| outside())?;
+    // Look at each existing component without following it: a symlink, even
+    // a dangling one the write would create through, must resolve inside the root
+    let mut current = root.clone();
+    for component in rest.components() {
+        current.push(component);
+        match fs::symlink_metadata(&current) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                if !current.canonicalize().is_ok_and(|t| t.starts_with(root)) {
+                    return Err(outside());
+                }
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    Ok(joined)
+}
+
+pub async fn run_mcp_server(mode: McpMode, options: McpServerOptions) -> Result<()> {
+    let McpServerOptions {
+        addr,
+        auth_token,
+        max_concurrent,
+        tls,
+        output_dir,
+    } = options;
+    let output_root = output_dir
+        .map(|dir| {
+            fs::create_dir_all(&dir)
+                .and_then(|_| dir.canonicalize())
+                .with_context(|| format!("invalid --mcp-output-dir: {}", dir.display()))
+        })
+        .transpose()?;
+    let _ = OUTPUT_ROOT.set(output_root);
+    let _ = SYNTH_LIMIT.set(mcp_tokio::sync::Semaphore::new(max_concurrent.max(1)));
+    match mode {
+        McpMode::Stdio => serve_stdio().await?,
+        McpMode::Http | McpMode::Sse => {
+            let state = McpState::default();
+            let has_token = auth_token.is_some();
+            let app: AxumRouter = AxumRouter::new()
+                .route("/rpc", post(rpc))
+                .route("/sse", get(sse))
+                // Pre-session alias kept for clients configured against the old route
+                .route("/events", get(sse))
+                .route("/message", post(sse_message))
+                .with_state(state.clone())
+                .layer(middleware::from_fn(move |req, next| {
+                    require_bearer(auth_token.clone(), req, next)
+                }))
+                // Probes stay unauthenticated
+                .route("/healthz", get(|| async { StatusCode::OK }))
+                .route("/readyz", get(readyz));
+
+            let bind_addr = addr.unwrap_or_else(|| "127.0.0.1:2024".to_string());
+            if !has_token && !is_loopback_addr(&bind_addr) {
+                eprintln!(
+                    "Warning: MCP server on {bind_addr} accepts unauthenticated calls; set --mcp-auth-token"
+                );
+            }
+            // Load certificates before binding so a bad path fails fast
+            let tls_config = tls
+                .map(|(cert, key)| load_tls_config(&cert, &key))
+                .transpose()?;
+            let listener = crate::listen::Listener::bind(&bind_addr).await?;
+            match (tls_config, listener) {
+                (Some(config), crate::listen::Listener::Tcp(listener)) => {
+                    eprintln!("MCP server listening on https://{}", listener.local_addr()?);
+                    serve_tls(listener, app, config, drain_on_shutdown(state)).await?
+                }
+                (Some(_), _) => {
+                    anyhow::bail!("--mcp-tls-cert requires a TCP address, not a unix socket")
+                }
+                (None, listener) => {
+                    eprintln!("MCP server listening on {}", listener.describe("http"));
+                    crate::listen::serve(listener, app, drain_on_shutdown(state)).await?
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn load_tls_config(cert: &Path, key: &Path) -> Result<Arc<rustls::ServerConfig>> {
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("failed to read TLS certificate: {}", cert.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("no certificates found in {}", cert.display());
+    }
+    let key = PrivateKeyDer::from_pem_file(key)
+        .with_context(|| format!("failed to read TLS private key: {}", key.display()))?;
+    let mut config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .context("TLS certificate and key do not match")?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// `axum::serve` only speaks plain TCP, so TLS connections are accepted
+/// here and handed to hyper with the same router.
+async fn serve_tls(
+    listener: mcp_tokio::net::TcpListener,
+    app: AxumRouter,
+    config: Arc<rustls::ServerConfig>,
+    shutdown: impl std::future::Future<Output = ()>,
+) -> Result<()> {
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    let acceptor = tokio_rustls::TlsAcceptor::from(config);
+    let mut shutdown = std::pin::pin!(shutdown);
+    let mut connections = mcp_tokio::task::JoinSet::new();
+    loop {
+        let tcp = mcp_tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((tcp, _)) => tcp,
+                Err(e) => {
+                    eprintln!("Warning: accept failed: {e}");
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        let acceptor = acceptor.clone();
+        let service = hyper_util::service::TowerToHyperService::new(app.clone());
+        connections.spawn(async move {
+            let Ok(stream) = acceptor.accept(tcp).await else {
+                return;
+            };
+            let _ = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+    // Give open connections the same bounded drain window as in-flight RPCs
+    let _ = mcp_tokio::time::timeout(Duration::from_secs(30), async {
+        while connections.join_next().await.is_some() {}
+    })
+    .await;
+    Ok(())
+}
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Ready until shutdown starts, so load balancers stop routing before we exit.
+async fn readyz() -> StatusCode {
+    if SHUTTING_DOWN.load(Ordering::Relaxed) {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = mcp_tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match mcp_tokio::signal::unix::signal(mcp_tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    mcp_tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// On SIGTERM/Ctrl-C: fail readiness, wait for in-flight RPCs (`/rpc` and SSE)
+/// to finish (bounded), then close SSE streams so axum can drain remaining connections.
+async fn drain_on_shutdown(state: McpState) {
+    const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+    shutdown_signal().await;
+    SHUTTING_DOWN.store(true, Ordering::Relaxed);
+    eprintln!("Shutting down MCP server; draining in-flight requests");
+    let deadline = mcp_tokio::time::Instant::now() + DRAIN_TIMEOUT;
+    while state.in_flight.lock().is_ok_and(|m| !m.is_empty())
+        && mcp_tokio::time::Instant::now() < deadline
+    {
+        mcp_tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    // Whatever outlived the drain window is cancelled so partial files get cleaned up
+    if let Ok(running) = state.in_flight.lock() {
+        running.values().for_each(CancellationToken::cancel);
+    }
+    if let Ok(mut sessions) = state.sessions.lock() {
+        sessions.clear();
+    }
+}
+
+/// Reject requests lacking `Authorization: Bearer <token>` when a token is configured.
+async fn require_bearer(
+    token: Option<String>,
+    req: axum::extract::Request,
+    next: middleware::Next,
+) -> Result<axum::response::Response, StatusCode> {
+    if let Some(expected) = token {
+        let presented = req
+            .headers()
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .unwrap_or("");
+        if !constant_time_eq(presented.as_bytes(), expected.as_bytes()) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    }
+    Ok(next.run(req).await)
+}
+
+/// Unix sockets count as local: filesystem permissions gate who can connect.
+fn is_loopback_addr(addr: &str) -> bool {
+    if addr.starts_with("unix:") {
+        return true;
+    }
+    let host = addr
+        .trim_start_matches("http://")
+        .trim_start_matches("https://")
+        .rsplit_once(':')
+        .map_or(addr, |(host, _)| host)
+        .trim_matches(['[', ']']);
+    host == "localhost"
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Open SSE streams keyed by session id; POSTed requests are answered on the matching stream.
+type SseSessions = Arc<Mutex<HashMap<String, UnboundedSender<JsonRpcResponse>>>>;
+
+/// Requests still running per (session id, JSON-RPC id as JSON), for
+/// `notifications/cancelled` and the shutdown drain.
+type InFlight = Arc<Mutex<HashMap<(String, String), CancellationToken>>>;
+
+#[derive(Clone, Default)]
+struct McpState {
+    sessions: SseSessions,
+    in_flight: InFlight,
+}
+
+/// Removes a session once its SSE stream is dropped by the client.
+struct SessionGuard {
+    sessions: SseSessions,
+    id: String,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(&self.id);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SessionQuery {
+    #[serde(rename = "sessionId")]
+    session_id: String,
+}
+
+/// MCP SSE transport: announce the POST endpoint, then relay JSON-RPC responses.
+async fn sse(
+    State(McpState { sessions, .. }): State<McpState>,
+) -> Sse<impl futures::Stream<Item = Result<Event, Infallible>>> {
+    let id = super::random_id();
+    let (tx, rx) = futures::channel::mpsc::unbounded();
+    if let Ok(mut map) = sessions.lock() {
+        map.insert(id.clone(), tx);
+    }
+    let endpoint = Event::default()
+        .event("endpoint")
+        .data(format!("/message?sessionId={id}"));
+    let guard = SessionGuard { sessions, id };
+    let messages = rx.map(move |resp: JsonRpcResponse| {
+        let _session = &guard;
+        let data = serde_json::to_string(&resp).unwrap_or_default();
+        Ok(Event::default().event("message").data(data))
+    });
+    Sse::new(tokio_stream::once(Ok(endpoint)).chain(messages))
+        .keep_alive(KeepAlive::new().interval(Duration::from_secs(10)))
+}
+
+async fn sse_message(
+    State(McpState {
+        sessions,
+        in_flight,
+    }): State<McpState>,
+    Query(query): Query<SessionQuery>,
+    Json(req): Json<JsonRpcRequest>,
+) -> StatusCode {
+    let tx = match sessions.lock() {
+        Ok(map) => map.get(&query.session_id).cloned(),
+        Err(_) => None,
+    };
+    let Some(tx) = tx else {
+        return StatusCode::NOT_FOUND;
+    };
+    let Some(call) = Call::admit(&in_flight, &query.session_id, req) else {
+        return StatusCode::ACCEPTED;
+    };
+    mcp_tokio::spawn(async move {
+        if let Some(resp) = call.run().await {
+            let _ = tx.unbounded_send(resp);
+        }
+    });
+    StatusCode::ACCEPTED
+}
+
+/// `POST /rpc`: one JSON-RPC message per request, answered in the response.
+/// Clients that cancel by id across requests name their session in `Mcp-Session-Id`.
+async fn rpc(
+    State(McpState { in_flight, .. }): State<McpState>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<JsonRpcRequest>,
+) -> axum::response::Response {
+    use axum::response::IntoResponse as _;
+    let session = headers
+        .get("mcp-session-id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("rpc");
+    let id = req.id.clone();
+    let Some(call) = Call::admit(&in_flight, session, req) else {
+        return StatusCode::ACCEPTED.into_response();
+    };
+    match (call.run().await, id) {
+        (Some(resp), _) => Json(resp).into_response(),
+        // Unlike SSE, every HTTP request needs an answer
+        (None, Some(id)) => Json(JsonRpcResponse::error(
+            id,
+            REQUEST_CANCELLED,
+            "request cancelled",
+        ))
+        .into_response(),
+        (None, None) => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+/// MCP over stdio: newline-delimited JSON-RPC. Calls run concurrently and
+/// answer as they finish; at end of input the outstanding ones are finished first.
+async fn serve_stdio() -> Result<()> {
+    use mcp_tokio::io::{AsyncBufReadExt as _, AsyncWriteExt as _};
+    let in_flight = InFlight::default();
+    let (tx, mut rx) = mcp_tokio::sync::mpsc::unbounded_channel::<JsonRpcResponse>();
+    let writer = mcp_tokio::spawn(async move {
+        let mut stdout = mcp_tokio::io::stdout();
+        while let Some(resp) = rx.recv().await {
+            let mut line = serde_json::to_vec(&resp)?;
+            line.push(b'\n');
+            stdout.write_all(&line).await?;
+            stdout.flush().await?;
+        }
+        anyhow::Ok(())
+    });
+    let mut lines = mcp_tokio::io::BufReader::new(mcp_tokio::io::stdin()).lines();
+    let mut calls = mcp_tokio::task::JoinSet::new();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let req = match serde_json::from_str::<JsonRpcRequest>(&line) {
+            Ok(req) => req,
+            Err(e) => {
+                let _ = tx.send(JsonRpcResponse::error(
+                    serde_json::Value::Null,
+                    protocol::PARSE_ERROR,
+                    format!("invalid JSON-RPC message: {e}"),
+                ));
+                continue;
+            }
+        };
+        // Admitted here, in order, so a cancellation never overtakes its request
+        let Some(call) = Call::admit(&in_flight, "stdio", req) else {
+            continue;
+        };
+        let tx = tx.clone();
+        calls.spawn(async move {
+            if let Some(resp) = call.run().await {
+                let _ = tx.send(resp);
+            }
+        });
+        while calls.try_join_next().is_some() {}
+    }
+    while calls.join_next().await.is_some() {}
+    drop(tx);
+    writer.await?
+}
+
+/// A request taken in by a transport, registered in the in-flight table
+/// until it finishes (or its connection goes away).
+struct Call {
+    req: JsonRpcRequest,
+    entry: InFlightEntry,
+}
+
+struct InFlightEntry {
+    in_flight: InFlight,
+    key: Option<(String, String)>,
+    cancel: CancellationToken,
+}
+
+impl Drop for InFlightEntry {
+    fn drop(&mut self) {
+        if let Some(key) = &self.key
+            && let Ok(mut running) = self.in_flight.lock()
+        {
+            running.remove(key);
+        }
+    }
+}
+
+impl Call {
+    /// `None` for `notifications/cancelled`, which is applied here: the tool
+    /// stops its provider request and removes partial output.
+    fn admit(in_flight: &InFlight, session: &str, req: JsonRpcRequest) -> Option<Self> {
+        let key = req.in_flight_key().map(|id| (session.to_string(), id));
+        if req.method == "notifications/cancelled" {
+            if let Some(key) = key
+                && let Ok(mut running) = in_flight.lock()
+                && let Some(token) = running.remove(&key)
+            {
+                token.cancel();
+            }
+            return None;
+        }
+        let cancel = CancellationToken::new();
+        if let Some(key) = &key
+            && let Ok(mut running) = in_flight.lock()
+        {
+            running.insert(key.clone(), cancel.clone());
+        }
+        Some(Self {
+            req,
+            entry: InFlightEntry {
+                in_flight: in_flight.clone(),
+                key,
+                cancel,
+            },
+        })
+    }
+
+    /// The response; `None` for notifications and cancelled requests.
+    async fn run(self) -> Option<JsonRpcResponse> {
+        let Self { req, entry } = self;
+        let resp = TOOL_CANCEL
+            .scope(entry.cancel.clone(), protocol::handle(&FastTtsRouter, req))
+            .await;
+        resp.filter(|_| !entry.cancel.is_cancelled())
+    }
+}
+
+const VOICES_SCHEME: &str = "voices://";
+
+struct PromptTemplate {
+    name: &'static str,
+    description: &'static str,
+    /// (name, description, required)
+    arguments: &'static [(&'static str, &'static str, bool)],
+    template: &'static str,
+}
+
+const PROMPTS: &[PromptTemplate] = &[
+    PromptTemplate {
+        name: "narrate",
+        description: "Narrate a piece of text to an audio file",
+        arguments: &[
+            ("text", "Text to narrate", true),
+            (
+                "output",
+                "Output file path (extension picks the format)",
+                true,
+            ),
+            (
+                "voice",
+                "Voice name; call listProviders for valid choices",
+                false,
+            ),
+        ],
+        template: "Narrate the following text with the synthesize tool.\n\
+            Call synthesize with text={{text}}, output={{output}} and, if given, voice={{voice}}. \
+            Pick encoding from the output extension (MP3 for .mp3, OGG_OPUS for .ogg, LINEAR16 for .wav). \
+            Split text longer than about 4000 characters into several calls.\n\n{{text}}",
+    },
+    PromptTemplate {
+        name: "dialogue-ssml",
+        description: "Turn a dialogue script into SSML and synthesize it",
+        arguments: &[
+            ("script", "Dialogue with one 'Speaker: line' per line", true),
+            ("output", "Output file path", true),
+            ("language", "BCP-47 language code, default en-US", false),
+        ],
+        template: "Convert this dialogue into an SSML script for the synthesize tool.\n\
+            Wrap everything in <speak>, put a <break time=\"400ms\"/> between speakers, \
+            use <emphasis> and <prosody> sparingly to convey tone, and escape &, < and > in the text. \
+            Then call synthesize with ssml=true, text=<the SSML>, output={{output}}, language={{language}}.\n\n\
+            {{script}}",
+    },
+    PromptTemplate {
+        name: "choose-voice",
+        description: "Pick a suitable voice before synthesizing",
+        arguments: &[
+            ("language", "BCP-47 language code", true),
+            (
+                "style",
+                "Desired character, e.g. calm, energetic, formal",
+                false,
+            ),
+        ],
+        template: "Call listProviders to see which providers are configured, then read the \
+            voices://google resource (or listVoices) and choose a {{style}} voice for {{language}}. \
+            Reply with the provider and voice name to pass to synthesize.",
+    },
+];
+
+/// Providers whose voice catalog can be served: Google (fetched on demand)
+/// plus any provider with a catalog already in the cache directory.
+fn voice_catalog_providers() -> Vec<String> {
+    let mut providers = vec!["google".to_string()];
+    if let Some(dir) = super::cache_dir().map(|d| d.join("voices"))
+        && let Ok(entries) = fs::read_dir(dir)
+    {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "json")
+                && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+                && !providers.iter().any(|p| p == stem)
+            {
+                providers.push(stem.to_string());
+            }
+        }
+    }
+    providers
+}
+
+#[derive(Clone)]
+struct FastTtsRouter;
+
+impl Router for FastTtsRouter {
+    fn name(&self) -> String {
+        "fast-tts-cli".to_string()
+    }
+    fn instructions(&self) -> String {
+        "Multi-provider TTS server providing synthesize and listVoices tools.".to_string()
+    }
+    fn list_tools(&self) -> Vec<Tool> {
+        vec![
+            Tool::new(
+                "synthesize",
+                "Synthesize speech to a file and/or return it inline as base64",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "text": {"type": "string"},
+                        "output": {"type": "string"},
+                        "provider": {
+                            "type": "string",
+                            "description": "TTS provider (defaults to the profile's provider, then google)"
+                        },
+                        "profile": {
+                            "type": "string",
+                            "description": "Named credential profile from profiles.yaml"
+                        },
+                        "language": {"type": "string"},
+                        "voice": {"type": "string"},
+                        "gender": {"type": "string"},
+                        "rate": {"type": "number"},
+                        "pitch": {"type": "number"},
+                        "sampleRate": {"type": "integer"},
+                        "encoding": {"type": "string"},
+                        "volumeGainDb": {"type": "number"},
+                        "effectsProfileId": {"type": "array", "items": {"type": "string"}},
+                        "ssml": {"type": "boolean"},
+                        "play": {"type": "boolean"},
+                        "returnAudio": {
+                            "type": "boolean",
+                            "description": "Return the audio inline as base64; output becomes optional"
+                        }
+                    },
+                    "required": ["text"]
+                }),
+            ),
+            Tool::new(
+                "speak",
+                "Synthesize text and play it on the server host's speakers",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "text": {"type": "string"},
+                        "provider": {"type": "string"},
+                        "profile": {"type": "string"},
+                        "language": {"type": "string"},
+                        "voice": {"type": "string"},
+                        "rate": {"type": "number"},
+                        "ssml": {"type": "boolean"}
+                    },
+                    "required": ["text"]
+                }),
+            ),
+            Tool::new(
+                "generateSubtitles",
+                "Synthesize text with Google and return SRT/VTT captions or word timings",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "text": {"type": "string", "description": "Plain text (not SSML)"},
+                        "output": {"type": "string", "description": "Audio path; omitted to return captions only"},
+                        "format": {"type": "string", "enum": ["srt", "vtt", "json"]},
+                        "language": {"type": "string"},
+                        "voice": {"type": "string"},
+                        "rate": {"type": "number"},
+                        "encoding": {"type": "string"},
+                        "maxWordsPerCue": {"type": "integer"}
+                    },
+                    "required": ["text"]
+                }),
+            ),
+            Tool::new(
+                "listProviders",
+                "List providers with credential status, encodings, sample rates, and voice rules",
+                serde_json::json!({ "type": "object", "properties": {}, "required": [] }),
+            ),
+            Tool::new(
+                "listVoices",
+                "List available voices from provider",
+                serde_json::json!({ "type": "object", "properties": {"json": {"type": "boolean"}}, "required": [] }),
+            ),
+        ]
+    }
+
+    fn call_tool(
+        &self,
+        tool_name: &str,
+        arguments: serde_json::Value,
+    ) -> futures::future::BoxFuture<'static, Result<Vec<Content>, ToolError>> {
+        let name = tool_name.to_string();
+        let span = tracing::info_span!("mcp.tool", otel.kind = "server", tool = %name);
+        Box::pin(
+            async move {
+                match name.as_str() {
+                    "synthesize" => {
+                        let text = arguments
+                            .get("text")
+                            .and_then(|v| v.as_str())
+                            .ok_or_else(|| ToolError::InvalidParameters("text required".into()))?
+                            .to_string();
+                        let return_audio = arguments
+                            .get("returnAudio")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+                        let output = arguments
+                            .get("output")
+                            .and_then(|v| v.as_str())
+                            .map(|s| s.to_string());
+                        if output.is_none() && !return_audio {
+                            return Err(ToolError::InvalidParameters(
+                                "output required unless returnAudio is true".into(),
+                            ));
+                        }
+                        let language = arguments
+                            .get("language")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default()
+                            .to_string();
+                        let voice = arguments
+                            .get("voice")
+                            .and_then(|v| v.as_str())
+                            .map(|s| s.to_string());
+                        let gender_opt =
+                            arguments.get("gender").and_then(|v| v.as_str()).map(|g| {
+                                match g.to_uppercase().as_str() {
+                                    "MALE" => Gender::Male,
+                                    "FEMALE" => Gender::Female,
+                                    _ => Gender::Neutral,
+                                }
+                            });
+                        let rate = arguments
+                            .get("rate")
+                            .and_then(|v| v.as_f64())
+                            .unwrap_or(1.0) as f32;
+                        let pitch = arguments
+                            .get("pitch")
+                            .and_then(|v| v.as_f64())
+                            .unwrap_or(0.0) as f32;
+                        let sample_rate = arguments
+                            .get("sampleRate")
+                            .and_then(|v| v.as_i64())
+                            .map(|n| n as i32);
+                        let encoding = arguments
+                            .get("encoding")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("LINEAR16");
+                        let volume_gain_db = arguments
+                            .get("volumeGainDb")
+                            .and_then(|v| v.as_f64())
+                            .unwrap_or(0.0) as f32;
+                        let effects_profile_id: Vec<String> = arguments
+                            .get("effectsProfileId")
+                            .and_then(|v| v.as_array())
+                            .map(|arr| {
+                                arr.iter()
+                                    .filter_map(|x| x.as_str().map(|s| s.to_string()))
+                                    .collect()
+                            })
+                            .unwrap_or_default();
+                        let is_ssml = arguments
+                            .get("ssml")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+                        let play = arguments
+                            .get("play")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+
+                        let enc = super::parse_encoding_from_str(encoding)
+                            .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+                        let profile = match arguments.get("profile").and_then(|v| v.as_str()) {
+                            Some(name) => super::load_profile(name)
+                                .map_err(|e| ToolError::InvalidParameters(e.to_string()))?,
+                            None => super::Profile::default(),
+                        };
+                        let provider = match arguments.get("provider").and_then(|v| v.as_str()) {
+                            Some(name) => super::parse_remote_provider(name),
+                            None => profile.provider_or(super::Provider::Google),
+                        }
+                        .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+                        // Without an output path the audio only travels back inline
+                        let output_path = match &output {
+                            Some(o) => resolve_output(o)?,
+                            None => std::env::temp_dir().join(format!(
+                                "fast-tts-mcp-{}-{}.{}",
+                                std::process::id(),
+                                super::unix_now_nanos(),
+                                enc.file_extension()
+                            )),
+                        };
+
+                        let cancel = tool_cancellation();
+                        let request = super::SynthesisRequest::builder()
+                            .provider(provider)
+                            .text(text.as_str())
+                            .output(output_path.clone())
+                            .language(language.as_str())
+                            .voice(voice.clone())
+                            .gender(gender_opt)
+                            .rate(rate)
+                            .pitch(pitch)
+                            .sample_rate(sample_rate)
+                            .encoding(enc)
+                            .volume_gain_db(volume_gain_db)
+                            .effects_profile_id(effects_profile_id.clone())
+                            .ssml(is_ssml)
+                            .cancellation(cancel.clone())
+                            .build()
+                            .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+
+                        let _permit = acquire_slot(&cancel).await?;
+                        let partial =
+                            PartialOutput((!output_path.exists()).then(|| output_path.clone()));
+
+                        super::with_profile(&profile, super::synthesize(&request))
+                            .await
+                            .map_err(|e| {
+                                let failure = super::ToolFailure::classify(provider, &e);
+                                ToolError::ExecutionError(
+                                    serde_json::to_string(&failure)
+                                        .unwrap_or_else(|_| e.to_string()),
+                                )
+                            })?;
+
+                        partial.disarm();
+
+                        if play && let Err(e) = super::play_audio(&output_path) {
+                            eprintln!("Warning: playback failed: {e}");
+                        }
+
+                        let mut content = vec![Content::text(
+                            serde_json::json!({
+                                "ok": true,
+                                "output": output,
+                                "mimeType": enc.mime_type(),
+                            })
+                            .to_string(),
+                        )];
+                        if return_audio {
+                            let bytes = std::fs::read(&output_path)
+                                .map_err(|e| ToolError::ExecutionError(e.to_string()));
+                            if output.is_none() {
+                                let _ = std::fs::remove_file(&output_path);
+                            }
+                            content.push(Content::resource(ResourceContents::Blob {
+                                uri: format!("audio:///synthesized.{}", enc.file_extension()),
+                                mime_type: Some(enc.mime_type().to_string()),
+                                blob: base64::engine::general_purpose::STANDARD.encode(bytes?),
+                            }));
+                        }
+                        Ok(content)
+                    }
+                    "speak" => {
+                        let text = arguments
+                            .get("text")
+                            .and_then(|v| v.as_str())
+                            .ok_or_else(|| ToolError::InvalidParameters("text required".into()))?
+                            .to_string();
+                        let language = arguments
+                            .get("language")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default()
+                            .to_string();
+                        let voice = arguments
+                            .get("voice")
+                            .and_then(|v| v.as_str())
+                            .map(|s| s.to_string());
+                        let rate = arguments
+                            .get("rate")
+                            .and_then(|v| v.as_f64())
+                            .unwrap_or(1.0) as f32;
+                        let is_ssml = arguments
+                            .get("ssml")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+                        let profile = match arguments.get("profile").and_then(|v| v.as_str()) {
+                            Some(name) => super::load_profile(name)
+                                .map_err(|e| ToolError::InvalidParameters(e.to_string()))?,
+                            None => super::Profile::default(),
+                        };
+                        let provider = match arguments.get("provider").and_then(|v| v.as_str()) {
+                            Some(name) => super::parse_remote_provider(name),
+                            None => profile.provider_or(super::Provider::Google),
+                        }
+                        .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+
+                        // WAV plays with every backend play_audio may pick (aplay included)
+                        let enc = super::AudioEncoding::Linear16;
+                        let path = std::env::temp_dir().join(format!(
+                            "fast-tts-speak-{}-{}.{}",
+                            std::process::id(),
+                            super::unix_now_nanos(),
+                            enc.file_extension()
+                        ));
+                        let cancel = tool_cancellation();
+                        let request = super::SynthesisRequest::builder()
+                            .provider(provider)
+                            .text(text.as_str())
+                            .output(path.clone())
+                            .language(language.as_str())
+                            .voice(voice.clone())
+                            .rate(rate)
+                            .encoding(enc)
+                            .ssml(is_ssml)
+                            .cancellation(cancel.clone())
+                            .build()
+                            .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+                        let _cleanup = PartialOutput(Some(path.clone()));
+                        {
+                            let _permit = acquire_slot(&cancel).await?;
+                            super::with_profile(&profile, super::synthesize(&request))
+                                .await
+                                .map_err(|e| {
+                                    let failure = super::ToolFailure::classify(provider, &e);
+                                    ToolError::ExecutionError(
+                                        serde_json::to_string(&failure)
+                                            .unwrap_or_else(|_| e.to_string()),
+                                    )
+                                })?;
+                        }
+
+                        // One utterance at a time so concurrent calls don't talk over each other
+                        let _speaker = SPEAKER.lock().await;
+                        let play_path = path.clone();
+                        mcp_tokio::task::spawn_blocking(move || super::play_audio(&play_path))
+                            .await
+                            .map_err(|e| ToolError::ExecutionError(e.to_string()))?
+                            .map_err(|e| {
+                                ToolError::ExecutionError(format!("playback failed: {e:#}"))
+                            })?;
+                        Ok(vec![Content::text(
+                            serde_json::json!({ "ok": true, "spoken": true }).to_string(),
+                        )])
+                    }
+                    "generateSubtitles" => {
+                        let text = arguments
+                            .get("text")
+                            .and_then(|v| v.as_str())
+                            .ok_or_else(|| ToolError::InvalidParameters("text required".into()))?
+                            .to_string();
+                        let format = arguments
+                            .get("format")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("srt")
+                            .to_ascii_lowercase();
+                        if !matches!(format.as_str(), "srt" | "vtt" | "json") {
+                            return Err(ToolError::InvalidParameters(
+                                "format must be srt, vtt, or json".into(),
+                            ));
+                        }
+                        let language = arguments
+                            .get("language")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default()
+                            .to_string();
+                        let voice = arguments
+                            .get("voice")
+                            .and_then(|v| v.as_str())
+                            .map(|s| s.to_string());
+                        let rate = arguments
+                            .get("rate")
+                            .and_then(|v| v.as_f64())
+                            .unwrap_or(1.0) as f32;
+                        let enc = super::parse_encoding_from_str(
+                            arguments
+                                .get("encoding")
+                                .and_then(|v| v.as_str())
+                                .unwrap_or("MP3"),
+                        )
+                        .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+                        let max_words = arguments
+                            .get("maxWordsPerCue")
+                            .and_then(|v| v.as_u64())
+                            .unwrap_or(8)
+                            .max(1) as usize;
+                        let output = arguments
+                            .get("output")
+                            .and_then(|v| v.as_str())
+                            .map(resolve_output)
+                            .transpose()?;
+                        let output_path = output.clone().unwrap_or_else(|| {
+                            std::env::temp_dir().join(format!(
+                                "fast-tts-mcp-{}-{}.{}",
+                                std::process::id(),
+                                super::unix_now_nanos(),
+                                enc.file_extension()
+                            ))
+                        });
+
+                        let cancel = tool_cancellation();
+                        let _permit = acquire_slot(&cancel).await?;
+                        let partial = PartialOutput(
+                            (output.is_none() || !output_path.exists())
+                                .then(|| output_path.clone()),
+                        );
+                        let marks = cancel
+                            .run_until_cancelled(super::google_speech_marks(
+                                &text,
+                                &output_path,
+                                &super::request::language_or_voice(
+                                    &language,
+                                    super::Provider::Google,
+                                    voice.as_deref(),
+                                ),
+                                voice.as_deref(),
+                                None,
+                                rate,
+                                0.0,
+                                enc,
+                            ))
+                            .await
+                            .unwrap_or_else(|| Err(super::cancelled()))
+                            .map_err(|e| {
+                                let failure =
+                                    super::ToolFailure::classify(super::Provider::Google, &e);
+                                ToolError::ExecutionError(
+                                    serde_json::to_string(&failure)
+                                        .unwrap_or_else(|_| e.to_string()),
+                                )
+                            })?;
+                        // Captions-only calls let the guard delete the temporary audio
+                        if output.is_some() {
+                            partial.disarm();
+                        }
+
+                        let cues = super::subtitle_cues(&marks, max_words);
+                        let captions = match format.as_str() {
+                            "vtt" => super::format_vtt(&cues),
+                            "json" => serde_json::to_string_pretty(&marks)
+                                .map_err(|e| ToolError::ExecutionError(e.to_string()))?,
+                            _ => super::format_srt(&cues),
+                        };
+                        Ok(vec![
+                            Content::text(
+                                serde_json::json!({
+                                    "ok": true,
+                                    "output": output,
+                                    "format": format,
+                                    "words": marks.len(),
+                                })
+                                .to_string(),
+                            ),
+                            Content::text(captions),
+                        ])
+                    }
+                    "listProviders" => {
+                        let providers: Vec<_> = super::Provider::all()
+                            .into_iter()
+                            .map(super::Provider::capabilities)
+                            .collect();
+                        let text = serde_json::to_string_pretty(&providers)
+                            .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+                        Ok(vec![Content::text(text)])
+                    }
+                    "listVoices" => {
+                        let data = super::list_voices()
+                            .await
+                            .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+                        Ok(vec![Content::text(
+                            serde_json::to_string(&data).unwrap_or_else(|_| "{}".into()),
+                        )])
+                    }
+                    _ => Err(ToolError::NotFound(format!("Tool {} not found", name))),
+                }
+            }
+            .instrument(span),
+        )
+    }
+
+    fn list_resources(&self) -> Vec<Resource> {
+        voice_catalog_providers()
+            .into_iter()
+            .map(|provider| Resource {
+                uri: format!("{VOICES_SCHEME}{provider}"),
+                name: format!("{provider} voices"),
+                description: Some(format!("Voice catalog for {provider}")),
+                mime_type: Some("application/json".to_string()),
+            })
+            .collect()
+    }
+    fn read_resource(
+        &self,
+        uri: &str,
+    ) -> futures::future::BoxFuture<'static, Result<String, ResourceError>> {
+        let provider = uri.strip_prefix(VOICES_SCHEME).map(str::to_string);
+        Box::pin(async move {
+            let provider =
+                provider.ok_or_else(|| ResourceError::NotFound("unknown resource".into()))?;
+            // Only names listed as resources, so the URI can't reach other files
+            if !voice_catalog_providers().contains(&provider) {
+                return Err(ResourceError::NotFound(format!(
+                    "no cached voice catalog for {provider}"
+                )));
+            }
+            if let Some(body) = super::voice_cache_path(&provider)
+                .and_then(|path| fs::read_to_string(path).ok())
+                .and_then(|data| serde_json::from_str::<super::VoiceCatalogCache>(&data).ok())
+            {
+                return Ok(body.body);
+            }
+            match provider.as_str() {
+                "google" => {
+                    let data = super::list_voices()
+                        .await
+                        .map_err(|e| ResourceError::ExecutionError(e.to_string()))?;
+                    serde_json::to_string(&data)
+                        .map_err(|e| ResourceError::ExecutionError(e.to_string()))
+                }
+                other => Err(ResourceError::NotFound(format!(
+                    "no cached voice catalog for {other}"
+                ))),
+            }
+        })
+    }
+    fn list_prompts(&self) -> Vec<Prompt> {
+        PROMPTS
+            .iter()
+            .map(|p| Prompt {
+                name: p.name.to_string(),
+                description: Some(p.description.to_string()),
+                arguments: p
+                    .arguments
+                    .iter()
+                    .map(|(name, description, required)| PromptArgument {
+                        name: name.to_string(),
+                        description: Some(description.to_string()),
+                        required: *required,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+    fn get_prompt(
+        &self,
+        prompt_name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<String, PromptError> {
+        let prompt = PROMPTS
+            .iter()
+            .find(|p| p.name == prompt_name)
+            .ok_or_else(|| PromptError::NotFound(format!("Prompt {prompt_name} not found")))?;
+        // One pass over the template: values go in verbatim and are never
+        // scanned again. Arguments left out (the optional ones) are filled in
+        // as empty, taking one of the template's spaces around them along.
+        let mut text = String::with_capacity(prompt.template.len());
+        let mut rest = prompt.template;
+        while let Some((head, tail)) = rest.split_once("{{")
+            && let Some((name, tail)) = tail.split_once("}}")
+        {
+            text.push_str(head);
+            let value = arguments.get(name).and_then(|v| v.as_str()).unwrap_or("");
+            text.push_str(value);
+            rest = match tail.strip_prefix(' ') {
+                Some(trimmed) if value.is_empty() && text.ends_with(' ') => trimmed,
+                _ => tail,
+            };
+        }
+        text.push_str(rest);
+        Ok(text)
+    }
+}
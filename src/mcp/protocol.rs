@@ -0,0 +1,266 @@
+//! The part of the Model Context Protocol (revision 2024-11-05) the server
+//! speaks: JSON-RPC 2.0 messages, the tool, resource and prompt types, and
+//! [`handle`], which answers a request from a [`Router`]. Transports (stdio,
+//! `/rpc`, SSE) only move these messages around.
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// JSON-RPC error codes.
+pub const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const INTERNAL_ERROR: i32 = -32603;
+/// Answer to a request cancelled by `notifications/cancelled` over a
+/// transport that must reply to every request (`/rpc`).
+pub const REQUEST_CANCELLED: i32 = -32800;
+
+/// A request, or a notification when `id` is absent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcRequest {
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+impl JsonRpcRequest {
+    /// Key for the request (or, for `notifications/cancelled`, the request
+    /// it cancels) in an in-flight table: the id as JSON, so `7` and `"7"` differ.
+    pub fn in_flight_key(&self) -> Option<String> {
+        match self.method.as_str() {
+            "notifications/cancelled" => self
+                .params
+                .as_ref()
+                .and_then(|p| p.get("requestId"))
+                .map(Value::to_string),
+            _ => self.id.as_ref().map(Value::to_string),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorData>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorData {
+    pub code: i32,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl JsonRpcResponse {
+    fn result(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".into(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn error(id: Value, code: i32, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0".into(),
+            id,
+            result: None,
+            error: Some(ErrorData {
+                code,
+                message: message.into(),
+                data: None,
+            }),
+        }
+    }
+}
+
+/// An item of a tool result.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Content {
+    Text { text: String },
+    Resource { resource: ResourceContents },
+}
+
+impl Content {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text { text: text.into() }
+    }
+
+    pub fn resource(resource: ResourceContents) -> Self {
+        Self::Resource { resource }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged, rename_all_fields = "camelCase")]
+pub enum ResourceContents {
+    Text {
+        uri: String,
+        mime_type: Option<String>,
+        text: String,
+    },
+    /// Base64 `blob`
+    Blob {
+        uri: String,
+        mime_type: Option<String>,
+        blob: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tool {
+    pub name: String,
+    pub description: String,
+    pub input_schema: Value,
+}
+
+impl Tool {
+    pub fn new(name: &str, description: &str, input_schema: Value) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            input_schema,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    pub uri: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Prompt {
+    pub name: String,
+    pub description: Option<String>,
+    pub arguments: Vec<PromptArgument>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptArgument {
+    pub name: String,
+    pub description: Option<String>,
+    pub required: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ToolError {
+    #[error("invalid parameters: {0}")]
+    InvalidParameters(String),
+    /// The message is the tool result's text as is (structured failures are JSON)
+    #[error("{0}")]
+    ExecutionError(String),
+    #[error("{0}")]
+    NotFound(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResourceError {
+    #[error("{0}")]
+    ExecutionError(String),
+    #[error("{0}")]
+    NotFound(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PromptError {
+    #[error("{0}")]
+    NotFound(String),
+}
+
+/// What the server offers; [`handle`] turns MCP requests into these calls.
+pub trait Router: Send + Sync + 'static {
+    fn name(&self) -> String;
+    fn instructions(&self) -> String;
+    fn list_tools(&self) -> Vec<Tool>;
+    fn call_tool(
+        &self,
+        name: &str,
+        arguments: Value,
+    ) -> BoxFuture<'static, Result<Vec<Content>, ToolError>>;
+    fn list_resources(&self) -> Vec<Resource>;
+    fn read_resource(&self, uri: &str) -> BoxFuture<'static, Result<String, ResourceError>>;
+    fn list_prompts(&self) -> Vec<Prompt>;
+    /// The prompt's text with `arguments` filled in.
+    fn get_prompt(&self, name: &str, arguments: Value) -> Result<String, PromptError>;
+}
+
+/// Answer `req`; notifications (no id) get no response.
+pub async fn handle(router: &impl Router, req: JsonRpcRequest) -> Option<JsonRpcResponse> {
+    let id = req.id?;
+    let params = req.params.unwrap_or(Value::Null);
+    let param = |name: &str| params.get(name).and_then(Value::as_str).unwrap_or_default();
+    let result = match req.method.as_str() {
+        "initialize" => Ok(json!({
+            // Every client revision so far understands this one's messages
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {
+                "tools": {"listChanged": false},
+                "resources": {"subscribe": false, "listChanged": false},
+                "prompts": {"listChanged": false},
+            },
+            "serverInfo": {"name": router.name(), "version": env!("CARGO_PKG_VERSION")},
+            "instructions": router.instructions(),
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": router.list_tools() })),
+        "tools/call" => {
+            let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+            match router.call_tool(param("name"), arguments).await {
+                Ok(content) => Ok(json!({ "content": content, "isError": false })),
+                Err(ToolError::NotFound(message)) => Err((INVALID_PARAMS, message)),
+                // Failures of the tool itself are results the model can read
+                Err(e) => Ok(json!({ "content": [Content::text(e.to_string())], "isError": true })),
+            }
+        }
+        "resources/list" => Ok(json!({ "resources": router.list_resources() })),
+        "resources/read" => {
+            let uri = param("uri");
+            match router.read_resource(uri).await {
+                Ok(text) => Ok(json!({ "contents": [ResourceContents::Text {
+                    uri: uri.to_string(),
+                    mime_type: Some("application/json".into()),
+                    text,
+                }] })),
+                Err(ResourceError::NotFound(message)) => Err((INVALID_PARAMS, message)),
+                Err(ResourceError::ExecutionError(message)) => Err((INTERNAL_ERROR, message)),
+            }
+        }
+        "prompts/list" => Ok(json!({ "prompts": router.list_prompts() })),
+        "prompts/get" => {
+            let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+            router
+                .get_prompt(param("name"), arguments)
+                .map(|text| {
+                    json!({ "messages": [{
+                        "role": "user",
+                        "content": Content::text(text),
+                    }] })
+                })
+                .map_err(|e| (INVALID_PARAMS, e.to_string()))
+        }
+        other => Err((METHOD_NOT_FOUND, format!("method not found: {other}"))),
+    };
+    Some(match result {
+        Ok(result) => JsonRpcResponse::result(id, result),
+        Err((code, message)) => JsonRpcResponse::error(id, code, message),
+    })
+}
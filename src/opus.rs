@@ -0,0 +1,131 @@
+//! OGG_OPUS encoder settings (`--bitrate`, `--vbr`, `--application`). A
+//! provider that takes the bitrate gets it in the request; anything else is
+//! applied by re-encoding the provider's audio with ffmpeg.
+
+use crate::{AudioEncoding, FastTtsError, SynthesisRequest};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::path::Path;
+
+/// How OGG_OPUS output is encoded; unset fields keep the provider's choice.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpusSettings {
+    /// Target bitrate in bits per second (6000–510000)
+    pub bitrate: Option<u32>,
+    pub vbr: Option<OpusVbr>,
+    pub application: Option<OpusApplication>,
+}
+
+impl OpusSettings {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OpusVbr {
+    On,
+    Off,
+    /// Variable, but never above the target bitrate
+    Constrained,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OpusApplication {
+    /// Tuned for speech intelligibility
+    Voip,
+    /// Tuned for fidelity
+    Audio,
+}
+
+/// Parse a bitrate such as `32k`, `32kbps` or `32000`.
+pub fn parse_bitrate(raw: &str) -> Result<u32> {
+    let lower = raw.trim().to_ascii_lowercase();
+    let number = lower.strip_suffix("bps").unwrap_or(&lower);
+    let (number, scale) = match number.strip_suffix('k') {
+        Some(kilo) => (kilo, 1000.0),
+        None => (number, 1.0),
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .map(|n| n * scale)
+        .filter(|bps| (6000.0..=510_000.0).contains(bps))
+        .map(|bps| bps.round() as u32)
+        .ok_or_else(|| {
+            FastTtsError::InvalidInput(format!(
+                "invalid Opus bitrate {raw:?}; use 6k to 510k, e.g. 32k"
+            ))
+            .into()
+        })
+}
+
+/// Settings only make sense for Opus output.
+pub(crate) fn check(settings: &OpusSettings, encoding: AudioEncoding) -> Result<(), String> {
+    if !settings.is_default() && encoding != AudioEncoding::OggOpus {
+        return Err(format!(
+            "bitrate, VBR and application settings apply to OGG_OPUS output, not {}",
+            encoding.api_str()
+        ));
+    }
+    Ok(())
+}
+
+/// Whether the provider's audio has to be re-encoded to honor the settings.
+pub(crate) fn needs_transcode(request: &SynthesisRequest) -> bool {
+    let settings = &request.opus;
+    settings.vbr.is_some()
+        || settings.application.is_some()
+        || (settings.bitrate.is_some() && !request.provider.capabilities().opus_bitrate)
+}
+
+/// Re-encode the Ogg audio at `path` in place with ffmpeg (`FAST_TTS_FFMPEG`
+/// names another binary).
+pub(crate) async fn transcode(path: &Path, settings: &OpusSettings) -> Result<()> {
+    let ffmpeg = crate::config_var("FAST_TTS_FFMPEG").unwrap_or_else(|| "ffmpeg".into());
+    let encoded = path.with_extension("opus.tmp");
+    let mut command = std::process::Command::new(&ffmpeg);
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(path)
+        .args(["-c:a", "libopus"]);
+    if let Some(bitrate) = settings.bitrate {
+        command.args(["-b:a", &bitrate.to_string()]);
+    }
+    if let Some(vbr) = settings.vbr {
+        let vbr = match vbr {
+            OpusVbr::On => "on",
+            OpusVbr::Off => "off",
+            OpusVbr::Constrained => "constrained",
+        };
+        command.args(["-vbr", vbr]);
+    }
+    if let Some(application) = settings.application {
+        let application = match application {
+            OpusApplication::Voip => "voip",
+            OpusApplication::Audio => "audio",
+        };
+        command.args(["-application", application]);
+    }
+    command.args(["-f", "ogg"]).arg(&encoded);
+    tracing::info!(ffmpeg = %ffmpeg, output = %path.display(), "re-encoding Opus");
+    let output = tokio::task::spawn_blocking(move || command.output())
+        .await?
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FastTtsError::Unsupported(format!(
+                "these Opus settings need ffmpeg to re-encode the provider's audio, and {ffmpeg} was not found; install ffmpeg or set FAST_TTS_FFMPEG"
+            ))
+            .into(),
+            _ => anyhow::Error::from(e).context(format!("failed to run {ffmpeg}")),
+        })?;
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&encoded);
+        anyhow::bail!(
+            "{ffmpeg} failed to re-encode {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    std::fs::rename(&encoded, path).with_context(|| format!("failed to write {}", path.display()))
+}
@@ -0,0 +1,358 @@
+//! Opt-in OpenTelemetry trace export. Spans from `tracing` are converted to
+//! OTLP/HTTP JSON and posted in batches to `OTEL_EXPORTER_OTLP_ENDPOINT`
+//! (or `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`) from a background thread.
+//! Incoming W3C `traceparent` values are honored via a `traceparent` span field.
+
+use anyhow::{Context, Result};
+use std::sync::mpsc;
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing_subscriber::layer::{Context as LayerContext, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+const BATCH_SIZE: usize = 256;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Per-span state kept in the registry's extensions until the span closes.
+struct SpanData {
+    trace_id: u128,
+    span_id: u64,
+    parent_span_id: Option<u64>,
+    name: String,
+    kind: u8,
+    start_nanos: u128,
+    error: bool,
+    attributes: Vec<serde_json::Value>,
+    events: Vec<serde_json::Value>,
+}
+
+impl SpanData {
+    fn to_otlp(&self, end_nanos: u128) -> serde_json::Value {
+        let mut span = serde_json::json!({
+            "traceId": format!("{:032x}", self.trace_id),
+            "spanId": format!("{:016x}", self.span_id),
+            "name": self.name,
+            "kind": self.kind,
+            "startTimeUnixNano": self.start_nanos.to_string(),
+            "endTimeUnixNano": end_nanos.to_string(),
+            "attributes": self.attributes,
+            "events": self.events,
+            "status": { "code": if self.error { 2 } else { 0 } },
+        });
+        if let Some(parent) = self.parent_span_id {
+            span["parentSpanId"] = format!("{parent:016x}").into();
+        }
+        span
+    }
+}
+
+fn attribute(key: &str, value: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({ "key": key, "value": value })
+}
+
+/// Parse a W3C `traceparent` (`00-<trace-id>-<parent-id>-<flags>`).
+fn parse_traceparent(value: &str) -> Option<(u128, u64)> {
+    let mut parts = value.trim().split('-');
+    let (_version, trace, parent) = (parts.next()?, parts.next()?, parts.next()?);
+    if trace.len() != 32 || parent.len() != 16 {
+        return None;
+    }
+    let trace_id = u128::from_str_radix(trace, 16).ok().filter(|t| *t != 0)?;
+    let parent_id = u64::from_str_radix(parent, 16).ok().filter(|p| *p != 0)?;
+    Some((trace_id, parent_id))
+}
+
+fn random_u64() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(crate::unix_now_nanos());
+    hasher.finish().max(1)
+}
+
+struct FieldVisitor<'a>(&'a mut SpanData);
+
+impl FieldVisitor<'_> {
+    fn push(&mut self, field: &Field, value: serde_json::Value) {
+        self.0.attributes.push(attribute(field.name(), value));
+    }
+}
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "otel.name" => self.0.name = value.to_string(),
+            "otel.kind" => {
+                self.0.kind = match value {
+                    "server" => 2,
+                    "client" => 3,
+                    _ => 1,
+                }
+            }
+            "otel.status_code" => self.0.error = value.eq_ignore_ascii_case("error"),
+            "traceparent" => {
+                if self.0.parent_span_id.is_none()
+                    && let Some((trace_id, parent)) = parse_traceparent(value)
+                {
+                    self.0.trace_id = trace_id;
+                    self.0.parent_span_id = Some(parent);
+                }
+            }
+            _ => self.push(field, serde_json::json!({ "stringValue": value })),
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, serde_json::json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, serde_json::json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, serde_json::json!({ "boolValue": value }));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, serde_json::json!({ "doubleValue": value }));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{value:?}"));
+    }
+}
+
+enum Message {
+    Span(serde_json::Value),
+    Shutdown,
+}
+
+struct OtlpLayer {
+    tx: mpsc::Sender<Message>,
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent = span.parent().and_then(|p| {
+            p.extensions()
+                .get::<SpanData>()
+                .map(|d| (d.trace_id, d.span_id))
+        });
+        let mut data = SpanData {
+            trace_id: parent.map_or_else(
+                || u128::from(random_u64()) << 64 | u128::from(random_u64()),
+                |p| p.0,
+            ),
+            span_id: random_u64(),
+            parent_span_id: parent.map(|p| p.1),
+            name: attrs.metadata().name().to_string(),
+            kind: 1,
+            start_nanos: crate::unix_now_nanos(),
+            error: false,
+            attributes: Vec::new(),
+            events: Vec::new(),
+        };
+        attrs.record(&mut FieldVisitor(&mut data));
+        span.extensions_mut().insert(data);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: LayerContext<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(data) = span.extensions_mut().get_mut::<SpanData>()
+        {
+            values.record(&mut FieldVisitor(data));
+        }
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.get_mut::<SpanData>() else {
+            return;
+        };
+        // Collect the event's fields with a throwaway SpanData, then attach as an OTLP event
+        let mut fields = SpanData {
+            trace_id: 0,
+            span_id: 0,
+            parent_span_id: None,
+            name: event.metadata().name().to_string(),
+            kind: 0,
+            start_nanos: 0,
+            error: false,
+            attributes: Vec::new(),
+            events: Vec::new(),
+        };
+        event.record(&mut FieldVisitor(&mut fields));
+        let name = fields
+            .attributes
+            .iter()
+            .position(|a| a["key"] == "message")
+            .map(|i| fields.attributes.remove(i)["value"]["stringValue"].clone())
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or(fields.name);
+        data.events.push(serde_json::json!({
+            "timeUnixNano": crate::unix_now_nanos().to_string(),
+            "name": name,
+            "attributes": fields.attributes,
+        }));
+    }
+
+    fn on_close(&self, id: Id, ctx: LayerContext<'_, S>) {
+        if let Some(span) = ctx.span(&id)
+            && let Some(data) = span.extensions_mut().remove::<SpanData>()
+        {
+            let _ = self
+                .tx
+                .send(Message::Span(data.to_otlp(crate::unix_now_nanos())));
+        }
+    }
+}
+
+/// Flushes pending spans when dropped; keep it alive for the whole run.
+pub struct OtelGuard {
+    tx: mpsc::Sender<Message>,
+    worker: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Drop for OtelGuard {
+    fn drop(&mut self) {
+        let _ = self.tx.send(Message::Shutdown);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn traces_endpoint() -> Option<String> {
+    let non_empty = |var: &str| std::env::var(var).ok().filter(|v| !v.trim().is_empty());
+    non_empty("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").or_else(|| {
+        non_empty("OTEL_EXPORTER_OTLP_ENDPOINT")
+            .map(|base| format!("{}/v1/traces", base.trim_end_matches('/')))
+    })
+}
+
+/// `OTEL_EXPORTER_OTLP_HEADERS`: comma-separated `key=value` pairs.
+fn export_headers() -> Result<reqwest::header::HeaderMap> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    let raw = std::env::var("OTEL_EXPORTER_OTLP_HEADERS").unwrap_or_default();
+    for pair in raw.split(',').filter(|p| !p.trim().is_empty()) {
+        let (key, value) = pair
+            .split_once('=')
+            .with_context(|| format!("invalid OTEL_EXPORTER_OTLP_HEADERS entry: {pair}"))?;
+        headers.insert(
+            reqwest::header::HeaderName::from_bytes(key.trim().as_bytes())?,
+            value.trim().parse()?,
+        );
+    }
+    Ok(headers)
+}
+
+/// The OTLP export layer when an endpoint is configured; `None` when tracing
+/// export is off (the default) or the run is `--offline`. Installed by
+/// [`crate::logging::init`].
+pub(crate) fn layer<S>(offline: bool) -> Result<Option<(impl Layer<S>, OtelGuard)>>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    let Some(endpoint) = traces_endpoint() else {
+        return Ok(None);
+    };
+    if offline {
+        return Ok(None);
+    }
+    let headers = export_headers()?;
+    let service = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "fast-tts-cli".into());
+    let (tx, rx) = mpsc::channel();
+    let worker = std::thread::Builder::new()
+        .name("otlp-export".into())
+        .spawn(move || export_loop(rx, endpoint, headers, service))?;
+    // Only our own spans; dependency internals (hyper, h2) would just add noise
+    let layer =
+        OtlpLayer { tx: tx.clone() }.with_filter(tracing_subscriber::filter::filter_fn(|meta| {
+            meta.target().starts_with(env!("CARGO_CRATE_NAME"))
+        }));
+    Ok(Some((
+        layer,
+        OtelGuard {
+            tx,
+            worker: Some(worker),
+        },
+    )))
+}
+
+fn export_loop(
+    rx: mpsc::Receiver<Message>,
+    endpoint: String,
+    headers: reqwest::header::HeaderMap,
+    service: String,
+) {
+    let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    else {
+        return;
+    };
+    let mut builder = reqwest::Client::builder()
+        .timeout(EXPORT_TIMEOUT)
+        .default_headers(headers);
+    if endpoint.contains("127.0.0.1") || endpoint.contains("localhost") {
+        builder = builder.no_proxy();
+    }
+    let Ok(client) = builder.build() else {
+        return;
+    };
+    let export = |spans: Vec<serde_json::Value>| {
+        let body = serde_json::json!({
+            "resourceSpans": [{
+                "resource": { "attributes": [
+                    attribute("service.name", serde_json::json!({ "stringValue": service })),
+                    attribute("service.version", serde_json::json!({ "stringValue": env!("CARGO_PKG_VERSION") })),
+                ]},
+                "scopeSpans": [{ "scope": { "name": "fast-tts-cli" }, "spans": spans }],
+            }]
+        });
+        let result = runtime.block_on(async {
+            client
+                .post(&endpoint)
+                .json(&body)
+                .send()
+                .await?
+                .error_for_status()
+        });
+        if let Err(e) = result {
+            eprintln!("Warning: OTLP export failed: {e}");
+        }
+    };
+
+    let mut batch = Vec::new();
+    loop {
+        match rx.recv_timeout(FLUSH_INTERVAL) {
+            Ok(Message::Span(span)) => {
+                batch.push(span);
+                if batch.len() >= BATCH_SIZE {
+                    export(std::mem::take(&mut batch));
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) if !batch.is_empty() => {
+                export(std::mem::take(&mut batch));
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Ok(Message::Shutdown) | Err(mpsc::RecvTimeoutError::Disconnected) => {
+                if !batch.is_empty() {
+                    export(batch);
+                }
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,662 @@
+//! One [`TtsProvider`] per backend, looked up by [`Provider`] through [`get`],
+//! so the CLI, bulk mode and the servers dispatch the same way.
+
+use crate::{
+    AudioStream, FastTtsError, ListVoicesResponse, Provider, ProviderCapabilities,
+    SynthesisRequest, provider_credentials_present, provider_enabled,
+};
+use anyhow::{Context, Result};
+
+pub(crate) mod custom;
+pub(crate) mod kokoro;
+#[cfg(any(feature = "piper", feature = "kokoro"))]
+mod onnx;
+pub(crate) mod piper;
+pub(crate) mod system;
+#[cfg(feature = "wasm")]
+pub(crate) mod wasm;
+
+const ALL: &[&str] = &["LINEAR16", "MP3", "OGG_OPUS", "MULAW", "ALAW"];
+const COMMON: &[&str] = &["LINEAR16", "MP3", "OGG_OPUS"];
+const GOOGLE_EFFECTS_PROFILES: &[&str] = &[
+    "wearable-class-device",
+    "handset-class-device",
+    "headphone-class-device",
+    "small-bluetooth-speaker-class-device",
+    "medium-bluetooth-speaker-class-device",
+    "large-home-entertainment-class-device",
+    "large-automotive-class-device",
+    "telephony-class-application",
+];
+const GOOGLE_EFFECTS_ALIASES: &[(&str, &str)] = &[
+    ("phone", "handset-class-device"),
+    ("headphones", "headphone-class-device"),
+    ("car", "large-automotive-class-device"),
+    ("smart-speaker", "small-bluetooth-speaker-class-device"),
+];
+
+#[async_trait::async_trait]
+pub trait TtsProvider: Send + Sync {
+    fn id(&self) -> Provider;
+
+    /// Synthesize `job`, writing the audio to its output path.
+    async fn synthesize(&self, job: &SynthesisRequest) -> Result<()>;
+
+    /// Stream the audio for `job` without writing its output path. The default
+    /// synthesizes in full and yields the result in chunks.
+    async fn synthesize_stream(&self, job: &SynthesisRequest) -> Result<AudioStream> {
+        buffered_stream(self, job).await
+    }
+
+    /// The provider's voice catalog, for providers that publish one.
+    async fn list_voices(&self) -> Result<ListVoicesResponse> {
+        Err(FastTtsError::Unsupported(format!(
+            "provider {:?} does not offer a voice listing",
+            self.id()
+        ))
+        .into())
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities;
+}
+
+/// Build capabilities with the enabled/configured state filled in for `p`.
+fn capabilities(
+    p: Provider,
+    encodings: &[&'static str],
+    sample_rates: &[u32],
+    default_voice: &'static str,
+    voice_selection: &'static str,
+) -> ProviderCapabilities {
+    ProviderCapabilities {
+        provider: p.to_string(),
+        enabled: provider_enabled(p),
+        configured: provider_credentials_present(p),
+        encodings: encodings.to_vec(),
+        sample_rates: sample_rates.to_vec(),
+        default_voice,
+        voice_selection,
+        ssml_dialect: None,
+        max_input_bytes: None,
+        streaming: false,
+        timestamps: false,
+        seed: false,
+        multi_speaker: false,
+        rate_range: None,
+        pitch_range: None,
+        volume_range: None,
+        effects_profiles: Vec::new(),
+        effects_profile_aliases: Default::default(),
+        opus_bitrate: false,
+        idempotency_header: None,
+        usd_per_million_chars: None,
+    }
+}
+
+/// Synthesize to a temporary file, then replay it as fixed-size chunks.
+async fn buffered_stream<P: TtsProvider + ?Sized>(
+    provider: &P,
+    job: &SynthesisRequest,
+) -> Result<AudioStream> {
+    let mut temp = job.clone();
+    temp.output = std::env::temp_dir().join(format!(
+        "fast-tts-stream-{}-{}.{}",
+        std::process::id(),
+        crate::unix_now_nanos(),
+        job.encoding.file_extension()
+    ));
+    let data = match job
+        .cancel
+        .run_until_cancelled(provider.synthesize(&temp))
+        .await
+    {
+        Some(Ok(())) => std::fs::read(&temp.output)
+            .with_context(|| format!("failed to read {}", temp.output.display())),
+        Some(Err(e)) => Err(e),
+        None => Err(crate::cancelled()),
+    };
+    let _ = std::fs::remove_file(&temp.output);
+    let data = bytes::Bytes::from(data?);
+    let chunks: Vec<Result<bytes::Bytes>> = (0..data.len())
+        .step_by(crate::STREAM_BUFFER_BYTES)
+        .map(|start| Ok(data.slice(start..data.len().min(start + crate::STREAM_BUFFER_BYTES))))
+        .collect();
+    Ok(Box::pin(futures::stream::iter(chunks)))
+}
+
+struct Google;
+#[cfg(feature = "provider-gemini")]
+struct Gemini;
+#[cfg(feature = "provider-azure")]
+struct Azure;
+#[cfg(feature = "provider-openai")]
+struct Openai;
+#[cfg(feature = "provider-elevenlabs")]
+struct Elevenlabs;
+#[cfg(feature = "provider-deepgram")]
+struct Deepgram;
+#[cfg(feature = "provider-lmnt")]
+struct Lmnt;
+struct Polly;
+/// Providers that are listed but have no implementation yet.
+struct Unimplemented(Provider);
+
+/// Providers whose Cargo feature is off; dispatch reports which feature to enable.
+struct Disabled(Provider);
+
+static DISABLED: [Disabled; 8] = [
+    Disabled(Provider::Gemini),
+    Disabled(Provider::Azure),
+    Disabled(Provider::Openai),
+    Disabled(Provider::Elevenlabs),
+    Disabled(Provider::Deepgram),
+    Disabled(Provider::Lmnt),
+    Disabled(Provider::Piper),
+    Disabled(Provider::Kokoro),
+];
+static HUME: Unimplemented = Unimplemented(Provider::Hume);
+static LISTNR: Unimplemented = Unimplemented(Provider::Listnr);
+static MURF: Unimplemented = Unimplemented(Provider::Murf);
+
+/// The implementation behind `provider`.
+pub fn get(provider: Provider) -> &'static dyn TtsProvider {
+    match provider {
+        Provider::Google => &Google,
+        #[cfg(feature = "provider-gemini")]
+        Provider::Gemini => &Gemini,
+        #[cfg(feature = "provider-azure")]
+        Provider::Azure => &Azure,
+        #[cfg(feature = "provider-openai")]
+        Provider::Openai => &Openai,
+        #[cfg(feature = "provider-elevenlabs")]
+        Provider::Elevenlabs => &Elevenlabs,
+        #[cfg(feature = "provider-deepgram")]
+        Provider::Deepgram => &Deepgram,
+        #[cfg(feature = "provider-lmnt")]
+        Provider::Lmnt => &Lmnt,
+        Provider::Polly => &Polly,
+        #[cfg(feature = "piper")]
+        Provider::Piper => &piper::Piper,
+        #[cfg(feature = "kokoro")]
+        Provider::Kokoro => &kokoro::Kokoro,
+        Provider::System => &system::System,
+        Provider::Hume => &HUME,
+        Provider::Listnr => &LISTNR,
+        Provider::Murf => &MURF,
+        Provider::Custom(name) => custom::get(name),
+        #[cfg(feature = "wasm")]
+        Provider::Wasm(spec) => wasm::get(spec),
+        #[allow(unreachable_patterns)]
+        disabled => DISABLED
+            .iter()
+            .find(|d| d.0 == disabled)
+            .expect("every feature-gated provider has a stand-in"),
+    }
+}
+
+#[async_trait::async_trait]
+impl TtsProvider for Google {
+    fn id(&self) -> Provider {
+        Provider::Google
+    }
+
+    async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+        crate::synthesize_to_wav(
+            &job.text,
+            &job.output,
+            &job.language,
+            job.voice.as_deref(),
+            job.gender,
+            job.rate,
+            job.pitch,
+            job.sample_rate,
+            job.encoding,
+            job.volume_gain_db,
+            &job.effects_profile_id,
+            job.ssml,
+            &job.dialogue,
+            job.custom_voice_model.as_deref(),
+            job.timeout_ms,
+            job.retries,
+        )
+        .await
+    }
+
+    async fn list_voices(&self) -> Result<ListVoicesResponse> {
+        crate::fetch_google_voices().await
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            usd_per_million_chars: Some(16.0),
+            ssml_dialect: Some("google"),
+            max_input_bytes: Some(5000),
+            timestamps: true,
+            multi_speaker: true,
+            rate_range: Some((0.25, 4.0)),
+            pitch_range: Some((-20.0, 20.0)),
+            volume_range: Some((-96.0, 16.0)),
+            effects_profiles: GOOGLE_EFFECTS_PROFILES.to_vec(),
+            effects_profile_aliases: GOOGLE_EFFECTS_ALIASES.iter().copied().collect(),
+            ..capabilities(
+                self.id(),
+                ALL,
+                &[8000, 16000, 22050, 24000, 44100, 48000],
+                "",
+                "Voice name such as en-US-Neural2-F; when omitted Google picks one from language and gender",
+            )
+        }
+    }
+}
+
+#[cfg(feature = "provider-gemini")]
+#[async_trait::async_trait]
+impl TtsProvider for Gemini {
+    fn id(&self) -> Provider {
+        Provider::Gemini
+    }
+
+    async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+        crate::synthesize_gemini(&job.text, &job.output, job.voice.as_deref(), job.encoding).await
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        capabilities(
+            self.id(),
+            COMMON,
+            &[24000],
+            "",
+            "Prebuilt Gemini voice name; language is inferred from the text",
+        )
+    }
+}
+
+#[cfg(feature = "provider-azure")]
+async fn azure_response(job: &SynthesisRequest) -> Result<reqwest::Response> {
+    crate::azure_audio_response(
+        &job.text,
+        job.ssml,
+        &job.language,
+        job.voice.as_deref(),
+        job.encoding,
+        job.sample_rate,
+    )
+    .await
+}
+
+#[cfg(feature = "provider-azure")]
+#[async_trait::async_trait]
+impl TtsProvider for Azure {
+    fn id(&self) -> Provider {
+        Provider::Azure
+    }
+
+    async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+        let resp = azure_response(job).await?;
+        crate::stream_response_to_file(resp, &job.output).await?;
+        Ok(())
+    }
+
+    async fn synthesize_stream(&self, job: &SynthesisRequest) -> Result<AudioStream> {
+        Ok(crate::response_stream(azure_response(job).await?))
+    }
+
+    async fn list_voices(&self) -> Result<ListVoicesResponse> {
+        crate::fetch_azure_voices().await
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            usd_per_million_chars: Some(16.0),
+            ssml_dialect: Some("azure"),
+            streaming: true,
+            ..capabilities(
+                self.id(),
+                ALL,
+                &[8000, 24000, 48000],
+                "en-US-JennyNeural",
+                "Neural voice short name such as en-GB-LibbyNeural; default depends on language; MULAW/ALAW are 8 kHz",
+            )
+        }
+    }
+}
+
+/// Built-in OpenAI voices; `OPENAI_TTS_VOICES` (comma-separated) replaces
+/// the list for models or accounts with others.
+#[cfg(feature = "provider-openai")]
+const OPENAI_VOICES: &[&str] = &[
+    "alloy", "ash", "ballad", "coral", "echo", "fable", "nova", "onyx", "sage", "shimmer", "verse",
+];
+
+#[cfg(feature = "provider-openai")]
+fn openai_voices() -> Vec<String> {
+    match crate::config_var("OPENAI_TTS_VOICES").filter(|v| !v.trim().is_empty()) {
+        Some(list) => list
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+            .collect(),
+        None => OPENAI_VOICES.iter().map(|v| v.to_string()).collect(),
+    }
+}
+
+#[cfg(feature = "provider-openai")]
+async fn openai_response(job: &SynthesisRequest) -> Result<reqwest::Response> {
+    if let Some(voice) = job.voice.as_deref() {
+        let voices = openai_voices();
+        if !voices.iter().any(|v| v == voice) {
+            return Err(crate::suggest::unknown_voice(
+                "openai",
+                voice,
+                voices.iter().map(String::as_str),
+            ));
+        }
+    }
+    crate::openai_audio_response(&job.text, job.voice.as_deref(), job.encoding).await
+}
+
+#[cfg(feature = "provider-openai")]
+#[async_trait::async_trait]
+impl TtsProvider for Openai {
+    fn id(&self) -> Provider {
+        Provider::Openai
+    }
+
+    async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+        let resp = openai_response(job).await?;
+        crate::stream_response_to_file(resp, &job.output).await?;
+        Ok(())
+    }
+
+    async fn synthesize_stream(&self, job: &SynthesisRequest) -> Result<AudioStream> {
+        Ok(crate::response_stream(openai_response(job).await?))
+    }
+
+    async fn list_voices(&self) -> Result<ListVoicesResponse> {
+        // The voices speak every supported language
+        let voices = openai_voices()
+            .into_iter()
+            .map(|name| crate::Voice {
+                name,
+                language_codes: Vec::new(),
+                ssml_gender: "NEUTRAL".into(),
+                natural_sample_rate_hertz: Some(24000),
+                ..crate::Voice::default()
+            })
+            .collect();
+        Ok(ListVoicesResponse { voices })
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            usd_per_million_chars: Some(15.0),
+            max_input_bytes: Some(4096),
+            streaming: true,
+            ..capabilities(
+                self.id(),
+                COMMON,
+                &[24000],
+                "alloy",
+                "One of the built-in voices (see --list-voices): alloy, ash, ballad, coral, echo, fable, nova, onyx, sage, shimmer, verse",
+            )
+        }
+    }
+}
+
+/// Longer ElevenLabs input is sent in pieces of about this many characters;
+/// past it the voice tends to drift within a single request.
+#[cfg(feature = "provider-elevenlabs")]
+const ELEVENLABS_CHUNK_CHARS: usize = 2500;
+
+#[cfg(feature = "provider-elevenlabs")]
+async fn elevenlabs_response(
+    job: &SynthesisRequest,
+    text: &str,
+    context: crate::ElevenlabsContext<'_>,
+) -> Result<reqwest::Response> {
+    crate::elevenlabs_audio_response(
+        text,
+        job.voice.as_deref(),
+        job.encoding,
+        crate::config_var("ELEVENLABS_MODEL_ID").as_deref(),
+        job.seed,
+        context,
+    )
+    .await
+}
+
+#[cfg(feature = "provider-elevenlabs")]
+#[async_trait::async_trait]
+impl TtsProvider for Elevenlabs {
+    fn id(&self) -> Provider {
+        Provider::Elevenlabs
+    }
+
+    async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+        let chunks = crate::chunk::split(&job.text, ELEVENLABS_CHUNK_CHARS);
+        if chunks.len() < 2 {
+            let resp = elevenlabs_response(job, &job.text, Default::default()).await?;
+            crate::stream_response_to_file(resp, &job.output).await?;
+            return Ok(());
+        }
+        // Each piece hears its neighbours so prosody carries across the joins
+        let mut parts = Vec::with_capacity(chunks.len());
+        for (idx, text) in chunks.iter().enumerate() {
+            let context = crate::ElevenlabsContext {
+                previous: idx.checked_sub(1).map(|i| chunks[i]),
+                next: chunks.get(idx + 1).copied(),
+            };
+            let resp = elevenlabs_response(job, text, context)
+                .await
+                .with_context(|| format!("chunk {} of {}", idx + 1, chunks.len()))?;
+            parts.push(resp.bytes().await?.to_vec());
+        }
+        let joined = crate::audio::concat(&parts, job.encoding, job.crossfade_ms)?;
+        std::fs::write(&job.output, joined)
+            .with_context(|| format!("failed to write {}", job.output.display()))?;
+        Ok(())
+    }
+
+    async fn synthesize_stream(&self, job: &SynthesisRequest) -> Result<AudioStream> {
+        if job.text.chars().count() > ELEVENLABS_CHUNK_CHARS {
+            // The pieces must be joined before the audio is playable
+            return buffered_stream(self, job).await;
+        }
+        Ok(crate::response_stream(
+            elevenlabs_response(job, &job.text, Default::default()).await?,
+        ))
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            usd_per_million_chars: Some(180.0),
+            max_input_bytes: Some(10_000),
+            streaming: true,
+            seed: true,
+            ..capabilities(
+                self.id(),
+                COMMON,
+                &[44100],
+                "Rachel",
+                "Voice ID from the ElevenLabs voice library",
+            )
+        }
+    }
+}
+
+#[cfg(feature = "provider-deepgram")]
+async fn deepgram_response(job: &SynthesisRequest) -> Result<reqwest::Response> {
+    crate::deepgram_audio_response(
+        &job.text,
+        job.voice.as_deref(),
+        job.encoding,
+        crate::config_var("DEEPGRAM_TTS_MODEL").as_deref(),
+        job.opus.bitrate,
+    )
+    .await
+}
+
+#[cfg(feature = "provider-deepgram")]
+#[async_trait::async_trait]
+impl TtsProvider for Deepgram {
+    fn id(&self) -> Provider {
+        Provider::Deepgram
+    }
+
+    async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+        let resp = deepgram_response(job).await?;
+        crate::stream_response_to_file(resp, &job.output).await?;
+        Ok(())
+    }
+
+    async fn synthesize_stream(&self, job: &SynthesisRequest) -> Result<AudioStream> {
+        Ok(crate::response_stream(deepgram_response(job).await?))
+    }
+
+    async fn list_voices(&self) -> Result<ListVoicesResponse> {
+        crate::fetch_deepgram_voices().await
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            usd_per_million_chars: Some(15.0),
+            max_input_bytes: Some(2000),
+            streaming: true,
+            opus_bitrate: true,
+            ..capabilities(
+                self.id(),
+                COMMON,
+                &[24000],
+                "aura-asteria-en",
+                "Aura model name such as aura-asteria-en",
+            )
+        }
+    }
+}
+
+#[cfg(feature = "provider-lmnt")]
+#[async_trait::async_trait]
+impl TtsProvider for Lmnt {
+    fn id(&self) -> Provider {
+        Provider::Lmnt
+    }
+
+    async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+        let audio = crate::lmnt_audio(
+            &job.text,
+            job.voice.as_deref(),
+            job.encoding,
+            job.sample_rate,
+            job.rate,
+            job.seed,
+        )
+        .await?;
+        std::fs::write(&job.output, audio)
+            .with_context(|| format!("failed to write {}", job.output.display()))
+    }
+
+    async fn list_voices(&self) -> Result<ListVoicesResponse> {
+        crate::fetch_lmnt_voices().await
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            max_input_bytes: Some(5000),
+            seed: true,
+            rate_range: Some((0.25, 2.0)),
+            ..capabilities(
+                self.id(),
+                &["LINEAR16", "MP3"],
+                &[8000, 16000, 24000],
+                "lily",
+                "Voice id from the LMNT library or one of your clones (see --list-voices)",
+            )
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TtsProvider for Polly {
+    fn id(&self) -> Provider {
+        Provider::Polly
+    }
+
+    async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+        #[cfg(feature = "polly")]
+        {
+            crate::synthesize_polly(&job.text, &job.output, job.voice.as_deref(), job.encoding)
+                .await
+        }
+        #[cfg(not(feature = "polly"))]
+        {
+            let _ = job;
+            Err(
+                FastTtsError::Unsupported("Amazon Polly support requires --features polly".into())
+                    .into(),
+            )
+        }
+    }
+
+    async fn list_voices(&self) -> Result<ListVoicesResponse> {
+        #[cfg(feature = "polly")]
+        {
+            crate::fetch_polly_voices().await
+        }
+        #[cfg(not(feature = "polly"))]
+        {
+            Err(
+                FastTtsError::Unsupported("Amazon Polly support requires --features polly".into())
+                    .into(),
+            )
+        }
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            usd_per_million_chars: Some(16.0),
+            max_input_bytes: Some(3000),
+            ..capabilities(
+                self.id(),
+                COMMON,
+                &[8000, 16000, 22050, 24000],
+                "Joanna",
+                "Polly voice ID such as Joanna or Matthew (engine from POLLY_ENGINE, default neural)",
+            )
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TtsProvider for Unimplemented {
+    fn id(&self) -> Provider {
+        self.0
+    }
+
+    async fn synthesize(&self, _job: &SynthesisRequest) -> Result<()> {
+        Err(FastTtsError::Unsupported(format!(
+            "provider {} not yet implemented. Please open an issue with API details.",
+            self.0
+        ))
+        .into())
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        capabilities(self.id(), &[], &[], "", "not implemented")
+    }
+}
+
+#[async_trait::async_trait]
+impl TtsProvider for Disabled {
+    fn id(&self) -> Provider {
+        self.0
+    }
+
+    async fn synthesize(&self, _job: &SynthesisRequest) -> Result<()> {
+        crate::ensure_provider_enabled(self.0)
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        capabilities(self.id(), &[], &[], "", "not compiled into this build")
+    }
+}
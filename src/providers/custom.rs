@@ -0,0 +1,398 @@
+//! Providers described by TOML templates, one file per provider in
+//! `<config dir>/fast-tts-cli/providers/<name>.toml` (or `FAST_TTS_PROVIDERS_DIR`),
+//! selected as `--provider custom:<name>`.
+
+use super::{TtsProvider, buffered_stream, capabilities};
+use crate::{
+    AudioEncoding, AudioStream, FastTtsError, Provider, ProviderCapabilities, SynthesisRequest,
+    parse_encoding_from_str,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Template {
+    /// Request URL; placeholders are percent-encoded
+    url: String,
+    #[serde(default = "default_method")]
+    method: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// Variable holding the secret, available to templates as `{api_key}`
+    auth_env: Option<String>,
+    /// JSON body; a string that is exactly one placeholder takes the value's type
+    body: Option<serde_json::Value>,
+    /// Encoding name (MP3, LINEAR16, ...) to the service's value for `{format}`
+    formats: BTreeMap<String, String>,
+    /// JSON field with base64 audio; without it the response body is the audio
+    audio_field: Option<String>,
+    default_voice: Option<String>,
+    voice_selection: Option<String>,
+    /// Header the service de-duplicates retries by, e.g. `Idempotency-Key`
+    idempotency_header: Option<String>,
+    /// `[min, max]` the service accepts for `{rate}`; required when it's used
+    rate_range: Option<(f32, f32)>,
+    /// `[min, max]` the service accepts for `{pitch}`; required when it's used
+    pitch_range: Option<(f32, f32)>,
+}
+
+fn default_method() -> String {
+    "POST".into()
+}
+
+impl Template {
+    /// Whether the URL, a header or the body contains placeholder `{name}`.
+    fn uses(&self, name: &str) -> bool {
+        let placeholder = format!("{{{name}}}");
+        self.url.contains(&placeholder)
+            || self.headers.values().any(|v| v.contains(&placeholder))
+            || self
+                .body
+                .as_ref()
+                .is_some_and(|b| b.to_string().contains(&placeholder))
+    }
+}
+
+/// A loaded template; leaked so it can back `Provider::Custom(&'static str)`.
+pub(crate) struct TemplateProvider {
+    name: &'static str,
+    template: Template,
+    formats: Vec<(AudioEncoding, String)>,
+    encodings: Vec<&'static str>,
+    default_voice: &'static str,
+    voice_selection: &'static str,
+    idempotency_header: Option<&'static str>,
+}
+
+static LOADED: Mutex<Option<HashMap<String, &'static TemplateProvider>>> = Mutex::new(None);
+
+fn providers_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("FAST_TTS_PROVIDERS_DIR").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    dirs::config_dir().map(|d| d.join("fast-tts-cli").join("providers"))
+}
+
+/// Load (once) the template for `name`.
+pub(crate) fn load(name: &str) -> Result<&'static TemplateProvider> {
+    if let Some(loaded) = lookup(name) {
+        return Ok(loaded);
+    }
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(
+            FastTtsError::InvalidInput(format!("invalid custom provider name: {name:?}")).into(),
+        );
+    }
+    let dir = providers_dir().context("cannot determine config directory for custom providers")?;
+    let path = dir.join(format!("{name}.toml"));
+    let data = std::fs::read_to_string(&path).map_err(|e| {
+        FastTtsError::InvalidInput(format!(
+            "custom provider {name:?} not found ({}: {e})",
+            path.display()
+        ))
+    })?;
+    let template: Template = toml::from_str(&data).map_err(|e| {
+        FastTtsError::InvalidInput(format!("invalid provider template {}: {e}", path.display()))
+    })?;
+    let provider = TemplateProvider::new(name, template)
+        .with_context(|| format!("invalid provider template {}", path.display()))?;
+
+    let mut loaded = LOADED.lock().unwrap_or_else(|e| e.into_inner());
+    let map = loaded.get_or_insert_with(HashMap::new);
+    let provider = *map
+        .entry(name.to_string())
+        .or_insert_with(|| Box::leak(Box::new(provider)));
+    Ok(provider)
+}
+
+fn lookup(name: &str) -> Option<&'static TemplateProvider> {
+    let loaded = LOADED.lock().unwrap_or_else(|e| e.into_inner());
+    loaded.as_ref()?.get(name).copied()
+}
+
+/// Stand-in for a `Provider::Custom` whose template failed to load; every call
+/// reports the load error.
+struct Missing {
+    name: &'static str,
+    error: String,
+}
+
+static MISSING: Mutex<Option<HashMap<String, &'static Missing>>> = Mutex::new(None);
+
+pub(crate) fn get(name: &'static str) -> &'static dyn TtsProvider {
+    let error = match load(name) {
+        Ok(template) => return template,
+        Err(e) => format!("{e:#}"),
+    };
+    let mut missing = MISSING.lock().unwrap_or_else(|e| e.into_inner());
+    *missing
+        .get_or_insert_with(HashMap::new)
+        .entry(name.to_string())
+        .or_insert_with(|| Box::leak(Box::new(Missing { name, error })))
+}
+
+#[async_trait::async_trait]
+impl TtsProvider for Missing {
+    fn id(&self) -> Provider {
+        Provider::Custom(self.name)
+    }
+
+    async fn synthesize(&self, _job: &SynthesisRequest) -> Result<()> {
+        Err(FastTtsError::InvalidInput(self.error.clone()).into())
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        capabilities(self.id(), &[], &[], "", "template failed to load")
+    }
+}
+
+/// Every template in the providers directory that loads, sorted by name.
+pub(crate) fn all() -> Vec<Provider> {
+    let Some(entries) = providers_dir().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let path = e.path();
+            (path.extension()? == "toml").then(|| path.file_stem()?.to_str().map(String::from))?
+        })
+        .collect();
+    names.sort();
+    names
+        .iter()
+        .filter_map(|name| load(name).ok())
+        .map(|t| t.id())
+        .collect()
+}
+
+impl TemplateProvider {
+    fn new(name: &str, template: Template) -> Result<Self> {
+        if template.formats.is_empty() {
+            anyhow::bail!("formats must map at least one encoding");
+        }
+        let formats = template
+            .formats
+            .iter()
+            .map(|(enc, value)| Ok((parse_encoding_from_str(enc)?, value.clone())))
+            .collect::<Result<Vec<_>>>()?;
+        let method = template.method.to_uppercase();
+        if method != "POST" && method != "GET" {
+            anyhow::bail!("method must be POST or GET, not {}", template.method);
+        }
+        for (name, range) in [
+            ("rate", template.rate_range),
+            ("pitch", template.pitch_range),
+        ] {
+            match range {
+                Some((min, max)) if min.is_nan() || max.is_nan() || min > max => {
+                    anyhow::bail!("{name}_range must be [min, max], got [{min}, {max}]")
+                }
+                None if template.uses(name) => {
+                    anyhow::bail!("{name}_range is required when the template uses {{{name}}}")
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            name: leak(name.to_string()),
+            encodings: formats.iter().map(|(enc, _)| enc.api_str()).collect(),
+            formats,
+            default_voice: leak(template.default_voice.clone().unwrap_or_default()),
+            voice_selection: leak(
+                template
+                    .voice_selection
+                    .clone()
+                    .unwrap_or_else(|| "Passed through as {voice}".into()),
+            ),
+            idempotency_header: template.idempotency_header.clone().map(leak),
+            template,
+        })
+    }
+
+    pub(crate) fn auth_env(&self) -> Option<&str> {
+        self.template.auth_env.as_deref()
+    }
+
+    /// Placeholder values for `job`.
+    fn values(&self, job: &SynthesisRequest) -> Result<Vec<(&'static str, serde_json::Value)>> {
+        let format = self
+            .formats
+            .iter()
+            .find(|(enc, _)| *enc == job.encoding)
+            .map(|(_, value)| value.clone())
+            .ok_or_else(|| {
+                FastTtsError::InvalidInput(format!(
+                    "provider custom:{} has no format for {}",
+                    self.name,
+                    job.encoding.api_str()
+                ))
+            })?;
+        let api_key = match self.auth_env() {
+            Some(var) => crate::provider_secret(var, &format!("custom:{}", self.name))?,
+            None => String::new(),
+        };
+        Ok(vec![
+            ("text", job.text.clone().into()),
+            (
+                "voice",
+                job.voice.as_deref().unwrap_or(self.default_voice).into(),
+            ),
+            ("language", job.language.clone().into()),
+            ("format", format.into()),
+            ("encoding", job.encoding.api_str().into()),
+            ("rate", job.rate.into()),
+            ("pitch", job.pitch.into()),
+            ("api_key", api_key.into()),
+        ])
+    }
+
+    async fn send(&self, job: &SynthesisRequest) -> Result<reqwest::Response> {
+        let values = self.values(job)?;
+        let url = substitute(&self.template.url, &values, percent_encode);
+        let client = crate::build_http_client_for_base(&url)?;
+        let mut req = if self.template.method.eq_ignore_ascii_case("GET") {
+            client.get(&url)
+        } else {
+            client.post(&url)
+        };
+        for (name, value) in &self.template.headers {
+            let filled = substitute(value, &values, str::to_string);
+            // Whatever header carries the key is redacted from --debug-http
+            req = if value.contains("{api_key}") {
+                req.header(name, crate::secret_header(&filled)?)
+            } else {
+                req.header(name, filled)
+            };
+        }
+        if let Some(name) = self.idempotency_header {
+            req = req.header(name, job.idempotency_key());
+        }
+        if let Some(body) = &self.template.body {
+            req = req.json(&fill_json(body, &values));
+        }
+        Ok(crate::send_http(req).await?.error_for_status()?)
+    }
+}
+
+fn leak(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+fn as_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Replace each `{name}` in `template`, passing values through `encode`. One
+/// left-to-right pass: filled-in values are never scanned again, so a
+/// `{api_key}` in the user's text stays literal.
+fn substitute(
+    template: &str,
+    values: &[(&str, serde_json::Value)],
+    encode: impl Fn(&str) -> String,
+) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let found = after.find('}').and_then(|end| {
+            let value = values.iter().find(|(name, _)| *name == &after[..end])?;
+            Some((end, &value.1))
+        });
+        match found {
+            Some((end, value)) => {
+                out.push_str(&encode(&as_text(value)));
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn fill_json(body: &serde_json::Value, values: &[(&str, serde_json::Value)]) -> serde_json::Value {
+    use serde_json::Value;
+    match body {
+        Value::String(s) => values
+            .iter()
+            .find(|(name, _)| *s == format!("{{{name}}}"))
+            .map(|(_, value)| value.clone())
+            .unwrap_or_else(|| Value::String(substitute(s, values, str::to_string))),
+        Value::Array(items) => Value::Array(items.iter().map(|v| fill_json(v, values)).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), fill_json(v, values)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+pub(crate) fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl TtsProvider for TemplateProvider {
+    fn id(&self) -> Provider {
+        Provider::Custom(self.name)
+    }
+
+    async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+        let resp = self.send(job).await?;
+        match &self.template.audio_field {
+            Some(field) => {
+                crate::stream_base64_field_to_file(resp, &job.output, &[field.as_str()]).await?
+            }
+            None => crate::stream_response_to_file(resp, &job.output).await?,
+        };
+        Ok(())
+    }
+
+    async fn synthesize_stream(&self, job: &SynthesisRequest) -> Result<AudioStream> {
+        if self.template.audio_field.is_some() {
+            return buffered_stream(self, job).await;
+        }
+        Ok(crate::response_stream(self.send(job).await?))
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: self.template.audio_field.is_none(),
+            rate_range: self.template.rate_range,
+            pitch_range: self.template.pitch_range,
+            idempotency_header: self.idempotency_header,
+            ..capabilities(
+                self.id(),
+                &self.encodings,
+                &[],
+                self.default_voice,
+                self.voice_selection,
+            )
+        }
+    }
+}
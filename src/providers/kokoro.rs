@@ -0,0 +1,380 @@
+//! Offline synthesis with Kokoro-82M, run with ONNX Runtime. Everything lives
+//! in one directory (`KOKORO_DIR`, by default `<data dir>/fast-tts-cli/kokoro`):
+//! the model (`*.onnx`, several exports side by side are told apart with
+//! `--model`), its vocabulary (`config.json` or `tokenizer.json`) and one
+//! voicepack per voice as `voices/<voice>.bin`. Text is turned into phonemes
+//! by espeak-ng in the voice's language.
+
+use crate::FastTtsError;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+pub(crate) fn dir() -> Option<PathBuf> {
+    if let Some(dir) = crate::config_var("KOKORO_DIR").filter(|d| !d.trim().is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    dirs::data_dir().map(|d| d.join("fast-tts-cli").join("kokoro"))
+}
+
+fn display_dir() -> String {
+    dir().map_or_else(|| "KOKORO_DIR".into(), |d| d.display().to_string())
+}
+
+/// Files in `dir` with extension `ext`, by stem.
+fn files(dir: &Path, ext: &str) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(String, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == ext))
+        .filter_map(|path| Some((path.file_stem()?.to_str()?.to_string(), path)))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Installed voicepacks, by voice name.
+pub(crate) fn voices() -> Vec<(String, PathBuf)> {
+    dir().map_or_else(Vec::new, |dir| files(&dir.join("voices"), "bin"))
+}
+
+/// The model to use: `model`, else `KOKORO_MODEL`, else the only `.onnx` in
+/// the directory. A name without a path separator or `.onnx` is looked up
+/// there.
+pub(crate) fn resolve(model: Option<&str>) -> Result<PathBuf> {
+    let models = dir().map_or_else(Vec::new, |dir| files(&dir, "onnx"));
+    let model = model
+        .map(str::to_string)
+        .or_else(|| crate::config_var("KOKORO_MODEL").filter(|m| !m.trim().is_empty()));
+    let Some(model) = model else {
+        return match models.as_slice() {
+            [(_, path)] => Ok(path.clone()),
+            [] => Err(FastTtsError::InvalidInput(format!(
+                "no Kokoro model in {}; download kokoro-v1.0.onnx (or another export) there, or pass --model PATH",
+                display_dir()
+            ))
+            .into()),
+            several => Err(FastTtsError::InvalidInput(format!(
+                "several Kokoro models are in {}; pick one with --model or KOKORO_MODEL ({})",
+                display_dir(),
+                several
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+            .into()),
+        };
+    };
+    if model.ends_with(".onnx") || model.contains(std::path::is_separator) {
+        let path = PathBuf::from(&model);
+        if !path.is_file() {
+            return Err(FastTtsError::InvalidInput(format!(
+                "Kokoro model file {} not found",
+                path.display()
+            ))
+            .into());
+        }
+        return Ok(path);
+    }
+    match models.iter().find(|(name, _)| *name == model) {
+        Some((_, path)) => Ok(path.clone()),
+        None => {
+            let names = models.iter().map(|(name, _)| name.as_str());
+            let hint = match crate::suggest::closest(&model, names).as_slice() {
+                [] => String::new(),
+                [name, ..] => format!("; did you mean '{name}'?"),
+            };
+            Err(FastTtsError::InvalidInput(format!(
+                "no Kokoro model {model} in {}{hint}",
+                display_dir()
+            ))
+            .into())
+        }
+    }
+}
+
+/// Whether a model and at least one voicepack are installed.
+pub(crate) fn configured() -> bool {
+    resolve(None).is_ok() && !voices().is_empty()
+}
+
+/// `auth check` for Kokoro: there are no credentials, only model files.
+pub(crate) fn auth_status() -> crate::AuthStatus {
+    match resolve(None) {
+        Err(e) => crate::AuthStatus::Missing(format!("{e:#}")),
+        Ok(_) if voices().is_empty() => {
+            crate::AuthStatus::Missing(format!("no Kokoro voicepacks in {}/voices", display_dir()))
+        }
+        Ok(_) => crate::AuthStatus::Ok,
+    }
+}
+
+#[cfg(feature = "kokoro")]
+pub(crate) use engine::Kokoro;
+
+#[cfg(feature = "kokoro")]
+mod engine {
+    use super::{dir, display_dir, resolve, voices};
+    use crate::providers::{TtsProvider, capabilities, onnx};
+    use crate::{
+        FastTtsError, ListVoicesResponse, Provider, ProviderCapabilities, SynthesisRequest,
+    };
+    use anyhow::{Context, Result};
+    use ort::session::Session;
+    use ort::tensor::TensorElementType;
+    use ort::value::Tensor;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    pub(crate) struct Kokoro;
+
+    /// Voice used without `--voice`
+    const DEFAULT_VOICE: &str = "af_heart";
+    const SAMPLE_RATE: u32 = 24000;
+    /// Tokens the model takes at once, not counting the padding at either end
+    const MAX_TOKENS: usize = 510;
+    /// Width of a voicepack row (the style vector)
+    const STYLE_DIM: usize = 256;
+
+    /// The vocabulary as `config.json` (hexgrad's release) or
+    /// `tokenizer.json` (the ONNX community export) has it.
+    #[derive(Debug, Deserialize)]
+    struct VocabFile {
+        #[serde(default)]
+        vocab: Option<HashMap<String, i64>>,
+        #[serde(default)]
+        model: Option<TokenizerModel>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct TokenizerModel {
+        vocab: HashMap<String, i64>,
+    }
+
+    /// espeak-ng voice and language code for a Kokoro voice, from the first
+    /// letter of its name (`af_heart` is American English).
+    fn language(voice: &str) -> Option<(&'static str, &'static str)> {
+        Some(match voice.chars().next()? {
+            'a' => ("en-us", "en-US"),
+            'b' => ("en-gb", "en-GB"),
+            'e' => ("es", "es-ES"),
+            'f' => ("fr-fr", "fr-FR"),
+            'h' => ("hi", "hi-IN"),
+            'i' => ("it", "it-IT"),
+            'j' => ("ja", "ja-JP"),
+            'p' => ("pt-br", "pt-BR"),
+            'z' => ("cmn", "cmn-CN"),
+            _ => return None,
+        })
+    }
+
+    fn load_vocab() -> Result<HashMap<String, i64>> {
+        let dir = dir().context("no data directory; set KOKORO_DIR")?;
+        for name in ["config.json", "tokenizer.json"] {
+            let path = dir.join(name);
+            let Ok(raw) = std::fs::read(&path) else {
+                continue;
+            };
+            let file: VocabFile = serde_json::from_slice(&raw)
+                .with_context(|| format!("invalid Kokoro vocabulary {}", path.display()))?;
+            if let Some(vocab) = file.vocab.or(file.model.map(|m| m.vocab)) {
+                return Ok(vocab);
+            }
+        }
+        Err(FastTtsError::InvalidInput(format!(
+            "no Kokoro vocabulary in {}; put the model's config.json or tokenizer.json there",
+            display_dir()
+        ))
+        .into())
+    }
+
+    /// A voicepack: one style vector per utterance length, as little-endian f32.
+    fn load_voice(name: &str) -> Result<Vec<f32>> {
+        let installed = voices();
+        let Some((_, path)) = installed.iter().find(|(voice, _)| voice == name) else {
+            return Err(crate::suggest::unknown_voice(
+                "kokoro",
+                name,
+                installed.iter().map(|(voice, _)| voice.as_str()),
+            ));
+        };
+        let raw =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        if raw.is_empty() || raw.len() % (STYLE_DIM * 4) != 0 {
+            anyhow::bail!(
+                "{} is not a Kokoro voicepack (rows of {STYLE_DIM} f32 values)",
+                path.display()
+            );
+        }
+        Ok(raw
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+
+    /// Token sequences of at most [`MAX_TOKENS`], filled clause by clause.
+    /// Phonemes missing from the vocabulary are dropped.
+    fn chunks(clauses: &[String], vocab: &HashMap<String, i64>) -> Vec<Vec<i64>> {
+        let space = vocab.get(" ").copied();
+        let mut chunks: Vec<Vec<i64>> = Vec::new();
+        let mut current: Vec<i64> = Vec::new();
+        for clause in clauses {
+            let tokens: Vec<i64> = clause
+                .chars()
+                .filter_map(|c| vocab.get(c.encode_utf8(&mut [0; 4]) as &str).copied())
+                .collect();
+            for piece in tokens.chunks(MAX_TOKENS) {
+                let joined = current.len() + space.iter().len() + piece.len();
+                if !current.is_empty() && joined > MAX_TOKENS {
+                    chunks.push(std::mem::take(&mut current));
+                }
+                if !current.is_empty() {
+                    current.extend(space);
+                }
+                current.extend(piece);
+            }
+        }
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+        chunks
+    }
+
+    fn infer(session: &mut Session, tokens: &[i64], style: &[f32], speed: f32) -> Result<Vec<f32>> {
+        let ort_err = |e: ort::Error| anyhow::anyhow!("Kokoro inference failed: {e}");
+        let mut ids = Vec::with_capacity(tokens.len() + 2);
+        ids.push(0);
+        ids.extend(tokens);
+        ids.push(0);
+        // Exports call the token input `tokens` or `input_ids`, and some take
+        // the speed as an integer
+        let token_input = session
+            .inputs
+            .iter()
+            .map(|input| input.name.clone())
+            .find(|name| name == "input_ids")
+            .unwrap_or_else(|| "tokens".into());
+        let integer_speed = session.inputs.iter().any(|input| {
+            input.name == "speed"
+                && input.input_type.tensor_type() == Some(TensorElementType::Int32)
+        });
+        let len = ids.len();
+        let mut inputs = ort::inputs![
+            token_input => Tensor::from_array(([1usize, len], ids)).map_err(ort_err)?,
+            "style" => Tensor::from_array(([1usize, STYLE_DIM], style.to_vec())).map_err(ort_err)?,
+        ];
+        let speed = if integer_speed {
+            Tensor::from_array(([1usize], vec![speed.round() as i32]))
+                .map_err(ort_err)?
+                .into()
+        } else {
+            Tensor::from_array(([1usize], vec![speed]))
+                .map_err(ort_err)?
+                .into()
+        };
+        inputs.push(("speed".into(), speed));
+        let outputs = session.run(inputs).map_err(ort_err)?;
+        let (_, samples) = outputs[0].try_extract_tensor::<f32>().map_err(ort_err)?;
+        Ok(samples.to_vec())
+    }
+
+    /// Render `job` as 16-bit WAV.
+    fn render(job: &SynthesisRequest) -> Result<Vec<u8>> {
+        if let Some(hz) = job.sample_rate
+            && u32::try_from(hz).ok() != Some(SAMPLE_RATE)
+        {
+            return Err(FastTtsError::InvalidInput(format!(
+                "Kokoro produces {SAMPLE_RATE} Hz audio, not {hz}"
+            ))
+            .into());
+        }
+        let model = resolve(job.model.as_deref())?;
+        let voice = job.voice.as_deref().unwrap_or(DEFAULT_VOICE);
+        let pack = load_voice(voice)?;
+        let Some((espeak_voice, _)) = language(voice) else {
+            return Err(FastTtsError::InvalidInput(format!(
+                "can't tell the language of Kokoro voice {voice}; voice names start with a language letter, as in af_heart"
+            ))
+            .into());
+        };
+        let vocab = load_vocab()?;
+        let clauses = onnx::espeak("Kokoro", &job.text, espeak_voice)?;
+        let session = onnx::session("Kokoro", &model)?;
+        let rows = pack.len() / STYLE_DIM;
+        let mut samples = Vec::new();
+        for tokens in chunks(&clauses, &vocab) {
+            if job.cancel.is_cancelled() {
+                return Err(crate::cancelled());
+            }
+            // The style vector is picked by utterance length
+            let row = tokens.len().min(rows - 1);
+            let style = &pack[row * STYLE_DIM..(row + 1) * STYLE_DIM];
+            samples.extend(infer(
+                &mut session.lock().unwrap(),
+                &tokens,
+                style,
+                job.rate,
+            )?);
+        }
+        Ok(crate::audio::DecodedAudio {
+            sample_rate: SAMPLE_RATE,
+            channels: 1,
+            samples,
+        }
+        .to_wav16())
+    }
+
+    #[async_trait::async_trait]
+    impl TtsProvider for Kokoro {
+        fn id(&self) -> Provider {
+            Provider::Kokoro
+        }
+
+        async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+            let job = job.clone();
+            onnx::run_blocking(move || onnx::write_output("Kokoro", &job, &render(&job)?)).await
+        }
+
+        async fn list_voices(&self) -> Result<ListVoicesResponse> {
+            let voices = voices()
+                .into_iter()
+                .map(|(name, _)| {
+                    // The second letter is the voice's gender: af_heart, am_adam
+                    let ssml_gender = match name.chars().nth(1) {
+                        Some('f') => "FEMALE",
+                        Some('m') => "MALE",
+                        _ => "NEUTRAL",
+                    };
+                    crate::Voice {
+                        language_codes: language(&name)
+                            .map(|(_, code)| code.to_string())
+                            .into_iter()
+                            .collect(),
+                        name,
+                        ssml_gender: ssml_gender.into(),
+                        natural_sample_rate_hertz: Some(SAMPLE_RATE as i32),
+                        ..crate::Voice::default()
+                    }
+                })
+                .collect();
+            Ok(ListVoicesResponse { voices })
+        }
+
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                usd_per_million_chars: Some(0.0),
+                rate_range: Some((0.5, 2.0)),
+                ..capabilities(
+                    self.id(),
+                    &["LINEAR16", "MP3"],
+                    &[24000],
+                    DEFAULT_VOICE,
+                    "Voicepack name from the voices directory, e.g. af_heart or bm_george",
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! What the local providers (Piper, Kokoro) share: ONNX Runtime sessions,
+//! espeak-ng phonemes and writing the rendered samples out.
+
+use crate::{AudioEncoding, FastTtsError, SynthesisRequest};
+use anyhow::{Context, Result};
+use ort::session::Session;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Loaded models, kept so a server doesn't reload one per request.
+static SESSIONS: LazyLock<Mutex<HashMap<PathBuf, Arc<Mutex<Session>>>>> =
+    LazyLock::new(Default::default);
+
+/// The session for the `provider` model at `model`, loaded on first use.
+pub(crate) fn session(provider: &str, model: &Path) -> Result<Arc<Mutex<Session>>> {
+    if let Some(session) = SESSIONS.lock().unwrap().get(model) {
+        return Ok(session.clone());
+    }
+    // Loaded without the lock held: a runtime that fails to load panics
+    let session = Session::builder()
+        .and_then(|builder| builder.commit_from_file(model))
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| format!("failed to load {provider} model {}", model.display()))?;
+    let session = Arc::new(Mutex::new(session));
+    SESSIONS
+        .lock()
+        .unwrap()
+        .insert(model.to_path_buf(), session.clone());
+    Ok(session)
+}
+
+/// IPA phonemes of `text` in espeak-ng `voice`, one line per clause as
+/// espeak-ng splits them (`FAST_TTS_ESPEAK` names another binary).
+pub(crate) fn espeak(provider: &str, text: &str, voice: &str) -> Result<Vec<String>> {
+    let espeak = crate::config_var("FAST_TTS_ESPEAK").unwrap_or_else(|| "espeak-ng".into());
+    let mut child = std::process::Command::new(&espeak)
+        .args(["-q", "--ipa", "-v", voice, "--stdin"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FastTtsError::Unsupported(format!(
+                "{provider} models read phonemes from espeak-ng, and {espeak} was not found; install espeak-ng or set FAST_TTS_ESPEAK"
+            ))
+            .into(),
+            _ => anyhow::Error::from(e).context(format!("failed to run {espeak}")),
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{espeak} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Encode the WAV at `wav` to MP3 at `output` with ffmpeg (`FAST_TTS_FFMPEG`).
+fn encode_mp3(provider: &str, wav: &Path, output: &Path) -> Result<()> {
+    let ffmpeg = crate::config_var("FAST_TTS_FFMPEG").unwrap_or_else(|| "ffmpeg".into());
+    let result = std::process::Command::new(&ffmpeg)
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(wav)
+        .args(["-c:a", "libmp3lame", "-q:a", "2", "-f", "mp3"])
+        .arg(output)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FastTtsError::Unsupported(format!(
+                "MP3 output from {provider} is encoded with ffmpeg, and {ffmpeg} was not found; install ffmpeg, set FAST_TTS_FFMPEG or use LINEAR16"
+            ))
+            .into(),
+            _ => anyhow::Error::from(e).context(format!("failed to run {ffmpeg}")),
+        })?;
+    if !result.status.success() {
+        anyhow::bail!(
+            "{ffmpeg} failed to encode {}: {}",
+            output.display(),
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Write `wav` (16-bit WAV) to the job's output, as MP3 when it asks for that.
+pub(crate) fn write_output(provider: &str, job: &SynthesisRequest, wav: &[u8]) -> Result<()> {
+    let write = |path: &Path| {
+        std::fs::write(path, wav).with_context(|| format!("failed to write {}", path.display()))
+    };
+    if job.encoding != AudioEncoding::Mp3 {
+        return write(&job.output);
+    }
+    let temp = job
+        .output
+        .with_extension(format!("{}.wav", provider.to_lowercase()));
+    let result = write(&temp).and_then(|()| encode_mp3(provider, &temp, &job.output));
+    let _ = std::fs::remove_file(&temp);
+    result
+}
+
+/// Run `render` off the async runtime.
+pub(crate) async fn run_blocking(
+    render: impl FnOnce() -> Result<()> + Send + 'static,
+) -> Result<()> {
+    match tokio::task::spawn_blocking(render).await {
+        Ok(result) => result,
+        // ort panics when it can't load the runtime library
+        Err(e) if e.is_panic() => Err(FastTtsError::Unsupported(format!(
+            "ONNX Runtime could not be loaded ({}); install it or point ORT_DYLIB_PATH at libonnxruntime",
+            e.into_panic()
+                .downcast::<String>()
+                .map(|message| *message)
+                .unwrap_or_else(|_| "no details".into())
+        ))
+        .into()),
+        Err(e) => Err(e.into()),
+    }
+}
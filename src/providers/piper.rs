@@ -0,0 +1,362 @@
+//! Offline synthesis with Piper voice models: `<name>.onnx` plus its
+//! `<name>.onnx.json` config, run with ONNX Runtime. Models are picked with
+//! `--model` (a path, or a name in `PIPER_MODELS_DIR`, by default
+//! `<data dir>/fast-tts-cli/piper`); text is turned into phonemes by
+//! espeak-ng, as Piper itself does.
+
+use crate::FastTtsError;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+pub(crate) fn models_dir() -> Option<PathBuf> {
+    if let Some(dir) = crate::config_var("PIPER_MODELS_DIR").filter(|d| !d.trim().is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    dirs::data_dir().map(|d| d.join("fast-tts-cli").join("piper"))
+}
+
+/// Models in the models directory that have their config next to them, by name.
+pub(crate) fn installed() -> Vec<(String, PathBuf)> {
+    let Some(entries) = models_dir().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut models: Vec<(String, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "onnx"))
+        .filter(|path| config_path(path).is_file())
+        .filter_map(|path| Some((path.file_stem()?.to_str()?.to_string(), path)))
+        .collect();
+    models.sort();
+    models
+}
+
+/// The model to use: `model`, else `PIPER_MODEL`, else the only installed
+/// one. A name without a path separator or `.onnx` is looked up in the
+/// models directory.
+pub(crate) fn resolve(model: Option<&str>) -> Result<PathBuf> {
+    let installed = installed();
+    let model = model
+        .map(str::to_string)
+        .or_else(|| crate::config_var("PIPER_MODEL").filter(|m| !m.trim().is_empty()));
+    let Some(model) = model else {
+        return match installed.as_slice() {
+            [(_, path)] => Ok(path.clone()),
+            [] => Err(FastTtsError::InvalidInput(format!(
+                "no Piper model given and none installed in {}; download a voice (.onnx and .onnx.json) there, or pass --model PATH",
+                display_dir()
+            ))
+            .into()),
+            several => Err(FastTtsError::InvalidInput(format!(
+                "several Piper models are installed; pick one with --model or PIPER_MODEL ({})",
+                several
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+            .into()),
+        };
+    };
+    let path = if model.ends_with(".onnx") || model.contains(std::path::is_separator) {
+        PathBuf::from(&model)
+    } else {
+        match installed.iter().find(|(name, _)| *name == model) {
+            Some((_, path)) => path.clone(),
+            None => {
+                let names = installed.iter().map(|(name, _)| name.as_str());
+                let hint = match crate::suggest::closest(&model, names).as_slice() {
+                    [] => String::new(),
+                    [name, ..] => format!("; did you mean '{name}'?"),
+                };
+                return Err(FastTtsError::InvalidInput(format!(
+                    "no Piper model {model} in {}{hint}",
+                    display_dir()
+                ))
+                .into());
+            }
+        }
+    };
+    for file in [&path, &config_path(&path)] {
+        if !file.is_file() {
+            return Err(FastTtsError::InvalidInput(format!(
+                "Piper model file {} not found",
+                file.display()
+            ))
+            .into());
+        }
+    }
+    Ok(path)
+}
+
+fn display_dir() -> String {
+    models_dir().map_or_else(|| "PIPER_MODELS_DIR".into(), |d| d.display().to_string())
+}
+
+/// `<name>.onnx.json`, Piper's config for `<name>.onnx`.
+fn config_path(model: &Path) -> PathBuf {
+    let mut config = model.as_os_str().to_owned();
+    config.push(".json");
+    PathBuf::from(config)
+}
+
+#[cfg(feature = "piper")]
+pub(crate) use engine::Piper;
+
+#[cfg(feature = "piper")]
+mod engine {
+    use super::{config_path, installed, resolve};
+    use crate::providers::{TtsProvider, capabilities, onnx};
+    use crate::{
+        FastTtsError, ListVoicesResponse, Provider, ProviderCapabilities, SynthesisRequest,
+    };
+    use anyhow::{Context, Result};
+    use ort::session::Session;
+    use ort::value::Tensor;
+    use serde::Deserialize;
+    use std::collections::{BTreeMap, HashMap};
+    use std::path::Path;
+
+    pub(crate) struct Piper;
+
+    #[derive(Debug, Deserialize)]
+    struct ModelConfig {
+        audio: AudioConfig,
+        #[serde(default)]
+        espeak: EspeakConfig,
+        #[serde(default)]
+        inference: InferenceConfig,
+        /// `espeak` (IPA from espeak-ng) or `text` (the characters themselves)
+        #[serde(default)]
+        phoneme_type: Option<String>,
+        phoneme_id_map: HashMap<String, Vec<i64>>,
+        #[serde(default)]
+        num_speakers: u32,
+        #[serde(default)]
+        speaker_id_map: BTreeMap<String, i64>,
+        #[serde(default)]
+        language: Option<LanguageConfig>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct AudioConfig {
+        sample_rate: u32,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct EspeakConfig {
+        voice: String,
+    }
+
+    impl Default for EspeakConfig {
+        fn default() -> Self {
+            Self {
+                voice: "en-us".into(),
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(default)]
+    struct InferenceConfig {
+        noise_scale: f32,
+        length_scale: f32,
+        noise_w: f32,
+    }
+
+    impl Default for InferenceConfig {
+        fn default() -> Self {
+            Self {
+                noise_scale: 0.667,
+                length_scale: 1.0,
+                noise_w: 0.8,
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct LanguageConfig {
+        code: String,
+    }
+
+    fn load_config(model: &Path) -> Result<ModelConfig> {
+        let path = config_path(model);
+        let raw =
+            std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_slice(&raw)
+            .with_context(|| format!("invalid Piper config {}", path.display()))
+    }
+
+    /// Phonemes per sentence.
+    fn phonemize(text: &str, config: &ModelConfig) -> Result<Vec<Vec<String>>> {
+        if config.phoneme_type.as_deref() == Some("text") {
+            return Ok(vec![text.chars().map(String::from).collect()]);
+        }
+        Ok(onnx::espeak("Piper", text, &config.espeak.voice)?
+            .iter()
+            .map(|line| line.chars().map(String::from).collect())
+            .collect())
+    }
+
+    /// Piper's id sequence: start, then each phoneme followed by the pad, then
+    /// end. Phonemes the model doesn't know are dropped.
+    fn phoneme_ids(phonemes: &[String], map: &HashMap<String, Vec<i64>>) -> Vec<i64> {
+        let id = |symbol: &str| map.get(symbol).cloned().unwrap_or_default();
+        let pad = id("_");
+        let mut ids = id("^");
+        ids.extend(&pad);
+        for phoneme in phonemes {
+            if let Some(phoneme_ids) = map.get(phoneme) {
+                ids.extend(phoneme_ids);
+                ids.extend(&pad);
+            }
+        }
+        ids.extend(id("$"));
+        ids
+    }
+
+    fn infer(
+        session: &mut Session,
+        ids: Vec<i64>,
+        scales: [f32; 3],
+        speaker: Option<i64>,
+    ) -> Result<Vec<f32>> {
+        let ort_err = |e: ort::Error| anyhow::anyhow!("Piper inference failed: {e}");
+        let len = ids.len();
+        let mut inputs = ort::inputs![
+            "input" => Tensor::from_array(([1usize, len], ids)).map_err(ort_err)?,
+            "input_lengths" => Tensor::from_array(([1usize], vec![len as i64])).map_err(ort_err)?,
+            "scales" => Tensor::from_array(([3usize], scales.to_vec())).map_err(ort_err)?,
+        ];
+        if let Some(speaker) = speaker {
+            inputs.push((
+                "sid".into(),
+                Tensor::from_array(([1usize], vec![speaker]))
+                    .map_err(ort_err)?
+                    .into(),
+            ));
+        }
+        let outputs = session.run(inputs).map_err(ort_err)?;
+        let (_, samples) = outputs[0].try_extract_tensor::<f32>().map_err(ort_err)?;
+        Ok(samples.to_vec())
+    }
+
+    /// Render `job` as 16-bit WAV.
+    fn render(job: &SynthesisRequest) -> Result<Vec<u8>> {
+        let model = resolve(job.model.as_deref())?;
+        let config = load_config(&model)?;
+        let name = model.file_stem().unwrap_or_default().to_string_lossy();
+        if let Some(hz) = job.sample_rate
+            && u32::try_from(hz).ok() != Some(config.audio.sample_rate)
+        {
+            return Err(FastTtsError::InvalidInput(format!(
+                "Piper model {name} produces {} Hz audio, not {hz}",
+                config.audio.sample_rate
+            ))
+            .into());
+        }
+        let speaker = match job.voice.as_deref() {
+            None => (config.num_speakers > 1).then_some(0),
+            Some(voice) => match config.speaker_id_map.get(voice) {
+                Some(&id) => Some(id),
+                None if config.speaker_id_map.is_empty() => {
+                    return Err(FastTtsError::InvalidInput(format!(
+                        "Piper model {name} has a single speaker; drop --voice {voice}"
+                    ))
+                    .into());
+                }
+                None => {
+                    return Err(crate::suggest::unknown_voice(
+                        "piper",
+                        voice,
+                        config.speaker_id_map.keys().map(String::as_str),
+                    ));
+                }
+            },
+        };
+        let inference = &config.inference;
+        let scales = [
+            inference.noise_scale,
+            inference.length_scale / job.rate,
+            inference.noise_w,
+        ];
+        let session = onnx::session("Piper", &model)?;
+        let mut samples = Vec::new();
+        for sentence in phonemize(&job.text, &config)? {
+            if job.cancel.is_cancelled() {
+                return Err(crate::cancelled());
+            }
+            let ids = phoneme_ids(&sentence, &config.phoneme_id_map);
+            samples.extend(infer(&mut session.lock().unwrap(), ids, scales, speaker)?);
+        }
+        // Piper's output isn't bounded; bring it to full scale as the piper CLI does
+        let peak = samples.iter().fold(0.01f32, |peak, s| peak.max(s.abs()));
+        samples.iter_mut().for_each(|s| *s /= peak);
+        Ok(crate::audio::DecodedAudio {
+            sample_rate: config.audio.sample_rate,
+            channels: 1,
+            samples,
+        }
+        .to_wav16())
+    }
+
+    #[async_trait::async_trait]
+    impl TtsProvider for Piper {
+        fn id(&self) -> Provider {
+            Provider::Piper
+        }
+
+        async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+            let job = job.clone();
+            onnx::run_blocking(move || onnx::write_output("Piper", &job, &render(&job)?)).await
+        }
+
+        async fn list_voices(&self) -> Result<ListVoicesResponse> {
+            let mut voices = Vec::new();
+            for (name, path) in installed() {
+                let config = load_config(&path)?;
+                voices.push(crate::Voice {
+                    name,
+                    language_codes: config
+                        .language
+                        .map(|l| l.code.replace('_', "-"))
+                        .into_iter()
+                        .collect(),
+                    ssml_gender: "NEUTRAL".into(),
+                    natural_sample_rate_hertz: Some(config.audio.sample_rate as i32),
+                    // Speakers of a multi-speaker model, for --voice
+                    roles: config.speaker_id_map.into_keys().collect(),
+                    ..crate::Voice::default()
+                });
+            }
+            Ok(ListVoicesResponse { voices })
+        }
+
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                usd_per_million_chars: Some(0.0),
+                rate_range: Some((0.25, 4.0)),
+                ..capabilities(
+                    self.id(),
+                    &["LINEAR16", "MP3"],
+                    &[16000, 22050],
+                    "",
+                    "Speaker of a multi-speaker model (listed as roles by --list-voices); the model itself is picked with --model",
+                )
+            }
+        }
+    }
+}
+
+/// Whether a model is available: installed, or named by `PIPER_MODEL`.
+pub(crate) fn configured() -> bool {
+    !installed().is_empty() || resolve(None).is_ok()
+}
+
+/// `auth check` for Piper: there are no credentials, only models.
+pub(crate) fn auth_status() -> crate::AuthStatus {
+    match resolve(None) {
+        Err(e) if installed().is_empty() => crate::AuthStatus::Missing(format!("{e:#}")),
+        _ => crate::AuthStatus::Ok,
+    }
+}
@@ -0,0 +1,287 @@
+//! `--provider system`: the operating system's own speech engine, so there is
+//! something to synthesize with before any API key is set up. macOS runs
+//! `say`, Windows SAPI through PowerShell, and other systems espeak-ng (the
+//! engine behind speech-dispatcher's default module; speech-dispatcher itself
+//! can only speak aloud, not write a file). Output is WAV only.
+
+use crate::providers::{TtsProvider, capabilities};
+use crate::{
+    FastTtsError, ListVoicesResponse, Provider, ProviderCapabilities, SynthesisRequest, Voice,
+};
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+pub(crate) struct System;
+
+/// Words per minute spoken at `--rate 1.0` by `say` and espeak-ng.
+#[cfg(not(windows))]
+const WORDS_PER_MINUTE: f32 = 175.0;
+
+#[cfg(any(target_os = "macos", windows))]
+const SAMPLE_RATES: &[u32] = &[8000, 16000, 22050, 24000, 44100, 48000];
+#[cfg(not(any(target_os = "macos", windows)))]
+const SAMPLE_RATES: &[u32] = &[22050];
+
+#[cfg(target_os = "macos")]
+const VOICE_SELECTION: &str = "macOS voice name as listed by `say -v ?`, e.g. Samantha";
+#[cfg(windows)]
+const VOICE_SELECTION: &str =
+    "Installed SAPI voice name, e.g. Microsoft Zira Desktop; without one, a voice for --language";
+#[cfg(not(any(target_os = "macos", windows)))]
+const VOICE_SELECTION: &str = "espeak-ng voice, e.g. en-us or en-gb-x-rp; default --language";
+
+/// What to do when the engine's program is missing.
+#[cfg(any(target_os = "macos", windows))]
+const INSTALL_HINT: &str = "it ships with the OS";
+#[cfg(not(any(target_os = "macos", windows)))]
+const INSTALL_HINT: &str =
+    "install espeak-ng (speech-dispatcher can't write audio files) or set FAST_TTS_ESPEAK";
+
+/// The engine's program.
+fn engine() -> String {
+    if cfg!(target_os = "macos") {
+        "say".into()
+    } else if cfg!(windows) {
+        "powershell".into()
+    } else {
+        crate::config_var("FAST_TTS_ESPEAK").unwrap_or_else(|| "espeak-ng".into())
+    }
+}
+
+/// Whether the engine can be run here.
+pub(crate) fn available() -> bool {
+    crate::find_program(&engine()).is_some()
+}
+
+/// `auth check` for the system engine: nothing to configure, only a program to find.
+pub(crate) fn auth_status() -> crate::AuthStatus {
+    if available() {
+        crate::AuthStatus::Ok
+    } else {
+        crate::AuthStatus::Missing(format!("{} not found; {INSTALL_HINT}", engine()))
+    }
+}
+
+/// Run `command`, feeding it `input`; its stdout on success.
+fn run(mut command: Command, input: &str) -> Result<String> {
+    let engine = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FastTtsError::Unsupported(format!(
+                "the system speech engine ({engine}) was not found; {INSTALL_HINT}"
+            ))
+            .into(),
+            _ => anyhow::Error::from(e).context(format!("failed to run {engine}")),
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{engine} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(target_os = "macos")]
+fn synthesize_command(job: &SynthesisRequest) -> Command {
+    let mut command = Command::new("say");
+    if let Some(voice) = &job.voice {
+        command.args(["-v", voice]);
+    }
+    command
+        .arg("-r")
+        .arg(format!("{}", (WORDS_PER_MINUTE * job.rate).round()))
+        .arg("--file-format=WAVE")
+        .arg(format!(
+            "--data-format=LEI16@{}",
+            job.sample_rate.unwrap_or(22050)
+        ))
+        .arg("-o")
+        .arg(&job.output);
+    command
+}
+
+#[cfg(windows)]
+const SAPI_SCRIPT: &str = r#"
+[Console]::InputEncoding = [Text.Encoding]::UTF8
+Add-Type -AssemblyName System.Speech
+$s = New-Object System.Speech.Synthesis.SpeechSynthesizer
+if ($env:FAST_TTS_SAPI_VOICE) { $s.SelectVoice($env:FAST_TTS_SAPI_VOICE) }
+elseif ($env:FAST_TTS_SAPI_CULTURE) {
+  try { $s.SelectVoiceByHints('NotSet', 'NotSet', 0, [Globalization.CultureInfo]$env:FAST_TTS_SAPI_CULTURE) } catch {}
+}
+$s.Rate = [int]$env:FAST_TTS_SAPI_RATE
+$format = New-Object System.Speech.AudioFormat.SpeechAudioFormatInfo([int]$env:FAST_TTS_SAPI_HZ, [System.Speech.AudioFormat.AudioBitsPerSample]::Sixteen, [System.Speech.AudioFormat.AudioChannel]::Mono)
+$s.SetOutputToWaveFile($env:FAST_TTS_SAPI_OUTPUT, $format)
+$s.Speak([Console]::In.ReadToEnd())
+$s.Dispose()
+"#;
+
+#[cfg(windows)]
+fn synthesize_command(job: &SynthesisRequest) -> Command {
+    // SAPI's rate runs from -10 to 10, roughly a third to three times normal speed
+    let rate = (10.0 * job.rate.ln() / 3f32.ln())
+        .round()
+        .clamp(-10.0, 10.0);
+    let mut command = Command::new("powershell");
+    // Everything but the text goes in through the environment, never into the script
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command", SAPI_SCRIPT])
+        .env("FAST_TTS_SAPI_VOICE", job.voice.as_deref().unwrap_or(""))
+        .env("FAST_TTS_SAPI_CULTURE", &job.language)
+        .env("FAST_TTS_SAPI_RATE", format!("{rate}"))
+        .env(
+            "FAST_TTS_SAPI_HZ",
+            job.sample_rate.unwrap_or(22050).to_string(),
+        )
+        .env("FAST_TTS_SAPI_OUTPUT", &job.output);
+    command
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn synthesize_command(job: &SynthesisRequest) -> Command {
+    let voice = match &job.voice {
+        Some(voice) => voice.clone(),
+        None if !job.language.is_empty() => job.language.to_lowercase(),
+        None => "en-us".into(),
+    };
+    let mut command = Command::new(engine());
+    command
+        .args(["-v", &voice])
+        .arg("-s")
+        .arg(format!("{}", (WORDS_PER_MINUTE * job.rate).round()))
+        .arg("-w")
+        .arg(&job.output)
+        .arg("--stdin");
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn list() -> Result<Vec<Voice>> {
+    let mut command = Command::new("say");
+    command.args(["-v", "?"]);
+    // `Samantha            en_US    # Hello! My name is Samantha.`
+    Ok(run(command, "")?
+        .lines()
+        .filter_map(|line| {
+            let (voice, _) = line.split_once('#')?;
+            let (name, locale) = voice.trim().rsplit_once(char::is_whitespace)?;
+            Some(Voice {
+                name: name.trim().to_string(),
+                language_codes: vec![locale.replace('_', "-")],
+                ssml_gender: "NEUTRAL".into(),
+                ..Voice::default()
+            })
+        })
+        .collect())
+}
+
+#[cfg(windows)]
+fn list() -> Result<Vec<Voice>> {
+    let mut command = Command::new("powershell");
+    command.args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        "Add-Type -AssemblyName System.Speech; (New-Object System.Speech.Synthesis.SpeechSynthesizer).GetInstalledVoices() | ForEach-Object { $v = $_.VoiceInfo; \"$($v.Name)`t$($v.Culture)`t$($v.Gender)\" }",
+    ]);
+    Ok(run(command, "")?
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().split('\t');
+            let name = fields.next().filter(|n| !n.is_empty())?;
+            Some(Voice {
+                name: name.to_string(),
+                language_codes: fields.next().map(str::to_string).into_iter().collect(),
+                ssml_gender: fields.next().unwrap_or("NEUTRAL").to_uppercase(),
+                ..Voice::default()
+            })
+        })
+        .collect())
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn list() -> Result<Vec<Voice>> {
+    let mut command = Command::new(engine());
+    command.arg("--voices");
+    // `Pty Language       Age/Gender VoiceName          File          Other Languages`
+    // ` 5  en-us           --/M      English_(America)  gmw/en-US     (en 3)`
+    Ok(run(command, "")?
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let language = fields.next()?;
+            let gender = match fields.next()?.rsplit('/').next() {
+                Some("M") => "MALE",
+                Some("F") => "FEMALE",
+                _ => "NEUTRAL",
+            };
+            Some(Voice {
+                name: language.to_string(),
+                language_codes: vec![language.to_string()],
+                ssml_gender: gender.into(),
+                natural_sample_rate_hertz: Some(22050),
+                ..Voice::default()
+            })
+        })
+        .collect())
+}
+
+#[async_trait::async_trait]
+impl TtsProvider for System {
+    fn id(&self) -> Provider {
+        Provider::System
+    }
+
+    async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+        // espeak-ng variants (`en-us+f3`) are checked by their base voice
+        if let Some(voice) = &job.voice
+            && let Ok(voices) = list()
+            && !voices.is_empty()
+            && !voices.iter().any(|v| {
+                v.name
+                    .eq_ignore_ascii_case(voice.split('+').next().unwrap_or(voice))
+            })
+        {
+            return Err(crate::suggest::unknown_voice(
+                "system",
+                voice,
+                voices.iter().map(|v| v.name.as_str()),
+            ));
+        }
+        if let Some(parent) = job.output.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create output directory: {}", parent.display())
+            })?;
+        }
+        let command = synthesize_command(job);
+        let text = job.text.clone();
+        tokio::task::spawn_blocking(move || run(command, &text)).await??;
+        Ok(())
+    }
+
+    async fn list_voices(&self) -> Result<ListVoicesResponse> {
+        let voices = tokio::task::spawn_blocking(list).await??;
+        Ok(ListVoicesResponse { voices })
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            usd_per_million_chars: Some(0.0),
+            rate_range: Some((0.5, 2.0)),
+            ..capabilities(self.id(), &["LINEAR16"], SAMPLE_RATES, "", VOICE_SELECTION)
+        }
+    }
+}
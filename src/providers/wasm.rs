@@ -0,0 +1,455 @@
+//! Providers shipped as WebAssembly components implementing `wit/provider.wit`,
+//! selected as `--provider wasm:<path>` (a `.wasm` file) or `wasm:<name>` (a
+//! component in `<config dir>/fast-tts-cli/plugins/`, or `FAST_TTS_PLUGINS_DIR`).
+//! The host performs HTTP for the plugin through the CLI's own client, so a
+//! plugin gets no network or filesystem access of its own, and only sees
+//! settings named `FAST_TTS_PLUGIN_<NAME>_*`.
+
+use super::{TtsProvider, capabilities};
+use crate::{AudioEncoding, FastTtsError, Provider, ProviderCapabilities, SynthesisRequest};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use wasmtime::component::{Component, Linker};
+use wasmtime::{Engine, Store, StoreLimits, StoreLimitsBuilder, UpdateDeadline};
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        path: "wit/provider.wit",
+        world: "tts-provider",
+    });
+}
+
+use bindings::fast_tts::provider::types;
+
+/// A compiled component and what it reported from `capabilities`; leaked so
+/// it can back `Provider::Wasm(&'static str)`.
+pub(crate) struct WasmProvider {
+    spec: &'static str,
+    /// `FAST_TTS_PLUGIN_<NAME>_`, the only settings `config-var` reads
+    config_prefix: String,
+    engine: Engine,
+    component: Component,
+    linker: Linker<Host>,
+    encodings: Vec<&'static str>,
+    sample_rates: Vec<u32>,
+    default_voice: &'static str,
+    voice_selection: &'static str,
+}
+
+static LOADED: Mutex<Option<HashMap<String, &'static WasmProvider>>> = Mutex::new(None);
+
+/// How often running plugins check their deadline and cancellation.
+const TICK: Duration = Duration::from_millis(50);
+/// Linear memory one plugin instance may grow to.
+const MEMORY_LIMIT: usize = 256 << 20;
+/// How long loading may spend in the plugin's `capabilities`.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The engine every plugin runs on, with a thread advancing its epoch so a
+/// plugin stuck in a loop still reaches its deadline check.
+fn engine() -> Result<Engine> {
+    static ENGINE: Mutex<Option<Engine>> = Mutex::new(None);
+    let mut shared = ENGINE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(engine) = shared.as_ref() {
+        return Ok(engine.clone());
+    }
+    let mut config = wasmtime::Config::new();
+    config.epoch_interruption(true);
+    let engine = Engine::new(&config)?;
+    let ticker = engine.clone();
+    std::thread::Builder::new()
+        .name("wasm-epoch".into())
+        .spawn(move || {
+            loop {
+                std::thread::sleep(TICK);
+                ticker.increment_epoch();
+            }
+        })?;
+    *shared = Some(engine.clone());
+    Ok(engine)
+}
+
+fn plugins_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("FAST_TTS_PLUGINS_DIR").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    dirs::config_dir().map(|d| d.join("fast-tts-cli").join("plugins"))
+}
+
+fn is_path(spec: &str) -> bool {
+    spec.ends_with(".wasm") || spec.contains(std::path::is_separator)
+}
+
+/// The component file `spec` names: itself when it is a path, else
+/// `<name>.wasm` in the plugins directory.
+fn component_path(spec: &str) -> Result<PathBuf> {
+    if is_path(spec) {
+        return Ok(PathBuf::from(spec));
+    }
+    let valid = !spec.is_empty()
+        && spec
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(FastTtsError::InvalidInput(format!("invalid plugin name: {spec:?}")).into());
+    }
+    let dir = plugins_dir().context("cannot determine config directory for plugins")?;
+    Ok(dir.join(format!("{spec}.wasm")))
+}
+
+/// Compile (once) the component for `spec` and ask it for its capabilities.
+pub(crate) fn load(spec: &str) -> Result<&'static WasmProvider> {
+    if let Some(loaded) = lookup(spec) {
+        return Ok(loaded);
+    }
+    let path = component_path(spec)?;
+    if !path.is_file() {
+        return Err(FastTtsError::InvalidInput(format!(
+            "plugin {spec:?} not found ({})",
+            path.display()
+        ))
+        .into());
+    }
+    let provider = WasmProvider::new(spec, &path)
+        .with_context(|| format!("failed to load plugin {}", path.display()))?;
+
+    let mut loaded = LOADED.lock().unwrap_or_else(|e| e.into_inner());
+    let map = loaded.get_or_insert_with(HashMap::new);
+    let provider = *map
+        .entry(spec.to_string())
+        .or_insert_with(|| Box::leak(Box::new(provider)));
+    Ok(provider)
+}
+
+/// `load` for specs from serve and MCP clients: only plugins installed in the
+/// plugins directory, never a path to some other file.
+#[cfg(any(feature = "serve", feature = "mcp"))]
+pub(crate) fn load_installed(spec: &str) -> Result<&'static WasmProvider> {
+    if is_path(spec) {
+        return Err(FastTtsError::InvalidInput(format!(
+            "plugin paths are not accepted here; install {spec:?} in the plugins directory and use wasm:<name>"
+        ))
+        .into());
+    }
+    load(spec)
+}
+
+fn lookup(spec: &str) -> Option<&'static WasmProvider> {
+    let loaded = LOADED.lock().unwrap_or_else(|e| e.into_inner());
+    loaded.as_ref()?.get(spec).copied()
+}
+
+/// Stand-in for a `Provider::Wasm` whose component failed to load; every call
+/// reports the load error.
+struct Missing {
+    spec: &'static str,
+    error: String,
+}
+
+static MISSING: Mutex<Option<HashMap<String, &'static Missing>>> = Mutex::new(None);
+
+pub(crate) fn get(spec: &'static str) -> &'static dyn TtsProvider {
+    let error = match load(spec) {
+        Ok(plugin) => return plugin,
+        Err(e) => format!("{e:#}"),
+    };
+    let mut missing = MISSING.lock().unwrap_or_else(|e| e.into_inner());
+    *missing
+        .get_or_insert_with(HashMap::new)
+        .entry(spec.to_string())
+        .or_insert_with(|| Box::leak(Box::new(Missing { spec, error })))
+}
+
+#[async_trait::async_trait]
+impl TtsProvider for Missing {
+    fn id(&self) -> Provider {
+        Provider::Wasm(self.spec)
+    }
+
+    async fn synthesize(&self, _job: &SynthesisRequest) -> Result<()> {
+        Err(FastTtsError::InvalidInput(self.error.clone()).into())
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        capabilities(self.id(), &[], &[], "", "plugin failed to load")
+    }
+}
+
+/// Every component in the plugins directory that loads, sorted by name.
+pub(crate) fn all() -> Vec<Provider> {
+    let Some(entries) = plugins_dir().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let path = e.path();
+            (path.extension()? == "wasm").then(|| path.file_stem()?.to_str().map(String::from))?
+        })
+        .collect();
+    names.sort();
+    names
+        .iter()
+        .filter_map(|name| load(name).ok())
+        .map(|p| p.id())
+        .collect()
+}
+
+/// Host side of the `host` interface, one per call into the plugin.
+struct Host {
+    /// `--profile` values, which live in a task-local the plugin's thread can't see
+    profile: HashMap<String, String>,
+    config_prefix: String,
+    limits: StoreLimits,
+    /// Runtime to send HTTP on; only set while synthesizing
+    runtime: Option<tokio::runtime::Handle>,
+    cancel: crate::CancellationToken,
+}
+
+impl types::Host for Host {}
+
+impl bindings::fast_tts::provider::host::Host for Host {
+    fn send(&mut self, request: types::HttpRequest) -> Result<types::HttpResponse, String> {
+        let runtime = self
+            .runtime
+            .clone()
+            .ok_or("HTTP is only available during synthesize")?;
+        let cancel = self.cancel.clone();
+        runtime
+            .block_on(async {
+                match cancel.run_until_cancelled(send(request)).await {
+                    Some(result) => result,
+                    None => Err(crate::cancelled()),
+                }
+            })
+            .map_err(|e| format!("{e:#}"))
+    }
+
+    fn config_var(&mut self, name: String) -> Option<String> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return None;
+        }
+        let var = format!("{}{name}", self.config_prefix);
+        self.profile
+            .get(&var)
+            .cloned()
+            .or_else(|| std::env::var(&var).ok())
+    }
+}
+
+/// Send `request` for the plugin through the shared HTTP stack (proxies, CAs,
+/// --debug-http, record/replay, --offline).
+async fn send(request: types::HttpRequest) -> Result<types::HttpResponse> {
+    let method = reqwest::Method::from_bytes(request.method.as_bytes())
+        .map_err(|_| FastTtsError::InvalidInput(format!("invalid method {}", request.method)))?;
+    let client = crate::build_http_client_for_base(&request.url)?;
+    let mut req = client.request(method, &request.url).body(request.body);
+    for (name, value) in &request.headers {
+        // Whatever looks like a credential is redacted from --debug-http
+        let lower = name.to_ascii_lowercase();
+        req = if lower == "authorization"
+            || ["key", "token", "secret"].iter().any(|s| lower.contains(s))
+        {
+            req.header(name, crate::secret_header(value)?)
+        } else {
+            req.header(name, value)
+        };
+    }
+    let resp = crate::send_http(req).await?;
+    let status = resp.status().as_u16();
+    let headers = resp
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let body = resp.bytes().await?.to_vec();
+    Ok(types::HttpResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+impl WasmProvider {
+    fn new(spec: &str, path: &std::path::Path) -> Result<Self> {
+        let engine = engine()?;
+        let component = Component::from_file(&engine, path)?;
+        let mut linker = Linker::new(&engine);
+        bindings::TtsProvider::add_to_linker(&mut linker, |host: &mut Host| host)?;
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or(spec);
+        let config_prefix = format!(
+            "FAST_TTS_PLUGIN_{}_",
+            name.chars()
+                .map(|c| if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                })
+                .collect::<String>()
+        );
+        let mut plugin = Self {
+            spec: leak(spec.to_string()),
+            config_prefix,
+            engine,
+            component,
+            linker,
+            encodings: Vec::new(),
+            sample_rates: Vec::new(),
+            default_voice: "",
+            voice_selection: "",
+        };
+        let (mut store, instance) =
+            plugin.instantiate(None, crate::CancellationToken::new(), LOAD_TIMEOUT)?;
+        let caps = instance
+            .fast_tts_provider_provider()
+            .call_capabilities(&mut store)?;
+        plugin.encodings = caps
+            .encodings
+            .iter()
+            .map(|e| encoding(*e).api_str())
+            .collect();
+        plugin.sample_rates = caps.sample_rates;
+        plugin.default_voice = leak(caps.default_voice);
+        plugin.voice_selection = leak(caps.voice_selection);
+        Ok(plugin)
+    }
+
+    /// A fresh instance that traps once `timeout` passes or `cancel` fires,
+    /// even mid-loop, and can't grow its memory past `MEMORY_LIMIT`.
+    fn instantiate(
+        &self,
+        runtime: Option<tokio::runtime::Handle>,
+        cancel: crate::CancellationToken,
+        timeout: Duration,
+    ) -> Result<(Store<Host>, bindings::TtsProvider)> {
+        let profile = crate::PROFILE_ENV
+            .try_with(|env| env.clone())
+            .unwrap_or_default();
+        let mut store = Store::new(
+            &self.engine,
+            Host {
+                profile,
+                config_prefix: self.config_prefix.clone(),
+                limits: StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build(),
+                runtime,
+                cancel: cancel.clone(),
+            },
+        );
+        store.limiter(|host| &mut host.limits);
+        let provider = self.id();
+        let deadline = Instant::now() + timeout;
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(move |_| {
+            if cancel.is_cancelled() {
+                return Err(crate::cancelled());
+            }
+            if Instant::now() >= deadline {
+                return Err(FastTtsError::ProviderHttp {
+                    status: None,
+                    message: format!("{provider} timed out after {} ms", timeout.as_millis()),
+                }
+                .into());
+            }
+            Ok(UpdateDeadline::Continue(1))
+        });
+        let instance =
+            bindings::TtsProvider::instantiate(&mut store, &self.component, &self.linker)?;
+        Ok((store, instance))
+    }
+
+    /// Call the plugin's `synthesize` on a blocking thread; the audio.
+    async fn call_synthesize(&self, job: &SynthesisRequest) -> Result<Vec<u8>> {
+        let (mut store, instance) = self.instantiate(
+            Some(tokio::runtime::Handle::current()),
+            job.cancel.clone(),
+            Duration::from_millis(job.timeout_ms),
+        )?;
+        let request = types::SynthesisRequest {
+            text: job.text.clone(),
+            language: job.language.clone(),
+            voice: job.voice.clone(),
+            rate: job.rate,
+            pitch: job.pitch,
+            sample_rate: job.sample_rate.and_then(|r| u32::try_from(r).ok()),
+            encoding: match job.encoding {
+                AudioEncoding::Linear16 => types::AudioEncoding::Linear16,
+                AudioEncoding::Mp3 => types::AudioEncoding::Mp3,
+                AudioEncoding::OggOpus => types::AudioEncoding::OggOpus,
+                AudioEncoding::Mulaw => types::AudioEncoding::Mulaw,
+                AudioEncoding::Alaw => types::AudioEncoding::Alaw,
+            },
+            ssml: job.ssml,
+        };
+        let result = tokio::task::spawn_blocking(move || {
+            instance
+                .fast_tts_provider_provider()
+                .call_synthesize(&mut store, &request)
+        })
+        .await??;
+        result.map_err(|e| plugin_error(self.id(), e).into())
+    }
+}
+
+fn encoding(encoding: types::AudioEncoding) -> AudioEncoding {
+    match encoding {
+        types::AudioEncoding::Linear16 => AudioEncoding::Linear16,
+        types::AudioEncoding::Mp3 => AudioEncoding::Mp3,
+        types::AudioEncoding::OggOpus => AudioEncoding::OggOpus,
+        types::AudioEncoding::Mulaw => AudioEncoding::Mulaw,
+        types::AudioEncoding::Alaw => AudioEncoding::Alaw,
+    }
+}
+
+/// The plugin's error as the matching failure class, so exit codes and MCP
+/// tool errors treat it like a built-in provider's.
+fn plugin_error(provider: Provider, error: types::Error) -> FastTtsError {
+    match error {
+        types::Error::Auth(m) => FastTtsError::Auth(m),
+        types::Error::Quota(m) => FastTtsError::Quota(m),
+        types::Error::InvalidInput(m) => FastTtsError::InvalidInput(m),
+        types::Error::ProviderHttp((status, message)) => FastTtsError::ProviderHttp {
+            status: Some(status),
+            message: format!("{provider} returned HTTP {status}: {message}"),
+        },
+        types::Error::Unsupported(m) => FastTtsError::Unsupported(m),
+        types::Error::Other(m) => FastTtsError::Other(m),
+    }
+}
+
+fn leak(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+#[async_trait::async_trait]
+impl TtsProvider for WasmProvider {
+    fn id(&self) -> Provider {
+        Provider::Wasm(self.spec)
+    }
+
+    async fn synthesize(&self, job: &SynthesisRequest) -> Result<()> {
+        let audio = self.call_synthesize(job).await?;
+        if let Some(parent) = job.output.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create output directory: {}", parent.display())
+            })?;
+        }
+        std::fs::write(&job.output, audio)
+            .with_context(|| format!("failed to write {}", job.output.display()))?;
+        Ok(())
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        capabilities(
+            self.id(),
+            &self.encodings,
+            &self.sample_rates,
+            self.default_voice,
+            self.voice_selection,
+        )
+    }
+}
@@ -0,0 +1,572 @@
+//! [`SynthesisRequest`] and its builder: the single validated description of a
+//! synthesis job used by the CLI, bulk mode, the servers and library callers.
+
+use crate::markup::{self, DialogueTurn};
+use crate::{
+    AudioEncoding, FastTtsError, Gender, OpusSettings, Provider, validate_output_extension,
+};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tokio_util::sync::CancellationToken;
+
+/// Provider-independent synthesis request. Build one with [`SynthesisRequest::builder`].
+#[derive(Debug, Clone)]
+pub struct SynthesisRequest {
+    pub(crate) provider: Provider,
+    pub(crate) text: String,
+    pub(crate) output: PathBuf,
+    pub(crate) language: String,
+    pub(crate) voice: Option<String>,
+    pub(crate) gender: Option<Gender>,
+    pub(crate) rate: f32,
+    pub(crate) pitch: f32,
+    pub(crate) sample_rate: Option<i32>,
+    pub(crate) encoding: AudioEncoding,
+    pub(crate) volume_gain_db: f32,
+    pub(crate) effects_profile_id: Vec<String>,
+    pub(crate) ssml: bool,
+    pub(crate) voice_markup: bool,
+    /// Parsed from the text when built with `dialogue(true)`
+    pub(crate) dialogue: Vec<DialogueTurn>,
+    pub(crate) seed: Option<u32>,
+    pub(crate) crossfade_ms: u32,
+    pub(crate) tracks: bool,
+    pub(crate) segment_offsets: bool,
+    pub(crate) opus: OpusSettings,
+    pub(crate) custom_voice_model: Option<String>,
+    pub(crate) model: Option<String>,
+    pub(crate) timeout_ms: u64,
+    pub(crate) retries: usize,
+    pub(crate) cancel: CancellationToken,
+}
+
+impl SynthesisRequest {
+    /// A builder with the CLI defaults: Google, en-US, LINEAR16, normal rate and pitch.
+    pub fn builder() -> SynthesisRequestBuilder {
+        SynthesisRequestBuilder::default()
+    }
+
+    pub fn provider(&self) -> Provider {
+        self.provider
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    pub fn encoding(&self) -> AudioEncoding {
+        self.encoding
+    }
+
+    pub fn seed(&self) -> Option<u32> {
+        self.seed
+    }
+
+    /// The per-speaker track files a [`tracks`](SynthesisRequestBuilder::tracks)
+    /// render writes, by speaker (or voice) in order of appearance; empty otherwise.
+    pub fn track_outputs(&self) -> Vec<(String, PathBuf)> {
+        if self.tracks {
+            markup::track_outputs(self)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Where a [`segment_offsets`](SynthesisRequestBuilder::segment_offsets)
+    /// render writes its offset table, `{stem}.segments.json`.
+    pub fn segment_offsets_output(&self) -> Option<PathBuf> {
+        self.segment_offsets
+            .then(|| self.output.with_extension("segments.json"))
+    }
+
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Stable fingerprint (16 hex digits) of everything that shapes the audio:
+    /// provider, text and voice settings, but not the output path or retry
+    /// policy. Equal hashes mean the same render.
+    pub fn params_hash(&self) -> String {
+        let mut params = serde_json::json!([
+            self.provider.to_string(),
+            self.text,
+            self.language,
+            self.voice,
+            self.gender.map(|g| format!("{g:?}")),
+            self.rate,
+            self.pitch,
+            self.sample_rate,
+            self.encoding.api_str(),
+            self.volume_gain_db,
+            self.effects_profile_id,
+            self.ssml,
+            self.voice_markup,
+            !self.dialogue.is_empty(),
+            self.seed,
+            self.crossfade_ms,
+            self.custom_voice_model,
+        ]);
+        // Appended only when set, so hashes from before the option still match
+        if let Some(model) = &self.model
+            && let Some(params) = params.as_array_mut()
+        {
+            params.push(model.as_str().into());
+        }
+        // FNV-1a: unlike std's hasher, identical across builds and platforms
+        let hash = params
+            .to_string()
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |h, b| {
+                (h ^ u64::from(b)).wrapping_mul(0x100000001b3)
+            });
+        format!("{hash:016x}")
+    }
+
+    /// Key for de-duplicating retries, derived from [`params_hash`](Self::params_hash):
+    /// sent to providers that take one (`idempotency_header` in the
+    /// [capabilities](Provider::capabilities)), so a request retried after a
+    /// network blip isn't generated and billed twice.
+    pub fn idempotency_key(&self) -> String {
+        format!("fast-tts-{}", self.params_hash())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SynthesisRequestBuilder {
+    request: SynthesisRequest,
+    dialogue_script: bool,
+}
+
+impl Default for SynthesisRequestBuilder {
+    fn default() -> Self {
+        Self {
+            request: SynthesisRequest {
+                provider: Provider::Google,
+                text: String::new(),
+                output: PathBuf::new(),
+                language: String::new(),
+                voice: None,
+                gender: None,
+                rate: 1.0,
+                pitch: 0.0,
+                sample_rate: None,
+                encoding: AudioEncoding::Linear16,
+                volume_gain_db: 0.0,
+                effects_profile_id: Vec::new(),
+                ssml: false,
+                voice_markup: false,
+                dialogue: Vec::new(),
+                seed: None,
+                crossfade_ms: 0,
+                tracks: false,
+                segment_offsets: false,
+                opus: OpusSettings::default(),
+                custom_voice_model: None,
+                model: None,
+                timeout_ms: 30_000,
+                retries: 2,
+                cancel: CancellationToken::new(),
+            },
+            dialogue_script: false,
+        }
+    }
+}
+
+impl SynthesisRequestBuilder {
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.request.provider = provider;
+        self
+    }
+
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.request.text = text.into();
+        self
+    }
+
+    pub fn output(mut self, output: impl Into<PathBuf>) -> Self {
+        self.request.output = output.into();
+        self
+    }
+
+    /// BCP-47 language code. Empty (the default) takes it from the voice: its
+    /// `ll-CC-` name prefix or the provider's cached catalog, else en-US.
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.request.language = language.into();
+        self
+    }
+
+    pub fn voice(mut self, voice: Option<impl Into<String>>) -> Self {
+        self.request.voice = voice.map(Into::into);
+        self
+    }
+
+    pub fn gender(mut self, gender: Option<Gender>) -> Self {
+        self.request.gender = gender;
+        self
+    }
+
+    /// Speaking rate multiplier, 0.25–4.0.
+    pub fn rate(mut self, rate: f32) -> Self {
+        self.request.rate = rate;
+        self
+    }
+
+    /// Pitch in semitones, -20.0–20.0.
+    pub fn pitch(mut self, pitch: f32) -> Self {
+        self.request.pitch = pitch;
+        self
+    }
+
+    pub fn sample_rate(mut self, sample_rate: Option<i32>) -> Self {
+        self.request.sample_rate = sample_rate;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: AudioEncoding) -> Self {
+        self.request.encoding = encoding;
+        self
+    }
+
+    /// Volume gain in dB, -96.0–16.0.
+    pub fn volume_gain_db(mut self, volume_gain_db: f32) -> Self {
+        self.request.volume_gain_db = volume_gain_db;
+        self
+    }
+
+    pub fn effects_profile_id(mut self, effects: Vec<String>) -> Self {
+        self.request.effects_profile_id = effects;
+        self
+    }
+
+    pub fn ssml(mut self, ssml: bool) -> Self {
+        self.request.ssml = ssml;
+        self
+    }
+
+    /// Honor `[voice:NAME]` ... `[/voice]` tags in plain text: each run is
+    /// synthesized with its own voice and the audio joined into one file.
+    pub fn voice_markup(mut self, voice_markup: bool) -> Self {
+        self.request.voice_markup = voice_markup;
+        self
+    }
+
+    /// Read the text as a dialogue script (`SPEAKER: text` per turn) and
+    /// speak it with the provider's multi-speaker voice.
+    pub fn dialogue(mut self, dialogue: bool) -> Self {
+        self.dialogue_script = dialogue;
+        self
+    }
+
+    /// Sampling seed, for providers whose [capabilities](Provider::capabilities)
+    /// list `seed`: the same seed and parameters give repeatable audio.
+    pub fn seed(mut self, seed: Option<u32>) -> Self {
+        self.request.seed = seed;
+        self
+    }
+
+    /// Overlap, in ms, where parts of one render are joined (voice markup,
+    /// chunked long text); 16-bit PCM only, other encodings are cut hard.
+    pub fn crossfade_ms(mut self, crossfade_ms: u32) -> Self {
+        self.request.crossfade_ms = crossfade_ms;
+        self
+    }
+
+    /// Besides the joined output, write each dialogue speaker or voice-markup
+    /// voice to its own `{stem}.{speaker}.wav` next to it, silent where the
+    /// others speak, so every track lines up with the mix. Each turn is then
+    /// synthesized separately. LINEAR16 only.
+    pub fn tracks(mut self, tracks: bool) -> Self {
+        self.request.tracks = tracks;
+        self
+    }
+
+    /// Also write a JSON table of where each voice-markup segment or tracks
+    /// turn starts and ends in the joined output, for chapter skipping within
+    /// the one file. Not for OGG_OPUS, which can't be measured.
+    pub fn segment_offsets(mut self, segment_offsets: bool) -> Self {
+        self.request.segment_offsets = segment_offsets;
+        self
+    }
+
+    /// Bitrate, VBR mode and application for OGG_OPUS output. Providers that
+    /// can't set them (see `opus_bitrate` in the
+    /// [capabilities](Provider::capabilities)) have their audio re-encoded with
+    /// ffmpeg, which must then be installed.
+    pub fn opus(mut self, opus: OpusSettings) -> Self {
+        self.request.opus = opus;
+        self
+    }
+
+    /// Google custom voice model,
+    /// `projects/{project}/locations/{location}/models/{model}`.
+    pub fn custom_voice_model(mut self, model: Option<&str>) -> Self {
+        self.request.custom_voice_model = model.map(str::to_string);
+        self
+    }
+
+    /// Local model for Piper or Kokoro: an `.onnx` path, or the name of one
+    /// in `PIPER_MODELS_DIR` or `KOKORO_DIR`.
+    pub fn model(mut self, model: Option<&str>) -> Self {
+        self.request.model = model.map(str::to_string);
+        self
+    }
+
+    pub fn timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.request.timeout_ms = timeout_ms;
+        self
+    }
+
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.request.retries = retries;
+        self
+    }
+
+    /// Stop synthesis (and any stream it returned) once `token` is cancelled;
+    /// the call then fails with [`FastTtsError::Cancelled`].
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.request.cancel = token;
+        self
+    }
+
+    /// Check ranges, the output extension (when an output is set) and the
+    /// provider's [capabilities](Provider::capabilities): encoding, SSML
+    /// support and input length. Every failure is [`FastTtsError::InvalidInput`].
+    pub fn build(self) -> Result<SynthesisRequest> {
+        let mut r = self.request;
+        if r.text.trim().is_empty() {
+            return Err(invalid("text must not be empty".into()));
+        }
+        r.language = language_or_voice(&r.language, r.provider, r.voice.as_deref());
+        check_range("crossfade", r.crossfade_ms as f32, 0.0, 500.0)?;
+        if let Some(hz) = r.sample_rate
+            && hz <= 0
+        {
+            return Err(invalid(format!("sample rate must be positive, got {hz}")));
+        }
+        // Unimplemented providers list no encodings; dispatch reports those
+        let caps = r.provider.capabilities();
+        if !caps.encodings.is_empty() {
+            check_control("rate", r.rate, 1.0, caps.rate_range, &caps.provider)?;
+            check_control("pitch", r.pitch, 0.0, caps.pitch_range, &caps.provider)?;
+            check_control(
+                "volume",
+                r.volume_gain_db,
+                0.0,
+                caps.volume_range,
+                &caps.provider,
+            )?;
+        }
+        // Requests that are only streamed need no output path
+        if !r.output.as_os_str().is_empty() {
+            validate_output_extension(&r.output, r.encoding)?;
+        }
+
+        if !caps.encodings.is_empty() && !caps.encodings.contains(&r.encoding.api_str()) {
+            return Err(invalid(format!(
+                "provider {} does not support encoding {} (supported: {})",
+                caps.provider,
+                r.encoding.api_str(),
+                caps.encodings.join(", ")
+            )));
+        }
+        if r.ssml && r.voice_markup {
+            return Err(invalid(
+                "voice markup applies to plain text; use <voice> elements in SSML".into(),
+            ));
+        }
+        if r.ssml && caps.ssml_dialect.is_none() {
+            return Err(invalid(format!(
+                "provider {} does not accept SSML input",
+                caps.provider
+            )));
+        }
+        if r.ssml {
+            crate::ssml::check(&r.text).map_err(|e| invalid(format!("invalid SSML: {e}")))?;
+        }
+        if self.dialogue_script {
+            if r.ssml || r.voice_markup {
+                return Err(invalid(
+                    "a dialogue script is plain text; it cannot be combined with SSML or voice markup"
+                        .into(),
+                ));
+            }
+            if !caps.multi_speaker {
+                return Err(invalid(format!(
+                    "provider {} does not support dialogue scripts",
+                    caps.provider
+                )));
+            }
+            r.dialogue = markup::parse_dialogue(&r.text).map_err(invalid)?;
+        }
+        crate::opus::check(&r.opus, r.encoding).map_err(invalid)?;
+        if r.tracks {
+            if !self.dialogue_script && !r.voice_markup {
+                return Err(invalid(
+                    "tracks split a dialogue or voice-markup render by speaker; use them with dialogue or voice markup"
+                        .into(),
+                ));
+            }
+            if r.encoding != AudioEncoding::Linear16 {
+                return Err(invalid(format!(
+                    "tracks are written as WAV aligned with silence; use encoding LINEAR16, not {}",
+                    r.encoding.api_str()
+                )));
+            }
+            if r.crossfade_ms > 0 {
+                return Err(invalid(
+                    "tracks line up with a hard-cut mix; they cannot be combined with a crossfade"
+                        .into(),
+                ));
+            }
+        }
+        if r.segment_offsets {
+            if !r.voice_markup && !r.tracks {
+                return Err(invalid(
+                    "segment offsets describe a render joined from parts; use them with voice markup or tracks"
+                        .into(),
+                ));
+            }
+            if r.encoding == AudioEncoding::OggOpus {
+                return Err(invalid(
+                    "segment offsets can't be measured in OGG_OPUS audio; use LINEAR16 or MP3"
+                        .into(),
+                ));
+            }
+        }
+        for id in &mut r.effects_profile_id {
+            if let Some(full) = caps.effects_profile_aliases.get(id.as_str()) {
+                *id = full.to_string();
+            }
+        }
+        if !caps.encodings.is_empty()
+            && let Some(unknown) = r
+                .effects_profile_id
+                .iter()
+                .find(|id| !caps.effects_profiles.contains(&id.as_str()))
+        {
+            return Err(invalid(if caps.effects_profiles.is_empty() {
+                format!(
+                    "provider {} has no effects profiles (--effects-profile {unknown})",
+                    caps.provider
+                )
+            } else {
+                let names = caps
+                    .effects_profiles
+                    .iter()
+                    .chain(caps.effects_profile_aliases.keys())
+                    .copied();
+                let hint = match crate::suggest::closest(unknown, names).as_slice() {
+                    [] => String::new(),
+                    [name, ..] => format!("; did you mean '{name}'?"),
+                };
+                format!(
+                    "unknown --effects-profile {unknown} for provider {}{hint} (known: {}; aliases: {})",
+                    caps.provider,
+                    caps.effects_profiles.join(", "),
+                    caps.effects_profile_aliases
+                        .iter()
+                        .map(|(alias, id)| format!("{alias}={id}"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            }));
+        }
+        if r.seed.is_some() && !caps.seed {
+            return Err(invalid(format!(
+                "provider {} does not accept a seed",
+                caps.provider
+            )));
+        }
+        if r.custom_voice_model.is_some() && r.provider != Provider::Google {
+            return Err(invalid(format!(
+                "custom voice models are a Google feature; provider {} does not accept one",
+                caps.provider
+            )));
+        }
+        if r.model.is_some() && !matches!(r.provider, Provider::Piper | Provider::Kokoro) {
+            return Err(invalid(format!(
+                "--model picks a local Piper or Kokoro model; provider {} does not take one",
+                caps.provider
+            )));
+        }
+        if let Some(max) = caps.max_input_bytes
+            && r.text.len() > max
+        {
+            return Err(invalid(format!(
+                "text is {} bytes; provider {} accepts at most {max}",
+                r.text.len(),
+                caps.provider
+            )));
+        }
+        Ok(r)
+    }
+}
+
+/// `language`, or when it is empty the language of `voice`, else en-US.
+pub(crate) fn language_or_voice(language: &str, provider: Provider, voice: Option<&str>) -> String {
+    if !language.trim().is_empty() {
+        return language.to_string();
+    }
+    voice
+        .and_then(|voice| voice_language(provider, voice))
+        .unwrap_or_else(|| "en-US".into())
+}
+
+/// The language `voice` speaks, from a Google/Azure style name
+/// (`en-GB-LibbyNeural`, `cmn-CN-Wavenet-A`) or else the provider's cached
+/// voice catalog.
+fn voice_language(provider: Provider, voice: &str) -> Option<String> {
+    let mut parts = voice.split('-');
+    if let (Some(lang), Some(region), Some(_)) = (parts.next(), parts.next(), parts.next())
+        && (2..=3).contains(&lang.len())
+        && lang.chars().all(|c| c.is_ascii_lowercase())
+        && ((region.len() == 2 && region.chars().all(|c| c.is_ascii_uppercase()))
+            || (region.len() == 3 && region.chars().all(|c| c.is_ascii_digit())))
+    {
+        return Some(format!("{lang}-{region}"));
+    }
+    crate::cached_voices(provider)?
+        .into_iter()
+        .find(|v| v.name == voice)?
+        .language_codes
+        .into_iter()
+        .next()
+}
+
+fn invalid(message: String) -> anyhow::Error {
+    FastTtsError::InvalidInput(message).into()
+}
+
+/// Check `--<flag> value` against the provider's range; a provider without
+/// the control only accepts its neutral `default`.
+fn check_control(
+    flag: &str,
+    value: f32,
+    default: f32,
+    range: Option<(f32, f32)>,
+    provider: &str,
+) -> Result<()> {
+    match range {
+        Some((min, max)) if !(min..=max).contains(&value) => Err(invalid(format!(
+            "--{flag} {value} is out of range for provider {provider} ({min} to {max})"
+        ))),
+        None if value != default => Err(invalid(format!(
+            "provider {provider} has no {flag} control (--{flag} {value})"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn check_range(name: &str, value: f32, min: f32, max: f32) -> Result<()> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(invalid(format!(
+            "{name} {value} is out of range ({min} to {max})"
+        )))
+    }
+}
@@ -0,0 +1,214 @@
+//! One document, several files: `--split-on-marker` and `--split-on-break`
+//! cut the text into sections that are synthesized to their own outputs,
+//! named from a template (one file per slide or chapter).
+
+use crate::FastTtsError;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Where [`split`] cuts a document.
+#[derive(Debug, Clone, Copy)]
+pub enum SplitAt<'a> {
+    /// Lines that consist of just this marker, e.g. `---`
+    Marker(&'a str),
+    /// SSML `<break time="..."/>` elements at least this long
+    Break(Duration),
+}
+
+/// Split `text` into sections, dropping the separators and empty sections.
+/// SSML sections each get the document's own `<speak>` tag, and each must
+/// still be a well-formed document (a cut inside `<prosody>` is not).
+pub fn split(text: &str, ssml: bool, at: SplitAt<'_>) -> Result<Vec<String>> {
+    if let SplitAt::Break(_) = at
+        && !ssml
+    {
+        return Err(invalid("splitting on breaks needs SSML input".into()));
+    }
+    let (open, body, close) = if ssml {
+        speak_envelope(text)
+    } else {
+        ("", text, "")
+    };
+    let pieces = match at {
+        SplitAt::Marker(marker) => split_on_marker(body, marker),
+        SplitAt::Break(min) => split_on_breaks(body, min)?,
+    };
+    let sections: Vec<String> = pieces
+        .into_iter()
+        .map(str::trim)
+        .filter(|piece| !piece.is_empty())
+        .map(|piece| format!("{open}{piece}{close}"))
+        .collect();
+    if ssml {
+        for (idx, section) in sections.iter().enumerate() {
+            crate::ssml::check(section).map_err(|e| {
+                invalid(format!(
+                    "section {} is not valid SSML on its own ({e}); split outside other elements",
+                    idx + 1
+                ))
+            })?;
+        }
+    }
+    if sections.is_empty() {
+        return Err(invalid(
+            "nothing to synthesize between the separators".into(),
+        ));
+    }
+    Ok(sections)
+}
+
+/// `text` as its opening `<speak ...>` tag, content and closing tag, when it
+/// has them.
+fn speak_envelope(text: &str) -> (&str, &str, &str) {
+    let trimmed = text.trim();
+    if trimmed.starts_with("<speak")
+        && let Some(open_end) = trimmed.find('>')
+        && let Some(close) = trimmed.rfind("</speak>")
+        && close > open_end
+    {
+        return (
+            &trimmed[..=open_end],
+            &trimmed[open_end + 1..close],
+            &trimmed[close..],
+        );
+    }
+    ("", text, "")
+}
+
+fn split_on_marker<'a>(text: &'a str, marker: &str) -> Vec<&'a str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut at = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim() == marker.trim() {
+            pieces.push(&text[start..at]);
+            start = at + line.len();
+        }
+        at += line.len();
+    }
+    pieces.push(&text[start..]);
+    pieces
+}
+
+fn split_on_breaks(text: &str, min: Duration) -> Result<Vec<&str>> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut from = 0;
+    while let Some(found) = text[from..].find("<break") {
+        let tag_start = from + found;
+        let Some(len) = text[tag_start..].find('>') else {
+            break;
+        };
+        let tag_end = tag_start + len + 1;
+        if let Some(time) = attribute(&text[tag_start..tag_end], "time")
+            && crate::parse_duration(time)? >= min
+        {
+            pieces.push(&text[start..tag_start]);
+            start = tag_end;
+        }
+        from = tag_end;
+    }
+    pieces.push(&text[start..]);
+    Ok(pieces)
+}
+
+/// The value of `name="..."` (or single-quoted) in an element's tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(at) = rest.find(name) {
+        let after = rest[at + name.len()..].trim_start();
+        let preceded = rest[..at].ends_with(char::is_whitespace);
+        if preceded && let Some(value) = after.strip_prefix('=') {
+            let value = value.trim_start();
+            let quote = value.chars().next().filter(|q| matches!(q, '"' | '\''))?;
+            let value = &value[1..];
+            return value.find(quote).map(|end| &value[..end]);
+        }
+        rest = &rest[at + name.len()..];
+    }
+    None
+}
+
+/// Output paths for `sections`, next to `output`, from `template`: `{stem}`
+/// and `{ext}` come from `output`, `{n}` numbers the sections from 1
+/// (zero-padded to the same width) and `{slug}` is the section's first few
+/// words.
+pub fn output_names(output: &Path, template: &str, sections: &[String]) -> Result<Vec<PathBuf>> {
+    let stem = output
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let ext = output
+        .extension()
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let width = sections.len().to_string().len();
+    let dir = output.parent().unwrap_or(Path::new(""));
+    let mut names: Vec<PathBuf> = Vec::with_capacity(sections.len());
+    for (idx, section) in sections.iter().enumerate() {
+        let mut name = String::new();
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            name.push_str(&rest[..open]);
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| invalid(format!("unclosed {{ in name template {template:?}")))?;
+            match &rest[open + 1..open + close] {
+                "stem" => name.push_str(&stem),
+                "ext" => name.push_str(&ext),
+                "n" => name.push_str(&format!("{:0width$}", idx + 1)),
+                "slug" => name.push_str(&slug(section)),
+                other => {
+                    return Err(invalid(format!(
+                        "unknown placeholder {{{other}}} in name template; use {{stem}}, {{n}}, {{slug}} or {{ext}}"
+                    )));
+                }
+            }
+            rest = &rest[open + close + 1..];
+        }
+        name.push_str(rest);
+        let path = dir.join(name);
+        if names.contains(&path) {
+            return Err(invalid(format!(
+                "name template {template:?} gives two sections the name {}; add {{n}}",
+                path.display()
+            )));
+        }
+        names.push(path);
+    }
+    Ok(names)
+}
+
+/// Up to the first five words of `section` (markup removed), lowercase and
+/// joined with `-`.
+fn slug(section: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in section.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .take(5)
+        .map(str::to_lowercase)
+        .collect();
+    if words.is_empty() {
+        "section".into()
+    } else {
+        words.join("-")
+    }
+}
+
+fn invalid(message: String) -> anyhow::Error {
+    FastTtsError::InvalidInput(message).into()
+}
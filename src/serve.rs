@@ -0,0 +1,589 @@
+//! REST API server (`fast-tts serve`): the provider layer over HTTP, so
+//! internal services can synthesize without shelling out to the CLI.
+
+use crate::{
+    AudioEncoding, Gender, Provider, SynthesisRequest, ToolFailure, list_voices, load_profile,
+    parse_encoding_from_str, parse_remote_provider, random_id, with_profile,
+};
+use anyhow::{Context, Result};
+use axum::{
+    Extension, Json, Router,
+    body::Body,
+    extract::{DefaultBodyLimit, Path as UrlPath, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware,
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+};
+use clap::ValueEnum;
+use futures::StreamExt as _;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::Instrument as _;
+
+#[cfg(unix)]
+pub mod daemon;
+mod idempotency;
+mod jobs;
+mod keys;
+mod limits;
+mod queue;
+pub mod storage;
+
+pub struct ServeOptions {
+    pub addr: String,
+    /// How long audio stored for URL responses stays downloadable
+    pub audio_ttl: Duration,
+    /// API keys file; without it the server is open to anyone who can reach it
+    pub api_keys: Option<PathBuf>,
+    /// Requests per minute per client IP (`None` = unlimited)
+    pub rate_limit: Option<u32>,
+    /// Longest `text` accepted by `POST /synthesize`, in characters
+    pub max_text_chars: usize,
+    /// Bucket for `"response": "signedUrl"`
+    pub storage: Option<storage::ObjectStore>,
+    /// Syntheses run at once
+    pub max_concurrent: usize,
+    /// Requests that may wait for a free synthesis slot before the server answers 429
+    pub queue_depth: usize,
+}
+
+#[derive(Clone)]
+struct AppState {
+    audio_dir: Arc<PathBuf>,
+    audio_ttl: Duration,
+    api_keys: Option<Arc<keys::ApiKeys>>,
+    client_limiter: Option<Arc<limits::ClientLimiter>>,
+    max_text_chars: usize,
+    replays: Arc<idempotency::Replays>,
+    storage: Option<Arc<storage::ObjectStore>>,
+    queue: Arc<queue::Queue>,
+    jobs: Arc<jobs::Jobs>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SynthesizeBody {
+    text: String,
+    provider: Option<String>,
+    profile: Option<String>,
+    language: Option<String>,
+    voice: Option<String>,
+    gender: Option<String>,
+    rate: Option<f32>,
+    pitch: Option<f32>,
+    sample_rate: Option<i32>,
+    encoding: Option<String>,
+    volume_gain_db: Option<f32>,
+    #[serde(default)]
+    effects_profile_id: Vec<String>,
+    #[serde(default)]
+    ssml: bool,
+    #[serde(default)]
+    dialogue: bool,
+    seed: Option<u32>,
+    custom_voice_model: Option<String>,
+    model: Option<String>,
+    /// `audio` (default) returns the bytes; `url` stores them and returns JSON
+    /// with a download URL; `signedUrl` uploads them to object storage and
+    /// returns JSON with a signed URL
+    response: Option<String>,
+}
+
+pub async fn run(options: ServeOptions) -> Result<()> {
+    let api_keys = options
+        .api_keys
+        .as_deref()
+        .map(keys::ApiKeys::load)
+        .transpose()?
+        .map(Arc::new);
+    let audio_dir = std::env::temp_dir().join(format!("fast-tts-serve-{}", std::process::id()));
+    std::fs::create_dir_all(&audio_dir)
+        .with_context(|| format!("failed to create {}", audio_dir.display()))?;
+    let state = AppState {
+        audio_dir: Arc::new(audio_dir),
+        audio_ttl: options.audio_ttl,
+        api_keys,
+        client_limiter: options
+            .rate_limit
+            .filter(|r| *r > 0)
+            .map(|r| Arc::new(limits::ClientLimiter::new(r))),
+        max_text_chars: options.max_text_chars,
+        replays: Arc::default(),
+        storage: options.storage.map(Arc::new),
+        queue: Arc::new(queue::Queue::new(
+            options.max_concurrent,
+            options.queue_depth,
+        )),
+        jobs: Arc::default(),
+    };
+    tokio::spawn(sweep_expired_audio(state.clone()));
+
+    let app = Router::new()
+        .route("/synthesize", post(synthesize))
+        .route(
+            "/synthesize/batch",
+            post(jobs::submit).layer(DefaultBodyLimit::max(limits::body_limit(
+                options.max_text_chars.saturating_mul(jobs::MAX_ITEMS),
+            ))),
+        )
+        .route("/jobs/:id", get(jobs::status))
+        .route("/jobs/:id/files/*output", get(jobs::file))
+        .route("/audio/:name", get(stored_audio))
+        .route("/voices", get(voices))
+        .route("/providers", get(providers))
+        .route("/usage", get(keys::usage))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            keys::require_api_key,
+        ))
+        // The demo page itself is public; it asks for an API key when the server needs one
+        .route("/", get(index))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            limits::limit_clients,
+        ))
+        .layer(DefaultBodyLimit::max(limits::body_limit(
+            options.max_text_chars,
+        )))
+        .with_state(state)
+        .layer(middleware::from_fn(trace_request));
+
+    let listener = crate::listen::Listener::bind(&options.addr).await?;
+    if !listener.is_tcp() && options.rate_limit.is_some() {
+        eprintln!("Warning: --rate-limit is per client IP and has no effect on a unix socket");
+    }
+    eprintln!("Listening on {}", listener.describe("http"));
+    crate::listen::serve(listener, app, std::future::pending()).await
+}
+
+/// Server span per request, continuing the caller's W3C `traceparent` if sent.
+async fn trace_request(req: axum::extract::Request, next: middleware::Next) -> Response {
+    let traceparent = req
+        .headers()
+        .get("traceparent")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let span = tracing::info_span!(
+        "http.server",
+        otel.kind = "server",
+        otel.name = %format!("{} {}", req.method(), req.uri().path()),
+        http.request.method = %req.method(),
+        url.path = %req.uri().path(),
+        traceparent = %traceparent,
+        http.response.status_code = tracing::field::Empty,
+        otel.status_code = tracing::field::Empty,
+    );
+    let response = next.run(req).instrument(span.clone()).await;
+    span.record("http.response.status_code", response.status().as_u16());
+    if response.status().is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+    response
+}
+
+/// Demo page: text box, provider/voice pickers and a play button.
+async fn index() -> Html<&'static str> {
+    Html(include_str!("serve/index.html"))
+}
+
+fn error_json(status: StatusCode, code: &str, message: impl Into<String>) -> Response {
+    let body = serde_json::json!({ "error": { "code": code, "message": message.into() } });
+    (status, Json(body)).into_response()
+}
+
+/// Map a provider failure to an HTTP status while keeping the structured payload.
+fn provider_error(provider: Provider, err: &anyhow::Error) -> Response {
+    let failure = ToolFailure::classify(provider, err);
+    let status = match failure.code {
+        "invalid_request" => StatusCode::BAD_REQUEST,
+        "rate_limited" => StatusCode::TOO_MANY_REQUESTS,
+        "timeout" => StatusCode::GATEWAY_TIMEOUT,
+        "auth_missing" | "unsupported" | "offline" => StatusCode::SERVICE_UNAVAILABLE,
+        "auth_failed" | "network" | "provider_unavailable" => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(serde_json::json!({ "error": failure }))).into_response()
+}
+
+/// How `POST /synthesize` hands the audio back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Delivery {
+    /// The bytes themselves
+    Audio,
+    /// JSON with a `/audio/...` URL on this server
+    Url,
+    /// JSON with a signed URL in object storage
+    SignedUrl,
+}
+
+fn delivery(headers: &HeaderMap, body: &SynthesizeBody) -> Delivery {
+    match body.response.as_deref() {
+        Some(mode) if mode.eq_ignore_ascii_case("url") => Delivery::Url,
+        Some(mode) if mode.eq_ignore_ascii_case("signedUrl") => Delivery::SignedUrl,
+        Some(_) => Delivery::Audio,
+        None if headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("application/json")) =>
+        {
+            Delivery::Url
+        }
+        None => Delivery::Audio,
+    }
+}
+
+async fn synthesize(
+    State(state): State<AppState>,
+    key: Option<Extension<Arc<keys::ApiKey>>>,
+    headers: HeaderMap,
+    Json(body): Json<SynthesizeBody>,
+) -> Response {
+    if body.text.trim().is_empty() {
+        return error_json(StatusCode::BAD_REQUEST, "invalid_request", "text is empty");
+    }
+    let encoding = match parse_encoding_from_str(body.encoding.as_deref().unwrap_or("MP3")) {
+        Ok(enc) => enc,
+        Err(e) => return error_json(StatusCode::BAD_REQUEST, "invalid_request", e.to_string()),
+    };
+    let profile = match body.profile.as_deref().map(load_profile).transpose() {
+        Ok(profile) => profile.unwrap_or_default(),
+        Err(e) => return error_json(StatusCode::BAD_REQUEST, "invalid_request", e.to_string()),
+    };
+    let provider = match body.provider.as_deref() {
+        Some(name) => parse_remote_provider(name),
+        None => profile.provider_or(Provider::Google),
+    };
+    let provider = match provider {
+        Ok(p) => p,
+        Err(e) => return error_json(StatusCode::BAD_REQUEST, "invalid_request", e.to_string()),
+    };
+
+    let name = format!("{}.{}", random_id(), encoding.file_extension());
+    let path = state.audio_dir.join(&name);
+    let request = match build_request(provider, &body, encoding, &path) {
+        Ok(request) => request,
+        Err(e) => return error_json(StatusCode::BAD_REQUEST, "invalid_request", e.to_string()),
+    };
+
+    let chars = body.text.chars().count() as u64;
+    if chars > state.max_text_chars as u64 {
+        return error_json(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "text_too_long",
+            format!(
+                "text is {chars} characters; this server accepts at most {}",
+                state.max_text_chars
+            ),
+        );
+    }
+    let idempotency_key = match headers.get("idempotency-key").map(|v| v.to_str()) {
+        None => None,
+        Some(Ok(value)) if !value.is_empty() && value.len() <= idempotency::MAX_KEY_LEN => {
+            Some(value.to_string())
+        }
+        Some(_) => {
+            return error_json(
+                StatusCode::BAD_REQUEST,
+                "invalid_request",
+                format!(
+                    "Idempotency-Key must be 1 to {} visible ASCII characters",
+                    idempotency::MAX_KEY_LEN
+                ),
+            );
+        }
+    };
+    let key = key.map(|Extension(key)| key);
+    let delivery = delivery(&headers, &body);
+    if delivery == Delivery::SignedUrl && state.storage.is_none() {
+        return error_json(
+            StatusCode::BAD_REQUEST,
+            "invalid_request",
+            "signed URLs need object storage; start the server with --storage",
+        );
+    }
+
+    if let Some(idempotency_key) = idempotency_key {
+        // Keys are per API key, so two clients can't collide or replay each other's audio
+        let scope = format!(
+            "{}:{idempotency_key}",
+            key.as_ref().map_or("", |k| k.name())
+        );
+        let params = format!("{}:{delivery:?}", request.params_hash());
+        let Ok(slot) = state.replays.slot(scope, params, state.audio_ttl) else {
+            return error_json(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "idempotency_key_reused",
+                "this Idempotency-Key was already used for a different request",
+            );
+        };
+        let mut replayed = true;
+        let stored = slot
+            .get_or_try_init(|| {
+                replayed = false;
+                produce(&state, key.as_deref(), &profile, &request, chars, delivery)
+            })
+            .await;
+        let mut response = match stored {
+            Ok(stored) => stored_response(&state, stored, provider, encoding, delivery),
+            Err(response) => return response,
+        };
+        if replayed {
+            response.headers_mut().insert(
+                "idempotent-replayed",
+                header::HeaderValue::from_static("true"),
+            );
+        }
+        return response;
+    }
+
+    if delivery == Delivery::Audio {
+        let slot = match state.queue.enter().await {
+            Ok(slot) => slot,
+            Err(response) => return response,
+        };
+        if let Some(key) = &key
+            && let Err(message) = key.reserve_chars(chars)
+        {
+            return error_json(StatusCode::TOO_MANY_REQUESTS, "quota_exceeded", message);
+        }
+        // Raw audio goes out as the provider produces it; the slot is held until it ends
+        return match with_profile(&profile, crate::synthesize_stream(&request)).await {
+            Ok(stream) => (
+                [(header::CONTENT_TYPE, encoding.mime_type())],
+                Body::from_stream(stream.map(move |chunk| {
+                    let _ = &slot;
+                    chunk
+                })),
+            )
+                .into_response(),
+            Err(e) => {
+                if let Some(key) = &key {
+                    key.refund_chars(chars);
+                }
+                provider_error(provider, &e)
+            }
+        };
+    }
+
+    match produce(&state, key.as_deref(), &profile, &request, chars, delivery).await {
+        Ok(stored) => stored_response(&state, &stored, provider, encoding, delivery),
+        Err(response) => response,
+    }
+}
+
+/// [`synthesize_file`], then for signed URLs move the file to object storage.
+async fn produce(
+    state: &AppState,
+    key: Option<&keys::ApiKey>,
+    profile: &crate::Profile,
+    request: &SynthesisRequest,
+    chars: u64,
+    delivery: Delivery,
+) -> Result<idempotency::Stored, Response> {
+    let stored = synthesize_file(state, key, profile, request, chars).await?;
+    if delivery != Delivery::SignedUrl {
+        return Ok(stored);
+    }
+    let Some(storage) = &state.storage else {
+        return Ok(stored);
+    };
+    let uploaded = match std::fs::read(&request.output) {
+        Ok(bytes) => {
+            storage
+                .put(&stored.name, bytes, request.encoding.mime_type())
+                .await
+        }
+        Err(e) => Err(e.into()),
+    };
+    let _ = std::fs::remove_file(&request.output);
+    match uploaded {
+        Ok(()) => Ok(stored),
+        Err(e) => {
+            tracing::warn!(error = %format!("{e:#}"), "upload to object storage failed");
+            Err(error_json(
+                StatusCode::BAD_GATEWAY,
+                "storage_failed",
+                format!("{e:#}"),
+            ))
+        }
+    }
+}
+
+/// Synthesize `request` to its output in the audio directory once the queue
+/// has a slot, charging `chars` to `key` (refunded if synthesis fails).
+async fn synthesize_file(
+    state: &AppState,
+    key: Option<&keys::ApiKey>,
+    profile: &crate::Profile,
+    request: &SynthesisRequest,
+    chars: u64,
+) -> Result<idempotency::Stored, Response> {
+    let _slot = state.queue.enter().await?;
+    if let Some(key) = key
+        && let Err(message) = key.reserve_chars(chars)
+    {
+        return Err(error_json(
+            StatusCode::TOO_MANY_REQUESTS,
+            "quota_exceeded",
+            message,
+        ));
+    }
+    if let Err(e) = with_profile(profile, crate::synthesize(request)).await {
+        let _ = std::fs::remove_file(&request.output);
+        if let Some(key) = key {
+            key.refund_chars(chars);
+        }
+        return Err(provider_error(request.provider, &e));
+    }
+    Ok(idempotency::Stored {
+        name: request
+            .output
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        bytes: std::fs::metadata(&request.output)
+            .map(|m| m.len())
+            .unwrap_or_default(),
+    })
+}
+
+/// Stored audio as the client asked for it: the bytes or a download URL.
+fn stored_response(
+    state: &AppState,
+    stored: &idempotency::Stored,
+    provider: Provider,
+    encoding: AudioEncoding,
+    delivery: Delivery,
+) -> Response {
+    if let Delivery::SignedUrl = delivery
+        && let Some(storage) = &state.storage
+    {
+        // Signed afresh, so a replayed key gets a URL with the full lifetime
+        return Json(serde_json::json!({
+            "url": storage.signed_get(&stored.name),
+            "mimeType": encoding.mime_type(),
+            "bytes": stored.bytes,
+            "provider": provider.to_string(),
+            "expiresIn": storage.url_ttl().as_secs(),
+        }))
+        .into_response();
+    }
+    if delivery == Delivery::Audio {
+        return match std::fs::read(state.audio_dir.join(&stored.name)) {
+            Ok(bytes) => ([(header::CONTENT_TYPE, encoding.mime_type())], bytes).into_response(),
+            Err(e) => error_json(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+                format!("stored audio is gone: {e}"),
+            ),
+        };
+    }
+    Json(serde_json::json!({
+        "url": format!("/audio/{}", stored.name),
+        "mimeType": encoding.mime_type(),
+        "bytes": stored.bytes,
+        "provider": provider.to_string(),
+        "expiresIn": state.audio_ttl.as_secs(),
+    }))
+    .into_response()
+}
+
+fn build_request(
+    provider: Provider,
+    body: &SynthesizeBody,
+    encoding: AudioEncoding,
+    output: &std::path::Path,
+) -> Result<SynthesisRequest> {
+    let gender = body
+        .gender
+        .as_deref()
+        .map(|g| Gender::from_str(g, true))
+        .transpose()
+        .map_err(|e| anyhow::anyhow!("invalid gender: {e}"))?;
+    SynthesisRequest::builder()
+        .provider(provider)
+        .text(body.text.as_str())
+        .output(output)
+        .language(body.language.as_deref().unwrap_or_default())
+        .voice(body.voice.as_deref())
+        .gender(gender)
+        .rate(body.rate.unwrap_or(1.0))
+        .pitch(body.pitch.unwrap_or(0.0))
+        .sample_rate(body.sample_rate)
+        .encoding(encoding)
+        .volume_gain_db(body.volume_gain_db.unwrap_or(0.0))
+        .effects_profile_id(body.effects_profile_id.clone())
+        .ssml(body.ssml)
+        .dialogue(body.dialogue)
+        .seed(body.seed)
+        .custom_voice_model(body.custom_voice_model.as_deref())
+        .model(body.model.as_deref())
+        .build()
+}
+
+async fn stored_audio(State(state): State<AppState>, UrlPath(name): UrlPath<String>) -> Response {
+    // Names are generated by us; anything else (including traversal) is simply unknown
+    let valid =
+        name.chars().all(|c| c.is_ascii_alphanumeric() || c == '.') && !name.starts_with('.');
+    if !valid {
+        return error_json(StatusCode::NOT_FOUND, "not_found", "no such audio");
+    }
+    let path = state.audio_dir.join(&name);
+    match std::fs::read(&path) {
+        Ok(bytes) => ([(header::CONTENT_TYPE, audio_mime(&path))], bytes).into_response(),
+        Err(_) => error_json(StatusCode::NOT_FOUND, "not_found", "no such audio"),
+    }
+}
+
+/// Content type of a stored audio file, by its extension.
+fn audio_mime(path: &std::path::Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") => "audio/ogg",
+        _ => "audio/wav",
+    }
+}
+
+async fn voices() -> Response {
+    match list_voices().await {
+        Ok(data) => Json(data).into_response(),
+        Err(e) => provider_error(Provider::Google, &e),
+    }
+}
+
+async fn providers() -> Response {
+    let list: Vec<_> = Provider::all()
+        .into_iter()
+        .map(Provider::capabilities)
+        .collect();
+    Json(list).into_response()
+}
+
+/// Delete stored audio older than the TTL once a minute, with the
+/// idempotency keys pointing at it.
+async fn sweep_expired_audio(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let Ok(entries) = std::fs::read_dir(state.audio_dir.as_ref()) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let expired = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.elapsed().ok())
+                .is_some_and(|age| age > state.audio_ttl);
+            if expired {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+        state.replays.sweep(state.audio_ttl);
+        state.jobs.sweep(state.audio_ttl);
+    }
+}
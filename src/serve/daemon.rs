@@ -0,0 +1,109 @@
+//! Client side of `serve --daemon`: a long-running server on a Unix socket
+//! that keeps provider tokens and TLS sessions warm, so repeated short CLI
+//! calls skip both.
+
+use crate::{AudioStream, FastTtsError, SynthesisRequest};
+use anyhow::{Context, Result};
+use axum::body::Body;
+use clap::ValueEnum as _;
+use futures::StreamExt as _;
+use hyper_util::rt::TokioIo;
+use std::path::PathBuf;
+
+/// `FAST_TTS_SOCKET`, else `fast-tts-cli.sock` in the user's runtime directory.
+pub fn socket_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("FAST_TTS_SOCKET").filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    dirs::runtime_dir().map(|d| d.join("fast-tts-cli.sock"))
+}
+
+/// Send `request` to the daemon and stream back its audio. `Ok(None)` means
+/// no daemon is listening and the caller should synthesize itself.
+pub async fn synthesize_stream(
+    request: &SynthesisRequest,
+    profile: Option<&str>,
+) -> Result<Option<AudioStream>> {
+    let Some(path) = socket_path() else {
+        return Ok(None);
+    };
+    // A missing socket or one left behind by a dead daemon both refuse the connection
+    let Ok(stream) = tokio::net::UnixStream::connect(&path).await else {
+        return Ok(None);
+    };
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .context("daemon handshake failed")?;
+    tokio::spawn(connection);
+
+    let body = serde_json::json!({
+        "text": request.text,
+        "provider": request.provider.to_string(),
+        "profile": profile,
+        "language": request.language,
+        "voice": request.voice,
+        "gender": request
+            .gender
+            .and_then(|g| g.to_possible_value())
+            .map(|v| v.get_name().to_string()),
+        "rate": request.rate,
+        "pitch": request.pitch,
+        "sampleRate": request.sample_rate,
+        "encoding": request.encoding.api_str(),
+        "volumeGainDb": request.volume_gain_db,
+        "effectsProfileId": request.effects_profile_id,
+        "ssml": request.ssml,
+        "dialogue": !request.dialogue.is_empty(),
+        "seed": request.seed,
+        "customVoiceModel": request.custom_voice_model,
+        "model": request.model,
+    });
+    let http_request = http::Request::post("/synthesize")
+        .header(http::header::HOST, "localhost")
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))?;
+    tracing::info!(socket = %path.display(), "synthesizing through daemon");
+    let response = tokio::select! {
+        response = sender.send_request(http_request) => response.context("daemon request failed")?,
+        _ = request.cancel.cancelled() => return Err(crate::cancelled()),
+    };
+
+    let status = response.status();
+    let mut data = Body::new(response.into_body()).into_data_stream();
+    if !status.is_success() {
+        let mut raw = Vec::new();
+        while let Some(chunk) = data.next().await {
+            raw.extend_from_slice(&chunk?);
+        }
+        let error = serde_json::from_slice::<serde_json::Value>(&raw).ok();
+        let message = error
+            .as_ref()
+            .and_then(|v| v["error"]["message"].as_str().map(str::to_string))
+            .unwrap_or_else(|| String::from_utf8_lossy(&raw).into_owned());
+        let message = format!("daemon: {message}");
+        let code = error.as_ref().and_then(|v| v["error"]["code"].as_str());
+        return Err(match (status.as_u16(), code) {
+            (_, Some("auth_missing")) => FastTtsError::MissingCredentials(message),
+            (_, Some("offline")) => FastTtsError::Offline(message),
+            (400 | 413, _) => FastTtsError::InvalidInput(message),
+            (401 | 403, _) => FastTtsError::Auth(message),
+            (429, _) => FastTtsError::Quota(message),
+            (503, _) => FastTtsError::Unsupported(message),
+            (code, _) => FastTtsError::ProviderHttp {
+                status: Some(code),
+                message,
+            },
+        }
+        .into());
+    }
+
+    let cancel = request.cancel.clone();
+    let stream = futures::stream::unfold((data, cancel), |(mut data, cancel)| async move {
+        let item = tokio::select! {
+            chunk = data.next() => chunk?.map_err(anyhow::Error::from),
+            _ = cancel.cancelled() => Err(crate::cancelled()),
+        };
+        Some((item, (data, cancel)))
+    });
+    Ok(Some(Box::pin(stream)))
+}
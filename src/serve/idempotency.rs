@@ -0,0 +1,67 @@
+//! `Idempotency-Key` for `POST /synthesize`: a client retrying after a
+//! network blip gets the first attempt's audio back instead of a second
+//! synthesis (and a second charge against its quota).
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+/// Keys a client may send; longer ones are rejected rather than stored.
+pub const MAX_KEY_LEN: usize = 255;
+
+/// The stored audio a key's first successful attempt produced.
+#[derive(Debug, Clone)]
+pub struct Stored {
+    /// File name in the server's audio directory
+    pub name: String,
+    pub bytes: u64,
+}
+
+struct Entry {
+    /// What the key was first used for; the same key with other parameters is an error
+    params: String,
+    created: Instant,
+    result: Arc<OnceCell<Stored>>,
+}
+
+/// Results by key, kept as long as the audio they point at.
+#[derive(Default)]
+pub struct Replays(Mutex<HashMap<String, Entry>>);
+
+impl Replays {
+    /// The slot for `key`, created on first use. Concurrent attempts share
+    /// the slot, so only one of them synthesizes; a failed attempt leaves it
+    /// empty for the next. `Err` when `key` was used for different `params`.
+    pub fn slot(
+        &self,
+        key: String,
+        params: String,
+        ttl: Duration,
+    ) -> Result<Arc<OnceCell<Stored>>, ()> {
+        let mut entries = self.0.lock().unwrap();
+        let expired = entries
+            .get(&key)
+            .is_some_and(|entry| entry.created.elapsed() > ttl);
+        if expired {
+            entries.remove(&key);
+        }
+        let entry = entries.entry(key).or_insert_with(|| Entry {
+            params: params.clone(),
+            created: Instant::now(),
+            result: Arc::new(OnceCell::new()),
+        });
+        if entry.params != params {
+            return Err(());
+        }
+        Ok(entry.result.clone())
+    }
+
+    /// Forget keys whose audio has been swept.
+    pub fn sweep(&self, ttl: Duration) {
+        self.0
+            .lock()
+            .unwrap()
+            .retain(|_, entry| entry.created.elapsed() <= ttl);
+    }
+}
@@ -0,0 +1,322 @@
+//! `POST /synthesize/batch`: a bulk config (the `--config` structure) run in
+//! the background by the bulk engine. The job is polled at `GET /jobs/{id}`
+//! and its files are downloaded from `GET /jobs/{id}/files/{output}`; both
+//! are kept for `--audio-ttl` after the job finishes.
+
+use super::{AppState, audio_mime, error_json, keys};
+use crate::providers::custom::percent_encode;
+use crate::{BulkConfig, BulkEvent, BulkOptions};
+use axum::{
+    Extension, Json,
+    extract::{Path as UrlPath, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Items accepted in one batch.
+pub const MAX_ITEMS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum Status {
+    /// Waiting for a synthesis slot
+    Queued,
+    Running,
+    Succeeded,
+    /// Stopped early, or finished with failed items (see `results`)
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ItemResult {
+    index: usize,
+    /// Path as given in the config
+    output: String,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Job {
+    id: String,
+    status: Status,
+    items: usize,
+    chars: u64,
+    completed: usize,
+    failed: usize,
+    results: Vec<ItemResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// API key that submitted the job; only it can see the job
+    #[serde(skip)]
+    owner: Option<String>,
+    #[serde(skip)]
+    dir: PathBuf,
+    #[serde(skip)]
+    finished: Option<Instant>,
+}
+
+/// Jobs by ID.
+#[derive(Default)]
+pub struct Jobs(Mutex<HashMap<String, Arc<Mutex<Job>>>>);
+
+impl Jobs {
+    fn get(&self, id: &str, key: Option<&keys::ApiKey>) -> Option<Arc<Mutex<Job>>> {
+        let job = self.0.lock().unwrap().get(id)?.clone();
+        let owner = key.map(|k| k.name().to_string());
+        let owned = job.lock().unwrap().owner == owner;
+        owned.then_some(job)
+    }
+
+    fn unfinished(&self) -> usize {
+        self.0
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|job| job.lock().unwrap().finished.is_none())
+            .count()
+    }
+
+    /// Forget jobs that finished more than `ttl` ago, and delete their files.
+    pub fn sweep(&self, ttl: Duration) {
+        self.0.lock().unwrap().retain(|_, job| {
+            let job = job.lock().unwrap();
+            let expired = job.finished.is_some_and(|at| at.elapsed() > ttl);
+            if expired {
+                let _ = std::fs::remove_dir_all(&job.dir);
+            }
+            !expired
+        });
+    }
+}
+
+/// Characters an item sends to the provider.
+fn item_chars(item: &crate::BulkItem) -> usize {
+    match &item.turns {
+        Some(turns) => turns.iter().map(|t| t.text.chars().count()).sum(),
+        None => item.text.chars().count(),
+    }
+}
+
+pub async fn submit(
+    State(state): State<AppState>,
+    key: Option<Extension<Arc<keys::ApiKey>>>,
+    Json(config): Json<BulkConfig>,
+) -> Response {
+    if config.items.is_empty() {
+        return error_json(StatusCode::BAD_REQUEST, "invalid_request", "items is empty");
+    }
+    if config.items.len() > MAX_ITEMS {
+        return error_json(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "too_many_items",
+            format!(
+                "batch has {} items; this server accepts at most {MAX_ITEMS}",
+                config.items.len()
+            ),
+        );
+    }
+    if let Some((index, chars)) = config
+        .items
+        .iter()
+        .map(item_chars)
+        .enumerate()
+        .find(|(_, chars)| *chars > state.max_text_chars)
+    {
+        return error_json(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "text_too_long",
+            format!(
+                "item {} is {chars} characters; this server accepts at most {}",
+                index + 1,
+                state.max_text_chars
+            ),
+        );
+    }
+    // Batches run in the background, so only so many may be outstanding
+    if state.jobs.unfinished() >= state.queue.depth().max(1) {
+        return state
+            .queue
+            .busy("too many batch jobs are queued or running");
+    }
+    let chars = config.items.iter().map(item_chars).sum::<usize>() as u64;
+    let key = key.map(|Extension(key)| key);
+    if let Some(key) = &key
+        && let Err(message) = key.reserve_chars(chars)
+    {
+        return error_json(StatusCode::TOO_MANY_REQUESTS, "quota_exceeded", message);
+    }
+
+    let id = crate::random_id();
+    let job = Arc::new(Mutex::new(Job {
+        id: id.clone(),
+        status: Status::Queued,
+        items: config.items.len(),
+        chars,
+        completed: 0,
+        failed: 0,
+        results: Vec::new(),
+        error: None,
+        owner: key.as_ref().map(|k| k.name().to_string()),
+        dir: state.audio_dir.join("jobs").join(&id),
+        finished: None,
+    }));
+    state.jobs.0.lock().unwrap().insert(id.clone(), job.clone());
+    tokio::spawn(run(state, job, config, key));
+
+    let url = format!("/jobs/{id}");
+    (
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, url.clone())],
+        Json(serde_json::json!({ "id": id, "status": Status::Queued, "url": url })),
+    )
+        .into_response()
+}
+
+async fn run(
+    state: AppState,
+    job: Arc<Mutex<Job>>,
+    config: BulkConfig,
+    key: Option<Arc<keys::ApiKey>>,
+) {
+    let _slot = state.queue.wait().await;
+    let (id, dir) = {
+        let mut job = job.lock().unwrap();
+        job.status = Status::Running;
+        (job.id.clone(), job.dir.clone())
+    };
+    let options = BulkOptions {
+        keep_going: true,
+        output_root: Some(dir.clone()),
+        ..BulkOptions::default()
+    };
+    let mut item_chars = 0;
+    let mut written_chars = 0;
+    let relative = |output: &Path| {
+        output
+            .strip_prefix(&dir)
+            .unwrap_or(output)
+            .to_string_lossy()
+            .into_owned()
+    };
+    let result = crate::run_bulk(config, &options, |event| {
+        let mut job = job.lock().unwrap();
+        match event {
+            BulkEvent::Planned { .. } => {}
+            BulkEvent::Started { chars, .. } => item_chars = chars,
+            BulkEvent::Written {
+                index,
+                output,
+                bytes,
+                ..
+            } => {
+                written_chars += item_chars;
+                job.completed += 1;
+                let output = relative(output);
+                let url = format!(
+                    "/jobs/{id}/files/{}",
+                    output
+                        .split('/')
+                        .map(percent_encode)
+                        .collect::<Vec<_>>()
+                        .join("/")
+                );
+                job.results.push(ItemResult {
+                    index,
+                    output,
+                    status: "written",
+                    bytes: Some(bytes),
+                    url: Some(url),
+                    error: None,
+                });
+            }
+            BulkEvent::Skipped { index, output } => job.results.push(ItemResult {
+                index,
+                output: relative(output),
+                status: "skipped",
+                bytes: None,
+                url: None,
+                error: None,
+            }),
+            BulkEvent::Failed {
+                index,
+                output,
+                error,
+            } => {
+                job.failed += 1;
+                job.results.push(ItemResult {
+                    index,
+                    output: relative(output),
+                    status: "failed",
+                    bytes: None,
+                    url: None,
+                    error: Some(format!("{error:#}")),
+                });
+            }
+        }
+    })
+    .await;
+    // Characters of items that weren't written are given back
+    if let Some(key) = &key {
+        let reserved = job.lock().unwrap().chars;
+        key.refund_chars(reserved.saturating_sub(written_chars as u64));
+    }
+    let mut job = job.lock().unwrap();
+    match result {
+        Ok(()) => job.status = Status::Succeeded,
+        Err(e) => {
+            tracing::warn!(job = %job.id, error = %format!("{e:#}"), "batch job failed");
+            job.status = Status::Failed;
+            job.error = Some(format!("{e:#}"));
+        }
+    }
+    job.finished = Some(Instant::now());
+}
+
+pub async fn status(
+    State(state): State<AppState>,
+    key: Option<Extension<Arc<keys::ApiKey>>>,
+    UrlPath(id): UrlPath<String>,
+) -> Response {
+    match state.jobs.get(&id, key.as_deref().map(Arc::as_ref)) {
+        Some(job) => {
+            Json(serde_json::to_value(&*job.lock().unwrap()).unwrap_or_default()).into_response()
+        }
+        None => error_json(StatusCode::NOT_FOUND, "not_found", "no such job"),
+    }
+}
+
+pub async fn file(
+    State(state): State<AppState>,
+    key: Option<Extension<Arc<keys::ApiKey>>>,
+    UrlPath((id, output)): UrlPath<(String, String)>,
+) -> Response {
+    let not_found = || error_json(StatusCode::NOT_FOUND, "not_found", "no such file");
+    let Some(job) = state.jobs.get(&id, key.as_deref().map(Arc::as_ref)) else {
+        return not_found();
+    };
+    let output = Path::new(&output);
+    if !output
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return not_found();
+    }
+    let path = job.lock().unwrap().dir.join(output);
+    match std::fs::read(&path) {
+        Ok(bytes) => ([(header::CONTENT_TYPE, audio_mime(&path))], bytes).into_response(),
+        Err(_) => not_found(),
+    }
+}
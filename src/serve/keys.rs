@@ -0,0 +1,171 @@
+//! API keys for `serve`: each key may carry a request rate limit and a
+//! character quota, and its usage is tracked for `GET /usage`.
+
+use super::limits::{TokenBucket, rate_limited};
+use super::{AppState, error_json};
+use crate::constant_time_eq;
+use anyhow::{Context, Result};
+use axum::{
+    Extension, Json,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyConfig {
+    key: String,
+    /// Requests per minute
+    rate_limit: Option<u32>,
+    /// Total characters this key may synthesize while the server runs
+    char_quota: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Usage {
+    requests: u64,
+    chars: u64,
+    rate_limited: u64,
+    quota_rejected: u64,
+}
+
+pub struct ApiKey {
+    name: String,
+    secret: String,
+    rate_limit: Option<u32>,
+    char_quota: Option<u64>,
+    bucket: Option<Mutex<TokenBucket>>,
+    usage: Mutex<Usage>,
+}
+
+impl ApiKey {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Charge `chars` against the quota up front; refunded if synthesis fails.
+    pub fn reserve_chars(&self, chars: u64) -> Result<(), String> {
+        let mut usage = self.usage.lock().unwrap();
+        if let Some(quota) = self.char_quota
+            && usage.chars + chars > quota
+        {
+            usage.quota_rejected += 1;
+            return Err(format!(
+                "character quota exhausted for key {:?} ({} of {quota} used)",
+                self.name, usage.chars
+            ));
+        }
+        usage.chars += chars;
+        Ok(())
+    }
+
+    pub fn refund_chars(&self, chars: u64) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.chars = usage.chars.saturating_sub(chars);
+    }
+}
+
+pub struct ApiKeys(Vec<Arc<ApiKey>>);
+
+impl ApiKeys {
+    /// Load a YAML map of key name to `{ key, rate_limit, char_quota }`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read API keys: {}", path.display()))?;
+        let config: BTreeMap<String, KeyConfig> = serde_yaml::from_str(&data)
+            .with_context(|| format!("invalid API keys file: {}", path.display()))?;
+        anyhow::ensure!(!config.is_empty(), "no API keys in {}", path.display());
+        let keys = config
+            .into_iter()
+            .map(|(name, cfg)| {
+                anyhow::ensure!(!cfg.key.trim().is_empty(), "API key {name:?} is empty");
+                let rate_limit = cfg.rate_limit.filter(|r| *r > 0);
+                Ok(Arc::new(ApiKey {
+                    name,
+                    secret: cfg.key,
+                    rate_limit,
+                    char_quota: cfg.char_quota,
+                    bucket: rate_limit.map(|r| Mutex::new(TokenBucket::new(r))),
+                    usage: Mutex::new(Usage::default()),
+                }))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self(keys))
+    }
+
+    fn find(&self, presented: &str) -> Option<Arc<ApiKey>> {
+        // Check every key so timing doesn't reveal which one (if any) matched
+        self.0.iter().fold(None, |found, key| {
+            if constant_time_eq(key.secret.as_bytes(), presented.as_bytes()) {
+                Some(key.clone())
+            } else {
+                found
+            }
+        })
+    }
+}
+
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    bearer.or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))
+}
+
+/// Authenticate the caller and apply its rate limit; the key is passed on as a
+/// request extension for quota accounting in the handlers.
+pub async fn require_api_key(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let Some(keys) = &state.api_keys else {
+        return next.run(req).await;
+    };
+    let Some(key) = presented_key(req.headers()).and_then(|k| keys.find(k)) else {
+        return error_json(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "missing or invalid API key (send Authorization: Bearer <key> or X-API-Key)",
+        );
+    };
+    if let Some(bucket) = &key.bucket
+        && let Err(retry_after) = bucket.lock().unwrap().take()
+    {
+        key.usage.lock().unwrap().rate_limited += 1;
+        return rate_limited(
+            retry_after,
+            format!("rate limit exceeded for key {:?}", key.name),
+        );
+    }
+    key.usage.lock().unwrap().requests += 1;
+    req.extensions_mut().insert(key);
+    next.run(req).await
+}
+
+/// `GET /usage`: the calling key's limits and usage so far.
+pub async fn usage(key: Option<Extension<Arc<ApiKey>>>) -> Response {
+    let Some(Extension(key)) = key else {
+        return error_json(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            "usage accounting requires --api-keys",
+        );
+    };
+    let usage = *key.usage.lock().unwrap();
+    Json(serde_json::json!({
+        "key": key.name,
+        "rateLimit": key.rate_limit,
+        "charQuota": key.char_quota,
+        "usage": usage,
+    }))
+    .into_response()
+}
@@ -0,0 +1,120 @@
+//! Request throttling and size caps for `serve`: token buckets per client IP
+//! (and per API key, see `keys`), plus a maximum input-text length.
+
+use super::{AppState, error_json};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Buckets kept per IP before idle (fully refilled) ones are dropped.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Allows `per_minute` requests per minute with bursts up to the same size.
+pub struct TokenBucket {
+    per_minute: u32,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            tokens: f64::from(per_minute),
+            refilled: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec()).min(f64::from(self.per_minute));
+        self.refilled = now;
+    }
+
+    fn per_sec(&self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+
+    /// Take one token, or return the seconds until one is available.
+    pub fn take(&mut self) -> Result<(), u64> {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - self.tokens) / self.per_sec()).ceil().max(1.0) as u64)
+        }
+    }
+
+    fn is_idle(&mut self) -> bool {
+        self.refill();
+        self.tokens >= f64::from(self.per_minute)
+    }
+}
+
+pub fn rate_limited(retry_after: u64, message: String) -> Response {
+    let mut resp = error_json(StatusCode::TOO_MANY_REQUESTS, "rate_limited", message);
+    resp.headers_mut()
+        .insert(header::RETRY_AFTER, retry_after.into());
+    resp
+}
+
+pub struct ClientLimiter {
+    per_minute: u32,
+    clients: Mutex<HashMap<IpAddr, TokenBucket>>,
+}
+
+impl ClientLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn take(&self, ip: IpAddr) -> Result<(), u64> {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.len() >= MAX_TRACKED_CLIENTS && !clients.contains_key(&ip) {
+            clients.retain(|_, bucket| !bucket.is_idle());
+        }
+        clients
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::new(self.per_minute))
+            .take()
+    }
+}
+
+/// Per-IP rate limit (`--rate-limit`); runs before API-key checks so
+/// unauthenticated floods are throttled too.
+pub async fn limit_clients(
+    State(state): State<AppState>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    // Unix socket connections have no peer IP to key on
+    if let Some(limiter) = &state.client_limiter
+        && let Some(ConnectInfo(peer)) = peer
+        && let Err(retry_after) = limiter.take(peer.ip())
+    {
+        return rate_limited(
+            retry_after,
+            format!("rate limit exceeded for {}", peer.ip()),
+        );
+    }
+    next.run(req).await
+}
+
+/// Request bodies may carry `max_text_chars` characters even if every one is
+/// JSON-escaped as `\uXXXX`, plus room for the other fields.
+pub fn body_limit(max_text_chars: usize) -> usize {
+    max_text_chars.saturating_mul(6).saturating_add(64 * 1024)
+}
@@ -0,0 +1,156 @@
+//! Backpressure for `POST /synthesize`: at most `--max-concurrent` provider
+//! calls run at once and up to `--queue-depth` more requests wait for one of
+//! them to finish. Past that the server answers 429 with a `Retry-After`
+//! estimated from how long recent syntheses took, so a burst degrades into
+//! retries instead of an unbounded pile of provider calls.
+
+use super::error_json;
+use axum::{
+    http::{StatusCode, header},
+    response::Response,
+};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Assumed synthesis time until one has been measured.
+const INITIAL_ESTIMATE: Duration = Duration::from_secs(1);
+
+pub struct Queue {
+    running: Arc<Semaphore>,
+    max_concurrent: usize,
+    depth: usize,
+    waiting: AtomicUsize,
+    /// Moving average of how long a synthesis holds its slot, in milliseconds
+    average_ms: AtomicU64,
+}
+
+/// A running synthesis; dropping it frees the slot for the next request.
+pub struct Slot {
+    _permit: OwnedSemaphorePermit,
+    queue: Arc<Queue>,
+    started: Instant,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let sample = self.started.elapsed().as_millis() as u64;
+        let _ =
+            self.queue
+                .average_ms
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |average| {
+                    Some(if average == 0 {
+                        sample.max(1)
+                    } else {
+                        (average * 4 + sample) / 5
+                    })
+                });
+    }
+}
+
+/// A place in line, given up when the request gets a slot or goes away.
+struct Place<'a>(&'a AtomicUsize);
+
+impl Drop for Place<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Queue {
+    pub fn new(max_concurrent: usize, depth: usize) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            running: Arc::new(Semaphore::new(max_concurrent)),
+            max_concurrent,
+            depth,
+            waiting: AtomicUsize::new(0),
+            average_ms: AtomicU64::new(0),
+        }
+    }
+
+    /// A slot to synthesize in, waiting for one when all are busy; the 429
+    /// response when the line is full as well.
+    pub async fn enter(self: &Arc<Self>) -> Result<Slot, Response> {
+        let permit = match self.running.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                let joined = self
+                    .waiting
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                        (n < self.depth).then_some(n + 1)
+                    });
+                if joined.is_err() {
+                    return Err(self.full());
+                }
+                let _place = Place(&self.waiting);
+                self.running
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("the semaphore is never closed")
+            }
+        };
+        Ok(self.slot(permit))
+    }
+
+    /// A slot for work admitted some other way (batch jobs), waiting as long
+    /// as it takes.
+    pub async fn wait(self: &Arc<Self>) -> Slot {
+        let permit = self
+            .running
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+        self.slot(permit)
+    }
+
+    fn slot(self: &Arc<Self>, permit: OwnedSemaphorePermit) -> Slot {
+        Slot {
+            _permit: permit,
+            queue: self.clone(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Seconds until the line has moved far enough to take another request.
+    fn retry_after(&self) -> u64 {
+        let average = match self.average_ms.load(Ordering::Relaxed) {
+            0 => INITIAL_ESTIMATE,
+            ms => Duration::from_millis(ms),
+        };
+        let rounds = (self.waiting.load(Ordering::SeqCst) + 1).div_ceil(self.max_concurrent);
+        (average * rounds as u32).as_secs_f64().ceil().max(1.0) as u64
+    }
+
+    fn full(&self) -> Response {
+        tracing::warn!(
+            max_concurrent = self.max_concurrent,
+            queue_depth = self.depth,
+            "synthesis queue full"
+        );
+        self.busy(&format!(
+            "the server is busy ({} syntheses running, {} waiting)",
+            self.max_concurrent, self.depth
+        ))
+    }
+
+    /// 429 `queue_full` saying `reason`, with the estimated `Retry-After`.
+    pub fn busy(&self, reason: &str) -> Response {
+        let retry_after = self.retry_after();
+        let mut resp = error_json(
+            StatusCode::TOO_MANY_REQUESTS,
+            "queue_full",
+            format!("{reason}; retry in {retry_after}s"),
+        );
+        resp.headers_mut()
+            .insert(header::RETRY_AFTER, retry_after.into());
+        resp
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
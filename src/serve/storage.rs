@@ -0,0 +1,244 @@
+//! Object storage for `"response": "signedUrl"`: audio is uploaded to an
+//! S3-compatible bucket and handed out as a presigned (SigV4) URL that
+//! expires, so large files and webhook consumers never go through this
+//! server.
+
+use crate::FastTtsError;
+use crate::providers::custom::percent_encode;
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+/// Longest validity SigV4 allows for a presigned URL.
+const MAX_URL_TTL: Duration = Duration::from_secs(7 * 24 * 3600);
+
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    /// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and, for temporary
+    /// credentials, `AWS_SESSION_TOKEN`.
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| crate::config_var(name).filter(|v| !v.trim().is_empty());
+        let (Some(access_key_id), Some(secret_access_key)) =
+            (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY"))
+        else {
+            return Err(FastTtsError::MissingCredentials(
+                "--storage needs AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY".into(),
+            )
+            .into());
+        };
+        Ok(Self {
+            access_key_id,
+            secret_access_key,
+            session_token: var("AWS_SESSION_TOKEN"),
+        })
+    }
+}
+
+/// A bucket (and key prefix) audio is uploaded to.
+pub struct ObjectStore {
+    /// Scheme and authority, e.g. `https://bucket.s3.amazonaws.com`
+    origin: String,
+    /// Path before the object key: `/bucket` on path-style endpoints
+    base_path: String,
+    prefix: String,
+    region: String,
+    credentials: Credentials,
+    /// How long a signed URL stays valid
+    url_ttl: Duration,
+}
+
+impl ObjectStore {
+    /// The bucket at `location` (`s3://bucket/prefix`). Without `endpoint`
+    /// it is on AWS in `region`; with one (MinIO, R2, ...) objects are
+    /// addressed path-style under it.
+    pub fn new(
+        location: &str,
+        region: &str,
+        endpoint: Option<&str>,
+        credentials: Credentials,
+        url_ttl: Duration,
+    ) -> Result<Self> {
+        let invalid = |message: String| anyhow::Error::from(FastTtsError::InvalidInput(message));
+        let rest = location.strip_prefix("s3://").ok_or_else(|| {
+            invalid(format!(
+                "storage location {location:?} must look like s3://bucket/prefix"
+            ))
+        })?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(invalid(format!(
+                "storage location {location:?} has no bucket"
+            )));
+        }
+        if url_ttl.is_zero() || url_ttl > MAX_URL_TTL {
+            return Err(invalid(
+                "signed URLs can be valid for 1 second to 7 days".into(),
+            ));
+        }
+        let (origin, base_path) = match endpoint {
+            Some(endpoint) => (
+                endpoint.trim_end_matches('/').to_string(),
+                format!("/{}", percent_encode(bucket)),
+            ),
+            // us-east-1 is also served by the global endpoint
+            None if region == "us-east-1" => {
+                (format!("https://{bucket}.s3.amazonaws.com"), String::new())
+            }
+            None => (
+                format!("https://{bucket}.s3.{region}.amazonaws.com"),
+                String::new(),
+            ),
+        };
+        if !origin.starts_with("http://") && !origin.starts_with("https://") {
+            return Err(invalid(format!(
+                "storage endpoint {origin:?} must be an http(s) URL"
+            )));
+        }
+        Ok(Self {
+            origin,
+            base_path,
+            prefix: prefix.trim_matches('/').to_string(),
+            region: region.to_string(),
+            credentials,
+            url_ttl,
+        })
+    }
+
+    /// [`new`](Self::new) with the credentials and region (`AWS_REGION` or
+    /// `AWS_DEFAULT_REGION`, else us-east-1) from the environment.
+    pub fn from_env(location: &str, endpoint: Option<&str>, url_ttl: Duration) -> Result<Self> {
+        let region = crate::config_var("AWS_REGION")
+            .or_else(|| crate::config_var("AWS_DEFAULT_REGION"))
+            .filter(|r| !r.trim().is_empty())
+            .unwrap_or_else(|| "us-east-1".into());
+        Self::new(
+            location,
+            &region,
+            endpoint,
+            Credentials::from_env()?,
+            url_ttl,
+        )
+    }
+
+    pub fn url_ttl(&self) -> Duration {
+        self.url_ttl
+    }
+
+    /// Upload `body` as object `name` (under the prefix).
+    pub async fn put(&self, name: &str, body: Vec<u8>, content_type: &str) -> Result<()> {
+        let url = self.presigned_url("PUT", name, Duration::from_secs(300), crate::unix_now());
+        let client = crate::build_http_client_for_base(&self.origin)?;
+        let resp = crate::send_http(
+            client
+                .put(&url)
+                .header(http::header::CONTENT_TYPE, content_type)
+                .body(body),
+        )
+        .await
+        .context("upload to object storage failed")?;
+        let status = resp.status();
+        if !status.is_success() {
+            let detail = resp.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "object storage rejected the upload of {name} ({status}): {}",
+                detail.trim()
+            );
+        }
+        Ok(())
+    }
+
+    /// A download URL for object `name`, valid for the store's URL TTL.
+    pub fn signed_get(&self, name: &str) -> String {
+        self.presigned_url("GET", name, self.url_ttl, crate::unix_now())
+    }
+
+    /// Query-string authenticated (SigV4) URL for `method` on object `name`,
+    /// signed at `unix_time` and valid for `expires_in`.
+    pub fn presigned_url(
+        &self,
+        method: &str,
+        name: &str,
+        expires_in: Duration,
+        unix_time: u64,
+    ) -> String {
+        let key = if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{name}", self.prefix)
+        };
+        let path = format!(
+            "{}/{}",
+            self.base_path,
+            key.split('/')
+                .map(percent_encode)
+                .collect::<Vec<_>>()
+                .join("/")
+        );
+        let host = self
+            .origin
+            .split_once("://")
+            .map_or(self.origin.as_str(), |(_, host)| host);
+        let (date, time) = timestamp(unix_time);
+        let amz_date = format!("{date}T{time}Z");
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let mut query = vec![
+            ("X-Amz-Algorithm", "AWS4-HMAC-SHA256".to_string()),
+            (
+                "X-Amz-Credential",
+                format!("{}/{scope}", self.credentials.access_key_id),
+            ),
+            ("X-Amz-Date", amz_date.clone()),
+            ("X-Amz-Expires", expires_in.as_secs().to_string()),
+            ("X-Amz-SignedHeaders", "host".to_string()),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            query.push(("X-Amz-Security-Token", token.clone()));
+        }
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(name, value)| format!("{name}={}", percent_encode(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let canonical = format!("{method}\n{path}\n{query}\nhost:{host}\n\nhost\nUNSIGNED-PAYLOAD");
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical.as_bytes()))
+        );
+        let signing_key = [self.region.as_str(), "s3", "aws4_request"].iter().fold(
+            hmac(
+                format!("AWS4{}", self.credentials.secret_access_key).as_bytes(),
+                &date,
+            ),
+            |key, part| hmac(&key, part),
+        );
+        let signature = hex(&hmac(&signing_key, &string_to_sign));
+        format!("{}{path}?{query}&X-Amz-Signature={signature}", self.origin)
+    }
+}
+
+/// `YYYYMMDD` and `HHMMSS` (UTC) of `unix_time`.
+fn timestamp(unix_time: u64) -> (String, String) {
+    let (year, month, day) = crate::feed::civil_from_days((unix_time / 86400) as i64);
+    let secs = unix_time % 86400;
+    (
+        format!("{year:04}{month:02}{day:02}"),
+        format!("{:02}{:02}{:02}", secs / 3600, secs / 60 % 60, secs % 60),
+    )
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
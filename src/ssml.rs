@@ -0,0 +1,210 @@
+//! SSML documents from files, and the `<speak>`/`<voice>` envelope Azure
+//! needs around them.
+
+use crate::FastTtsError;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+#[cfg(feature = "provider-azure")]
+const SSML_NAMESPACE: &str = "http://www.w3.org/2001/10/synthesis";
+
+/// Read an SSML document from `path`: UTF-8 (a byte-order mark is dropped) or
+/// UTF-16 with a byte-order mark. A leading XML declaration is removed, and
+/// the document must pass [`check`].
+pub(crate) fn read_file(path: &Path) -> Result<String> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let invalid =
+        |message: String| FastTtsError::InvalidInput(format!("{}: {message}", path.display()));
+    let text = match bytes.as_slice() {
+        [0xFF, 0xFE, rest @ ..] => decode_utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => decode_utf16(rest, u16::from_be_bytes),
+        [0xEF, 0xBB, 0xBF, rest @ ..] | rest => {
+            std::str::from_utf8(rest).map(str::to_string).map_err(|e| {
+                format!(
+                    "not valid UTF-8 (byte {}); save the file as UTF-8",
+                    e.valid_up_to()
+                )
+            })
+        }
+    }
+    .map_err(invalid)?;
+    let text = strip_xml_declaration(&text);
+    if text.trim().is_empty() {
+        return Err(invalid("the SSML file is empty".into()).into());
+    }
+    check(text).map_err(invalid)?;
+    Ok(text.to_string())
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> Result<String, String> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err("truncated UTF-16 (odd number of bytes)".into());
+    }
+    char::decode_utf16(bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]])))
+        .collect::<Result<String, _>>()
+        .map_err(|e| format!("not valid UTF-16: {e}"))
+}
+
+/// `text` without a leading `<?xml ...?>` declaration, which providers reject.
+fn strip_xml_declaration(text: &str) -> &str {
+    let trimmed = text.trim_start();
+    if trimmed.starts_with("<?xml")
+        && let Some(end) = trimmed.find("?>")
+    {
+        return trimmed[end + 2..].trim_start();
+    }
+    text
+}
+
+/// Catch SSML mistakes before they cost a request: entities SSML doesn't
+/// define and unbalanced or stray tags. Not a full XML parser.
+pub(crate) fn check(ssml: &str) -> Result<(), String> {
+    check_entities(ssml)?;
+    check_tags(ssml)
+}
+
+/// 1-based line of byte offset `at`.
+fn line_of(text: &str, at: usize) -> usize {
+    text[..at].matches('\n').count() + 1
+}
+
+/// Every opening tag is closed in order; comments, CDATA and processing
+/// instructions are skipped.
+fn check_tags(ssml: &str) -> Result<(), String> {
+    let mut open: Vec<(&str, usize)> = Vec::new();
+    let mut at = 0;
+    while let Some(found) = ssml[at..].find('<') {
+        let start = at + found;
+        let line = line_of(ssml, start);
+        let tail = &ssml[start..];
+        let len = if tail.starts_with("<!--") {
+            tail.find("-->").map(|end| end + 3)
+        } else if tail.starts_with("<![CDATA[") {
+            tail.find("]]>").map(|end| end + 3)
+        } else if tail.starts_with("<?") {
+            tail.find("?>").map(|end| end + 2)
+        } else {
+            tail.find('>').map(|end| end + 1)
+        }
+        .ok_or_else(|| format!("line {line}: unterminated markup"))?;
+        let inner = &tail[1..len - 1];
+        if let Some(name) = inner.strip_prefix('/') {
+            let name = name.trim();
+            match open.pop() {
+                Some((opened, _)) if opened == name => {}
+                Some((opened, opened_line)) => {
+                    return Err(format!(
+                        "line {line}: </{name}> closes <{opened}> from line {opened_line}"
+                    ));
+                }
+                None => return Err(format!("line {line}: </{name}> has no opening tag")),
+            }
+        } else if !tail.starts_with("<!") && !tail.starts_with("<?") && !inner.ends_with('/') {
+            match inner.split_whitespace().next() {
+                Some(name) if inner.starts_with(|c: char| !c.is_whitespace()) => {
+                    open.push((name, line));
+                }
+                _ => return Err(format!("line {line}: write a literal < as &lt;")),
+            }
+        }
+        at = start + len;
+    }
+    match open.pop() {
+        Some((name, line)) => Err(format!("<{name}> from line {line} is never closed")),
+        None => Ok(()),
+    }
+}
+
+/// Every `&` must start `&amp;`, `&lt;`, `&gt;`, `&quot;`, `&apos;` or a
+/// numeric reference; HTML entities such as `&nbsp;` aren't defined in SSML.
+fn check_entities(ssml: &str) -> Result<(), String> {
+    for (at, _) in ssml.match_indices('&') {
+        let line = line_of(ssml, at);
+        let rest = &ssml[at + 1..];
+        let name = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| &rest[..end]);
+        let known = name.is_some_and(|name| {
+            matches!(name, "amp" | "lt" | "gt" | "quot" | "apos")
+                || name.strip_prefix("#x").is_some_and(|hex| {
+                    !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit())
+                })
+                || name
+                    .strip_prefix('#')
+                    .is_some_and(|dec| !dec.is_empty() && dec.chars().all(|c| c.is_ascii_digit()))
+        });
+        match name {
+            _ if known => {}
+            Some(name) if !name.contains(char::is_whitespace) => {
+                return Err(format!(
+                    "line {line}: unknown entity &{name}; (SSML only defines &amp; &lt; &gt; &quot; &apos; and numeric references)"
+                ));
+            }
+            _ => return Err(format!("line {line}: write a literal & as &amp;")),
+        }
+    }
+    Ok(())
+}
+
+/// The SSML Azure expects: plain text is escaped and wrapped in
+/// `<speak><voice>`; a document without `<speak>` is wrapped as is; a
+/// `<speak>` document gets any missing required attributes, and a `<voice>`
+/// around its content unless it already picks voices itself.
+#[cfg(feature = "provider-azure")]
+pub(crate) fn azure_document(text: &str, is_ssml: bool, language: &str, voice: &str) -> String {
+    let language = htmlescape::encode_minimal(language);
+    let voice = htmlescape::encode_minimal(voice);
+    if !is_ssml {
+        // Control characters other than tab and newlines aren't allowed in XML at all
+        let text: String = text
+            .chars()
+            .filter(|&c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+            .collect();
+        return format!(
+            "<speak version=\"1.0\" xml:lang=\"{language}\"><voice xml:lang=\"{language}\" name=\"{voice}\">{}</voice></speak>",
+            htmlescape::encode_minimal(&text)
+        );
+    }
+    let body = text.trim();
+    let Some((before, tag, rest)) = split_speak_tag(body) else {
+        return format!(
+            "<speak version=\"1.0\" xmlns=\"{SSML_NAMESPACE}\" xml:lang=\"{language}\"><voice name=\"{voice}\">{body}</voice></speak>"
+        );
+    };
+    let mut tag = tag.trim_end_matches('>').to_string();
+    for (attr, value) in [
+        ("version", "1.0"),
+        ("xmlns", SSML_NAMESPACE),
+        ("xml:lang", language.as_str()),
+    ] {
+        let prefix = format!("{attr}=");
+        if !tag.split_whitespace().any(|part| part.starts_with(&prefix)) {
+            tag.push_str(&format!(" {attr}=\"{value}\""));
+        }
+    }
+    tag.push('>');
+    if rest.contains("<voice") {
+        return format!("{before}{tag}{rest}");
+    }
+    let close = rest.rfind("</speak>").unwrap_or(rest.len());
+    format!(
+        "{before}{tag}<voice name=\"{voice}\">{}</voice>{}",
+        &rest[..close],
+        &rest[close..]
+    )
+}
+
+/// `body` around its opening `<speak ...>` tag: what precedes it, the tag
+/// itself and what follows.
+#[cfg(feature = "provider-azure")]
+fn split_speak_tag(body: &str) -> Option<(&str, &str, &str)> {
+    let start = body.match_indices("<speak").find_map(|(at, _)| {
+        body[at + "<speak".len()..]
+            .starts_with(|c: char| c == '>' || c.is_whitespace())
+            .then_some(at)
+    })?;
+    let end = start + body[start..].find('>')? + 1;
+    Some((&body[..start], &body[start..end], &body[end..]))
+}
@@ -0,0 +1,172 @@
+//! Provider health for `fast-tts auth check` (are the credentials there and
+//! accepted?) and `fast-tts status` (does a one-character synthesis succeed,
+//! and how fast?).
+
+use crate::{
+    AudioEncoding, AuthStatus, CancellationToken, FastTtsError, Provider, SynthesisRequest,
+    check_provider_auth, provider_enabled, provider_feature_flag, selected_providers,
+};
+use anyhow::Result;
+use serde::Serialize;
+
+/// Check the credentials of `provider`, or of every provider in the build.
+pub async fn check_auth(provider: Option<Provider>) -> Vec<(Provider, AuthStatus)> {
+    let mut results = Vec::new();
+    for p in selected_providers(provider) {
+        let status = if provider_enabled(p) {
+            check_provider_auth(p).await
+        } else {
+            AuthStatus::Unsupported(format!(
+                "not enabled in this build (--features {})",
+                provider_feature_flag(p)
+            ))
+        };
+        results.push((p, status));
+    }
+    results
+}
+
+/// The `auth check` exit status: missing credentials are fine when checking
+/// every provider (`single` is false); broken ones never are.
+pub fn auth_outcome(results: &[(Provider, AuthStatus)], single: bool) -> Result<()> {
+    let failed = results.iter().any(|(_, status)| match status {
+        AuthStatus::Invalid(_) | AuthStatus::Error(_) => true,
+        AuthStatus::Missing(_) | AuthStatus::Unsupported(_) => single,
+        AuthStatus::Ok => false,
+    });
+    if failed {
+        return Err(FastTtsError::Auth("credential check failed".into()).into());
+    }
+    Ok(())
+}
+
+/// One provider's answer to the `status` probe.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderStatus {
+    pub provider: String,
+    /// `up`; `down` (unreachable, timed out or HTTP 5xx); `limited` (HTTP
+    /// 429); `auth` (credentials missing or rejected); `error` (any other
+    /// failure, more likely on our side); `unconfigured` (not probed)
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    pub detail: String,
+}
+
+/// Synthesize one character with `provider`, without retries, and classify
+/// the outcome so provider outages stand apart from our own failures.
+pub async fn probe(
+    provider: Provider,
+    timeout_ms: u64,
+    cancel: CancellationToken,
+) -> Result<ProviderStatus> {
+    let caps = provider.capabilities();
+    // The smallest response where there is a choice
+    let encoding = ["MP3", "LINEAR16"]
+        .into_iter()
+        .find(|e| caps.encodings.contains(e))
+        .or(caps.encodings.first().copied())
+        .and_then(|e| crate::parse_encoding_from_str(e).ok())
+        .unwrap_or(AudioEncoding::Linear16);
+    let started = std::time::Instant::now();
+    let probe = async {
+        let request = SynthesisRequest::builder()
+            .provider(provider)
+            .text("a")
+            .encoding(encoding)
+            .timeout_ms(timeout_ms)
+            .retries(0)
+            .cancellation(cancel)
+            .build()?;
+        crate::bench::measure(&request).await
+    };
+    let mut status = ProviderStatus {
+        provider: provider.to_string(),
+        status: "up",
+        latency_ms: None,
+        http_status: None,
+        detail: String::new(),
+    };
+    match probe.await {
+        Ok(sample) => status.latency_ms = Some(sample.total_ms),
+        Err(err) => {
+            let failure = FastTtsError::classify(&err);
+            status.latency_ms = Some(started.elapsed().as_millis() as u64);
+            status.status = match &failure {
+                FastTtsError::Cancelled(_) => return Err(err),
+                FastTtsError::Auth(_) | FastTtsError::MissingCredentials(_) => "auth",
+                FastTtsError::Quota(_) => "limited",
+                FastTtsError::ProviderHttp { status: code, .. } => {
+                    status.http_status = *code;
+                    match code {
+                        Some(400..500) => "error",
+                        _ => "down",
+                    }
+                }
+                _ => "error",
+            };
+            status.detail = failure.to_string();
+        }
+    }
+    Ok(status)
+}
+
+/// Probe `provider`, or every configured provider in the build at once.
+pub async fn check(
+    provider: Option<Provider>,
+    timeout_ms: u64,
+    cancel: &CancellationToken,
+) -> Result<Vec<ProviderStatus>> {
+    let probes = selected_providers(provider).into_iter().map(|p| {
+        let cancel = cancel.clone();
+        async move {
+            if provider.is_none() && !p.capabilities().configured {
+                return Ok(ProviderStatus {
+                    provider: p.to_string(),
+                    status: "unconfigured",
+                    latency_ms: None,
+                    http_status: None,
+                    detail: "credentials not set".into(),
+                });
+            }
+            probe(p, timeout_ms, cancel).await
+        }
+    });
+    futures::future::join_all(probes)
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// The `status` exit status: an outage outranks credential trouble, which
+/// outranks anything else.
+pub fn outcome(results: &[ProviderStatus]) -> Result<()> {
+    let failing = |wanted: &str| {
+        let names: Vec<&str> = results
+            .iter()
+            .filter(|r| r.status == wanted)
+            .map(|r| r.provider.as_str())
+            .collect();
+        (!names.is_empty()).then(|| names.join(", "))
+    };
+    if let Some(names) = failing("down") {
+        return Err(FastTtsError::ProviderHttp {
+            status: None,
+            message: format!("provider down: {names}"),
+        }
+        .into());
+    }
+    if let Some(names) = failing("auth") {
+        return Err(FastTtsError::Auth(format!("credentials failed: {names}")).into());
+    }
+    if let Some(names) = failing("limited") {
+        return Err(FastTtsError::Quota(format!("rate limited: {names}")).into());
+    }
+    if let Some(names) = failing("error") {
+        return Err(FastTtsError::Other(format!("probe failed: {names}")).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+//! "Did you mean" suggestions for mistyped names.
+
+use crate::{FastTtsError, SynthesisRequest};
+
+/// Up to three `candidates` closest to `name` by edit distance (ignoring
+/// case); only plausible typos, and only the nearest ones, are returned.
+pub(crate) fn closest<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+    let name = name.to_lowercase();
+    let limit = (name.chars().count() / 3).max(2);
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .map(|c| (levenshtein(&name, &c.to_lowercase()), c))
+        .filter(|(distance, _)| *distance <= limit)
+        .collect();
+    scored.sort();
+    scored.dedup_by(|a, b| a.1 == b.1);
+    let best = scored.first().map_or(0, |(distance, _)| *distance);
+    scored
+        .into_iter()
+        .take_while(|(distance, _)| *distance == best)
+        .take(3)
+        .map(|(_, c)| c)
+        .collect()
+}
+
+/// [`FastTtsError::InvalidInput`] for a voice `provider` doesn't have, with
+/// the closest names from `catalog` when there are any.
+#[cfg_attr(not(feature = "provider-openai"), allow(dead_code))]
+pub(crate) fn unknown_voice<'a>(
+    provider: &str,
+    voice: &str,
+    catalog: impl IntoIterator<Item = &'a str>,
+) -> anyhow::Error {
+    FastTtsError::InvalidInput(unknown_voice_message(provider, voice, catalog)).into()
+}
+
+fn unknown_voice_message<'a>(
+    provider: &str,
+    voice: &str,
+    catalog: impl IntoIterator<Item = &'a str>,
+) -> String {
+    let mut message = format!("unknown voice '{voice}' for provider {provider}");
+    match closest(voice, catalog).as_slice() {
+        [] => {}
+        [only] => message += &format!("; did you mean '{only}'?"),
+        several => {
+            let quoted: Vec<String> = several.iter().map(|s| format!("'{s}'")).collect();
+            message += &format!("; did you mean one of {}?", quoted.join(", "));
+        }
+    }
+    message
+}
+
+/// When the provider turned `request` down as a bad request and its cached
+/// voice catalog lacks the requested voice, blame the voice and suggest
+/// the nearest names. Other errors pass through unchanged.
+pub(crate) fn explain_voice_rejection(
+    err: anyhow::Error,
+    request: &SynthesisRequest,
+) -> anyhow::Error {
+    let Some(voice) = request.voice.as_deref() else {
+        return err;
+    };
+    if !matches!(
+        FastTtsError::classify(&err),
+        FastTtsError::ProviderHttp {
+            status: Some(400 | 404),
+            ..
+        }
+    ) {
+        return err;
+    }
+    let Some(names) = crate::cached_voice_names(request.provider) else {
+        return err;
+    };
+    if names.iter().any(|name| name == voice) {
+        return err;
+    }
+    // Keep the provider's answer in the chain; the typed context sets the class
+    let message = unknown_voice_message(
+        &request.provider.to_string(),
+        voice,
+        names.iter().map(String::as_str),
+    );
+    err.context(FastTtsError::InvalidInput(message))
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
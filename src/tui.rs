@@ -0,0 +1,302 @@
+//! `--config FILE --tui`: live dashboard for bulk runs with pause, skip and abort.
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use fast_tts::{BulkEvent, BulkOptions, CancellationToken};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize as _};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, Paragraph, Row, Table};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Running,
+    Written,
+    Skipped,
+    Failed,
+}
+
+struct Item {
+    index: usize,
+    output: String,
+    chars: usize,
+    status: Status,
+    bytes: u64,
+    elapsed: Option<Duration>,
+}
+
+struct Dashboard {
+    started: Instant,
+    total_items: usize,
+    total_chars: usize,
+    done_chars: usize,
+    usd_per_million_chars: Option<f64>,
+    items: Vec<Item>,
+    current: Option<CancellationToken>,
+    paused: bool,
+    finished: bool,
+    last_error: Option<String>,
+}
+
+impl Dashboard {
+    fn count(&self, status: Status) -> usize {
+        self.items.iter().filter(|i| i.status == status).count()
+    }
+
+    fn update(&mut self, event: BulkEvent<'_>) {
+        match event {
+            BulkEvent::Planned {
+                provider,
+                items,
+                chars,
+            } => {
+                self.total_items = items;
+                self.total_chars = chars;
+                self.usd_per_million_chars = provider.capabilities().usd_per_million_chars;
+            }
+            BulkEvent::Started {
+                index,
+                output,
+                chars,
+                cancel,
+            } => {
+                self.current = Some(cancel.clone());
+                self.items.push(Item {
+                    index,
+                    output: output.display().to_string(),
+                    chars,
+                    status: Status::Running,
+                    bytes: 0,
+                    elapsed: None,
+                });
+            }
+            BulkEvent::Written { bytes, elapsed, .. } => {
+                self.finish(Status::Written, bytes, Some(elapsed))
+            }
+            BulkEvent::Skipped { .. } => self.finish(Status::Skipped, 0, None),
+            BulkEvent::Failed { error, .. } => {
+                self.last_error = Some(format!("{error:#}"));
+                self.finish(Status::Failed, 0, None);
+            }
+        }
+    }
+
+    fn finish(&mut self, status: Status, bytes: u64, elapsed: Option<Duration>) {
+        self.current = None;
+        if let Some(item) = self.items.last_mut() {
+            item.status = status;
+            item.bytes = bytes;
+            item.elapsed = elapsed;
+            // Billed once sent, whether or not the file was kept
+            self.done_chars += item.chars;
+        }
+    }
+
+    fn summary(&self) -> String {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = self.done_chars as f64 / elapsed.max(0.001);
+        let eta = (self.done_chars > 0 && !self.finished).then(|| {
+            let remaining = self.total_chars.saturating_sub(self.done_chars);
+            format_duration(Duration::from_secs_f64(remaining as f64 / rate.max(0.001)))
+        });
+        let cost = self
+            .usd_per_million_chars
+            .map(|price| format!("${:.4}", self.done_chars as f64 * price / 1_000_000.0));
+        format!(
+            "{:.0} chars/s · {} written · {} skipped · {} failed · cost ~{} · ETA {}{}",
+            rate,
+            self.count(Status::Written),
+            self.count(Status::Skipped),
+            self.count(Status::Failed),
+            cost.as_deref().unwrap_or("n/a"),
+            eta.as_deref().unwrap_or("-"),
+            if self.paused { " · PAUSED" } else { "" }
+        )
+    }
+}
+
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+fn draw(frame: &mut ratatui::Frame, state: &Dashboard) {
+    let [gauge, stats, table, help] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(2),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let finished = state
+        .items
+        .iter()
+        .filter(|i| i.status != Status::Running)
+        .count();
+    let ratio = if state.total_items == 0 {
+        0.0
+    } else {
+        finished as f64 / state.total_items as f64
+    };
+    frame.render_widget(
+        Gauge::default()
+            .block(Block::bordered().title(" fast-tts bulk "))
+            .gauge_style(Style::default().fg(Color::Green))
+            .ratio(ratio.min(1.0))
+            .label(format!("{finished}/{} items", state.total_items)),
+        gauge,
+    );
+
+    let mut lines = vec![Line::from(state.summary())];
+    if let Some(error) = &state.last_error {
+        lines.push(Line::from(format!("last error: {error}")).fg(Color::Red));
+    }
+    frame.render_widget(Paragraph::new(lines), stats);
+
+    let visible = table.height.saturating_sub(3) as usize;
+    let rows = state.items.iter().rev().take(visible).rev().map(|item| {
+        let (label, color) = match item.status {
+            Status::Running => ("running", Color::Yellow),
+            Status::Written => ("written", Color::Green),
+            Status::Skipped => ("skipped", Color::DarkGray),
+            Status::Failed => ("failed", Color::Red),
+        };
+        Row::new([
+            item.index.to_string(),
+            label.to_string(),
+            item.output.clone(),
+            item.chars.to_string(),
+            if item.bytes > 0 {
+                item.bytes.to_string()
+            } else {
+                "-".into()
+            },
+            item.elapsed
+                .map_or("-".into(), |d| format!("{:.1}s", d.as_secs_f64())),
+        ])
+        .style(Style::default().fg(color))
+    });
+    frame.render_widget(
+        Table::new(
+            rows,
+            [
+                Constraint::Length(5),
+                Constraint::Length(8),
+                Constraint::Fill(1),
+                Constraint::Length(7),
+                Constraint::Length(9),
+                Constraint::Length(7),
+            ],
+        )
+        .header(Row::new(["#", "status", "output", "chars", "bytes", "time"]).bold())
+        .block(Block::bordered()),
+        table,
+    );
+    frame.render_widget(
+        Paragraph::new("p pause/resume · s skip item · q abort").dim(),
+        help,
+    );
+}
+
+/// Draw until the run finishes; keys pause, skip the current item or abort.
+fn ui_loop(
+    state: &Mutex<Dashboard>,
+    pause: &tokio::sync::watch::Sender<bool>,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let mut terminal = ratatui::init();
+    let result = (|| -> Result<()> {
+        loop {
+            {
+                let state = state.lock().unwrap_or_else(|e| e.into_inner());
+                terminal.draw(|frame| draw(frame, &state))?;
+                if state.finished {
+                    return Ok(());
+                }
+            }
+            if !event::poll(Duration::from_millis(200))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+            match key.code {
+                KeyCode::Char('p') | KeyCode::Char(' ') => {
+                    state.paused = !state.paused;
+                    pause.send_replace(state.paused);
+                }
+                KeyCode::Char('s') => {
+                    if let Some(current) = &state.current {
+                        current.cancel();
+                    }
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    cancel.cancel()
+                }
+                KeyCode::Char('q') | KeyCode::Esc => cancel.cancel(),
+                _ => {}
+            }
+        }
+    })();
+    ratatui::restore();
+    result
+}
+
+pub async fn run_bulk(
+    path: &PathBuf,
+    mut options: BulkOptions,
+    mut observe: impl FnMut(&BulkEvent<'_>),
+) -> Result<()> {
+    let (pause, paused) = tokio::sync::watch::channel(false);
+    options.paused = Some(paused);
+    options.keep_going = true;
+    // The dashboard owns the terminal, so there is no one to ask; large runs need --yes
+    options.confirm = None;
+    let state = Arc::new(Mutex::new(Dashboard {
+        started: Instant::now(),
+        total_items: 0,
+        total_chars: 0,
+        done_chars: 0,
+        usd_per_million_chars: None,
+        items: Vec::new(),
+        current: None,
+        paused: false,
+        finished: false,
+        last_error: None,
+    }));
+
+    let ui = {
+        let state = state.clone();
+        let cancel = options.cancel.clone();
+        std::thread::spawn(move || ui_loop(&state, &pause, &cancel))
+    };
+    let result = fast_tts::run_bulk_from_config(path, &options, |event| {
+        observe(&event);
+        state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .update(event)
+    })
+    .await;
+    state.lock().unwrap_or_else(|e| e.into_inner()).finished = true;
+    let ui_result = ui.join().unwrap_or_else(|_| Ok(()));
+
+    let state = state.lock().unwrap_or_else(|e| e.into_inner());
+    for item in state.items.iter().filter(|i| i.status == Status::Written) {
+        println!("Wrote {}", item.output);
+    }
+    eprintln!("{}", state.summary());
+    result.and(ui_result)
+}
@@ -0,0 +1,38 @@
+use base64::Engine as _;
+use httpmock::prelude::*;
+use tempfile::tempdir;
+
+#[test]
+fn blocking_api_runs_without_a_caller_runtime() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    // SAFETY: this test binary has a single test, so nothing reads the environment concurrently
+    unsafe {
+        std::env::set_var("FAST_TTS_TOKEN", "test-token");
+        std::env::set_var("FAST_TTS_BASE_URL", server.base_url());
+        std::env::set_var("FAST_TTS_CACHE_DIR", dir.path());
+        for var in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+            std::env::remove_var(var);
+        }
+    }
+
+    let out = dir.path().join("hello.mp3");
+    let request = fast_tts::SynthesisRequest::builder()
+        .text("hello")
+        .output(&out)
+        .encoding(fast_tts::AudioEncoding::Mp3)
+        .build()
+        .unwrap();
+    fast_tts::blocking::synthesize(&request).unwrap();
+    assert_eq!(std::fs::read(&out).unwrap(), b"MP3DATA");
+    assert_eq!(
+        fast_tts::blocking::synthesize_bytes(&request).unwrap(),
+        b"MP3DATA"
+    );
+}
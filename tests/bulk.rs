@@ -28,3 +28,180 @@ items:
         .failure()
         .stderr(predicate::str::contains("Error:"));
 }
+
+#[test]
+fn bulk_items_mix_encodings_with_matching_names() {
+    use base64::Engine as _;
+    use httpmock::prelude::*;
+
+    let server = MockServer::start();
+    let audio = |data: &str| {
+        serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode(data)
+        })
+    };
+    let mp3 = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("\"audioEncoding\":\"MP3\"");
+        then.status(200).json_body_obj(&audio("MP3DATA"));
+    });
+    let ogg = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("\"audioEncoding\":\"OGG_OPUS\"");
+        then.status(200).json_body_obj(&audio("OGGDATA"));
+    });
+    let dir = tempdir().unwrap();
+    let cfg_path = dir.path().join("tts.yaml");
+    fs::write(
+        &cfg_path,
+        r#"
+defaults:
+  encoding: MP3
+  outputDir: out
+items:
+  - text: first
+  - text: second
+    encoding: ogg_opus
+  - text: third
+    encoding: OGG_OPUS
+    output: named.mp3
+    autoExtension: true
+"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.current_dir(dir.path())
+        .env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["--config", "tts.yaml"]);
+    cmd.assert().success();
+
+    mp3.assert_hits(1);
+    ogg.assert_hits(2);
+    assert_eq!(
+        fs::read(dir.path().join("out/item_1.mp3")).unwrap(),
+        b"MP3DATA"
+    );
+    assert_eq!(
+        fs::read(dir.path().join("out/item_2.ogg")).unwrap(),
+        b"OGGDATA"
+    );
+    assert_eq!(fs::read(dir.path().join("named.ogg")).unwrap(), b"OGGDATA");
+}
+
+#[test]
+fn large_bulk_run_needs_confirmation() {
+    use base64::Engine as _;
+    use httpmock::prelude::*;
+
+    let server = MockServer::start();
+    let synth = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let cfg_path = dir.path().join("tts.yaml");
+    fs::write(
+        &cfg_path,
+        "items:\n  - text: hello there\n    output: one.wav\n  - text: general kenobi\n    output: two.wav\n",
+    )
+    .unwrap();
+    let run = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.current_dir(dir.path())
+            .env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env("FAST_TTS_CONFIRM_ABOVE_CHARS", "20")
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(["--no-history", "--config", cfg_path.to_str().unwrap()])
+            .args(extra)
+            .assert()
+    };
+
+    // Not a terminal, so nobody can say yes: nothing is sent
+    run(&[])
+        .code(130)
+        .stderr(predicate::str::contains(
+            "This run will synthesize 25 characters with google",
+        ))
+        .stderr(predicate::str::contains("pass --yes"));
+    synth.assert_hits(0);
+
+    run(&["--confirm-above-chars", "100"]).success();
+    run(&["--yes"]).success();
+    synth.assert_hits(4);
+}
+
+#[test]
+fn failed_bulk_item_names_its_entry_and_parameter() {
+    use base64::Engine as _;
+    use httpmock::prelude::*;
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("\"name\":\"en-US-Bogus\"");
+        then.status(400).json_body_obj(&serde_json::json!({
+            "error": {"code": 400, "message": "Invalid value at 'voice.name'"}
+        }));
+    });
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let cfg_path = dir.path().join("tts.yaml");
+    fs::write(
+        &cfg_path,
+        "items:\n  - text: fine\n    output: one.wav\n  - text: broken\n    output: two.wav\n    voice: en-US-Bogus\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.current_dir(dir.path())
+        .env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env("FAST_TTS_CACHE_DIR", dir.path().join("cache"))
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--no-history",
+            "--json",
+            "--config",
+            cfg_path.to_str().unwrap(),
+        ]);
+    let output = cmd.assert().code(5).get_output().stdout.clone();
+    let lines: Vec<serde_json::Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2, "{lines:?}");
+    assert_eq!(lines[1]["index"], 2);
+    assert_eq!(lines[1]["output"], "two.wav");
+    assert_eq!(lines[1]["error"]["httpStatus"], 400);
+    let message = lines[1]["error"]["message"].as_str().unwrap();
+    assert!(
+        message.contains(
+            "bulk item 2 (two.wav): provider google, language en-US, encoding LINEAR16, voice en-US-Bogus"
+        ) && message.contains("Google: Invalid value at 'voice.name'"),
+        "{message}"
+    );
+}
@@ -0,0 +1,56 @@
+use httpmock::prelude::*;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn cancelling_a_request_stops_it_without_output() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200)
+            .delay(Duration::from_secs(10))
+            .json_body_obj(&serde_json::json!({ "audioContent": "TVAz" }));
+    });
+    let dir = tempdir().unwrap();
+    // SAFETY: this test binary has a single test, so nothing reads the environment concurrently
+    unsafe {
+        std::env::set_var("FAST_TTS_TOKEN", "test-token");
+        std::env::set_var("FAST_TTS_BASE_URL", server.base_url());
+        std::env::set_var("FAST_TTS_CACHE_DIR", dir.path());
+        for var in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+            std::env::remove_var(var);
+        }
+    }
+
+    let cancel = fast_tts::CancellationToken::new();
+    let out = dir.path().join("hello.mp3");
+    let request = fast_tts::SynthesisRequest::builder()
+        .text("hello")
+        .output(&out)
+        .encoding(fast_tts::AudioEncoding::Mp3)
+        .retries(0)
+        .cancellation(cancel.clone())
+        .build()
+        .unwrap();
+
+    let started = Instant::now();
+    let canceller = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        cancel.cancel();
+    });
+    let err = fast_tts::synthesize(&request).await.unwrap_err();
+    canceller.await.unwrap();
+    assert!(
+        started.elapsed() < Duration::from_secs(5),
+        "took {:?}",
+        started.elapsed()
+    );
+    let failure = fast_tts::FastTtsError::classify(&err);
+    assert!(
+        matches!(failure, fast_tts::FastTtsError::Cancelled(_)),
+        "{failure:?}"
+    );
+    assert_eq!(failure.exit_code(), 130);
+    assert!(!out.exists());
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
@@ -22,3 +22,617 @@ fn validates_extension_vs_encoding() {
         .failure()
         .stderr(predicate::str::contains("does not match encoding"));
 }
+
+#[test]
+fn rejects_unreadable_ca_cert() {
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("out.wav");
+    let missing = dir.path().join("missing.pem");
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args([
+        "--ca-cert",
+        missing.to_str().unwrap(),
+        "hello",
+        out.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("failed to read CA certificate"));
+}
+
+#[test]
+fn client_key_requires_client_cert() {
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("out.wav");
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args(["--client-key", "key.pem", "hello", out.to_str().unwrap()]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--client-cert"));
+}
+
+#[test]
+fn mcp_tls_cert_requires_key() {
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args(["--mcp-mode", "http", "--mcp-tls-cert", "cert.pem"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--mcp-tls-key"));
+}
+
+#[test]
+fn rejects_malformed_resolve() {
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("out.wav");
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args([
+        "--resolve",
+        "texttospeech.googleapis.com:443",
+        "hello",
+        out.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("expected HOST:PORT:ADDR"));
+}
+
+#[test]
+fn auth_check_reports_missing_key() {
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env_remove("OPENAI_API_KEY")
+        .args(["auth", "check", "openai"]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("missing"))
+        .stdout(predicate::str::contains("OPENAI_API_KEY"));
+}
+
+#[test]
+fn encodings_lists_provider_capabilities() {
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args(["encodings", "--provider", "openai"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "MP3       .mp3  --sample-rate 24000",
+        ))
+        .stdout(predicate::str::contains("MULAW").not());
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args(["--json", "encodings", "--provider", "google"]);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let report: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let encodings = report[0]["encodings"].as_array().unwrap();
+    assert!(
+        encodings
+            .iter()
+            .any(|e| e["encoding"] == "MULAW" && e["extension"] == "wav")
+    );
+    assert!(
+        encodings[0]["sampleRates"]
+            .as_array()
+            .unwrap()
+            .contains(&48000.into())
+    );
+}
+
+#[test]
+fn openai_voices_are_listed_and_checked() {
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env_remove("OPENAI_TTS_VOICES")
+        .args(["--provider", "openai", "--list-voices"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("alloy"))
+        .stdout(predicate::str::contains("shimmer"));
+
+    let dir = tempdir().unwrap();
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env_remove("OPENAI_TTS_VOICES")
+        .env_remove("OPENAI_API_KEY")
+        .args([
+            "--provider",
+            "openai",
+            "--voice",
+            "allly",
+            "--encoding",
+            "MP3",
+            "hi",
+        ])
+        .arg(dir.path().join("hi.mp3"));
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("unknown voice 'allly'"))
+        .stderr(predicate::str::contains("did you mean 'alloy'?"));
+
+    // The catalog can be replaced for voices the built-in list doesn't know yet
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("OPENAI_TTS_VOICES", "alloy, marin").args([
+        "--provider",
+        "openai",
+        "--list-voices",
+        "--json",
+    ]);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let voices: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(voices["voices"][1]["name"], "marin");
+}
+
+#[test]
+fn deepgram_aura_models_are_listed_as_voices() {
+    use base64::Engine as _;
+
+    let models = serde_json::json!({
+        "stt": [{"name": "nova-3", "canonical_name": "nova-3"}],
+        "tts": [
+            {
+                "name": "asteria",
+                "canonical_name": "aura-asteria-en",
+                "architecture": "aura",
+                "languages": ["en", "en-US"],
+                "metadata": {"accent": "American", "tags": ["feminine"]}
+            },
+            {
+                "name": "orion",
+                "canonical_name": "aura-2-orion-en",
+                "architecture": "aura-2",
+                "languages": ["en"],
+                "metadata": {"tags": ["masculine"]}
+            }
+        ]
+    });
+    let dir = tempdir().unwrap();
+    let cassette = dir.path().join("cassette.json");
+    std::fs::write(
+        &cassette,
+        serde_json::json!({"interactions": [{
+            "method": "GET",
+            "url": "https://api.deepgram.com/v1/models",
+            "status": 200,
+            "body": base64::engine::general_purpose::STANDARD.encode(models.to_string())
+        }]})
+        .to_string(),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_CACHE_DIR", dir.path().join("cache"))
+        .args(["--provider", "deepgram", "--list-voices", "--replay"])
+        .arg(&cassette);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("aura-asteria-en"))
+        .stdout(predicate::str::contains("FEMALE"))
+        .stdout(predicate::str::contains("aura-2-orion-en"))
+        .stdout(predicate::str::contains("nova-3").not());
+
+    // Offline, the cached catalog answers without a request
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_CACHE_DIR", dir.path().join("cache"))
+        .args([
+            "--provider",
+            "deepgram",
+            "--list-voices",
+            "--json",
+            "--offline",
+        ]);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let voices: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(voices["voices"][1]["name"], "aura-2-orion-en");
+    assert_eq!(voices["voices"][1]["ssmlGender"], "MALE");
+}
+
+#[test]
+fn azure_voices_list_type_styles_and_roles() {
+    use base64::Engine as _;
+
+    let voices = serde_json::json!([
+        {
+            "Name": "Microsoft Server Speech Text to Speech Voice (en-US, JennyNeural)",
+            "ShortName": "en-US-JennyNeural",
+            "Gender": "Female",
+            "Locale": "en-US",
+            "SampleRateHertz": "24000",
+            "VoiceType": "Neural",
+            "StyleList": ["assistant", "chat", "cheerful"],
+            "Status": "GA"
+        },
+        {
+            "ShortName": "zh-CN-XiaomoNeural",
+            "Gender": "Female",
+            "Locale": "zh-CN",
+            "SecondaryLocaleList": ["en-US"],
+            "SampleRateHertz": "24000",
+            "VoiceType": "Neural",
+            "RolePlayList": ["Boy", "Girl"]
+        },
+        {
+            "ShortName": "en-US-Ava:DragonHDLatestNeural",
+            "Gender": "Female",
+            "Locale": "en-US",
+            "SampleRateHertz": "24000",
+            "VoiceType": "Neural"
+        }
+    ]);
+    let dir = tempdir().unwrap();
+    let cassette = dir.path().join("cassette.json");
+    std::fs::write(
+        &cassette,
+        serde_json::json!({"interactions": [{
+            "method": "GET",
+            "url": "https://westus.tts.speech.microsoft.com/cognitiveservices/voices/list",
+            "status": 200,
+            "body": base64::engine::general_purpose::STANDARD.encode(voices.to_string())
+        }]})
+        .to_string(),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_CACHE_DIR", dir.path().join("cache"))
+        .env("AZURE_SPEECH_REGION", "westus")
+        .args(["--provider", "azure", "--list-voices", "--replay"])
+        .arg(&cassette);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "[en-US]  Neural  styles: assistant,chat,cheerful",
+        ))
+        .stdout(predicate::str::contains(
+            "[zh-CN,en-US]  Neural  roles: Boy,Girl",
+        ))
+        .stdout(predicate::str::contains("[en-US]  HD"));
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_CACHE_DIR", dir.path().join("cache"))
+        .env("AZURE_SPEECH_REGION", "westus")
+        .args([
+            "--provider",
+            "azure",
+            "--list-voices",
+            "--json",
+            "--offline",
+        ]);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let listed: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let jenny = &listed["voices"][0];
+    assert_eq!(jenny["name"], "en-US-JennyNeural");
+    assert_eq!(jenny["ssmlGender"], "FEMALE");
+    assert_eq!(jenny["voiceType"], "Neural");
+    assert_eq!(jenny["styles"][1], "chat");
+    assert_eq!(listed["voices"][1]["roles"][0], "Boy");
+}
+
+#[test]
+fn failures_map_to_exit_codes_and_json() {
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("out.mp3");
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env_remove("OPENAI_API_KEY").args([
+        "--provider",
+        "openai",
+        "--encoding",
+        "MP3",
+        "--json",
+        "hello",
+        out.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .code(3)
+        .stderr(predicate::str::contains(r#""kind":"auth""#))
+        .stderr(predicate::str::contains(r#""exitCode":3"#));
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args(["hello", out.to_str().unwrap()]);
+    cmd.assert().code(2);
+}
+
+#[test]
+fn model_is_only_for_piper() {
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("out.wav");
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args([
+        "--model",
+        "en_US-lessac-medium",
+        "hello",
+        out.to_str().unwrap(),
+    ]);
+    cmd.assert().code(2).stderr(predicate::str::contains(
+        "provider google does not take one",
+    ));
+}
+
+#[cfg(not(feature = "piper"))]
+#[test]
+fn piper_needs_its_feature() {
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("out.wav");
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args(["--provider", "piper", "hello", out.to_str().unwrap()]);
+    cmd.assert()
+        .code(7)
+        .stderr(predicate::str::contains("Rebuild with --features piper"))
+        .stderr(predicate::str::contains("all-cloud").not());
+}
+
+#[cfg(feature = "piper")]
+#[test]
+fn piper_models_resolve_from_the_models_dir() {
+    let dir = tempdir().unwrap();
+    let models = dir.path().join("models");
+    std::fs::create_dir(&models).unwrap();
+    std::fs::write(models.join("en_US-test-low.onnx"), b"").unwrap();
+    std::fs::write(
+        models.join("en_US-test-low.onnx.json"),
+        r#"{"audio":{"sample_rate":16000},"language":{"code":"en_US"},
+            "phoneme_id_map":{"_":[0]},"num_speakers":2,"speaker_id_map":{"amy":0,"joe":1}}"#,
+    )
+    .unwrap();
+    let out = dir.path().join("out.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("PIPER_MODELS_DIR", &models).args([
+        "--provider",
+        "piper",
+        "--model",
+        "en_US-tset-low",
+        "hello",
+        out.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("did you mean 'en_US-test-low'"));
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("PIPER_MODELS_DIR", &models)
+        .args(["--provider", "piper", "--list-voices", "--json"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let listed: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    let voice = &listed["voices"][0];
+    assert_eq!(voice["name"], "en_US-test-low");
+    assert_eq!(voice["languageCodes"][0], "en-US");
+    assert_eq!(voice["naturalSampleRateHertz"], 16000);
+    assert_eq!(voice["roles"][1], "joe");
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+fn system_provider_runs_espeak() {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let dir = tempdir().unwrap();
+    // Stands in for espeak-ng: lists two voices, or records its arguments and
+    // input and writes the file named by -w
+    let espeak = dir.path().join("espeak-ng");
+    std::fs::write(
+        &espeak,
+        format!(
+            r#"#!/bin/sh
+if [ "$1" = --voices ]; then
+  echo 'Pty Language       Age/Gender VoiceName          File                 Other Languages'
+  echo ' 5  en-gb           --/M      English_(Great_Britain) gmw/en         (en 2)'
+  echo ' 5  en-us           --/F      English_(America)  gmw/en-US            (en 3)'
+  exit 0
+fi
+echo "$@" > {args}
+cat > {text}
+while [ $# -gt 0 ]; do
+  if [ "$1" = -w ]; then printf WAVDATA > "$2"; fi
+  shift
+done
+"#,
+            args = dir.path().join("args").display(),
+            text = dir.path().join("text").display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&espeak, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let out = dir.path().join("out.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_ESPEAK", &espeak).args([
+        "--no-history",
+        "--provider",
+        "system",
+        "--rate",
+        "2",
+        "Hello there",
+        out.to_str().unwrap(),
+    ]);
+    cmd.assert().success();
+    assert_eq!(std::fs::read(&out).unwrap(), b"WAVDATA");
+    let args = std::fs::read_to_string(dir.path().join("args")).unwrap();
+    assert_eq!(
+        args.trim(),
+        format!("-v en-us -s 350 -w {} --stdin", out.display())
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("text")).unwrap(),
+        "Hello there"
+    );
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_ESPEAK", &espeak).args([
+        "--no-history",
+        "--provider",
+        "system",
+        "--voice",
+        "en-gbb",
+        "Hello",
+        out.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("en-gb"));
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_ESPEAK", &espeak)
+        .args(["--provider", "system", "--list-voices", "--json"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let listed: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(listed["voices"][1]["name"], "en-us");
+    assert_eq!(listed["voices"][1]["ssmlGender"], "FEMALE");
+}
+
+#[cfg(not(feature = "kokoro"))]
+#[test]
+fn kokoro_needs_its_feature() {
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("out.wav");
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args(["--provider", "kokoro", "hello", out.to_str().unwrap()]);
+    cmd.assert()
+        .code(7)
+        .stderr(predicate::str::contains("Rebuild with --features kokoro"));
+}
+
+#[cfg(feature = "kokoro")]
+#[test]
+fn kokoro_reads_models_and_voicepacks_from_its_dir() {
+    let dir = tempdir().unwrap();
+    let kokoro = dir.path().join("kokoro");
+    std::fs::create_dir_all(kokoro.join("voices")).unwrap();
+    std::fs::write(kokoro.join("kokoro-v1.0.onnx"), b"").unwrap();
+    std::fs::write(kokoro.join("model_quantized.onnx"), b"").unwrap();
+    for voice in ["af_heart", "bm_george"] {
+        std::fs::write(
+            kokoro.join("voices").join(format!("{voice}.bin")),
+            [0u8; 1024],
+        )
+        .unwrap();
+    }
+    let out = dir.path().join("out.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("KOKORO_DIR", &kokoro)
+        .args(["--provider", "kokoro", "hello", out.to_str().unwrap()]);
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("several Kokoro models"));
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("KOKORO_DIR", &kokoro).args([
+        "--provider",
+        "kokoro",
+        "--model",
+        "kokoro-v1.0",
+        "--voice",
+        "af_haert",
+        "hello",
+        out.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("af_heart"));
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("KOKORO_DIR", &kokoro)
+        .args(["--provider", "kokoro", "--list-voices", "--json"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let listed: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    let voice = &listed["voices"][1];
+    assert_eq!(voice["name"], "bm_george");
+    assert_eq!(voice["languageCodes"][0], "en-GB");
+    assert_eq!(voice["ssmlGender"], "MALE");
+    assert_eq!(voice["naturalSampleRateHertz"], 24000);
+}
+
+#[test]
+fn doctor_json_reports_each_check() {
+    let dir = tempdir().unwrap();
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "token")
+        .env("FAST_TTS_CACHE_DIR", dir.path().join("cache"))
+        .env("FAST_TTS_FFMPEG", dir.path().join("no-ffmpeg"))
+        .args(["doctor", "--json"]);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let checks: Vec<serde_json::Value> = serde_json::from_slice(&out).unwrap();
+    let check = |name: &str| {
+        checks
+            .iter()
+            .find(|c| c["name"] == name)
+            .unwrap_or_else(|| panic!("no {name} check in {checks:?}"))
+    };
+    assert_eq!(check("provider google")["status"], "ok");
+    assert_eq!(check("ffmpeg")["status"], "warn");
+    assert_eq!(check("cache")["status"], "ok");
+    assert!(checks.iter().all(|c| c["name"] != "credentials"));
+}
+
+#[test]
+fn cache_info_and_clear_report_json() {
+    let dir = tempdir().unwrap();
+    let cache = dir.path().join("cache");
+    std::fs::create_dir_all(cache.join("voices")).unwrap();
+    std::fs::write(cache.join("voices/google.json"), "{}").unwrap();
+    std::fs::create_dir_all(cache.join("tokens")).unwrap();
+    std::fs::write(cache.join("tokens/google.json"), "12345").unwrap();
+
+    let report = |action: &str| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_CACHE_DIR", &cache)
+            .args(["cache", action, "--json"]);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        serde_json::from_slice::<serde_json::Value>(&out).unwrap()
+    };
+    let info = report("info");
+    assert_eq!(info["files"], 2);
+    assert_eq!(info["bytes"], 7);
+    assert_eq!(info["dir"], cache.to_str().unwrap());
+    assert_eq!(report("clear")["files"], 2);
+    assert!(!cache.exists());
+    assert_eq!(report("info")["files"], 0);
+}
+
+#[test]
+fn usage_sums_the_history_per_provider() {
+    let dir = tempdir().unwrap();
+    let history = dir.path().join("history.jsonl");
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let entry = |id: u64, age_days: u64, provider: &str, chars: usize, error: Option<&str>| {
+        serde_json::json!({
+            "id": id,
+            "time": now - age_days * 86400,
+            "provider": provider,
+            "output": "out.mp3",
+            "paramsHash": null,
+            "chars": chars,
+            "durationMs": 100,
+            "costUsd": chars as f64 * 16.0 / 1_000_000.0,
+            "error": error,
+            "args": [],
+            "cwd": "/",
+        })
+        .to_string()
+    };
+    let lines = [
+        entry(1, 30, "google", 1000, None),
+        entry(2, 0, "google", 500, None),
+        entry(3, 0, "openai", 200, Some("quota")),
+    ];
+    std::fs::write(&history, lines.join("\n") + "\n").unwrap();
+
+    let usage = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_HISTORY", &history)
+            .args(["usage", "--json"])
+            .args(extra);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        serde_json::from_slice::<serde_json::Value>(&out).unwrap()
+    };
+    let all = usage(&[]);
+    assert_eq!(all["jobs"], 3);
+    assert_eq!(all["failed"], 1);
+    assert_eq!(all["chars"], 1700);
+    assert_eq!(all["providers"]["google"]["jobs"], 2);
+    assert_eq!(all["providers"]["google"]["chars"], 1500);
+    assert_eq!(all["providers"]["openai"]["failed"], 1);
+
+    let recent = usage(&["--days", "7"]);
+    assert_eq!(recent["jobs"], 2);
+    assert_eq!(recent["providers"]["google"]["chars"], 500);
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "cdylib")]
+
+use base64::Engine as _;
+use fast_tts::ffi::*;
+use httpmock::prelude::*;
+use std::ffi::{CStr, CString};
+use tempfile::tempdir;
+
+#[test]
+fn ffi_synthesizes_and_reports_errors() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    // SAFETY: this test binary has a single test, so nothing reads the environment concurrently
+    unsafe {
+        std::env::set_var("FAST_TTS_TOKEN", "test-token");
+        std::env::set_var("FAST_TTS_BASE_URL", server.base_url());
+        std::env::set_var("FAST_TTS_CACHE_DIR", dir.path());
+        for var in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+            std::env::remove_var(var);
+        }
+    }
+
+    let out = dir.path().join("hello.mp3");
+    let request = CString::new(
+        serde_json::json!({"text": "hello", "output": out, "encoding": "mp3"}).to_string(),
+    )
+    .unwrap();
+    // SAFETY: valid NUL-terminated strings and writable out-pointers throughout
+    unsafe {
+        assert_eq!(fast_tts_synthesize(request.as_ptr()), 0);
+        assert!(fast_tts_last_error().is_null());
+        assert_eq!(std::fs::read(&out).unwrap(), b"MP3DATA");
+
+        let streamed = CString::new(r#"{"text": "hello", "encoding": "MP3"}"#).unwrap();
+        let (mut data, mut len) = (std::ptr::null_mut(), 0);
+        assert_eq!(
+            fast_tts_synthesize_bytes(streamed.as_ptr(), &mut data, &mut len),
+            0
+        );
+        assert_eq!(std::slice::from_raw_parts(data, len), b"MP3DATA");
+        fast_tts_bytes_free(data, len);
+
+        let mut json = std::ptr::null_mut();
+        assert_eq!(fast_tts_providers_json(&mut json), 0);
+        assert!(
+            CStr::from_ptr(json)
+                .to_str()
+                .unwrap()
+                .contains("\"google\"")
+        );
+        fast_tts_string_free(json);
+
+        let bad = CString::new(r#"{"text": "hi", "output": "hi.wav", "rate": 9}"#).unwrap();
+        assert_eq!(fast_tts_synthesize(bad.as_ptr()), 2);
+        let message = CStr::from_ptr(fast_tts_last_error()).to_str().unwrap();
+        assert!(message.contains("rate"), "{message}");
+    }
+}
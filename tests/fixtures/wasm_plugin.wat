@@ -0,0 +1,115 @@
+;; Core module for the test plugin, wrapped into a component implementing
+;; wit/provider.wit by tests/wasm.rs. It POSTs the text to the URL setting
+;; (with the voice, if any, as an x-voice header) and returns the response
+;; body as the audio. A voice starting with `~` spins forever.
+;;
+;; Fixed memory: 256.. constants, 512 capabilities record, 768 config-var
+;; result, 784 send result, 832 header tuple, 1024 synthesize result; the
+;; allocator hands out memory from 4096 up.
+(module
+  (import "fast-tts:provider/host@0.1.0" "send"
+    (func $send (param i32 i32 i32 i32 i32 i32 i32 i32 i32)))
+  (import "fast-tts:provider/host@0.1.0" "config-var"
+    (func $config_var (param i32 i32 i32)))
+  (memory (export "memory") 1)
+  (global $heap (mut i32) (i32.const 4096))
+
+  (data (i32.const 256) "POST")
+  (data (i32.const 272) "URL")
+  (data (i32.const 288) "URL not set")
+  (data (i32.const 320) "x-voice")
+  ;; encodings: mp3, linear16
+  (data (i32.const 336) "\01\00")
+  ;; sample rates: 24000
+  (data (i32.const 344) "\c0\5d\00\00")
+  (data (i32.const 352) "plugin")
+  (data (i32.const 368) "any name the service accepts")
+
+  (func (export "cabi_realloc")
+    (param $old i32) (param $old_size i32) (param $align i32) (param $size i32)
+    (result i32)
+    (local $ptr i32)
+    (local.set $ptr
+      (i32.and
+        (i32.add (global.get $heap) (i32.sub (local.get $align) (i32.const 1)))
+        (i32.sub (i32.const 0) (local.get $align))))
+    (global.set $heap (i32.add (local.get $ptr) (local.get $size)))
+    (if (i32.gt_u (global.get $heap) (i32.mul (memory.size) (i32.const 65536)))
+      (then
+        (drop (memory.grow
+          (i32.add
+            (i32.div_u
+              (i32.sub (global.get $heap) (i32.mul (memory.size) (i32.const 65536)))
+              (i32.const 65536))
+            (i32.const 1))))))
+    (if (local.get $old_size)
+      (then (memory.copy (local.get $ptr) (local.get $old) (local.get $old_size))))
+    (local.get $ptr))
+
+  (func (export "fast-tts:provider/provider@0.1.0#capabilities") (result i32)
+    (i32.store (i32.const 512) (i32.const 336))
+    (i32.store (i32.const 516) (i32.const 2))
+    (i32.store (i32.const 520) (i32.const 344))
+    (i32.store (i32.const 524) (i32.const 1))
+    (i32.store (i32.const 528) (i32.const 352))
+    (i32.store (i32.const 532) (i32.const 6))
+    (i32.store (i32.const 536) (i32.const 368))
+    (i32.store (i32.const 540) (i32.const 28))
+    (i32.const 512))
+
+  (func (export "fast-tts:provider/provider@0.1.0#synthesize")
+    (param $text i32) (param $text_len i32)
+    (param $language i32) (param $language_len i32)
+    (param $has_voice i32) (param $voice i32) (param $voice_len i32)
+    (param $rate f32) (param $pitch f32)
+    (param $has_sample_rate i32) (param $sample_rate i32)
+    (param $encoding i32) (param $ssml i32)
+    (result i32)
+    (local $headers i32)
+    (if (i32.and (local.get $has_voice)
+          (i32.eq (i32.load8_u (local.get $voice)) (i32.const 126)))
+      (then (loop $spin (br $spin))))
+    (call $config_var (i32.const 272) (i32.const 3) (i32.const 768))
+    (if (i32.eqz (i32.load8_u (i32.const 768)))
+      (then
+        ;; err(invalid-input("URL not set"))
+        (i32.store8 (i32.const 1024) (i32.const 1))
+        (i32.store8 (i32.const 1028) (i32.const 2))
+        (i32.store (i32.const 1032) (i32.const 288))
+        (i32.store (i32.const 1036) (i32.const 11))
+        (return (i32.const 1024))))
+    (if (local.get $has_voice)
+      (then
+        (i32.store (i32.const 832) (i32.const 320))
+        (i32.store (i32.const 836) (i32.const 7))
+        (i32.store (i32.const 840) (local.get $voice))
+        (i32.store (i32.const 844) (local.get $voice_len))
+        (local.set $headers (i32.const 1))))
+    (call $send
+      (i32.const 256) (i32.const 4)
+      (i32.load (i32.const 772)) (i32.load (i32.const 776))
+      (i32.const 832) (local.get $headers)
+      (local.get $text) (local.get $text_len)
+      (i32.const 784))
+    (if (i32.load8_u (i32.const 784))
+      (then
+        ;; err(other(message))
+        (i32.store8 (i32.const 1024) (i32.const 1))
+        (i32.store8 (i32.const 1028) (i32.const 5))
+        (i32.store (i32.const 1032) (i32.load (i32.const 788)))
+        (i32.store (i32.const 1036) (i32.load (i32.const 792)))
+        (return (i32.const 1024))))
+    (if (i32.ne (i32.load16_u (i32.const 788)) (i32.const 200))
+      (then
+        ;; err(provider-http((status, body)))
+        (i32.store8 (i32.const 1024) (i32.const 1))
+        (i32.store8 (i32.const 1028) (i32.const 3))
+        (i32.store16 (i32.const 1032) (i32.load16_u (i32.const 788)))
+        (i32.store (i32.const 1036) (i32.load (i32.const 800)))
+        (i32.store (i32.const 1040) (i32.load (i32.const 804)))
+        (return (i32.const 1024))))
+    ;; ok(body)
+    (i32.store8 (i32.const 1024) (i32.const 0))
+    (i32.store (i32.const 1028) (i32.load (i32.const 800)))
+    (i32.store (i32.const 1032) (i32.load (i32.const 804)))
+    (i32.const 1024)))
@@ -74,6 +74,63 @@ fn synthesize_plain_text_linear16() {
     synth_mock.assert();
 }
 
+#[test]
+fn effects_profile_aliases_expand_to_google_ids() {
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize").body_contains(
+            r#""effectsProfileId":["large-automotive-class-device","handset-class-device"]"#,
+        );
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audio_content": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("car.wav");
+    Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["--effects-profile=car,phone", "hello"])
+        .arg(&out)
+        .assert()
+        .success();
+    synth_mock.assert();
+}
+
+#[test]
+fn language_is_taken_from_the_voice_name() {
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains(r#""voice":{"languageCode":"en-GB","name":"en-GB-Neural2-A"}"#);
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+
+    let dir = tempdir().unwrap();
+    Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["--no-history", "--voice", "en-GB-Neural2-A", "hello"])
+        .arg(dir.path().join("gb.wav"))
+        .assert()
+        .success();
+    synth_mock.assert();
+}
+
 #[test]
 fn synthesize_ssml_mp3() {
     let server = MockServer::start();
@@ -126,6 +183,171 @@ fn synthesize_ssml_mp3() {
     synth_mock.assert();
 }
 
+#[test]
+fn stream_writes_audio_to_stdout() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["--stream", "--encoding", "MP3", "hello", "-"]);
+    cmd.assert().success().stdout("MP3DATA");
+}
+
+#[test]
+fn custom_template_provider() {
+    let server = MockServer::start();
+    let speak = server.mock(|when, then| {
+        when.method(POST)
+            .path("/speak")
+            .query_param("voice", "amy smith")
+            .header("authorization", "Bearer local-secret")
+            .header("x-service-key", "local-secret")
+            .header_exists("idempotency-key")
+            .json_body_obj(&serde_json::json!({"input": "hello", "speed": 1.5, "fmt": "mp3"}));
+        then.status(200).body("MP3DATA");
+    });
+    let dir = tempdir().unwrap();
+    fs::write(
+        dir.path().join("local.toml"),
+        format!(
+            "url = \"{}/speak?voice={{voice}}\"\n\
+             auth_env = \"LOCAL_TTS_KEY\"\n\
+             idempotency_header = \"Idempotency-Key\"\n\
+             rate_range = [0.25, 2.0]\n\
+             [headers]\nAuthorization = \"Bearer {{api_key}}\"\nX-Service-Key = \"{{api_key}}\"\n\
+             [body]\ninput = \"{{text}}\"\nspeed = \"{{rate}}\"\nfmt = \"{{format}}\"\n\
+             [formats]\nMP3 = \"mp3\"\n",
+            server.base_url()
+        ),
+    )
+    .unwrap();
+    let out = dir.path().join("out.mp3");
+    let log = dir.path().join("transcript.log");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_PROVIDERS_DIR", dir.path())
+        .env("LOCAL_TTS_KEY", "local-secret")
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--provider",
+            "custom:local",
+            "--voice",
+            "amy smith",
+            "--rate",
+            "1.5",
+            "--encoding",
+            "MP3",
+            "--debug-http",
+            log.to_str().unwrap(),
+            "hello",
+            out.to_str().unwrap(),
+        ]);
+    cmd.assert().success();
+    assert_eq!(read_file(&out), b"MP3DATA");
+    speak.assert();
+    // Headers built from {api_key} are redacted, whatever their name
+    let transcript = fs::read_to_string(&log).unwrap();
+    assert!(
+        transcript.contains(r#""x-service-key":"REDACTED""#),
+        "{transcript}"
+    );
+    assert!(!transcript.contains("local-secret"));
+
+    // Encodings the template doesn't map are rejected before any request
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_PROVIDERS_DIR", dir.path())
+        .args(["--provider", "custom:local", "hello"])
+        .arg(dir.path().join("out.wav"));
+    cmd.assert().code(2);
+
+    // So are rates outside the template's rate_range
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_PROVIDERS_DIR", dir.path())
+        .args([
+            "--provider",
+            "custom:local",
+            "--encoding",
+            "MP3",
+            "--rate",
+            "3",
+        ])
+        .arg("hello")
+        .arg(dir.path().join("fast.mp3"));
+    cmd.assert().code(2).stderr(predicate::str::contains(
+        "out of range for provider custom:local (0.25 to 2)",
+    ));
+    speak.assert_hits(1);
+}
+
+#[test]
+fn custom_template_fills_each_placeholder_once() {
+    let server = MockServer::start();
+    let speak = server.mock(|when, then| {
+        when.method(POST)
+            .path("/speak")
+            .query_param("q", "say {api_key} as {voice}")
+            .header("authorization", "Bearer local-secret")
+            .json_body_obj(&serde_json::json!({"input": "Read: say {api_key} as {voice}"}));
+        then.status(200).body("MP3DATA");
+    });
+    let dir = tempdir().unwrap();
+    fs::write(
+        dir.path().join("local.toml"),
+        format!(
+            "url = \"{}/speak?q={{text}}\"\n\
+             auth_env = \"LOCAL_TTS_KEY\"\n\
+             [headers]\nAuthorization = \"Bearer {{api_key}}\"\n\
+             [body]\ninput = \"Read: {{text}}\"\n\
+             [formats]\nMP3 = \"mp3\"\n",
+            server.base_url()
+        ),
+    )
+    .unwrap();
+    let custom = || {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_PROVIDERS_DIR", dir.path())
+            .env("LOCAL_TTS_KEY", "local-secret")
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(["--provider", "custom:local", "--encoding", "MP3"]);
+        cmd
+    };
+
+    let out = dir.path().join("out.mp3");
+    custom()
+        .arg("say {api_key} as {voice}")
+        .arg(&out)
+        .assert()
+        .success();
+    speak.assert();
+    assert_eq!(read_file(&out), b"MP3DATA");
+
+    // Without {rate} in the template there is no rate control to set
+    custom()
+        .args(["--rate", "1.5", "hello"])
+        .arg(dir.path().join("rate.mp3"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("has no rate control"));
+    speak.assert_hits(1);
+}
+
 #[test]
 fn list_voices_json() {
     let server = MockServer::start();
@@ -141,9 +363,11 @@ fn list_voices_json() {
         }));
     });
 
+    let cache = tempdir().unwrap();
     let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
     cmd.env("FAST_TTS_TOKEN", "fake")
         .env("FAST_TTS_BASE_URL", server.base_url())
+        .env("FAST_TTS_CACHE_DIR", cache.path())
         .env_remove("HTTP_PROXY")
         .env_remove("HTTPS_PROXY")
         .env_remove("http_proxy")
@@ -155,3 +379,2409 @@ fn list_voices_json() {
         .stdout(predicate::str::contains("\"voices\""));
     voices_mock.assert();
 }
+
+#[test]
+fn resolve_override_applies_to_its_port_only() {
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let port = server.port();
+    let synthesize = |resolve: String| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", format!("http://tts.invalid:{port}"))
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(["--no-history", "--resolve", &resolve, "hello"])
+            .arg(dir.path().join("hello.wav"));
+        cmd.assert()
+    };
+
+    synthesize(format!("tts.invalid:{port}:127.0.0.1")).success();
+    synth_mock.assert();
+    // An override for another port leaves this one to DNS, which has no such host
+    synthesize("tts.invalid:443:127.0.0.1".into()).failure();
+    synth_mock.assert_hits(1);
+}
+
+#[test]
+fn extra_headers_are_forwarded() {
+    let server = MockServer::start();
+
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .header("x-org-id", "42")
+            .header("x-trace", "abc");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audio_content": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("hdr.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--header",
+            "X-Org-Id: 42",
+            "--header",
+            "X-Trace:abc",
+            "hello",
+            out.to_str().unwrap(),
+        ]);
+    cmd.assert().success();
+    synth_mock.assert();
+}
+
+#[test]
+fn debug_http_transcript_redacts_secrets() {
+    let server = MockServer::start();
+
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audio_content": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("dbg.wav");
+    let log = dir.path().join("transcript.log");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "super-secret-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--debug-http",
+            log.to_str().unwrap(),
+            "hello",
+            out.to_str().unwrap(),
+        ]);
+    cmd.assert().success();
+    synth_mock.assert();
+
+    let transcript = fs::read_to_string(&log).unwrap();
+    let entry: serde_json::Value =
+        serde_json::from_str(transcript.lines().next().unwrap()).unwrap();
+    assert_eq!(entry["method"], "POST");
+    assert_eq!(entry["status"], 200);
+    assert_eq!(entry["requestHeaders"]["authorization"], "REDACTED");
+    assert!(!transcript.contains("super-secret-token"));
+}
+
+#[test]
+fn debug_http_transcript_redacts_lmnt_key() {
+    let dir = tempdir().unwrap();
+    let log = dir.path().join("transcript.log");
+    // Nothing listens on the loopback HTTPS port: the request fails, but is still logged
+    Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .env("LMNT_API_KEY", "sk-lmnt-SECRET")
+        .args([
+            "--no-history",
+            "--provider",
+            "lmnt",
+            "--resolve",
+            "api.lmnt.com:443:127.0.0.1",
+            "--debug-http",
+        ])
+        .arg(&log)
+        .arg("hello")
+        .arg(dir.path().join("hello.wav"))
+        .assert()
+        .failure();
+
+    let transcript = fs::read_to_string(&log).unwrap();
+    let entry: serde_json::Value =
+        serde_json::from_str(transcript.lines().next().unwrap()).unwrap();
+    assert_eq!(entry["url"], "https://api.lmnt.com/v1/ai/speech");
+    assert_eq!(entry["requestHeaders"]["x-api-key"], "REDACTED");
+    assert!(!transcript.contains("sk-lmnt-SECRET"));
+}
+
+#[test]
+fn record_then_replay_without_network() {
+    let dir = tempdir().unwrap();
+    let cassette = dir.path().join("cassette.json");
+    let recorded = dir.path().join("recorded.wav");
+    let replayed = dir.path().join("replayed.wav");
+
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audio_content": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+    let base = server.base_url();
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", &base)
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--record",
+            cassette.to_str().unwrap(),
+            "hello",
+            recorded.to_str().unwrap(),
+        ]);
+    cmd.assert().success();
+    synth_mock.assert();
+    drop(server);
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env_remove("FAST_TTS_TOKEN")
+        .env_remove("GOOGLE_APPLICATION_CREDENTIALS")
+        .env("FAST_TTS_BASE_URL", &base)
+        .args([
+            "--replay",
+            cassette.to_str().unwrap(),
+            "hello",
+            replayed.to_str().unwrap(),
+        ]);
+    cmd.assert().success();
+    assert_eq!(read_file(&replayed), b"WAVDATA");
+}
+
+#[test]
+fn streams_camel_case_audio_content() {
+    let server = MockServer::start();
+
+    // Large enough to span several response chunks
+    let audio: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    let body = format!(
+        "{{\n  \"audioContent\" : \"{}\",\n  \"timepoints\": []\n}}",
+        base64::engine::general_purpose::STANDARD
+            .encode(&audio)
+            .replace('/', "\\/")
+    );
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(body);
+    });
+
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("big.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["hello", out.to_str().unwrap()]);
+    cmd.assert().success();
+    synth_mock.assert();
+    assert_eq!(read_file(&out), audio);
+}
+
+#[test]
+fn offline_mode_refuses_network() {
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200);
+    });
+
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("offline.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .args(["--offline", "hello", out.to_str().unwrap()]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "network access disabled by --offline",
+    ));
+    synth_mock.assert_hits(0);
+}
+
+#[test]
+fn list_voices_revalidates_with_etag() {
+    let server = MockServer::start();
+    let cache = tempdir().unwrap();
+
+    let mut first = server.mock(|when, then| {
+        when.method(GET).path("/v1/voices");
+        then.status(200)
+            .header("etag", "\"v1\"")
+            .json_body_obj(&serde_json::json!({
+                "voices": [
+                  {"name": "en-US-Cached", "languageCodes": ["en-US"], "ssmlGender": "MALE", "naturalSampleRateHertz": 24000}
+                ]
+            }));
+    });
+
+    let run = || {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "fake")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env("FAST_TTS_CACHE_DIR", cache.path())
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(["--list-voices"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("en-US-Cached"));
+    };
+
+    run();
+    first.assert();
+    first.delete();
+
+    let revalidate = server.mock(|when, then| {
+        when.method(GET)
+            .path("/v1/voices")
+            .header("if-none-match", "\"v1\"");
+        then.status(304);
+    });
+    run();
+    revalidate.assert();
+}
+
+#[test]
+fn service_account_token_is_cached_between_runs() {
+    let server = MockServer::start();
+    let dir = tempdir().unwrap();
+    let cache = tempdir().unwrap();
+
+    let token_mock = server.mock(|when, then| {
+        when.method(POST).path("/token");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "access_token": "sa-token",
+            "expires_in": 3600,
+            "token_type": "Bearer"
+        }));
+    });
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .header("authorization", "Bearer sa-token");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+
+    let key_path = dir.path().join("sa.json");
+    fs::write(
+        &key_path,
+        serde_json::json!({
+            "type": "service_account",
+            "client_email": "tts@example.iam.gserviceaccount.com",
+            "private_key": include_str!("fixtures/test_service_account_key.pem"),
+            "token_uri": server.url("/token"),
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    for name in ["one.wav", "two.wav"] {
+        let out = dir.path().join(name);
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env_remove("FAST_TTS_TOKEN")
+            .env("GOOGLE_APPLICATION_CREDENTIALS", &key_path)
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env("FAST_TTS_CACHE_DIR", cache.path())
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(["hello", out.to_str().unwrap()]);
+        cmd.assert().success();
+    }
+
+    token_mock.assert_hits(1);
+    synth_mock.assert_hits(2);
+}
+
+#[test]
+fn workload_identity_federation_exchanges_subject_token() {
+    let server = MockServer::start();
+    let dir = tempdir().unwrap();
+
+    let sts_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/token")
+            .x_www_form_urlencoded_tuple("subject_token", "oidc-jwt")
+            .x_www_form_urlencoded_tuple(
+                "grant_type",
+                "urn:ietf:params:oauth:grant-type:token-exchange",
+            );
+        then.status(200).json_body_obj(&serde_json::json!({
+            "access_token": "federated-token",
+            "expires_in": 3600,
+            "token_type": "Bearer"
+        }));
+    });
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .header("authorization", "Bearer federated-token");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+
+    let subject = dir.path().join("oidc.json");
+    fs::write(&subject, r#"{"value": "oidc-jwt"}"#).unwrap();
+    let creds = dir.path().join("wif.json");
+    fs::write(
+        &creds,
+        serde_json::json!({
+            "type": "external_account",
+            "audience": "//iam.googleapis.com/projects/1/locations/global/workloadIdentityPools/ci/providers/github",
+            "subject_token_type": "urn:ietf:params:oauth:token-type:jwt",
+            "token_url": server.url("/v1/token"),
+            "credential_source": {
+                "file": subject.to_str().unwrap(),
+                "format": {"type": "json", "subject_token_field_name": "value"}
+            }
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let out = dir.path().join("wif.wav");
+    let cache = tempdir().unwrap();
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env_remove("FAST_TTS_TOKEN")
+        .env("GOOGLE_APPLICATION_CREDENTIALS", &creds)
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env("FAST_TTS_CACHE_DIR", cache.path())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["hello", out.to_str().unwrap()]);
+    cmd.assert().success();
+    sts_mock.assert();
+    synth_mock.assert();
+}
+
+#[cfg(unix)]
+#[test]
+fn gcloud_fallback_supplies_token() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .header("authorization", "Bearer gcloud-token");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+
+    let dir = tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    let gcloud = bin.join("gcloud");
+    fs::write(&gcloud, "#!/bin/sh\necho gcloud-token\n").unwrap();
+    fs::set_permissions(&gcloud, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+
+    let out = dir.path().join("gcloud.wav");
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env_remove("FAST_TTS_TOKEN")
+        .env_remove("GOOGLE_APPLICATION_CREDENTIALS")
+        .env("HOME", dir.path())
+        .env("PATH", path)
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["--gcloud-auth", "hello", out.to_str().unwrap()]);
+    cmd.assert().success();
+    synth_mock.assert();
+}
+
+#[test]
+fn google_api_key_authentication() {
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .header("x-goog-api-key", "my-api-key");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("key.wav");
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env_remove("FAST_TTS_TOKEN")
+        .env_remove("GOOGLE_APPLICATION_CREDENTIALS")
+        .env("GOOGLE_API_KEY", "my-api-key")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["hello", out.to_str().unwrap()]);
+    cmd.assert().success();
+    synth_mock.assert();
+}
+
+#[test]
+fn profile_supplies_provider_credentials() {
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .header("x-goog-api-key", "profile-key");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+
+    let dir = tempdir().unwrap();
+    let profiles = dir.path().join("profiles.yaml");
+    std::fs::write(
+        &profiles,
+        "work:\n  provider: google\n  env:\n    GOOGLE_API_KEY: profile-key\n",
+    )
+    .unwrap();
+    let out = dir.path().join("profile.wav");
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env_remove("FAST_TTS_TOKEN")
+        .env_remove("GOOGLE_APPLICATION_CREDENTIALS")
+        .env("GOOGLE_API_KEY", "env-key")
+        .env("FAST_TTS_PROFILES", &profiles)
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["--profile", "work", "hello", out.to_str().unwrap()]);
+    cmd.assert().success();
+    synth_mock.assert();
+}
+
+#[test]
+fn adc_is_read_from_cloudsdk_config_dir() {
+    let server = MockServer::start();
+    let sts_mock = server.mock(|when, then| {
+        when.method(POST).path("/v1/token");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "access_token": "adc-token",
+            "expires_in": 3600
+        }));
+    });
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .header("authorization", "Bearer adc-token");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+
+    let dir = tempdir().unwrap();
+    let gcloud_dir = dir.path().join("gcloud");
+    fs::create_dir_all(&gcloud_dir).unwrap();
+    let subject = dir.path().join("subject.txt");
+    fs::write(&subject, "oidc-jwt").unwrap();
+    fs::write(
+        gcloud_dir.join("application_default_credentials.json"),
+        serde_json::json!({
+            "type": "external_account",
+            "audience": "//iam.googleapis.com/projects/1/locations/global/workloadIdentityPools/p/providers/q",
+            "subject_token_type": "urn:ietf:params:oauth:token-type:jwt",
+            "token_url": server.url("/v1/token"),
+            "credential_source": {"file": subject.to_str().unwrap()}
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let out = dir.path().join("adc.wav");
+    let cache = tempdir().unwrap();
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env_remove("FAST_TTS_TOKEN")
+        .env_remove("GOOGLE_APPLICATION_CREDENTIALS")
+        .env_remove("GOOGLE_API_KEY")
+        .env("CLOUDSDK_CONFIG", &gcloud_dir)
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env("FAST_TTS_CACHE_DIR", cache.path())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["hello", out.to_str().unwrap()]);
+    cmd.assert().success();
+    sts_mock.assert();
+    synth_mock.assert();
+}
+
+#[test]
+fn auth_check_google_ok() {
+    let server = MockServer::start();
+    let voices_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/v1/voices")
+            .header("authorization", "Bearer fake");
+        then.status(200)
+            .json_body_obj(&serde_json::json!({"voices": []}));
+    });
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "fake")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("GOOGLE_API_KEY")
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["auth", "check", "google", "--json"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"status\": \"ok\""));
+    voices_mock.assert();
+}
+
+#[test]
+fn status_tells_outages_from_credential_failures() {
+    let probe = |status: u16| {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/text:synthesize")
+                .body_contains("\"text\":\"a\"");
+            then.status(status).json_body_obj(&if status == 200 {
+                serde_json::json!({
+                    "audioContent": base64::engine::general_purpose::STANDARD.encode("ID3")
+                })
+            } else {
+                serde_json::json!({"error": {"message": "nope"}})
+            });
+        });
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "fake")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env_remove("GOOGLE_API_KEY")
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(["status", "google", "--json"]);
+        let out = cmd.output().unwrap();
+        // One attempt: a probe that retried would hide how the provider is doing
+        mock.assert_hits(1);
+        let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        (out.status.code(), report[0].clone())
+    };
+
+    let (code, up) = probe(200);
+    assert_eq!(code, Some(0));
+    assert_eq!(up["status"], "up");
+    assert!(up["latencyMs"].is_u64());
+
+    let (code, down) = probe(503);
+    assert_eq!(code, Some(5));
+    assert_eq!(down["status"], "down");
+    assert_eq!(down["httpStatus"], 503);
+
+    let (code, auth) = probe(403);
+    assert_eq!(code, Some(3));
+    assert_eq!(auth["status"], "auth");
+}
+
+#[test]
+fn provider_rate_limit_exits_with_quota_code() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(429)
+            .json_body_obj(&serde_json::json!({"error": {"message": "quota"}}));
+    });
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("hello.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["--json", "hello", out.to_str().unwrap()]);
+    cmd.assert()
+        .code(4)
+        .stderr(predicate::str::contains(r#""kind":"quota""#));
+}
+
+#[test]
+fn verbose_logs_requests_to_stderr() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let run = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(extra)
+            .args(["--encoding", "MP3", "hello"])
+            .arg(dir.path().join("hello.mp3"));
+        let output = cmd.output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let debug = run(&["--verbose", "--verbose"]);
+    assert!(debug.contains("using Google access token"), "{debug}");
+    assert!(debug.contains("http request"), "{debug}");
+    assert!(debug.contains("synthesis finished"), "{debug}");
+
+    let json = run(&["--verbose", "--log-format", "json"]);
+    let first: serde_json::Value = serde_json::from_str(json.lines().next().unwrap()).unwrap();
+    assert_eq!(first["level"], "INFO");
+    assert!(!json.contains("http request"));
+
+    assert_eq!(run(&["--quiet"]), "");
+}
+
+#[test]
+fn json_flag_reports_written_files() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let cfg = dir.path().join("bulk.yaml");
+    fs::write(
+        &cfg,
+        format!(
+            "defaults:\n  encoding: MP3\nitems:\n  - text: one\n    output: {}\n",
+            dir.path().join("one.mp3").display()
+        ),
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .arg("--json")
+            .args(args);
+        let output = cmd.output().unwrap();
+        assert!(output.status.success());
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+
+    let out = dir.path().join("hello.mp3");
+    let report = run(&["--encoding", "MP3", "hello", out.to_str().unwrap()]);
+    assert_eq!(report["output"], out.to_str().unwrap());
+    assert_eq!(report["provider"], "google");
+    assert_eq!(report["bytes"], 7);
+
+    let report = run(&["--config", cfg.to_str().unwrap()]);
+    assert_eq!(report["index"], 1);
+    assert!(report["output"].as_str().unwrap().ends_with("one.mp3"));
+
+    // --print-path-only leaves nothing but the path on stdout
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["--print-path-only", "--encoding", "MP3", "hello"])
+        .arg(&out);
+    cmd.assert()
+        .success()
+        .stdout(format!("{}\n", out.display()));
+}
+
+#[test]
+fn bench_reports_latency_percentiles() {
+    let server = MockServer::start();
+    let synth = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let text = dir.path().join("sample.txt");
+    fs::write(&text, "hello").unwrap();
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--json",
+            "bench",
+            "--providers",
+            "google",
+            "-n",
+            "3",
+            "--text",
+        ])
+        .arg(&text);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report[0]["provider"], "google");
+    assert_eq!(report[0]["runs"], 3);
+    assert_eq!(report[0]["errors"], 0);
+    assert_eq!(report[0]["avgBytes"], 7);
+    assert!(report[0]["firstByteMs"]["p95"].is_u64());
+    synth.assert_hits(3);
+}
+
+#[test]
+fn compare_writes_one_file_per_voice_and_an_index() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let out_dir = dir.path().join("cmp");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "compare",
+            "Same <sentence>",
+            "--providers",
+            "google",
+            "--voice",
+            "google=en-US-Neural2-F",
+            "--voice",
+            "google=en-US-Neural2-D",
+            "--out-dir",
+        ])
+        .arg(&out_dir);
+    cmd.assert().success();
+
+    for file in [
+        "01-google-en-US-Neural2-F.mp3",
+        "02-google-en-US-Neural2-D.mp3",
+    ] {
+        assert_eq!(read_file(&out_dir.join(file)), b"MP3DATA");
+    }
+    let index = fs::read_to_string(out_dir.join("index.html")).unwrap();
+    assert!(index.contains("src=\"02-google-en-US-Neural2-D.mp3\""));
+    assert!(index.contains("Same &lt;sentence&gt;"));
+}
+
+#[test]
+fn history_records_and_reruns_jobs() {
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let history = dir.path().join("history.jsonl");
+    let cli = || {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env("FAST_TTS_HISTORY", &history)
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .current_dir(dir.path());
+        cmd
+    };
+
+    cli()
+        .args([
+            "--encoding",
+            "MP3",
+            "--api-key",
+            "s3cret",
+            "hello",
+            "out.mp3",
+        ])
+        .assert()
+        .success();
+    cli()
+        .args(["--no-history", "--encoding", "MP3", "skip", "skip.mp3"])
+        .assert()
+        .success();
+
+    let listed = cli().args(["history", "list", "--json"]).assert().success();
+    let entries: serde_json::Value = serde_json::from_slice(&listed.get_output().stdout).unwrap();
+    assert_eq!(entries.as_array().unwrap().len(), 1);
+    assert_eq!(entries[0]["id"], 1);
+    assert_eq!(entries[0]["provider"], "google");
+    assert_eq!(entries[0]["chars"], 5);
+    assert_eq!(entries[0]["paramsHash"].as_str().unwrap().len(), 16);
+    assert!(entries[0]["costUsd"].as_f64().unwrap() > 0.0);
+
+    cli()
+        .args(["history", "show", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "command:  fast-tts-cli --encoding MP3 hello out.mp3",
+        ))
+        .stdout(predicate::str::contains("s3cret").not());
+
+    fs::remove_file(dir.path().join("out.mp3")).unwrap();
+    cli().args(["history", "rerun", "1"]).assert().success();
+    assert_eq!(read_file(&dir.path().join("out.mp3")), b"MP3DATA");
+    synth_mock.assert_hits(3);
+
+    let reran = fs::read_to_string(&history).unwrap();
+    let hashes: Vec<String> = reran
+        .lines()
+        .map(|line| {
+            serde_json::from_str::<serde_json::Value>(line).unwrap()["paramsHash"].to_string()
+        })
+        .collect();
+    assert_eq!(hashes.len(), 2);
+    assert_eq!(hashes[0], hashes[1]);
+
+    cli()
+        .args(["history", "show", "9"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("no history entry with id 9"));
+}
+
+#[test]
+fn feed_synthesizes_only_new_entries() {
+    let server = MockServer::start();
+    let rss = format!(
+        r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/">
+  <channel>
+    <title>Reading list</title>
+    <item>
+      <title>Second post</title>
+      <guid>post-2</guid>
+      <link>{base}/posts/2</link>
+      <description>Only a teaser</description>
+    </item>
+    <item>
+      <title>First post</title>
+      <guid>post-1</guid>
+      <content:encoded><![CDATA[<p>Full &amp; inline</p><script>track()</script>]]></content:encoded>
+    </item>
+  </channel>
+</rss>"#,
+        base = server.base_url()
+    );
+    server.mock(|when, then| {
+        when.method(GET).path("/feed.xml");
+        then.status(200).body(rss);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/posts/2");
+        then.status(200).body(
+            "<html><body><nav>Menu</nav><article><h1>Second</h1><p>The whole article</p></article></body></html>",
+        );
+    });
+    let audio = serde_json::json!({
+        "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+    });
+    let first = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("First post.\\n\\nFull & inline\"");
+        then.status(200).json_body_obj(&audio);
+    });
+    let second = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("Second post.\\n\\nSecond\\n\\nThe whole article\"");
+        then.status(200).json_body_obj(&audio);
+    });
+    let dir = tempdir().unwrap();
+    let out_dir = dir.path().join("episodes");
+    let feed = || {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(["--encoding", "MP3", "feed"])
+            .arg(server.url("/feed.xml"))
+            .arg("--out-dir")
+            .arg(&out_dir);
+        cmd
+    };
+
+    feed().arg("--limit").arg("1").assert().success();
+    assert_eq!(read_file(&out_dir.join("Second_post.mp3")), b"MP3DATA");
+    feed().assert().success();
+    assert_eq!(read_file(&out_dir.join("First_post.mp3")), b"MP3DATA");
+    feed()
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("No new entries"));
+    first.assert_hits(1);
+    second.assert_hits(1);
+
+    let manifest = fs::read_to_string(out_dir.join(".fast-tts-feed.jsonl")).unwrap();
+    let guids: Vec<String> = manifest
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap()["guid"].to_string())
+        .collect();
+    assert_eq!(guids, ["\"post-2\"", "\"post-1\""]);
+}
+
+/// 16-bit mono 24 kHz WAV holding `samples`.
+fn wav_bytes(samples: &[i16]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&24000u32.to_le_bytes());
+    wav.extend_from_slice(&48000u32.to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for s in samples {
+        wav.extend_from_slice(&s.to_le_bytes());
+    }
+    wav
+}
+
+#[test]
+fn voice_markup_stitches_segments() {
+    let server = MockServer::start();
+    let audio = |samples: &[i16]| {
+        serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode(wav_bytes(samples))
+        })
+    };
+    let narrator = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("\"name\":\"en-US-Narrator\"");
+        then.status(200).json_body_obj(&audio(&[1, 1]));
+    });
+    let character = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("\"text\":\"\\\"Hello there,\\\"\"")
+            .body_contains("\"name\":\"en-GB-Character\"");
+        then.status(200).json_body_obj(&audio(&[7, 7, 7]));
+    });
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("story.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--voice",
+            "en-US-Narrator",
+            "--voice-markup",
+            "She said [voice:en-GB-Character]\"Hello there,\"[/voice] and left.",
+        ])
+        .arg(&output);
+    cmd.assert().success();
+
+    narrator.assert_hits(2);
+    character.assert_hits(1);
+    assert_eq!(read_file(&output), wav_bytes(&[1, 1, 7, 7, 7, 1, 1]));
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn segment_offsets_locate_each_part_of_the_join() {
+    let server = MockServer::start();
+    // 24 kHz mono: 2400 samples are 100 ms
+    let voice = |name: &str, samples: usize| {
+        let body = serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD
+                .encode(wav_bytes(&vec![1; samples]))
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/text:synthesize")
+                .body_contains(format!("\"name\":\"{name}\""));
+            then.status(200).json_body_obj(&body);
+        })
+    };
+    let narrator = voice("en-US-Narrator", 2400);
+    let character = voice("en-GB-Character", 4800);
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("story.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--no-history",
+            "--json",
+            "--voice",
+            "en-US-Narrator",
+            "--voice-markup",
+            "--crossfade-ms",
+            "20",
+            "--segment-offsets",
+            "Chapter one. [voice:en-GB-Character]Chapter two.[/voice] The end.",
+        ])
+        .arg(&output);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"segmentOffsets\":"));
+    narrator.assert_hits(2);
+    character.assert_hits(1);
+
+    // Each 20 ms crossfade pulls the next part back over the previous one
+    let table: serde_json::Value =
+        serde_json::from_slice(&read_file(&dir.path().join("story.segments.json"))).unwrap();
+    assert_eq!(table["durationMs"], 360);
+    let spans: Vec<_> = table["segments"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| {
+            (
+                s["label"].as_str().unwrap(),
+                s["text"].as_str().unwrap(),
+                s["startMs"].as_u64().unwrap(),
+                s["endMs"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            ("en-US-Narrator", "Chapter one.", 0, 100),
+            ("en-GB-Character", "Chapter two.", 80, 280),
+            ("en-US-Narrator", "The end.", 260, 360),
+        ]
+    );
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args(["--segment-offsets", "Just one part."])
+        .arg(dir.path().join("solo.wav"));
+    cmd.assert().code(2).stderr(predicate::str::contains(
+        "use them with voice markup or tracks",
+    ));
+}
+
+#[test]
+fn target_duration_adjusts_the_speaking_rate() {
+    let server = MockServer::start();
+    // 24 kHz mono: 24000 samples a second
+    let seconds = |secs: f32| {
+        serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD
+                .encode(wav_bytes(&vec![1; (secs * 24000.0) as usize]))
+        })
+    };
+    let normal = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("\"speakingRate\":1.0");
+        then.status(200).json_body_obj(&seconds(1.5));
+    });
+    let faster = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("\"speakingRate\":1.5");
+        then.status(200).json_body_obj(&seconds(1.0));
+    });
+    let fastest = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("\"speakingRate\":4.0");
+        then.status(200).json_body_obj(&seconds(0.5));
+    });
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("spot.wav");
+    let run = |target: &str| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(["--no-history", "--json", "--target-duration", target])
+            .arg("Buy one, get one free.")
+            .arg(&output);
+        cmd.assert()
+    };
+
+    // 1.5s at the normal rate, so the second render goes 1.5x faster
+    run("1s").success().stdout(predicate::str::contains(
+        r#""fit":{"attempts":2,"durationMs":1000,"rate":1.5}"#,
+    ));
+    normal.assert_hits(1);
+    faster.assert_hits(1);
+    assert_eq!(read_file(&output).len(), 44 + 24000 * 2);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    // Even the fastest rate Google allows is too slow; the closest render is kept
+    run("100ms").code(2).stderr(predicate::str::contains(
+        "the closest render is 0.5s at rate 4.00",
+    ));
+    fastest.assert_hits(1);
+    assert_eq!(read_file(&output).len(), 44 + 12000 * 2);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn background_music_is_ducked_under_speech() {
+    let server = MockServer::start();
+    // Half a second of speech, then a second of silence (24 kHz mono)
+    let speech: Vec<i16> = [vec![16000; 12000], vec![0; 24000]].concat();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode(wav_bytes(&speech))
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let bed = dir.path().join("bed.wav");
+    // A short constant bed, looped under the whole render
+    fs::write(&bed, wav_bytes(&[1000; 2400])).unwrap();
+    let output = dir.path().join("promo.wav");
+
+    Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--no-history",
+            "--bg-gain",
+            "-6dB",
+            "--duck",
+            "-12dB",
+            "--bg-music",
+        ])
+        .arg(&bed)
+        .arg("Big savings this weekend.")
+        .arg(&output)
+        .assert()
+        .success();
+    synth_mock.assert();
+
+    let mixed = read_file(&output);
+    let samples: Vec<i16> = mixed[44..]
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    // The bed plays on for a second after the speech, fading out
+    assert_eq!(samples.len(), speech.len() + 24000);
+    // Under speech the bed is 18 dB down: 1000 * 0.126
+    assert!(
+        (16120..=16132).contains(&samples[6000]),
+        "{}",
+        samples[6000]
+    );
+    // Well after the speech it has recovered to about -6 dB: 1000 * 0.5
+    assert!((420..=502).contains(&samples[35000]), "{}", samples[35000]);
+    assert_eq!(samples.last(), Some(&0));
+
+    // Only 16-bit WAV can be written after mixing
+    Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .args(["--no-history", "--encoding", "MP3", "--bg-music"])
+        .arg(&bed)
+        .arg("Big savings this weekend.")
+        .arg(dir.path().join("promo.mp3"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("use --encoding LINEAR16"));
+    synth_mock.assert_hits(1);
+}
+
+#[test]
+fn crossfade_overlaps_joined_segments() {
+    let server = MockServer::start();
+    let audio = |sample: i16| {
+        serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode(wav_bytes(&[sample; 100]))
+        })
+    };
+    server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("\"name\":\"en-US-Narrator\"");
+        then.status(200).json_body_obj(&audio(1000));
+    });
+    server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("\"name\":\"en-GB-Character\"");
+        then.status(200).json_body_obj(&audio(3000));
+    });
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("story.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--voice",
+            "en-US-Narrator",
+            "--voice-markup",
+            "--crossfade-ms",
+            "1",
+            "Once upon a time [voice:en-GB-Character]Hello",
+        ])
+        .arg(&output);
+    cmd.assert().success();
+
+    // 1 ms at 24 kHz: the last 24 samples of the first part blend into the second
+    let wav = read_file(&output);
+    let samples: Vec<i16> = wav[44..]
+        .chunks_exact(2)
+        .map(|s| i16::from_le_bytes([s[0], s[1]]))
+        .collect();
+    assert_eq!(samples.len(), 176);
+    assert!(samples[..76].iter().all(|&s| s == 1000));
+    assert!(samples[76..100].windows(2).all(|w| w[0] < w[1]));
+    assert!(samples[76] > 1000 && samples[99] < 3000);
+    assert!(samples[100..].iter().all(|&s| s == 3000));
+}
+
+#[test]
+fn custom_voice_model_and_cloning_key_reach_google() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains("\"customVoice\":{\"model\":\"projects/p/locations/us/models/brand\"}")
+            .body_contains("\"voiceClone\":{\"voiceCloningKey\":\"clone-key\"}");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode(wav_bytes(&[1]))
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("brand.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env("GOOGLE_VOICE_CLONING_KEY", "clone-key")
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--custom-voice-model",
+            "projects/p/locations/us/models/brand",
+            "Welcome back",
+        ])
+        .arg(&output);
+    cmd.assert().success();
+    mock.assert();
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args([
+        "--provider",
+        "openai",
+        "--custom-voice-model",
+        "projects/p/locations/us/models/brand",
+        "Welcome back",
+    ])
+    .arg(dir.path().join("other.wav"));
+    cmd.assert().code(2).stderr(predicate::str::contains(
+        "custom voice models are a Google feature",
+    ));
+}
+
+#[test]
+fn dialogue_script_uses_google_multi_speaker_markup() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1beta1/text:synthesize")
+            .json_body_partial(
+                r#"{
+                    "input": {"multiSpeakerMarkup": {"turns": [
+                        {"speaker": "R", "text": "Did you hear the news? It is big."},
+                        {"speaker": "S", "text": "Tell me everything."}
+                    ]}},
+                    "voice": {"name": "en-US-Studio-MultiSpeaker"}
+                }"#,
+            );
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode(wav_bytes(&[1]))
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("dialogue.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--dialogue",
+            "R: Did you hear the news?\nIt is big.\n\nS: Tell me everything.",
+        ])
+        .arg(&output);
+    cmd.assert().success();
+    mock.assert();
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args(["--dialogue", "Hello there\nR: hi"])
+        .arg(dir.path().join("bad.wav"));
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("dialogue line 1 has no speaker"));
+}
+
+#[test]
+fn dialogue_tracks_line_up_with_the_mix() {
+    let server = MockServer::start();
+    let turn = |speaker: &str, samples: &[i16]| {
+        let body = serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode(wav_bytes(samples))
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1beta1/text:synthesize")
+                .body_contains(format!(r#""turns":[{{"speaker":"{speaker}""#));
+            then.status(200).json_body_obj(&body);
+        })
+    };
+    let host = turn("R", &[1, 1]);
+    let guest = turn("S", &[5, 5, 5]);
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("episode.wav");
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--no-history",
+            "--json",
+            "--tracks",
+            "--dialogue",
+            "R: Welcome back.\nS: Glad to be here.\nR: Let's start.",
+        ])
+        .arg(&output);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(r#""tracks":{"R":"#));
+    host.assert_hits(2);
+    guest.assert_hits(1);
+
+    // Each turn in its own request, so each speaker can be kept apart
+    assert_eq!(read_file(&output), wav_bytes(&[1, 1, 5, 5, 5, 1, 1]));
+    assert_eq!(
+        read_file(&dir.path().join("episode.R.wav")),
+        wav_bytes(&[1, 1, 0, 0, 0, 1, 1])
+    );
+    assert_eq!(
+        read_file(&dir.path().join("episode.S.wav")),
+        wav_bytes(&[0, 0, 5, 5, 5, 0, 0])
+    );
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.args(["--tracks", "Just one voice."])
+        .arg(dir.path().join("solo.wav"));
+    cmd.assert().code(2).stderr(predicate::str::contains(
+        "use them with dialogue or voice markup",
+    ));
+}
+
+#[test]
+fn split_document_writes_one_file_per_section() {
+    let server = MockServer::start();
+    let section = |input: &str| {
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/text:synthesize")
+                .body_contains(input);
+            then.status(200).json_body_obj(&serde_json::json!({
+                "audioContent": base64::engine::general_purpose::STANDARD.encode(wav_bytes(&[1]))
+            }));
+        })
+    };
+    let intro = section(r#""text":"Welcome to the course.""#);
+    let outline = section(r#""text":"Today: variables.\nThen loops.""#);
+    let speak_a =
+        section(r#""ssml":"<speak>Slide one. <break time=\"300ms\"/> Still one.</speak>""#);
+    let speak_b = section(r#""ssml":"<speak>Slide two.</speak>""#);
+    let dir = tempdir().unwrap();
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .arg("--no-history")
+            .args(args)
+            .arg(dir.path().join("course.wav"));
+        cmd.assert()
+    };
+
+    run(&[
+        "--split-on-marker",
+        "---",
+        "Welcome to the course.\n---\nToday: variables.\nThen loops.\n ---\n",
+    ])
+    .success();
+    intro.assert();
+    outline.assert();
+    assert!(dir.path().join("course-1.wav").exists());
+    assert!(dir.path().join("course-2.wav").exists());
+    assert!(!dir.path().join("course.wav").exists());
+
+    // Only breaks of at least a second cut; each section keeps <speak>
+    run(&[
+        "--ssml",
+        "--split-on-break",
+        "1s",
+        "--name-template",
+        "{n}-{slug}.{ext}",
+        "<speak>Slide one. <break time=\"300ms\"/> Still one. <break time=\"2s\"/> Slide two.</speak>",
+    ])
+    .success();
+    speak_a.assert();
+    speak_b.assert();
+    assert!(dir.path().join("1-slide-one-still-one.wav").exists());
+    assert!(dir.path().join("2-slide-two.wav").exists());
+
+    // A cut inside another element would leave it unclosed
+    run(&[
+        "--ssml",
+        "--split-on-break",
+        "1s",
+        "<speak><prosody rate=\"slow\">A <break time=\"2s\"/> B</prosody></speak>",
+    ])
+    .code(2)
+    .stderr(predicate::str::contains(
+        "section 1 is not valid SSML on its own",
+    ));
+}
+
+#[test]
+fn cover_art_is_embedded_in_the_id3_tag() {
+    // The provider's own ID3v2.4 tag, with an encoder frame, then the audio
+    let encoder = b"TSSE\x00\x00\x00\x09\x00\x00\x00Lavf60.0";
+    let mut provider_mp3 = b"ID3\x04\x00\x00\x00\x00\x00\x13".to_vec();
+    provider_mp3.extend_from_slice(encoder);
+    provider_mp3.extend_from_slice(b"MP3AUDIO");
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode(&provider_mp3)
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let cover = dir.path().join("cover.png");
+    fs::write(&cover, b"\x89PNG\r\n\x1a\nIMAGE").unwrap();
+    let run = |encoding: &str, output: &str| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(["--no-history", "--encoding", encoding, "--cover"])
+            .arg(&cover)
+            .arg("Episode one.")
+            .arg(dir.path().join(output));
+        cmd.assert()
+    };
+
+    run("MP3", "episode.mp3").success();
+    synth_mock.assert();
+    let apic = b"APIC\x00\x00\x00\x1a\x00\x00\x00image/png\x00\x03\x00\x89PNG\r\n\x1a\nIMAGE";
+    let mut expected = b"ID3\x04\x00\x00\x00\x00\x00\x37".to_vec();
+    expected.extend_from_slice(encoder);
+    expected.extend_from_slice(apic);
+    expected.extend_from_slice(b"MP3AUDIO");
+    assert_eq!(read_file(&dir.path().join("episode.mp3")), expected);
+
+    run("LINEAR16", "episode.wav")
+        .code(2)
+        .stderr(predicate::str::contains("use --encoding MP3"));
+    synth_mock.assert_hits(1);
+}
+
+#[cfg(unix)]
+#[test]
+fn opus_settings_are_sent_natively_or_re_encoded() {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let dir = tempdir().unwrap();
+    // Stands in for ffmpeg: records its arguments and writes the output file
+    let ffmpeg = dir.path().join("ffmpeg");
+    fs::write(
+        &ffmpeg,
+        format!(
+            "#!/bin/sh\necho \"$@\" > {}\nfor last; do :; done\nprintf REENCODED > \"$last\"\n",
+            dir.path().join("ffmpeg-args").display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&ffmpeg, fs::Permissions::from_mode(0o755)).unwrap();
+
+    // Google has no Opus settings, so its audio is re-encoded
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("OGGDATA")
+        }));
+    });
+    let output = dir.path().join("prompt.ogg");
+    Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env("FAST_TTS_FFMPEG", &ffmpeg)
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--no-history",
+            "--encoding",
+            "OGG_OPUS",
+            "--bitrate",
+            "24k",
+            "--vbr",
+            "constrained",
+            "--application",
+            "voip",
+            "Press one for sales.",
+        ])
+        .arg(&output)
+        .assert()
+        .success();
+    synth_mock.assert();
+    assert_eq!(read_file(&output), b"REENCODED");
+    let args = fs::read_to_string(dir.path().join("ffmpeg-args")).unwrap();
+    assert!(
+        args.contains("-c:a libopus -b:a 24000 -vbr constrained -application voip -f ogg"),
+        "{args}"
+    );
+
+    // Deepgram takes the bitrate in the request; no re-encoding
+    let cassette = dir.path().join("cassette.json");
+    fs::write(
+        &cassette,
+        serde_json::json!({"interactions": [{
+            "method": "POST",
+            "url": "https://api.deepgram.com/v1/speak?model=aura-asteria-en&voice=aura-asteria-en&format=opus&bit_rate=24000",
+            "status": 200,
+            "body": base64::engine::general_purpose::STANDARD.encode("DEEPGRAMOGG")
+        }]})
+        .to_string(),
+    )
+    .unwrap();
+    Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .env("DEEPGRAM_API_KEY", "dg-key")
+        .env("FAST_TTS_FFMPEG", dir.path().join("missing-ffmpeg"))
+        .args([
+            "--no-history",
+            "--provider",
+            "deepgram",
+            "--encoding",
+            "OGG_OPUS",
+            "--bitrate",
+            "24k",
+            "--replay",
+        ])
+        .arg(&cassette)
+        .arg("Press one for sales.")
+        .arg(&output)
+        .assert()
+        .success();
+    assert_eq!(read_file(&output), b"DEEPGRAMOGG");
+
+    Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .args(["--bitrate", "24k", "Press one for sales."])
+        .arg(dir.path().join("prompt.wav"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "apply to OGG_OPUS output, not LINEAR16",
+        ));
+}
+
+#[test]
+fn lmnt_speech_is_decoded_and_its_voices_listed() {
+    let dir = tempdir().unwrap();
+    let encode = |data: &[u8]| base64::engine::general_purpose::STANDARD.encode(data);
+    let speech = serde_json::json!({"audio": encode(b"LMNTMP3"), "seed": 7});
+    let voices = serde_json::json!([
+        {"id": "lily", "name": "Lily", "owner": "system", "gender": "F"},
+        {"id": "my-clone", "name": "Mine", "owner": "me", "gender": "M"}
+    ]);
+    let cassette = dir.path().join("cassette.json");
+    fs::write(
+        &cassette,
+        serde_json::json!({"interactions": [
+            {
+                "method": "POST",
+                "url": "https://api.lmnt.com/v1/ai/speech",
+                "status": 200,
+                "body": encode(speech.to_string().as_bytes())
+            },
+            {
+                "method": "GET",
+                "url": "https://api.lmnt.com/v1/ai/voice/list",
+                "status": 200,
+                "body": encode(voices.to_string().as_bytes())
+            }
+        ]})
+        .to_string(),
+    )
+    .unwrap();
+
+    let output = dir.path().join("hello.mp3");
+    Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .env("LMNT_API_KEY", "lmnt-key")
+        .args([
+            "--no-history",
+            "--provider",
+            "lmnt",
+            "--encoding",
+            "MP3",
+            "--rate",
+            "1.5",
+            "--replay",
+        ])
+        .arg(&cassette)
+        .arg("Hello there")
+        .arg(&output)
+        .assert()
+        .success();
+    assert_eq!(read_file(&output), b"LMNTMP3");
+
+    let out = Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .env("LMNT_API_KEY", "lmnt-key")
+        .env("FAST_TTS_CACHE_DIR", dir.path().join("cache"))
+        .args(["--provider", "lmnt", "--list-voices", "--json", "--replay"])
+        .arg(&cassette)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let listed: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(listed["voices"][1]["name"], "my-clone");
+    assert_eq!(listed["voices"][1]["ssmlGender"], "MALE");
+
+    // LMNT's speed runs from 0.25 to 2
+    Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .env("LMNT_API_KEY", "lmnt-key")
+        .args(["--provider", "lmnt", "--rate", "3", "Hello there"])
+        .arg(dir.path().join("fast.wav"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("rate"));
+}
+
+#[test]
+fn output_extension_is_inferred_from_encoding() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.current_dir(dir.path())
+            .env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(["--encoding", "MP3"])
+            .args(args)
+            .assert()
+    };
+
+    run(&["hello", "wrong.wav"])
+        .code(2)
+        .stderr(predicate::str::contains("does not match encoding"));
+    run(&["--auto-extension", "hello", "wrong.wav"]).success();
+    run(&["--auto-extension", "hello", "take.2"]).success();
+    run(&["--output-dir", "out", "hello", "intro"]).success();
+
+    assert_eq!(read_file(&dir.path().join("wrong.mp3")), b"MP3DATA");
+    assert!(dir.path().join("take.2.mp3").exists());
+    assert!(dir.path().join("out/intro.mp3").exists());
+    assert!(!dir.path().join("wrong.wav").exists());
+}
+
+#[test]
+fn rejected_voice_suggests_names_from_cached_catalog() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/v1/voices");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "voices": [
+              {"name": "en-US-Neural2-F", "languageCodes": ["en-US"], "ssmlGender": "FEMALE", "naturalSampleRateHertz": 24000},
+              {"name": "en-GB-Neural2-A", "languageCodes": ["en-GB"], "ssmlGender": "FEMALE", "naturalSampleRateHertz": 24000}
+            ]
+        }));
+    });
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(400).json_body_obj(&serde_json::json!({
+            "error": {"code": 400, "message": "Voice 'en-US-Neural-F' does not exist."}
+        }));
+    });
+    let cache = tempdir().unwrap();
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "fake")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env("FAST_TTS_CACHE_DIR", cache.path())
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(args);
+        cmd.assert()
+    };
+
+    // Without a cached catalog the provider's error stands
+    run(&["--voice", "en-US-Neural-F", "hi", "out.wav"])
+        .code(5)
+        .stderr(predicate::str::contains("did you mean").not());
+    run(&["--list-voices"]).success();
+    run(&["--voice", "en-US-Neural-F", "hi", "out.wav"])
+        .code(2)
+        .stderr(predicate::str::contains(
+            "unknown voice 'en-US-Neural-F' for provider google; did you mean 'en-US-Neural2-F'?",
+        ));
+}
+
+#[test]
+fn announce_speaks_each_stdin_line() {
+    let server = MockServer::start();
+    let audio = serde_json::json!({
+        "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+    });
+    let lines: Vec<_> = ["Build started", "Tests passed"]
+        .into_iter()
+        .map(|line| {
+            server.mock(|when, then| {
+                when.method(POST)
+                    .path("/v1/text:synthesize")
+                    .body_contains(format!("\"text\":\"{line}\""));
+                then.status(200).json_body_obj(&audio);
+            })
+        })
+        .collect();
+
+    let mut cmd = assert_cmd::Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env("PATH", "")
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["--encoding", "MP3", "announce"])
+        .write_stdin("Build started\n\n  Tests passed  \n");
+    // No player on an empty PATH: each line is synthesized, then warned about
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("playback failed").count(2));
+
+    for line in lines {
+        line.assert_hits(1);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn writes_into_existing_fifo() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let fifo = dir.path().join("live.pipe");
+    assert!(
+        Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap()
+            .success()
+    );
+    let reader = |fifo: std::path::PathBuf| std::thread::spawn(move || fs::read(fifo).unwrap());
+    let cli = || {
+        let mut cmd = assert_cmd::Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(["--encoding", "MP3"]);
+        cmd
+    };
+
+    // The pipe's name needn't match the encoding
+    let received = reader(fifo.clone());
+    cli().arg("hello").arg(&fifo).assert().success();
+    assert_eq!(received.join().unwrap(), b"MP3DATA");
+
+    // announce keeps the pipe open across lines: one reader sees both
+    let received = reader(fifo.clone());
+    cli()
+        .arg("announce")
+        .arg("--output")
+        .arg(&fifo)
+        .write_stdin("one\ntwo\n")
+        .assert()
+        .success();
+    assert_eq!(received.join().unwrap(), b"MP3DATAMP3DATA");
+    assert!(fifo.exists());
+}
+
+#[test]
+fn ssml_file_is_decoded_and_checked() {
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .body_contains(r#""input":{"ssml":"<speak>Fish &amp; chips</speak>"}"#);
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("WAVDATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let run = |ssml: &[u8]| {
+        let input = dir.path().join("input.xml");
+        fs::write(&input, ssml).unwrap();
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .arg("--no-history")
+            .arg("--ssml-file")
+            .arg(&input)
+            .arg(dir.path().join("out.wav"));
+        cmd.assert()
+    };
+
+    // A UTF-8 BOM and the XML declaration are dropped
+    run(b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<speak>Fish &amp; chips</speak>")
+        .success();
+    // UTF-16 (as saved by some Windows editors) is decoded
+    let utf16: Vec<u8> = [0xFF, 0xFE]
+        .into_iter()
+        .chain(
+            "<speak>Fish &amp; chips</speak>"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        )
+        .collect();
+    run(&utf16).success();
+    synth_mock.assert_hits(2);
+
+    run(b"<speak>Fish & chips</speak>")
+        .code(2)
+        .stderr(predicate::str::contains(
+            "line 1: write a literal & as &amp;",
+        ));
+    run(b"<speak>\nFish&nbsp;chips</speak>")
+        .code(2)
+        .stderr(predicate::str::contains("line 2: unknown entity &nbsp;"));
+    run(b"<speak>caf\xE9</speak>")
+        .code(2)
+        .stderr(predicate::str::contains("not valid UTF-8 (byte 10)"));
+    synth_mock.assert_hits(2);
+}
+
+#[test]
+fn azure_ssml_gets_one_speak_and_voice_wrapper() {
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/cognitiveservices/v1")
+            .header("Ocp-Apim-Subscription-Key", "azure-key")
+            .body(
+                "<speak version=\"1.0\" xml:lang=\"en-GB\" xmlns=\"http://www.w3.org/2001/10/synthesis\">\
+                 <voice name=\"en-GB-LibbyNeural\">Hello <break time=\"500ms\"/> there</voice></speak>",
+            );
+        then.status(200).body("RIFFDATA");
+    });
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("input.xml");
+    fs::write(
+        &input,
+        "<speak version=\"1.0\" xml:lang=\"en-GB\">Hello <break time=\"500ms\"/> there</speak>\n",
+    )
+    .unwrap();
+    Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .env("AZURE_SPEECH_KEY", "azure-key")
+        .env("AZURE_SPEECH_AUTH", "key")
+        .env("AZURE_SPEECH_ENDPOINT", server.base_url())
+        .env_remove("AZURE_SPEECH_REGION")
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--no-history",
+            "--provider",
+            "azure",
+            "--voice",
+            "en-GB-LibbyNeural",
+        ])
+        .arg("--ssml-file")
+        .arg(&input)
+        .arg(dir.path().join("out.wav"))
+        .assert()
+        .success();
+    synth_mock.assert();
+}
+
+fn azure_command(server: &MockServer) -> Command {
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("AZURE_SPEECH_KEY", "azure-key")
+        .env("AZURE_SPEECH_AUTH", "key")
+        .env("AZURE_SPEECH_ENDPOINT", server.base_url())
+        .env_remove("AZURE_SPEECH_REGION")
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args([
+            "--no-history",
+            "--provider",
+            "azure",
+            "--voice",
+            "en-GB-LibbyNeural",
+        ]);
+    cmd
+}
+
+#[test]
+fn azure_exchanges_its_key_for_a_cached_bearer_token() {
+    let dir = tempdir().unwrap();
+    let encode = |data: &[u8]| base64::engine::general_purpose::STANDARD.encode(data);
+    let issue_token = serde_json::json!({
+        "method": "POST",
+        "url": "https://westus.api.cognitive.microsoft.com/sts/v1.0/issueToken",
+        "status": 200,
+        "body": encode(b"azure-bearer")
+    });
+    let speech = serde_json::json!({
+        "method": "POST",
+        "url": "https://westus.tts.speech.microsoft.com/cognitiveservices/v1",
+        "status": 200,
+        "body": encode(b"RIFFDATA")
+    });
+    let with_exchange = dir.path().join("with-exchange.json");
+    fs::write(
+        &with_exchange,
+        serde_json::json!({"interactions": [issue_token, speech]}).to_string(),
+    )
+    .unwrap();
+    let speech_only = dir.path().join("speech-only.json");
+    fs::write(
+        &speech_only,
+        serde_json::json!({"interactions": [speech]}).to_string(),
+    )
+    .unwrap();
+    let synthesize = |cassette: &std::path::Path, cache: &str, auth: Option<&str>| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("AZURE_SPEECH_KEY", "azure-key")
+            .env("AZURE_SPEECH_REGION", "westus")
+            .env("FAST_TTS_CACHE_DIR", dir.path().join(cache))
+            .env_remove("AZURE_SPEECH_ENDPOINT")
+            .env_remove("AZURE_SPEECH_AUTH")
+            .args(["--no-history", "--provider", "azure", "--replay"])
+            .arg(cassette)
+            .arg("hello")
+            .arg(dir.path().join("out.wav"));
+        if let Some(auth) = auth {
+            cmd.env("AZURE_SPEECH_AUTH", auth);
+        }
+        cmd.assert()
+    };
+
+    // By default the key is exchanged at issueToken...
+    synthesize(&speech_only, "fresh", None)
+        .failure()
+        .stderr(predicate::str::contains("issueToken"));
+    synthesize(&with_exchange, "cache", None).success();
+    let cached = fs::read_to_string(dir.path().join("cache/tokens/azure.json")).unwrap();
+    let cached: serde_json::Value = serde_json::from_str(&cached).unwrap();
+    assert_eq!(cached["accessToken"], "azure-bearer");
+    // ...and the token is cached under a stable fingerprint of the key, never the key itself
+    assert_eq!(cached["source"], "westus:d832c76cffcd0597");
+    synthesize(&speech_only, "cache", None).success();
+
+    // AZURE_SPEECH_AUTH=key sends the key with every request instead
+    synthesize(&speech_only, "opt-out", Some("key")).success();
+    assert!(!dir.path().join("opt-out/tokens/azure.json").exists());
+}
+
+#[test]
+fn azure_escapes_plain_text_but_passes_ssml_through() {
+    let server = MockServer::start();
+    let plain_mock = server.mock(|when, then| {
+        when.method(POST).path("/cognitiveservices/v1").body(
+            "<speak version=\"1.0\" xml:lang=\"en-GB\"><voice xml:lang=\"en-GB\" name=\"en-GB-LibbyNeural\">\
+             Fish &amp; chips &lt;b&gt; &quot;now&quot;</voice></speak>",
+        );
+        then.status(200).body("RIFFDATA");
+    });
+    let ssml_mock = server.mock(|when, then| {
+        when.method(POST).path("/cognitiveservices/v1").body(
+            "<speak version=\"1.0\" xmlns=\"http://www.w3.org/2001/10/synthesis\" xml:lang=\"en-GB\">\
+             <voice name=\"en-GB-LibbyNeural\">Hi <emphasis>there</emphasis> &amp; bye</voice></speak>",
+        );
+        then.status(200).body("RIFFDATA");
+    });
+    let dir = tempdir().unwrap();
+
+    // Plain text is spoken literally, markup characters included
+    azure_command(&server)
+        .arg("Fish & chips <b> \"now\"\u{7}")
+        .arg(dir.path().join("plain.wav"))
+        .assert()
+        .success();
+    plain_mock.assert();
+
+    // SSML reaches Azure as markup, not as escaped text
+    azure_command(&server)
+        .arg("--ssml")
+        .arg("<speak>Hi <emphasis>there</emphasis> &amp; bye</speak>")
+        .arg(dir.path().join("ssml.wav"))
+        .assert()
+        .success();
+    ssml_mock.assert();
+
+    // Malformed SSML is refused before any request is sent
+    for (ssml, message) in [
+        (
+            "<speak>Hi <emphasis>there</speak>",
+            "</speak> closes <emphasis>",
+        ),
+        ("<speak>Hi there", "<speak> from line 1 is never closed"),
+        ("<speak>1 < 2</speak>", "write a literal < as &lt;"),
+    ] {
+        azure_command(&server)
+            .arg("--ssml")
+            .arg(ssml)
+            .arg(dir.path().join("bad.wav"))
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(message));
+    }
+    plain_mock.assert_hits(1);
+    ssml_mock.assert_hits(1);
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_c_cancels_synthesis_without_partial_output() {
+    let server = MockServer::start();
+    let slow = server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200)
+            .delay(std::time::Duration::from_secs(10))
+            .json_body_obj(&serde_json::json!({
+                "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+            }));
+    });
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("hello.mp3");
+    let child = Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", server.base_url())
+        .env("FAST_TTS_HISTORY", dir.path().join("history.jsonl"))
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["--json", "--retries", "0", "--encoding", "MP3", "hello"])
+        .arg(&out)
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Interrupt once the request is in flight
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while slow.hits() == 0 {
+        assert!(std::time::Instant::now() < deadline, "request never sent");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    let started = std::time::Instant::now();
+    let sent = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(sent.success());
+    let output = child.wait_with_output().unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert_eq!(output.status.code(), Some(130));
+    let report: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(report["error"]["kind"], "cancelled");
+    assert!(!out.exists());
+}
+
+#[cfg(all(unix, feature = "notify"))]
+#[test]
+fn notify_reports_finished_and_failed_jobs() {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let log = dir.path().join("notifications.log");
+    let notifier = dir.path().join("notifier");
+    fs::write(
+        &notifier,
+        format!(
+            "#!/bin/sh\nprintf '%s|%s\\n' \"$1\" \"$2\" >> {}\n",
+            log.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&notifier, fs::Permissions::from_mode(0o755)).unwrap();
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env("FAST_TTS_HISTORY", dir.path().join("history.jsonl"))
+            .env("FAST_TTS_NOTIFIER", &notifier)
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .arg("--notify")
+            .args(args);
+        cmd.assert()
+    };
+
+    let out = dir.path().join("hello.mp3");
+    run(&["--encoding", "MP3", "hello", out.to_str().unwrap()]).success();
+    let bad = dir.path().join("bad.mp3");
+    run(&["--encoding", "LINEAR16", "hello", bad.to_str().unwrap()]).failure();
+    // Commands that aren't synthesis jobs don't notify
+    run(&["encodings"]).success();
+
+    let lines: Vec<String> = fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(String::from)
+        .collect();
+    assert_eq!(lines.len(), 2, "{lines:?}");
+    assert!(
+        lines[0].starts_with("fast-tts finished|hello.mp3 done in"),
+        "{}",
+        lines[0]
+    );
+    assert!(
+        lines[1].starts_with("fast-tts failed|bad.mp3: ") && lines[1].contains("encoding"),
+        "{}",
+        lines[1]
+    );
+}
+
+#[cfg(all(unix, feature = "clipboard"))]
+#[test]
+fn clip_watch_saves_each_new_copy() {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let server = MockServer::start();
+    let copied = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .json_body_partial(r#"{"input": {"text": "a sentence copied later"}}"#);
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    let current = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .json_body_partial(r#"{"input": {"text": "already on the clipboard"}}"#);
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let contents = dir.path().join("clipboard.txt");
+    fs::write(&contents, "already on the clipboard").unwrap();
+    let paste = dir.path().join("paste");
+    fs::write(&paste, format!("#!/bin/sh\ncat {}\n", contents.display())).unwrap();
+    fs::set_permissions(&paste, fs::Permissions::from_mode(0o755)).unwrap();
+    let clip = |out_dir: &std::path::Path| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.env("FAST_TTS_TOKEN", "test-token")
+            .env("FAST_TTS_BASE_URL", server.base_url())
+            .env("FAST_TTS_CLIPBOARD", &paste)
+            .env_remove("HTTP_PROXY")
+            .env_remove("HTTPS_PROXY")
+            .env_remove("http_proxy")
+            .env_remove("https_proxy")
+            .args(["--encoding", "MP3", "clip", "--out-dir"])
+            .arg(out_dir);
+        cmd
+    };
+    let files = |out_dir: &std::path::Path| {
+        fs::read_dir(out_dir)
+            .map(|entries| entries.count())
+            .unwrap_or_default()
+    };
+
+    // Without --watch, the current text is read once
+    let once = dir.path().join("once");
+    clip(&once).assert().success();
+    assert_eq!(files(&once), 1);
+    current.assert();
+
+    // With --watch, only later copies long enough to count are synthesized
+    let watched = dir.path().join("watched");
+    let mut child = clip(&watched)
+        .args(["--watch", "--interval", "50", "--min-chars", "10"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    // Renamed into place, so the fake paste never sees a half-written copy
+    let copy = |text: &str| {
+        let staged = dir.path().join("staged.txt");
+        fs::write(&staged, text).unwrap();
+        fs::rename(&staged, &contents).unwrap();
+    };
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    copy("short");
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    copy("a sentence copied later");
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while files(&watched) == 0 {
+        assert!(std::time::Instant::now() < deadline, "copy never saved");
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    let sent = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(sent.success());
+    assert!(child.wait().unwrap().success());
+    assert_eq!(files(&watched), 1);
+    copied.assert_hits(1);
+    current.assert_hits(1);
+}
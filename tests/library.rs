@@ -0,0 +1,217 @@
+use base64::Engine as _;
+use httpmock::prelude::*;
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn library_synthesizes_and_lists_voices() {
+    let server = MockServer::start();
+    let synth_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .header("authorization", "Bearer test-token")
+            .json_body_partial(
+                r#"{"input": {"text": "hello"}, "audioConfig": {"audioEncoding": "MP3"}}"#,
+            );
+        then.status(200).json_body_obj(&serde_json::json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode("MP3DATA")
+        }));
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/v1/voices");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "voices": [{"name": "en-US-Neural2-F", "languageCodes": ["en-US"], "ssmlGender": "FEMALE"}]
+        }));
+    });
+    let dir = tempdir().unwrap();
+    // SAFETY: this test binary has a single test, so nothing reads the environment concurrently
+    unsafe {
+        std::env::set_var("FAST_TTS_TOKEN", "test-token");
+        std::env::set_var("FAST_TTS_BASE_URL", server.base_url());
+        std::env::set_var("FAST_TTS_CACHE_DIR", dir.path());
+        for var in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+            std::env::remove_var(var);
+        }
+    }
+
+    let out = dir.path().join("hello.mp3");
+    let request = fast_tts::SynthesisRequest::builder()
+        .text("hello")
+        .output(&out)
+        .encoding(fast_tts::AudioEncoding::Mp3)
+        .build()
+        .unwrap();
+    fast_tts::synthesize(&request).await.unwrap();
+    assert_eq!(std::fs::read(&out).unwrap(), b"MP3DATA");
+    synth_mock.assert();
+
+    use futures::StreamExt as _;
+    let streamed: Vec<u8> = fast_tts::synthesize_stream(&request)
+        .await
+        .unwrap()
+        .map(|chunk| chunk.unwrap().to_vec())
+        .concat()
+        .await;
+    assert_eq!(streamed, b"MP3DATA");
+
+    let voices = fast_tts::list_voices().await.unwrap();
+    assert_eq!(voices.voices[0].name, "en-US-Neural2-F");
+
+    // Builder validation rejects bad ranges and encodings before any request is sent
+    let invalid = |builder: fast_tts::SynthesisRequestBuilder| {
+        let err = builder.build().unwrap_err();
+        fast_tts::FastTtsError::classify(&err).exit_code()
+    };
+    let base = || {
+        fast_tts::SynthesisRequest::builder()
+            .text("hi")
+            .output("hi.wav")
+    };
+    assert_eq!(invalid(base().rate(5.0)), 2);
+    assert_eq!(invalid(base().pitch(-21.0)), 2);
+    let message = base().rate(5.0).build().unwrap_err().to_string();
+    assert!(
+        message.contains("--rate 5") && message.contains("0.25 to 4"),
+        "{message}"
+    );
+    // Ranges and effects profiles come from the provider's capabilities
+    assert_eq!(invalid(base().effects_profile_id(vec!["studio".into()])), 2);
+    assert!(
+        base()
+            .effects_profile_id(vec!["headphone-class-device".into()])
+            .build()
+            .is_ok()
+    );
+    let message = base()
+        .effects_profile_id(vec!["headphone-clas-device".into()])
+        .build()
+        .unwrap_err()
+        .to_string();
+    assert!(
+        message.contains("did you mean 'headphone-class-device'?"),
+        "{message}"
+    );
+    assert_eq!(
+        invalid(
+            base()
+                .provider(fast_tts::Provider::Openai)
+                .output("hi.mp3")
+                .encoding(fast_tts::AudioEncoding::Mp3)
+                .pitch(2.0)
+        ),
+        2
+    );
+    assert_eq!(invalid(base().output("hi.mp3")), 2);
+    assert_eq!(
+        invalid(
+            base()
+                .provider(fast_tts::Provider::Openai)
+                .encoding(fast_tts::AudioEncoding::Mulaw)
+        ),
+        2
+    );
+    // Capabilities drive the SSML and input-length checks
+    assert_eq!(
+        invalid(
+            base()
+                .provider(fast_tts::Provider::Openai)
+                .output("hi.mp3")
+                .encoding(fast_tts::AudioEncoding::Mp3)
+                .ssml(true)
+        ),
+        2
+    );
+    assert_eq!(invalid(base().text("a".repeat(5001))), 2);
+    // Seeds only go to providers that honor them, and change the render's identity
+    assert_eq!(invalid(base().seed(Some(7))), 2);
+    let seeded = |seed| {
+        fast_tts::SynthesisRequest::builder()
+            .provider(fast_tts::Provider::Elevenlabs)
+            .text("hi")
+            .output("hi.mp3")
+            .encoding(fast_tts::AudioEncoding::Mp3)
+            .seed(seed)
+            .build()
+            .unwrap()
+            .params_hash()
+    };
+    assert_eq!(seeded(Some(7)), seeded(Some(7)));
+    assert_ne!(seeded(Some(7)), seeded(Some(8)));
+    assert_ne!(seeded(Some(7)), seeded(None));
+    let request = base().build().unwrap();
+    assert_eq!(
+        request.idempotency_key(),
+        format!("fast-tts-{}", request.params_hash())
+    );
+    let google = fast_tts::Provider::Google.capabilities();
+    assert_eq!(google.ssml_dialect, Some("google"));
+    assert!(google.timestamps && !google.streaming);
+    assert!(fast_tts::Provider::Openai.capabilities().streaming);
+
+    // A cancelled token stops synthesis without leaving a file behind
+    let cancel = fast_tts::CancellationToken::new();
+    cancel.cancel();
+    let cancelled_out = dir.path().join("cancelled.mp3");
+    let request = fast_tts::SynthesisRequest::builder()
+        .text("hello")
+        .output(&cancelled_out)
+        .encoding(fast_tts::AudioEncoding::Mp3)
+        .cancellation(cancel)
+        .build()
+        .unwrap();
+    let err = fast_tts::synthesize(&request).await.unwrap_err();
+    assert_eq!(fast_tts::FastTtsError::classify(&err).exit_code(), 130);
+    assert!(!cancelled_out.exists());
+
+    // Bulk runs report each item and, with keep_going, finish despite failures
+    server.mock(|when, then| {
+        when.method(POST)
+            .path("/v1/text:synthesize")
+            .json_body_partial(r#"{"input": {"text": "boom"}}"#);
+        then.status(400).body("bad input");
+    });
+    let cfg = dir.path().join("bulk.yaml");
+    std::fs::write(
+        &cfg,
+        format!(
+            "defaults: {{encoding: MP3}}\nitems:\n  - {{text: boom, output: {0}/a.mp3}}\n  - {{text: hello, output: {0}/b.mp3}}\n",
+            dir.path().display()
+        ),
+    )
+    .unwrap();
+    let options = fast_tts::BulkOptions {
+        keep_going: true,
+        retries: 0,
+        ..Default::default()
+    };
+    let mut events = Vec::new();
+    let err = fast_tts::run_bulk_from_config(&cfg, &options, |event| {
+        events.push(match event {
+            fast_tts::BulkEvent::Planned { items, chars, .. } => format!("planned {items} {chars}"),
+            fast_tts::BulkEvent::Started { index, .. } => format!("started {index}"),
+            fast_tts::BulkEvent::Written { index, bytes, .. } => format!("written {index} {bytes}"),
+            fast_tts::BulkEvent::Skipped { index, .. } => format!("skipped {index}"),
+            fast_tts::BulkEvent::Failed { index, .. } => format!("failed {index}"),
+        })
+    })
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("1 bulk item(s) failed"), "{err}");
+    assert_eq!(
+        events,
+        [
+            "planned 2 9",
+            "started 1",
+            "failed 1",
+            "started 2",
+            "written 2 7"
+        ]
+    );
+    assert_eq!(std::fs::read(dir.path().join("b.mp3")).unwrap(), b"MP3DATA");
+
+    let openai = fast_tts::providers::get(fast_tts::Provider::Openai);
+    assert_eq!(openai.capabilities().default_voice, "alloy");
+    let voices = openai.list_voices().await.unwrap();
+    assert!(voices.voices.iter().any(|v| v.name == "alloy"));
+    let gemini = fast_tts::providers::get(fast_tts::Provider::Gemini);
+    assert!(gemini.list_voices().await.is_err());
+}
@@ -0,0 +1,907 @@
+#![cfg(feature = "mcp")]
+
+use assert_cmd::prelude::*;
+use base64::Engine as _;
+use httpmock::prelude::*;
+use serde_json::{Value, json};
+use std::io::Write as _;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+fn mcp_command(google: &MockServer) -> Command {
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_TOKEN", "test-token")
+        .env("FAST_TTS_BASE_URL", google.base_url())
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy");
+    cmd
+}
+
+fn mock_synthesize<'a>(google: &'a MockServer, audio: &str) -> httpmock::Mock<'a> {
+    google.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200).json_body_obj(&json!({
+            "audioContent": base64::engine::general_purpose::STANDARD.encode(audio)
+        }));
+    })
+}
+
+fn call(id: u64, tool: &str, arguments: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": {"name": tool, "arguments": arguments},
+    })
+}
+
+/// Send `messages` to a stdio server, each after its delay, then close its
+/// input; the responses it wrote.
+fn stdio_session(mut cmd: Command, messages: &[(Duration, Value)]) -> Vec<Value> {
+    let mut child = cmd
+        .args(["--mcp-mode", "stdio"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for (delay, message) in messages {
+        std::thread::sleep(*delay);
+        writeln!(stdin, "{message}").unwrap();
+    }
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn response(responses: &[Value], id: u64) -> &Value {
+    responses
+        .iter()
+        .find(|r| r["id"] == id)
+        .unwrap_or_else(|| panic!("no response {id} in {responses:?}"))
+}
+
+#[test]
+fn synthesize_returns_audio_inline() {
+    let google = MockServer::start();
+    let synth = mock_synthesize(&google, "MP3DATA");
+
+    let responses = stdio_session(
+        mcp_command(&google),
+        &[(
+            Duration::ZERO,
+            call(
+                1,
+                "synthesize",
+                json!({"text": "hello", "encoding": "MP3", "returnAudio": true}),
+            ),
+        )],
+    );
+    let result = &response(&responses, 1)["result"];
+    assert_eq!(result["isError"], false);
+    let resource = &result["content"][1]["resource"];
+    assert_eq!(resource["mimeType"], "audio/mpeg");
+    let audio = base64::engine::general_purpose::STANDARD
+        .decode(resource["blob"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(audio, b"MP3DATA");
+    synth.assert();
+}
+
+/// Text of content item `index` of tool response `id`.
+fn tool_text(responses: &[Value], id: u64, index: usize) -> &str {
+    response(responses, id)["result"]["content"][index]["text"]
+        .as_str()
+        .unwrap()
+}
+
+#[test]
+fn synthesize_uses_the_requested_provider() {
+    let google = MockServer::start();
+    let custom = MockServer::start();
+    let speak = custom.mock(|when, then| {
+        when.method(POST)
+            .path("/speak")
+            .header("authorization", "Bearer local-secret")
+            .json_body_obj(&json!({"input": "hello"}));
+        then.status(200).body("CUSTOMMP3");
+    });
+    let dir = tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("local.toml"),
+        format!(
+            "url = \"{}/speak\"\n\
+             auth_env = \"LOCAL_TTS_KEY\"\n\
+             [headers]\nAuthorization = \"Bearer {{api_key}}\"\n\
+             [body]\ninput = \"{{text}}\"\n\
+             [formats]\nMP3 = \"mp3\"\n",
+            custom.base_url()
+        ),
+    )
+    .unwrap();
+    let out = dir.path().join("out.mp3");
+
+    let mut cmd = mcp_command(&google);
+    cmd.env("FAST_TTS_PROVIDERS_DIR", dir.path())
+        .env("LOCAL_TTS_KEY", "local-secret");
+    let responses = stdio_session(
+        cmd,
+        &[(
+            Duration::ZERO,
+            call(
+                1,
+                "synthesize",
+                json!({
+                    "text": "hello",
+                    "output": out,
+                    "encoding": "MP3",
+                    "provider": "custom:local",
+                }),
+            ),
+        )],
+    );
+    assert_eq!(response(&responses, 1)["result"]["isError"], false);
+    assert_eq!(std::fs::read(&out).unwrap(), b"CUSTOMMP3");
+    speak.assert();
+
+    // An unknown provider is the caller's mistake, reported as such
+    let responses = stdio_session(
+        mcp_command(&google),
+        &[(
+            Duration::ZERO,
+            call(
+                1,
+                "synthesize",
+                json!({"text": "hi", "output": out, "provider": "nope"}),
+            ),
+        )],
+    );
+    assert_eq!(response(&responses, 1)["result"]["isError"], true);
+    assert!(tool_text(&responses, 1, 0).contains("invalid parameters"));
+}
+
+#[test]
+fn failures_carry_their_class_as_a_code() {
+    let google = MockServer::start();
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("out.mp3");
+    let synthesize = |id, provider: &str| {
+        (
+            Duration::ZERO,
+            call(
+                id,
+                "synthesize",
+                json!({"text": "hi", "output": out, "provider": provider, "encoding": "MP3"}),
+            ),
+        )
+    };
+    let code = |responses: &[Value], id| {
+        let failure: Value = serde_json::from_str(
+            tool_text(responses, id, 0)
+                .split_once('{')
+                .map(|(_, rest)| format!("{{{rest}"))
+                .unwrap()
+                .as_str(),
+        )
+        .unwrap();
+        failure["code"].as_str().unwrap().to_string()
+    };
+
+    let mut cmd = mcp_command(&google);
+    cmd.env_remove("OPENAI_API_KEY");
+    let responses = stdio_session(cmd, &[synthesize(1, "openai"), synthesize(2, "hume")]);
+    assert_eq!(code(&responses, 1), "auth_missing");
+    assert_eq!(code(&responses, 2), "unsupported");
+
+    let mut cmd = mcp_command(&google);
+    cmd.env("FAST_TTS_OFFLINE", "1");
+    let responses = stdio_session(cmd, &[synthesize(1, "google")]);
+    assert_eq!(code(&responses, 1), "offline");
+}
+
+struct Server {
+    child: Child,
+    base: String,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn client() -> reqwest::Client {
+    // The TLS test's certificate is self-signed
+    reqwest::Client::builder()
+        .no_proxy()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap()
+}
+
+async fn start_http(mut cmd: Command, args: &[&str]) -> Server {
+    let port = free_port();
+    let scheme = if args.contains(&"--mcp-tls-cert") {
+        "https"
+    } else {
+        "http"
+    };
+    let child = cmd
+        .args(["--mcp-mode", "http", "--mcp-addr"])
+        .arg(format!("127.0.0.1:{port}"))
+        .args(args)
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let server = Server {
+        child,
+        base: format!("{scheme}://127.0.0.1:{port}"),
+    };
+    for _ in 0..100 {
+        if client()
+            .get(format!("{}/healthz", server.base))
+            .send()
+            .await
+            .is_ok()
+        {
+            return server;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("MCP server did not start");
+}
+
+/// Read SSE `resp` until an event named `name`; its data.
+async fn next_event(resp: &mut reqwest::Response, buffer: &mut String, name: &str) -> String {
+    loop {
+        while let Some(end) = buffer.find("\n\n") {
+            let event: String = buffer.drain(..end + 2).collect();
+            let field = |key: &str| {
+                event
+                    .lines()
+                    .find_map(|line| line.strip_prefix(key))
+                    .map(|v| v.trim_start().to_string())
+            };
+            if field("event:").as_deref() == Some(name) {
+                return field("data:").unwrap_or_default();
+            }
+        }
+        let chunk = tokio::time::timeout(Duration::from_secs(10), resp.chunk())
+            .await
+            .expect("no SSE event in time")
+            .unwrap()
+            .expect("SSE stream ended");
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+    }
+}
+
+#[tokio::test]
+async fn sse_session_carries_responses() {
+    let google = MockServer::start();
+    let server = start_http(mcp_command(&google), &[]).await;
+
+    let mut events = client()
+        .get(format!("{}/sse", server.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(events.headers()["content-type"], "text/event-stream");
+    let mut buffer = String::new();
+    let endpoint = next_event(&mut events, &mut buffer, "endpoint").await;
+    assert!(endpoint.starts_with("/message?sessionId="), "{endpoint}");
+
+    let resp = client()
+        .post(format!("{}{endpoint}", server.base))
+        .json(&json!({"jsonrpc": "2.0", "id": 7, "method": "tools/list"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 202);
+    let message: Value =
+        serde_json::from_str(&next_event(&mut events, &mut buffer, "message").await).unwrap();
+    assert_eq!(message["id"], 7);
+    assert!(
+        message["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|t| t["name"] == "synthesize")
+    );
+
+    // Messages for a session that isn't open go nowhere
+    let resp = client()
+        .post(format!("{}/message?sessionId=unknown", server.base))
+        .json(&json!({"jsonrpc": "2.0", "id": 8, "method": "ping"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn http_requires_the_bearer_token() {
+    let google = MockServer::start();
+    let server = start_http(mcp_command(&google), &["--mcp-auth-token", "s3cret"]).await;
+    let ping = json!({"jsonrpc": "2.0", "id": 1, "method": "ping"});
+
+    for auth in [None, Some("Bearer wrong")] {
+        let mut req = client().post(format!("{}/rpc", server.base)).json(&ping);
+        if let Some(auth) = auth {
+            req = req.header("authorization", auth);
+        }
+        assert_eq!(req.send().await.unwrap().status(), 401);
+    }
+    let resp = client()
+        .get(format!("{}/sse", server.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+
+    let resp: Value = client()
+        .post(format!("{}/rpc", server.base))
+        .bearer_auth("s3cret")
+        .json(&ping)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(resp["result"], json!({}));
+
+    // Probes stay open
+    let resp = client()
+        .get(format!("{}/healthz", server.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[test]
+fn list_providers_reports_capabilities() {
+    let google = MockServer::start();
+    let responses = stdio_session(
+        mcp_command(&google),
+        &[(Duration::ZERO, call(1, "listProviders", json!({})))],
+    );
+    let providers: Vec<Value> = serde_json::from_str(tool_text(&responses, 1, 0)).unwrap();
+    let google = providers
+        .iter()
+        .find(|p| p["provider"] == "google")
+        .unwrap();
+    assert_eq!(google["configured"], true);
+    assert!(
+        google["encodings"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("MP3"))
+    );
+    assert!(providers.iter().any(|p| p["provider"] == "openai"));
+}
+
+#[test]
+fn cached_voice_catalogs_are_resources() {
+    let google = MockServer::start();
+    let cache = tempdir().unwrap();
+    std::fs::create_dir_all(cache.path().join("voices")).unwrap();
+    let catalog =
+        r#"{"voices":[{"name":"alloy","languageCodes":["en-US"],"ssmlGender":"NEUTRAL"}]}"#;
+    std::fs::write(
+        cache.path().join("voices/openai.json"),
+        json!({"url": "https://api.openai.com/v1/voices", "etag": null, "body": catalog})
+            .to_string(),
+    )
+    .unwrap();
+    // Outside the voices directory, so never served
+    std::fs::write(
+        cache.path().join("private.json"),
+        json!({"url": "", "etag": null, "body": "private"}).to_string(),
+    )
+    .unwrap();
+
+    let mut cmd = mcp_command(&google);
+    cmd.env("FAST_TTS_CACHE_DIR", cache.path());
+    let responses = stdio_session(
+        cmd,
+        &[
+            (
+                Duration::ZERO,
+                json!({"jsonrpc": "2.0", "id": 1, "method": "resources/list"}),
+            ),
+            (
+                Duration::ZERO,
+                json!({"jsonrpc": "2.0", "id": 2, "method": "resources/read",
+                       "params": {"uri": "voices://openai"}}),
+            ),
+            (
+                Duration::ZERO,
+                json!({"jsonrpc": "2.0", "id": 3, "method": "resources/read",
+                       "params": {"uri": "voices://lmnt"}}),
+            ),
+            (
+                Duration::ZERO,
+                json!({"jsonrpc": "2.0", "id": 4, "method": "resources/read",
+                       "params": {"uri": "voices://../private"}}),
+            ),
+        ],
+    );
+    let uris: Vec<&str> = response(&responses, 1)["result"]["resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["uri"].as_str().unwrap())
+        .collect();
+    assert_eq!(uris, ["voices://google", "voices://openai"]);
+    let contents = &response(&responses, 2)["result"]["contents"][0];
+    assert_eq!(contents["uri"], "voices://openai");
+    assert_eq!(contents["mimeType"], "application/json");
+    assert_eq!(contents["text"], catalog);
+    assert!(
+        response(&responses, 3)["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("no cached voice catalog")
+    );
+    assert!(response(&responses, 4)["result"].is_null());
+    assert!(
+        response(&responses, 4)["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("no cached voice catalog for ../private")
+    );
+}
+
+#[test]
+fn prompts_fill_in_their_arguments() {
+    let google = MockServer::start();
+    let responses = stdio_session(
+        mcp_command(&google),
+        &[
+            (
+                Duration::ZERO,
+                json!({"jsonrpc": "2.0", "id": 1, "method": "prompts/list"}),
+            ),
+            (
+                Duration::ZERO,
+                json!({"jsonrpc": "2.0", "id": 2, "method": "prompts/get", "params": {
+                    "name": "narrate",
+                    "arguments": {"text": "Once upon a time", "output": "story.mp3"},
+                }}),
+            ),
+            (
+                Duration::ZERO,
+                json!({"jsonrpc": "2.0", "id": 3, "method": "prompts/get",
+                       "params": {"name": "missing"}}),
+            ),
+            (
+                Duration::ZERO,
+                json!({"jsonrpc": "2.0", "id": 4, "method": "prompts/get", "params": {
+                    "name": "dialogue-ssml",
+                    "arguments": {"script": "A:  hi {{output}}\n    B:  hello", "output": "talk.mp3"},
+                }}),
+            ),
+            (
+                Duration::ZERO,
+                json!({"jsonrpc": "2.0", "id": 5, "method": "prompts/get", "params": {
+                    "name": "choose-voice",
+                    "arguments": {"language": "de-DE"},
+                }}),
+            ),
+        ],
+    );
+    let prompts = response(&responses, 1)["result"]["prompts"]
+        .as_array()
+        .unwrap();
+    let names: Vec<&str> = prompts
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["narrate", "dialogue-ssml", "choose-voice"]);
+    assert_eq!(prompts[0]["arguments"][0]["name"], "text");
+    assert_eq!(prompts[0]["arguments"][0]["required"], true);
+
+    let message = &response(&responses, 2)["result"]["messages"][0];
+    assert_eq!(message["role"], "user");
+    let text = message["content"]["text"].as_str().unwrap();
+    assert!(
+        text.contains("text=Once upon a time, output=story.mp3"),
+        "{text}"
+    );
+    assert!(text.ends_with("Once upon a time"), "{text}");
+    assert!(!text.contains("{{"), "{text}");
+    assert_eq!(response(&responses, 3)["error"]["code"], -32602);
+
+    // The user's script arrives exactly as sent, placeholders and spacing included
+    let text = response(&responses, 4)["result"]["messages"][0]["content"]["text"]
+        .as_str()
+        .unwrap();
+    assert!(
+        text.ends_with("\n\nA:  hi {{output}}\n    B:  hello"),
+        "{text}"
+    );
+    assert!(text.contains("output=talk.mp3, language=."), "{text}");
+    // An optional argument left out doesn't leave a double space behind
+    let text = response(&responses, 5)["result"]["messages"][0]["content"]["text"]
+        .as_str()
+        .unwrap();
+    assert!(text.contains("choose a voice for de-DE."), "{text}");
+}
+
+#[test]
+fn cancellation_stops_the_call_and_removes_partial_output() {
+    let google = MockServer::start();
+    google.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200)
+            .delay(Duration::from_secs(5))
+            .json_body_obj(&json!({"audioContent": "TVAz"}));
+    });
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("slow.mp3");
+
+    let started = Instant::now();
+    let responses = stdio_session(
+        mcp_command(&google),
+        &[
+            (
+                Duration::ZERO,
+                call(
+                    1,
+                    "synthesize",
+                    json!({"text": "slow", "output": out, "encoding": "MP3"}),
+                ),
+            ),
+            (
+                Duration::from_millis(500),
+                json!({"jsonrpc": "2.0", "method": "notifications/cancelled",
+                       "params": {"requestId": 1, "reason": "user gave up"}}),
+            ),
+        ],
+    );
+    // Cancelled requests get no response
+    assert!(responses.is_empty(), "{responses:?}");
+    assert!(started.elapsed() < Duration::from_secs(4));
+    assert!(!out.exists());
+}
+
+#[test]
+fn max_concurrent_queues_synthesis() {
+    let google = MockServer::start();
+    let synth = google.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200)
+            .delay(Duration::from_millis(800))
+            .json_body_obj(&json!({"audioContent": "TVAz"}));
+    });
+    let dir = tempdir().unwrap();
+    let calls: Vec<_> = (1..=2)
+        .map(|id| {
+            let out = dir.path().join(format!("{id}.mp3"));
+            (
+                Duration::ZERO,
+                call(
+                    id,
+                    "synthesize",
+                    json!({"text": "hi", "output": out, "encoding": "MP3"}),
+                ),
+            )
+        })
+        .collect();
+
+    let mut cmd = mcp_command(&google);
+    cmd.args(["--mcp-max-concurrent", "1"]);
+    let started = Instant::now();
+    let responses = stdio_session(cmd, &calls);
+    assert!(started.elapsed() >= Duration::from_millis(1600));
+    for id in 1..=2 {
+        assert_eq!(response(&responses, id)["result"]["isError"], false);
+    }
+    synth.assert_hits(2);
+}
+
+#[tokio::test]
+async fn rpc_requests_can_be_cancelled() {
+    let google = MockServer::start();
+    google.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200)
+            .delay(Duration::from_secs(5))
+            .json_body_obj(&json!({"audioContent": "TVAz"}));
+    });
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("slow.mp3");
+    let server = start_http(mcp_command(&google), &[]).await;
+
+    let rpc = format!("{}/rpc", server.base);
+    let slow = tokio::spawn(
+        client()
+            .post(&rpc)
+            .header("mcp-session-id", "agent-1")
+            .json(&call(
+                4,
+                "synthesize",
+                json!({"text": "slow", "output": out, "encoding": "MP3"}),
+            ))
+            .send(),
+    );
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let resp = client()
+        .post(&rpc)
+        .header("mcp-session-id", "agent-1")
+        .json(
+            &json!({"jsonrpc": "2.0", "method": "notifications/cancelled",
+                      "params": {"requestId": 4}}),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 202);
+
+    let resp: Value = tokio::time::timeout(Duration::from_secs(3), slow)
+        .await
+        .expect("cancelled request still running")
+        .unwrap()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(resp["id"], 4);
+    assert_eq!(resp["error"]["code"], -32800);
+    assert!(!out.exists());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn sigterm_drains_in_flight_rpcs() {
+    let google = MockServer::start();
+    google.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(200)
+            .delay(Duration::from_millis(1500))
+            .json_body_obj(&json!({"audioContent": "TVAz"}));
+    });
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("drained.mp3");
+    let mut server = start_http(mcp_command(&google), &[]).await;
+    let resp = client()
+        .get(format!("{}/readyz", server.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let slow = tokio::spawn(
+        client()
+            .post(format!("{}/rpc", server.base))
+            .json(&call(
+                1,
+                "synthesize",
+                json!({"text": "drain", "output": out, "encoding": "MP3"}),
+            ))
+            .send(),
+    );
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let killed = Command::new("kill")
+        .args(["-TERM", &server.child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let resp = client()
+        .get(format!("{}/readyz", server.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 503);
+
+    // The request in progress still finishes, then the server exits
+    let resp: Value = slow.await.unwrap().unwrap().json().await.unwrap();
+    assert_eq!(resp["result"]["isError"], false);
+    assert_eq!(std::fs::read(&out).unwrap(), b"MP3");
+    let status = tokio::task::spawn_blocking(move || server.child.wait())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[tokio::test]
+async fn http_is_served_over_tls() {
+    let google = MockServer::start();
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+    let cert = format!("{fixtures}/mcp_tls_cert.pem");
+    let key = format!("{fixtures}/mcp_tls_key.pem");
+    let server = start_http(
+        mcp_command(&google),
+        &["--mcp-tls-cert", &cert, "--mcp-tls-key", &key],
+    )
+    .await;
+    assert!(server.base.starts_with("https://"));
+
+    let resp: Value = client()
+        .post(format!("{}/rpc", server.base))
+        .json(&json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(resp["result"]["serverInfo"]["name"], "fast-tts-cli");
+
+    // Plain HTTP isn't answered on the TLS port
+    let plain = server.base.replace("https://", "http://");
+    assert!(
+        client()
+            .get(format!("{plain}/healthz"))
+            .send()
+            .await
+            .is_err()
+    );
+}
+
+#[test]
+fn provider_failures_are_structured() {
+    let google = MockServer::start();
+    google.mock(|when, then| {
+        when.method(POST).path("/v1/text:synthesize");
+        then.status(503).body("backend unavailable");
+    });
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("out.mp3");
+    let responses = stdio_session(
+        mcp_command(&google),
+        &[(
+            Duration::ZERO,
+            call(
+                1,
+                "synthesize",
+                json!({"text": "hi", "output": out, "encoding": "MP3"}),
+            ),
+        )],
+    );
+    assert_eq!(response(&responses, 1)["result"]["isError"], true);
+    let failure: Value = serde_json::from_str(tool_text(&responses, 1, 0)).unwrap();
+    assert_eq!(failure["code"], "provider_unavailable");
+    assert_eq!(failure["provider"], "google");
+    assert_eq!(failure["httpStatus"], 503);
+    assert_eq!(failure["retryable"], true);
+    assert!(!out.exists());
+}
+
+#[test]
+fn generate_subtitles_returns_captions() {
+    let google = MockServer::start();
+    let synth = google.mock(|when, then| {
+        when.method(POST)
+            .path("/v1beta1/text:synthesize")
+            .json_body_partial(r#"{"enableTimePointing": ["SSML_MARK"]}"#);
+        then.status(200).json_body_obj(&json!({
+            "audioContent": "TVAz",
+            "timepoints": [
+                {"markName": "0", "timeSeconds": 0.0},
+                {"markName": "1", "timeSeconds": 0.5},
+            ],
+        }));
+    });
+    let dir = tempdir().unwrap();
+    let out = dir.path().join("captioned.mp3");
+    let responses = stdio_session(
+        mcp_command(&google),
+        &[(
+            Duration::ZERO,
+            call(
+                1,
+                "generateSubtitles",
+                json!({"text": "hello world", "output": out, "format": "vtt"}),
+            ),
+        )],
+    );
+    let summary: Value = serde_json::from_str(tool_text(&responses, 1, 0)).unwrap();
+    assert_eq!(summary["words"], 2);
+    assert_eq!(summary["format"], "vtt");
+    let captions = tool_text(&responses, 1, 1);
+    assert!(captions.starts_with("WEBVTT"), "{captions}");
+    assert!(captions.contains("hello world"), "{captions}");
+    assert_eq!(std::fs::read(&out).unwrap(), b"MP3");
+    synth.assert();
+}
+
+#[test]
+fn output_paths_stay_inside_the_output_dir() {
+    let google = MockServer::start();
+    mock_synthesize(&google, "MP3DATA");
+    let root = tempdir().unwrap();
+    let outside = tempdir().unwrap();
+    let escape = outside.path().join("escaped.mp3");
+    // A dangling link: writing through it would create a file outside the root
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&escape, root.path().join("link.mp3")).unwrap();
+
+    let mut cmd = mcp_command(&google);
+    cmd.arg("--mcp-output-dir").arg(root.path());
+    let synthesize = |id, output: &str| {
+        (
+            Duration::ZERO,
+            call(
+                id,
+                "synthesize",
+                json!({"text": "hi", "output": output, "encoding": "MP3"}),
+            ),
+        )
+    };
+    let responses = stdio_session(
+        cmd,
+        &[
+            synthesize(1, "ok.mp3"),
+            synthesize(2, "../escaped.mp3"),
+            synthesize(3, escape.to_str().unwrap()),
+            synthesize(4, "link.mp3"),
+        ],
+    );
+    assert_eq!(response(&responses, 1)["result"]["isError"], false);
+    assert_eq!(
+        std::fs::read(root.path().join("ok.mp3")).unwrap(),
+        b"MP3DATA"
+    );
+    assert!(tool_text(&responses, 2, 0).contains("may not contain '..'"));
+    assert!(tool_text(&responses, 3, 0).contains("must be inside"));
+    #[cfg(unix)]
+    assert!(tool_text(&responses, 4, 0).contains("must be inside"));
+    assert!(!escape.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn speak_plays_on_the_host() {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let google = MockServer::start();
+    let synth = mock_synthesize(&google, "WAVDATA");
+    let bin = tempdir().unwrap();
+    let played = bin.path().join("played");
+    // Stands in for ffplay, the first player tried on Linux
+    let player = bin.path().join("ffplay");
+    std::fs::write(
+        &player,
+        format!("#!/bin/sh\necho \"$@\" > '{}'\n", played.display()),
+    )
+    .unwrap();
+    std::fs::set_permissions(&player, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = std::env::join_paths(
+        std::iter::once(bin.path().to_path_buf())
+            .chain(std::env::split_paths(&std::env::var_os("PATH").unwrap())),
+    )
+    .unwrap();
+
+    let mut cmd = mcp_command(&google);
+    cmd.env("PATH", path);
+    let responses = stdio_session(
+        cmd,
+        &[(Duration::ZERO, call(1, "speak", json!({"text": "hello"})))],
+    );
+    let result: Value = serde_json::from_str(tool_text(&responses, 1, 0)).unwrap();
+    assert_eq!(result, json!({"ok": true, "spoken": true}));
+    let args = std::fs::read_to_string(&played).unwrap();
+    assert!(args.contains(".wav"), "{args}");
+    synth.assert();
+}
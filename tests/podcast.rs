@@ -0,0 +1,66 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+/// `seconds` of 16-bit mono silence at 8 kHz.
+fn write_wav(path: &Path, seconds: u32) {
+    let data_len = 8000 * 2 * seconds;
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&8000u32.to_le_bytes());
+    wav.extend_from_slice(&16000u32.to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(wav.len() + data_len as usize, 0);
+    fs::write(path, wav).unwrap();
+}
+
+#[test]
+fn publish_writes_podcast_rss() {
+    let dir = tempdir().unwrap();
+    write_wav(&dir.path().join("First_post.wav"), 2);
+    write_wav(&dir.path().join("My recording.wav"), 65);
+    fs::write(dir.path().join("cover.png"), b"PNG").unwrap();
+    fs::write(dir.path().join("notes.txt"), b"not audio").unwrap();
+    fs::write(
+        dir.path().join(".fast-tts-feed.jsonl"),
+        r#"{"guid":"post-1","title":"First post","link":"https://blog.example/1","published":"2024-05-01T09:30:00+02:00","summary":"<p>Fish &amp; chips</p>","feed":"https://blog.example/rss","file":"First_post.wav"}"#,
+    )
+    .unwrap();
+
+    Command::cargo_bin("fast-tts-cli")
+        .unwrap()
+        .args(["podcast", "publish", "--title", "Reading <list>", "--dir"])
+        .arg(dir.path())
+        .args(["--base-url", "https://cdn.example/pod"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(2 episodes)"));
+
+    let rss = fs::read_to_string(dir.path().join("feed.xml")).unwrap();
+    assert!(rss.contains("<title>Reading &lt;list&gt;</title>"));
+    assert!(rss.contains(r#"<itunes:image href="https://cdn.example/pod/cover.png"/>"#));
+    assert!(rss.contains(
+        r#"<enclosure url="https://cdn.example/pod/First_post.wav" length="32044" type="audio/wav"/>"#
+    ));
+    assert!(rss.contains(r#"<guid isPermaLink="false">post-1</guid>"#));
+    assert!(rss.contains("<pubDate>Wed, 01 May 2024 07:30:00 +0000</pubDate>"));
+    assert!(rss.contains("<itunes:duration>00:00:02</itunes:duration>"));
+    assert!(rss.contains("<description>Fish &amp; chips</description>"));
+    assert!(rss.contains("https://cdn.example/pod/My%20recording.wav"));
+    assert!(rss.contains("<title>My recording</title>"));
+    assert!(rss.contains("<itunes:duration>00:01:05</itunes:duration>"));
+    assert!(!rss.contains("notes.txt"));
+    // The untracked file was just written, so it is the newest episode
+    assert!(rss.find("My%20recording").unwrap() < rss.find("First_post").unwrap());
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "polly")]
+
+use assert_cmd::prelude::*;
+use httpmock::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::tempdir;
+
+/// The CLI pointed at `server` as the Polly endpoint, with throwaway AWS
+/// credentials and nothing read from `~/.aws`.
+fn polly_command(server: &MockServer, dir: &std::path::Path) -> Command {
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("AWS_ENDPOINT_URL", server.base_url())
+        .env("AWS_ACCESS_KEY_ID", "AKIDEXAMPLE")
+        .env("AWS_SECRET_ACCESS_KEY", "secret")
+        .env("AWS_REGION", "us-east-1")
+        .env("AWS_CONFIG_FILE", dir.join("aws-config"))
+        .env("AWS_SHARED_CREDENTIALS_FILE", dir.join("aws-credentials"))
+        .env("AWS_EC2_METADATA_DISABLED", "true")
+        .env("FAST_TTS_CACHE_DIR", dir.join("cache"))
+        .env("FAST_TTS_HISTORY", dir.join("history.jsonl"))
+        .env_remove("AWS_PROFILE")
+        .env_remove("POLLY_ENGINE")
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .args(["--provider", "polly"]);
+    cmd
+}
+
+#[test]
+fn voices_list_engines_and_the_engine_is_checked_against_them() {
+    let server = MockServer::start();
+    let voices = server.mock(|when, then| {
+        when.method(GET).path("/v1/voices");
+        then.status(200).json_body_obj(&serde_json::json!({
+            "Voices": [
+                {
+                    "Id": "Joanna",
+                    "Name": "Joanna",
+                    "Gender": "Female",
+                    "LanguageCode": "en-US",
+                    "LanguageName": "US English",
+                    "SupportedEngines": ["neural", "standard"]
+                },
+                {
+                    "Id": "Danielle",
+                    "Name": "Danielle",
+                    "Gender": "Female",
+                    "LanguageCode": "en-US",
+                    "LanguageName": "US English",
+                    "AdditionalLanguageCodes": ["en-GB"],
+                    "SupportedEngines": ["generative", "long-form", "neural"]
+                }
+            ]
+        }));
+    });
+    let speech = server.mock(|when, then| {
+        when.method(POST).path("/v1/speech").json_body_partial(
+            r#"{"Engine": "neural", "VoiceId": "Joanna", "OutputFormat": "mp3", "Text": "hello"}"#,
+        );
+        then.status(200)
+            .header("content-type", "audio/mpeg")
+            .body("MP3DATA");
+    });
+    let dir = tempdir().unwrap();
+
+    let out = polly_command(&server, dir.path())
+        .args(["--list-voices", "--json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    voices.assert();
+    let listed: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(listed["voices"][0]["name"], "Joanna");
+    assert_eq!(listed["voices"][0]["ssmlGender"], "FEMALE");
+    assert_eq!(
+        listed["voices"][0]["engines"],
+        serde_json::json!(["neural", "standard"])
+    );
+    assert_eq!(
+        listed["voices"][1]["languageCodes"],
+        serde_json::json!(["en-US", "en-GB"])
+    );
+
+    // With the catalog cached, an engine the voice lacks fails before any request
+    polly_command(&server, dir.path())
+        .env("POLLY_ENGINE", "long-form")
+        .args(["--voice", "Joanna", "--encoding", "MP3", "hello"])
+        .arg(dir.path().join("rejected.mp3"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "Polly voice Joanna does not support the long-form engine (supports: neural, standard)",
+        ));
+    speech.assert_hits(0);
+
+    let out = dir.path().join("hello.mp3");
+    polly_command(&server, dir.path())
+        .args(["--voice", "Joanna", "--encoding", "MP3", "hello"])
+        .arg(&out)
+        .assert()
+        .success();
+    speech.assert();
+    assert_eq!(std::fs::read(&out).unwrap(), b"MP3DATA");
+}
@@ -0,0 +1,77 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+/// 16-bit mono WAV of a 440 Hz tone switched on and off per `pattern` (100 ms steps).
+fn write_wav(path: &Path, sample_rate: u32, gain: f32, pattern: &[bool]) {
+    let step = sample_rate as usize / 10;
+    let samples: Vec<i16> = (0..pattern.len() * step)
+        .map(|i| {
+            let on = pattern[i / step];
+            let t = i as f32 / sample_rate as f32;
+            let s = if on {
+                (t * 440.0 * std::f32::consts::TAU).sin()
+            } else {
+                0.0
+            };
+            (s * gain * 16000.0) as i16
+        })
+        .collect();
+    let data_len = samples.len() as u32 * 2;
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for s in samples {
+        wav.extend_from_slice(&s.to_le_bytes());
+    }
+    std::fs::write(path, wav).unwrap();
+}
+
+#[test]
+fn verify_compares_renders() {
+    let dir = tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    let speech = [
+        true, true, false, true, false, false, true, true, true, false,
+    ];
+    write_wav(&path("a.wav"), 24000, 1.0, &speech);
+    write_wav(&path("quieter.wav"), 24000, 0.95, &speech);
+    write_wav(&path("other.wav"), 24000, 1.0, &speech.map(|on| !on));
+    write_wav(&path("resampled.wav"), 16000, 1.0, &speech);
+    let verify = |b: &str| {
+        let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+        cmd.arg("verify").arg(path("a.wav")).arg(path(b));
+        cmd
+    };
+
+    verify("quieter.wav")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("equivalent"));
+    verify("other.wav")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("fingerprint similarity"));
+    verify("resampled.wav")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("sample rate 24000 Hz vs 16000 Hz"));
+
+    let output = verify("quieter.wav").arg("--json").output().unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["equivalent"], true);
+    assert_eq!(report["a"]["sampleRate"], 24000);
+    assert!((report["a"]["durationMs"].as_f64().unwrap() - 1000.0).abs() < 1.0);
+}
@@ -0,0 +1,235 @@
+#![cfg(feature = "wasm")]
+
+use assert_cmd::prelude::*;
+use httpmock::prelude::*;
+use predicates::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::tempdir;
+
+/// tests/fixtures/wasm_plugin.wat as a component implementing wit/provider.wit.
+fn write_plugin(dir: &Path) -> PathBuf {
+    let mut resolve = wit_parser::Resolve::default();
+    let (package, _) = resolve
+        .push_path(concat!(env!("CARGO_MANIFEST_DIR"), "/wit"))
+        .unwrap();
+    let world = resolve.select_world(package, Some("tts-provider")).unwrap();
+    let mut module = wat::parse_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/wasm_plugin.wat"
+    ))
+    .unwrap();
+    wit_component::embed_component_metadata(
+        &mut module,
+        &resolve,
+        world,
+        wit_component::StringEncoding::UTF8,
+    )
+    .unwrap();
+    let component = wit_component::ComponentEncoder::default()
+        .module(&module)
+        .unwrap()
+        .validate(true)
+        .encode()
+        .unwrap();
+    let path = dir.join("echo.wasm");
+    std::fs::write(&path, component).unwrap();
+    path
+}
+
+fn plugin_command(dir: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("fast-tts-cli").unwrap();
+    cmd.env("FAST_TTS_PLUGINS_DIR", dir)
+        .env("FAST_TTS_HISTORY", dir.join("history.jsonl"))
+        .env_remove("FAST_TTS_PLUGIN_ECHO_URL")
+        .env_remove("HTTP_PROXY")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("https_proxy");
+    cmd
+}
+
+#[test]
+fn plugin_synthesizes_through_the_host() {
+    let server = MockServer::start();
+    let tts = server.mock(|when, then| {
+        when.method(POST)
+            .path("/tts")
+            .header("x-voice", "nova")
+            .body("hello");
+        then.status(200).body("AUDIO");
+    });
+    let dir = tempdir().unwrap();
+    let plugin = write_plugin(dir.path());
+    let provider = format!("wasm:{}", plugin.display());
+
+    let out = dir.path().join("hello.mp3");
+    plugin_command(dir.path())
+        .env("FAST_TTS_PLUGIN_ECHO_URL", server.url("/tts"))
+        .args([
+            "--provider",
+            &provider,
+            "--voice",
+            "nova",
+            "--encoding",
+            "MP3",
+        ])
+        .arg("hello")
+        .arg(&out)
+        .assert()
+        .success();
+    tts.assert();
+    assert_eq!(std::fs::read(&out).unwrap(), b"AUDIO");
+
+    // A plugin in the plugins directory is found by name
+    let out = dir.path().join("by-name.mp3");
+    plugin_command(dir.path())
+        .env("FAST_TTS_PLUGIN_ECHO_URL", server.url("/tts"))
+        .args([
+            "--provider",
+            "wasm:echo",
+            "--voice",
+            "nova",
+            "--encoding",
+            "MP3",
+        ])
+        .arg("hello")
+        .arg(&out)
+        .assert()
+        .success();
+    assert_eq!(std::fs::read(&out).unwrap(), b"AUDIO");
+}
+
+#[test]
+fn plugin_capabilities_and_errors_reach_the_cli() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/down");
+        then.status(503).body("maintenance");
+    });
+    let dir = tempdir().unwrap();
+    let plugin = write_plugin(dir.path());
+    let provider = format!("wasm:{}", plugin.display());
+
+    let out = plugin_command(dir.path())
+        .args(["encodings", "--json", "--provider", &provider])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let report: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(report[0]["provider"], provider);
+    assert_eq!(report[0]["encodings"][0]["encoding"], "MP3");
+    assert_eq!(report[0]["encodings"][1]["encoding"], "LINEAR16");
+    assert_eq!(
+        report[0]["encodings"][0]["sampleRates"],
+        serde_json::json!([24000])
+    );
+
+    // The plugin's error variants keep their exit codes; settings outside
+    // its FAST_TTS_PLUGIN_ECHO_ prefix stay hidden from it
+    plugin_command(dir.path())
+        .env("URL", server.url("/down"))
+        .args(["--provider", &provider, "--encoding", "MP3", "hello"])
+        .arg(dir.path().join("unset.mp3"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("URL not set"));
+    plugin_command(dir.path())
+        .env("FAST_TTS_PLUGIN_ECHO_URL", server.url("/down"))
+        .args(["--provider", &provider, "--encoding", "MP3", "hello"])
+        .arg(dir.path().join("down.mp3"))
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("HTTP 503: maintenance"));
+
+    plugin_command(dir.path())
+        .args(["--provider", "wasm:missing", "hello"])
+        .arg(dir.path().join("missing.wav"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("plugin \"missing\" not found"));
+}
+
+#[test]
+fn plugin_stuck_in_a_loop_times_out() {
+    let dir = tempdir().unwrap();
+    write_plugin(dir.path());
+
+    let started = std::time::Instant::now();
+    plugin_command(dir.path())
+        .args([
+            "--provider",
+            "wasm:echo",
+            "--voice",
+            "~spin",
+            "--encoding",
+            "MP3",
+            "--timeout",
+            "300",
+            "hello",
+        ])
+        .arg(dir.path().join("spin.mp3"))
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("timed out after 300 ms"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(20));
+    assert!(!dir.path().join("spin.mp3").exists());
+}
+
+#[tokio::test]
+async fn serve_only_runs_installed_plugins() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/tts");
+        then.status(200).body("AUDIO");
+    });
+    let dir = tempdir().unwrap();
+    let plugin = write_plugin(dir.path());
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut child = plugin_command(dir.path())
+        .env("FAST_TTS_PLUGIN_ECHO_URL", server.url("/tts"))
+        .args(["serve", "--addr", &format!("127.0.0.1:{port}")])
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let client = reqwest::Client::builder().no_proxy().build().unwrap();
+    let synthesize = |provider: String| {
+        client
+            .post(format!("http://127.0.0.1:{port}/synthesize"))
+            .json(&serde_json::json!({"text": "hello", "provider": provider, "encoding": "MP3"}))
+            .send()
+    };
+    let mut resp = None;
+    for _ in 0..100 {
+        if let Ok(r) = synthesize("wasm:echo".into()).await {
+            resp = Some(r);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    let resp = resp.expect("serve did not start");
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.bytes().await.unwrap().as_ref(), b"AUDIO");
+
+    // A client can't point the server at an arbitrary file
+    let resp = synthesize(format!("wasm:{}", plugin.display()))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(
+        body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("plugin paths are not accepted")
+    );
+
+    let _ = child.kill();
+    let _ = child.wait();
+}